@@ -18,6 +18,9 @@ let Wasabi = {
         "call_pre",
         "call_post",
         "return_",
+        "throw_",
+        "rethrow",
+        "catch_begin",
         "const_",
         "unary",
         "binary",
@@ -25,20 +28,30 @@ let Wasabi = {
         "store",
         "memory_size",
         "memory_grow",
+        "memory_copy",
+        "memory_fill",
+        "memory_init",
+        "data_drop",
+        "atomic_load",
+        "atomic_store",
+        "atomic_rmw",
+        "atomic_wait",
+        "atomic_notify",
+        "atomic_fence",
         "local",
         "global"
     ],
 
-    // map a table index to a function index
-    resolveTableIdx: function (tableIdx) {
-        if (Wasabi.module.exports === undefined || Wasabi.module.table === undefined) {
-            console.warn("Wasabi: cannot resolve table index without module exports and table (possible reason: exports and table are usually not available during execution of the Wasm start function)");
+    // map an index into the given table (the first one if not given) to a function index
+    resolveTableIdx: function (tableIdx, table = 0) {
+        if (Wasabi.module.exports === undefined || Wasabi.module.tables === undefined) {
+            console.warn("Wasabi: cannot resolve table index without module exports and tables (possible reason: exports and tables are usually not available during execution of the Wasm start function)");
             return undefined;
         }
 
-        const resolvedFunction = Wasabi.module.table.get(tableIdx);
+        const resolvedFunction = Wasabi.module.tables[table].get(tableIdx);
         if (resolvedFunction === null) {
-            console.warn("Wasabi: resolving indirectly called function failed because table returned `null` at index " + tableIdx);
+            console.warn("Wasabi: resolving indirectly called function failed because table " + table + " returned `null` at index " + tableIdx);
             return undefined;
         }
 
@@ -73,8 +86,10 @@ let Wasabi = {
                 {func, instr: end},
                 type,
                 {func, instr: begin},
-                // not undefined only for block type "else"
-                (begin_if === undefined) ? undefined : {func, instr: begin_if});
+                // not undefined only for block types "else" and "catch"
+                (begin_if === undefined) ? undefined : {func, instr: begin_if},
+                // blocks that are left via a branch do not report values
+                []);
         }
     },
 
@@ -83,7 +98,7 @@ let Wasabi = {
         // TODO flatten info into module itself, by using Object.assign in generated code
         info: undefined, lowlevelHooks: undefined,
         // filled after instantiation
-        exports: undefined, table: undefined,
+        exports: undefined, tables: undefined,
     },
 
     // filled by user or with empty hooks (as fallback) before instantiation
@@ -102,20 +117,43 @@ let Wasabi = {
         br(location, target) {},
         br_if(location, conditionalTarget, condition) {},
         br_table(location, table, defaultTarget, tableIdx) {},
-        begin(location, type) {},
-        end(location, type, beginLocation, ifLocation) {},
+        // ifLocation is the location of the matching if for else blocks and of the try for catch blocks
+        begin(location, type, ifLocation, values) {},
+        end(location, type, beginLocation, ifLocation, values) {},
         drop(location, value) {},
         select(location, cond, first, second) {},
-        call_pre(location, targetFunc, args, indirectTableIdx) {},
+        // for tail calls (tail call extension), call_pre is followed by return_ without values
+        // and end hooks for the calling function, and there is no call_post
+        // indirectTable is the index of the table of indirect calls (reference types extension)
+        call_pre(location, targetFunc, args, indirectTableIdx, indirectTable) {},
         call_post(location, values) {},
         return_(location, values) {},
+        // exception handling extension, tagIdx is undefined for catch_all handlers
+        throw_(location, tagIdx, values) {},
+        rethrow(location, target) {},
+        catch_begin(location, tryLocation, tagIdx, values) {},
         const_(location, op, value) {},
-        unary(location, op, input, result) {},
-        binary(location, op, first, second, result) {},
-        load(location, op, memarg, value) {},
-        store(location, op, memarg, value) {},
-        memory_size(location, currentSizePages) {},
-        memory_grow(location, byPages, previousSizePages) {},
+        // lane is only given for extract_lane (unary) and replace_lane (binary) (SIMD extension)
+        unary(location, op, input, result, lane) {},
+        binary(location, op, first, second, result, lane) {},
+        // for 64-bit memories (memory64 extension), addresses, offsets, and sizes are Longs
+        // memarg.memory and memoryIdx are the index of the accessed memory (multi-memory extension)
+        // for v128.loadN_lane and v128.storeN_lane (SIMD extension), value is only the accessed lane
+        load(location, op, memarg, value, lane) {},
+        store(location, op, memarg, value, lane) {},
+        memory_size(location, currentSizePages, memoryIdx) {},
+        memory_grow(location, byPages, previousSizePages, memoryIdx) {},
+        memory_copy(location, dest, src, len, destMemoryIdx, srcMemoryIdx) {},
+        memory_fill(location, dest, value, len, memoryIdx) {},
+        memory_init(location, dataIdx, dest, src, len, memoryIdx) {},
+        data_drop(location, dataIdx) {},
+        atomic_load(location, op, memarg, value) {},
+        atomic_store(location, op, memarg, value) {},
+        // expected is only given for compare-exchange operations, undefined otherwise
+        atomic_rmw(location, op, memarg, value, oldValue, expected) {},
+        atomic_wait(location, op, memarg, expected, timeout, result) {},
+        atomic_notify(location, memarg, count, wokenCount) {},
+        atomic_fence(location) {},
         local(location, op, localIndex, value) {},
         global(location, op, globalIndex, value) {},
     }
@@ -140,7 +178,7 @@ let Wasabi = {
 
     const wireInstanceExports = function(instance) {
         Wasabi.module.exports = instance.exports;
-        Wasabi.module.table = instance.exports[Wasabi.module.info.tableExportName];
+        Wasabi.module.tables = Wasabi.module.info.tableExportNames.map(name => instance.exports[name]);
     }
 
     const oldInstantiate = WebAssembly.instantiate;
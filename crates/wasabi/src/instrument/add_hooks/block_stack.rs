@@ -6,6 +6,7 @@ use smallvec::SmallVec;
 use wasabi_wasm::Idx;
 use wasabi_wasm::Instr;
 use wasabi_wasm::Label;
+use wasabi_wasm::Tag;
 
 use self::BlockStackElement::*;
 
@@ -13,8 +14,9 @@ use self::BlockStackElement::*;
  * Data structure for representing the "control stack", i.e., the implicit nested block structure
  * of control-flow instructions.
  * Needed for:
- *  - resolving End instructions to their corresponding begins (i.e., Block, Loop, If, Else)
+ *  - resolving End instructions to their corresponding begins (i.e., Block, Loop, If, Else, Try, Catch)
  *  - resolving labels of branches to actual instruction indices (which requires the previous as a first step)
+ *  - resolving thrown exceptions to their handlers (i.e., which blocks are unwound by a throw)
  */
 
 #[derive(Debug)]
@@ -22,8 +24,23 @@ pub struct BlockStack {
     block_stack: SmallVec<[BlockStackElement; 8]>,
     /// Maps the beginning of a block to its end (or else, for if) instruction. Pre-computed on new().
     begin_end_map: IntMap<Idx<Instr>, Idx<Instr>>,
+    /// Maps the beginning of a try block to its handlers, if it has any. Pre-computed on new().
+    try_handlers: IntMap<Idx<Instr>, TryHandlers>,
 }
 
+/// What happens to an exception that is thrown inside the body of a try block.
+#[derive(Debug, Clone)]
+enum TryHandlers {
+    /// Caught by the first matching handler, in order of the catch/catch_all instructions.
+    /// The tag is `None` for catch_all.
+    Catch(SmallVec<[CatchHandler; 2]>),
+    /// Forwarded to the block with the given label (relative to the blocks around the try).
+    Delegate(Label),
+}
+
+/// The caught tag (or `None` for catch_all) and the catch/catch_all instruction.
+type CatchHandler = (Option<Idx<Tag>>, Idx<Instr>);
+
 #[derive(Debug, Clone)]
 pub enum BlockStackElement {
     Function {
@@ -47,6 +64,18 @@ pub enum BlockStackElement {
         begin_if: Idx<Instr>,
         end: Idx<Instr>,
     },
+    /// The body of a try block (exception handling extension), ended by the first catch/catch_all
+    /// or by delegate or end.
+    Try {
+        begin: Idx<Instr>,
+        end: Idx<Instr>,
+    },
+    /// A handler block, begun by catch or catch_all.
+    Catch {
+        begin_catch: Idx<Instr>,
+        begin_try: Idx<Instr>,
+        end: Idx<Instr>,
+    },
 }
 
 impl BlockStack {
@@ -57,20 +86,51 @@ impl BlockStack {
         let mut begin_end_map: IntMap<Idx<Instr>, Idx<Instr>> =
             IntMap::with_capacity_and_hasher(PREALLOC_BLOCK_STACK_SIZE, Default::default());
 
+        let mut try_handlers: IntMap<Idx<Instr>, TryHandlers> = IntMap::default();
+        // for every catch, the try block that it belongs to
+        let mut catch_try_map: IntMap<Idx<Instr>, Idx<Instr>> = IntMap::default();
+
         let mut begin_stack: SmallVec<[Idx<Instr>; 16]> =
             SmallVec::with_capacity(PREALLOC_BLOCK_STACK_SIZE);
         for (iidx, instr) in instrs[..instrs.len() - 1].iter().enumerate() {
             let iidx = iidx.into();
             match *instr {
-                Instr::Block(_) | Instr::Loop(_) | Instr::If(_) => begin_stack.push(iidx),
-                Instr::Else | Instr::End => {
+                Instr::Block(_) | Instr::Loop(_) | Instr::If(_) | Instr::Try(_) => {
+                    begin_stack.push(iidx)
+                }
+                Instr::Else
+                | Instr::End
+                | Instr::Catch(_)
+                | Instr::CatchAll
+                | Instr::Delegate(_) => {
                     let begin_iidx = begin_stack
                         .pop()
                         .expect("invalid block nesting: could not end block, stack was empty");
                     begin_end_map.insert(begin_iidx, iidx);
-                    // special case: Else also starts its own block
-                    if let Instr::Else = instr {
-                        begin_stack.push(iidx);
+
+                    let begin_try = || *catch_try_map.get(&begin_iidx).unwrap_or(&begin_iidx);
+                    match *instr {
+                        // special case: Else, Catch, and CatchAll also start their own block
+                        Instr::Else => begin_stack.push(iidx),
+                        Instr::Catch(_) | Instr::CatchAll => {
+                            let begin_try = begin_try();
+                            let tag = match *instr {
+                                Instr::Catch(tag) => Some(tag),
+                                _ => None,
+                            };
+                            if let TryHandlers::Catch(handlers) = try_handlers
+                                .entry(begin_try)
+                                .or_insert_with(|| TryHandlers::Catch(SmallVec::new()))
+                            {
+                                handlers.push((tag, iidx));
+                            }
+                            catch_try_map.insert(iidx, begin_try);
+                            begin_stack.push(iidx);
+                        }
+                        Instr::Delegate(label) => {
+                            try_handlers.insert(begin_iidx, TryHandlers::Delegate(label));
+                        }
+                        _ => {}
                     }
                 }
                 _ => {}
@@ -89,6 +149,7 @@ impl BlockStack {
         BlockStack {
             block_stack,
             begin_end_map,
+            try_handlers,
         }
     }
 
@@ -155,6 +216,45 @@ impl BlockStack {
         }
     }
 
+    pub fn begin_try(&mut self, begin: Idx<Instr>) {
+        self.block_stack.push(Try {
+            begin,
+            end: self.try_end(begin),
+        });
+    }
+
+    /// returns the ended try or previous catch block (of which this catch is a "sibling")
+    pub fn catch_(&mut self, begin_catch: Idx<Instr>) -> BlockStackElement {
+        let block_element = self
+            .block_stack
+            .pop()
+            .expect("invalid block nesting: expected try or catch, but stack was empty");
+        let begin_try = match block_element {
+            Try { begin, .. } => begin,
+            Catch { begin_try, .. } => begin_try,
+            block => panic!(
+                "invalid block nesting: expected try or catch on block stack, but got {block:?}"
+            ),
+        };
+        self.block_stack.push(Catch {
+            begin_catch,
+            begin_try,
+            end: self.try_end(begin_try),
+        });
+        block_element
+    }
+
+    /// follows the chain of catch blocks (if any) to the end or delegate of the whole try
+    fn try_end(&self, begin_try: Idx<Instr>) -> Idx<Instr> {
+        let mut end = *self.begin_end_map.get(&begin_try).unwrap_or_else(|| {
+            panic!("invalid block nesting: could not find end for try begin at {begin_try:?}")
+        });
+        while let Some(&next) = self.begin_end_map.get(&end) {
+            end = next;
+        }
+        end
+    }
+
     pub fn end(&mut self) -> BlockStackElement {
         self.block_stack
             .pop()
@@ -183,7 +283,12 @@ impl BlockStack {
 
             match *target_block {
                 Loop { begin, .. } => begin,
-                Function { end } | Block { end, .. } | If { end, .. } | Else { end, .. } => end,
+                Function { end }
+                | Block { end, .. }
+                | If { end, .. }
+                | Else { end, .. }
+                | Try { end, .. }
+                | Catch { end, .. } => end,
             }
         };
 
@@ -204,6 +309,71 @@ impl BlockStack {
             ended_blocks: self.block_stack.iter().rev().cloned().collect(),
         }
     }
+
+    /// resolves which blocks are unwound by an exception with the given tag that is thrown at the
+    /// current instruction, i.e., all blocks up to the try block with a matching handler (which is
+    /// not included, since its end is reported when the handler begins) or all blocks including
+    /// the function, if the exception is not caught in this function
+    /// NOTE the tag is `None` if it is statically unknown (rethrow of a catch_all block), in which
+    /// case only catch_all handlers are assumed to match
+    /// NOTE tags are compared by index, i.e., two imports of the same tag are considered different
+    pub fn unwound_blocks(&self, tag: Option<Idx<Tag>>) -> SmallVec<[BlockStackElement; 4]> {
+        let mut unwound_blocks = SmallVec::new();
+        let mut blocks = self.block_stack.iter().rev();
+        while let Some(block) = blocks.next() {
+            if let Try { begin, .. } = *block {
+                match self.try_handlers.get(&begin) {
+                    Some(TryHandlers::Catch(handlers))
+                        if handlers.iter().any(|&(handler_tag, _)| {
+                            handler_tag.is_none() || handler_tag == tag
+                        }) =>
+                    {
+                        return unwound_blocks;
+                    }
+                    Some(&TryHandlers::Delegate(label)) => {
+                        // the blocks between the try and the delegate target are unwound as well,
+                        // the exception is then thrown again in the target block
+                        unwound_blocks.push(block.clone());
+                        unwound_blocks.extend(blocks.by_ref().take(label.to_usize()).cloned());
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            unwound_blocks.push(block.clone());
+        }
+        unwound_blocks
+    }
+
+    /// similar to br_target(), but resolves the catch block targeted by a rethrow, and the
+    /// blocks that are unwound by re-throwing its exception (see unwound_blocks())
+    pub fn rethrow_target(&self, label: Label) -> BranchTarget {
+        let target_block = self
+            .block_stack
+            .iter()
+            .rev()
+            .nth(label.to_usize())
+            .unwrap_or_else(|| panic!("invalid label: cannot find target block for {label:?}"));
+        let (begin_catch, begin_try) = match *target_block {
+            Catch {
+                begin_catch,
+                begin_try,
+                ..
+            } => (begin_catch, begin_try),
+            ref block => panic!("invalid rethrow: target must be a catch block, but got {block:?}"),
+        };
+        let tag = match self.try_handlers.get(&begin_try) {
+            Some(TryHandlers::Catch(handlers)) => handlers
+                .iter()
+                .find(|&&(_, handler)| handler == begin_catch)
+                .and_then(|&(tag, _)| tag),
+            _ => None,
+        };
+        BranchTarget {
+            absolute_instr: begin_catch,
+            ended_blocks: self.unwound_blocks(tag),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,27 +1,33 @@
 use wasabi_wasm::BinaryOp::I64ShrS;
+use wasabi_wasm::ExtractLaneOp::I32x4ExtractLane;
 use wasabi_wasm::Instr;
 use wasabi_wasm::Instr::Binary;
 use wasabi_wasm::Instr::Const;
+use wasabi_wasm::Instr::ExtractLane;
 use wasabi_wasm::Instr::Unary;
 use wasabi_wasm::UnaryOp::I32WrapI64;
 use wasabi_wasm::Val;
 use wasabi_wasm::ValType;
 use wasabi_wasm::ValType::I32;
 use wasabi_wasm::ValType::I64;
+use wasabi_wasm::ValType::V128;
 
 /*
- * Helper functions for turning i64's into two i32's so that we can pass them to JavaScript
+ * Helper functions for turning i64's into two i32's (and v128's into four i32's) so that we can
+ * pass them to JavaScript
  */
 
 pub fn convert_i64_type(ty: &ValType) -> &[ValType] {
     match ty {
         &I64 => &[I32, I32],
+        &V128 => &[I32, I32, I32, I32],
         ty => std::slice::from_ref(ty),
     }
 }
 
 /// instr is assumed to have no side-effects or influences on the stack (other than pushing one value)
-/// so that we can execute it safely twice (once for lower and higher bit half).
+/// so that we can execute it safely twice (once for lower and higher bit half), or four times for
+/// v128 (once for every i32 lane).
 /// ty is necessary because for some instructions, the type cannot be determined but needs external information, e.g., for LocalGet
 pub fn convert_i64_instr(append_to: &mut Vec<Instr>, instr: Instr, ty: ValType) {
     match ty {
@@ -33,6 +39,11 @@ pub fn convert_i64_instr(append_to: &mut Vec<Instr>, instr: Instr, ty: ValType)
             Binary(I64ShrS),
             Unary(I32WrapI64), // high bits
         ]),
+        V128 => {
+            for lane in 0..4 {
+                append_to.extend_from_slice(&[instr.clone(), ExtractLane(I32x4ExtractLane, lane)]);
+            }
+        }
         _ => append_to.push(instr),
     }
 }
@@ -2,11 +2,13 @@ use std::collections::HashMap;
 
 use parking_lot::RwLock;
 use parking_lot::RwLockUpgradableReadGuard;
+use wasabi_wasm::AtomicWaitNotifyOp;
 use wasabi_wasm::Function;
 use wasabi_wasm::FunctionType;
 use wasabi_wasm::Idx;
 use wasabi_wasm::Instr;
 use wasabi_wasm::Instr::*;
+use wasabi_wasm::Memory;
 use wasabi_wasm::MemoryOp;
 use wasabi_wasm::Module;
 use wasabi_wasm::ValType;
@@ -23,6 +25,7 @@ use super::convert_i64::convert_i64_type;
  */
 
 /// helper struct to encapsulate JavaScript arguments + their Wasm type
+#[derive(Clone)]
 pub struct Arg {
     name: String,
     ty: ValType,
@@ -34,6 +37,10 @@ impl Arg {
     fn to_lowlevel_param_name(&self) -> String {
         match self.ty {
             I64 => self.name.clone() + "_low, " + &self.name + "_high",
+            V128 => (0..4)
+                .map(|lane| format!("{}_{lane}", self.name))
+                .collect::<Vec<_>>()
+                .join(", "),
             _ => self.name.clone(),
         }
     }
@@ -42,11 +49,49 @@ impl Arg {
     fn to_lowlevel_long_expr(&self) -> String {
         match self.ty {
             I64 => format!("new Long({})", self.to_lowlevel_param_name()),
+            V128 => format!("new Int32Array([{}])", self.to_lowlevel_param_name()),
             _ => self.name.clone(),
         }
     }
 }
 
+/// JavaScript object for the memory argument of loads, stores, and atomic instructions, where
+/// args start with `offset`, `align`, `memory`, and `addr` (in that order).
+/// For 64-bit memories (memory64 extension), `addr` and `offset` are passed as `Long`s.
+fn memarg_js_expr(args: &[Arg]) -> String {
+    let field = |arg: &Arg| match arg.ty {
+        I64 => format!("{}: {}", arg.name, arg.to_lowlevel_long_expr()),
+        _ => arg.name.clone(),
+    };
+    format!(
+        "{{{}, {}, align, memory}}",
+        field(&args[3]),
+        field(&args[0])
+    )
+}
+
+/// arguments for a variable number of values, e.g., block inputs or results
+fn value_args(tys: &[ValType]) -> Vec<Arg> {
+    tys.iter()
+        .enumerate()
+        .map(|(i, &ty)| Arg {
+            name: format!("value{i}"),
+            ty,
+        })
+        .collect()
+}
+
+/// JavaScript array expression of all values, passed to the high-level hook
+fn values_js_array(args: &[Arg]) -> String {
+    format!(
+        "[{}]",
+        args.iter()
+            .map(Arg::to_lowlevel_long_expr)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
 /// to make creation of hooks easier and somewhat similar to rust function declarations (i.e. list of "name: type")
 macro_rules! args {
     ($($name:ident: $ty:expr),*) => (vec![ $(Arg { name: stringify!($name).into(), ty: $ty }),* ]);
@@ -84,6 +129,7 @@ impl Hook {
             lowlevel_args.extend(
                 args.iter()
                     // and expand i64 to a tuple of (i32, i32) since there is no JS interop for i64
+                    // (and v128 to four i32's, one per lane)
                     .flat_map(
                         |Arg {
                              name: _name,
@@ -137,9 +183,36 @@ impl HookMap {
         result
     }
 
+    /// For instructions that access memory, the address types of the accessed memories (looked
+    /// up with `address_ty`) are the polymorphic types, i.e., the destination and source memory
+    /// for `memory.copy` and the single accessed memory otherwise.
+    /// If all are 32-bit memories, no polymorphic types are passed (such that those hooks keep
+    /// their names).
+    pub fn memory_instr(
+        &self,
+        instr: &Instr,
+        address_ty: impl Fn(Idx<Memory>) -> ValType,
+    ) -> Instr {
+        let address_tys = match *instr {
+            MemoryCopy { dst, src } => vec![address_ty(dst), address_ty(src)],
+            _ => instr.memory().map(&address_ty).into_iter().collect(),
+        };
+        if address_tys.iter().all(|&ty| ty == I32) {
+            self.instr(instr, &[])
+        } else {
+            self.instr(instr, &address_tys)
+        }
+    }
+
     pub fn instr(&self, instr: &Instr, polymorphic_tys: &[ValType]) -> Instr {
         let ll_name = LowLevelHookName::polymorphic(instr.to_name(), polymorphic_tys);
         let generate_hook = |ll_name: String| {
+            // i64 for instructions accessing a 64-bit memory (memory64 extension), see `memory_instr`.
+            let address_ty = polymorphic_tys.first().copied().unwrap_or(I32);
+            let address_ty_src = polymorphic_tys.get(1).copied().unwrap_or(address_ty);
+            // the high-level hooks of memory instructions do not depend on the address type
+            let memory_hl_name =
+                &LowLevelHookName::monomorphic(instr.to_name()).to_mangled_string();
             match *instr {
             /*
                 monomorphic instructions:
@@ -147,7 +220,7 @@ impl HookMap {
                 - types are determined just from instruction
             */
 
-            Nop | Unreachable => Hook::new(&ll_name, args!(), &ll_name, ""),
+            Nop | Unreachable | AtomicFence => Hook::new(&ll_name, args!(), &ll_name, ""),
 
             If(_) => Hook::new(&ll_name, args!(condition: I32), "if_", "condition !== 0"),
             Br(_) => Hook::new(&ll_name, args!(targetLabel: I32, targetInstr: I32), &ll_name, "{label: targetLabel, location: {func, instr: targetInstr}}"),
@@ -155,24 +228,111 @@ impl HookMap {
             // NOTE js_args is very hacky! We rely on the Hook constructor to close the parenthesis and insert the call statement to endBrTableBlock() here
             BrTable { .. } => Hook::new(&ll_name, args!(tableIdx: I32, brTablesInfoIdx: I32), &ll_name, "Wasabi.module.info.brTables[brTablesInfoIdx].table, Wasabi.module.info.brTables[brTablesInfoIdx].default, tableIdx); Wasabi.endBrTableBlocks(brTablesInfoIdx, tableIdx, func"),
 
-            MemorySize(_) => Hook::new(&ll_name, args!(currentSizePages: I32), &ll_name, "currentSizePages"),
-            MemoryGrow(_) => Hook::new(&ll_name, args!(deltaPages: I32, previousSizePages: I32), &ll_name, "deltaPages, previousSizePages"),
+            // NOTE the memory index is the first low-level argument (since it is a constant that is
+            // pushed right after the location), but the last argument of the high-level hook.
+            MemorySize(_) => {
+                let args = args!(memoryIdx: I32, currentSizePages: address_ty);
+                let js_args = &format!("{}, memoryIdx", args[1].to_lowlevel_long_expr());
+                Hook::new(&ll_name, args, memory_hl_name, js_args)
+            }
+            MemoryGrow(_) => {
+                let args = args!(memoryIdx: I32, deltaPages: address_ty, previousSizePages: address_ty);
+                let js_args = &format!("{}, {}, memoryIdx", args[1].to_lowlevel_long_expr(), args[2].to_lowlevel_long_expr());
+                Hook::new(&ll_name, args, memory_hl_name, js_args)
+            }
+
+            MemoryCopy { .. } => {
+                // the length is only i64 if both memories are 64-bit
+                let len_ty = if address_ty == address_ty_src { address_ty } else { I32 };
+                let args = args!(destMemoryIdx: I32, srcMemoryIdx: I32, dest: address_ty, src: address_ty_src, len: len_ty);
+                let js_args = &format!("{}, {}, {}, destMemoryIdx, srcMemoryIdx", args[2].to_lowlevel_long_expr(), args[3].to_lowlevel_long_expr(), args[4].to_lowlevel_long_expr());
+                Hook::new(&ll_name, args, memory_hl_name, js_args)
+            }
+            MemoryFill(_) => {
+                let args = args!(memoryIdx: I32, dest: address_ty, value: I32, len: address_ty);
+                let js_args = &format!("{}, value, {}, memoryIdx", args[1].to_lowlevel_long_expr(), args[3].to_lowlevel_long_expr());
+                Hook::new(&ll_name, args, memory_hl_name, js_args)
+            }
+            MemoryInit(_, _) => {
+                let args = args!(memoryIdx: I32, dataIdx: I32, dest: address_ty, src: I32, len: I32);
+                let js_args = &format!("dataIdx, {}, src, len, memoryIdx", args[2].to_lowlevel_long_expr());
+                Hook::new(&ll_name, args, memory_hl_name, js_args)
+            }
+            DataDrop(_) => Hook::new(&ll_name, args!(dataIdx: I32), &ll_name, "dataIdx"),
 
             Load(op, _) => {
                 let ty = op.to_type().results()[0];
-                let args = args!(offset: I32, align: I32, addr: I32, value: ty);
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, value: ty);
                 let instr_name = instr.to_name();
-                let js_args = &format!("\"{}\", {{addr, offset, align}}, {}", instr_name, &args[3].to_lowlevel_long_expr());
+                let js_args = &format!("\"{}\", {}, {}", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr());
                 Hook::new(ll_name, args, "load", js_args)
             }
             Store(op, _) => {
                 let ty = op.to_type().inputs()[1];
-                let args = args!(offset: I32, align: I32, addr: I32, value: ty);
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, value: ty);
                 let instr_name = instr.to_name();
-                let js_args = &format!("\"{}\", {{addr, offset, align}}, {}", instr_name, &args[3].to_lowlevel_long_expr());
+                let js_args = &format!("\"{}\", {}, {}", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr());
                 Hook::new(ll_name, args, "store", js_args)
             }
 
+            // NOTE the value is only the accessed lane, see LoadLane in add_hooks
+            LoadLane(_, _, _) | StoreLane(_, _, _) => {
+                let lane_width_exp = match *instr {
+                    LoadLane(op, _, _) => op.natural_alignment_exp(),
+                    StoreLane(op, _, _) => op.natural_alignment_exp(),
+                    _ => unreachable!(),
+                };
+                let ty = super::lane_value_op(lane_width_exp).to_type().results()[0];
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, value: ty, lane: I32);
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, {}, lane", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr());
+                let hl_name = if let LoadLane(_, _, _) = instr { "load" } else { "store" };
+                Hook::new(ll_name, args, hl_name, js_args)
+            }
+
+            AtomicLoad(op, _) => {
+                let ty = op.to_type().results()[0];
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, value: ty);
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, {}", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr());
+                Hook::new(ll_name, args, "atomic_load", js_args)
+            }
+            AtomicStore(op, _) => {
+                let ty = op.to_type().inputs()[1];
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, value: ty);
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, {}", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr());
+                Hook::new(ll_name, args, "atomic_store", js_args)
+            }
+            AtomicRmw(op, _) => {
+                let ty = op.to_type().results()[0];
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, value: ty, oldValue: ty);
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, {}, {}", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr(), &args[5].to_lowlevel_long_expr());
+                Hook::new(ll_name, args, "atomic_rmw", js_args)
+            }
+            // NOTE shares the high-level hook with the other read-modify-write operations: the
+            // replacement is the value that is (potentially) written, the expected value is appended.
+            AtomicCmpxchg(op, _) => {
+                let ty = op.to_type().results()[0];
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, expected: ty, replacement: ty, oldValue: ty);
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, {}, {}, {}", instr_name, memarg_js_expr(&args), &args[5].to_lowlevel_long_expr(), &args[6].to_lowlevel_long_expr(), &args[4].to_lowlevel_long_expr());
+                Hook::new(ll_name, args, "atomic_rmw", js_args)
+            }
+            AtomicWaitNotify(AtomicWaitNotifyOp::MemoryAtomicNotify, _) => {
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, count: I32, wokenCount: I32);
+                let js_args = &format!("{}, count, wokenCount", memarg_js_expr(&args));
+                Hook::new(ll_name, args, "atomic_notify", js_args)
+            }
+            AtomicWaitNotify(op, _) => {
+                let ty = op.to_type().inputs()[1];
+                let args = args!(offset: address_ty, align: I32, memory: I32, addr: address_ty, expected: ty, timeout: I64, result: I32);
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, {}, {}, result", instr_name, memarg_js_expr(&args), &args[4].to_lowlevel_long_expr(), &args[5].to_lowlevel_long_expr());
+                Hook::new(ll_name, args, "atomic_wait", js_args)
+            }
+
             Const(val) => {
                 let ty = val.to_type();
                 let args = args!(value: ty);
@@ -199,6 +359,17 @@ impl HookMap {
                 Hook::new(ll_name, args, "binary", js_args)
             }
 
+            ExtractLane(_, _) | ReplaceLane(_, _) => {
+                let ty = instr.simple_type().unwrap();
+                let inputs = ty.inputs().iter().enumerate().map(|(i, &ty)| Arg { name: format!("input{i}"), ty });
+                let results = ty.results().iter().enumerate().map(|(i, &ty)| Arg { name: format!("result{i}"), ty });
+                let mut args = inputs.chain(results).collect::<Vec<_>>();
+                let instr_name = instr.to_name();
+                let js_args = &format!("\"{}\", {}, lane", instr_name, args.iter().map(Arg::to_lowlevel_long_expr).collect::<Vec<_>>().join(", "));
+                args.push(Arg { name: "lane".to_string(), ty: I32 });
+                let hl_name = if let ExtractLane(_, _) = instr { "unary" } else { "binary" };
+                Hook::new(ll_name, args, hl_name, js_args)
+            }
 
             /*
                 polymorphic instructions:
@@ -214,7 +385,7 @@ impl HookMap {
                 let js_args = &args[0].to_lowlevel_long_expr();
                 Hook::new(ll_name, args, "drop", js_args)
             }
-            Select => {
+            Select | TypedSelect(_) => {
                 assert_eq!(polymorphic_tys.len(), 2, "select has two polymorphic arguments");
                 assert_eq!(polymorphic_tys[0], polymorphic_tys[1], "select arguments must be equal");
                 let args = args!(condition: I32, input0: polymorphic_tys[0], input1: polymorphic_tys[1]);
@@ -240,24 +411,56 @@ impl HookMap {
                 let js_args = &format!("[{}]", args.iter().map(Arg::to_lowlevel_long_expr).collect::<Vec<_>>().join(", "));
                 Hook::new(ll_name, args, "return_", js_args)
             }
-            Call(_) => {
+            // NOTE tail calls (tail call extension) use the same high-level hook as regular calls
+            Call(_) | ReturnCall(_) => {
                 let mut args = args!(targetFunc: I32);
                 args.extend(polymorphic_tys.iter().enumerate().map(|(i, &ty)| Arg { name: format!("arg{i}"), ty }));
                 // NOTE calls the high-level call_pre hook with one argument less than call_indirect, thus tableIdx === undefined since this is a direct call
                 let js_args = &format!("targetFunc, [{}]", args[1..].iter().map(Arg::to_lowlevel_long_expr).collect::<Vec<_>>().join(", "));
                 Hook::new(ll_name, args, "call_pre", js_args)
             }
-            CallIndirect(_, _) => {
-                let mut args = args!(tableIndex: I32);
+            CallIndirect(_, _) | ReturnCallIndirect(_, _) => {
+                // NOTE the table index is a constant, pushed right after the location (like the memory index above)
+                let mut args = args!(table: I32, tableIndex: I32);
                 args.extend(polymorphic_tys.iter().enumerate().map(|(i, &ty)| Arg { name: format!("arg{i}"), ty }));
-                let js_args = &format!("Wasabi.resolveTableIdx(tableIndex), [{}], tableIndex", args[1..].iter().map(Arg::to_lowlevel_long_expr).collect::<Vec<_>>().join(", "));
+                let js_args = &format!("Wasabi.resolveTableIdx(tableIndex, table), [{}], tableIndex, table", args[2..].iter().map(Arg::to_lowlevel_long_expr).collect::<Vec<_>>().join(", "));
                 Hook::new(ll_name, args, "call_pre", js_args)
             }
 
 
             /* instructions that need additional information and thus have own method */
 
-            Block(_) | Loop(_) | Else | End => panic!("cannot get hook for block-type instruction with this method, please use the other methods specialized to the block type"),
+
+            /* exception handling: polymorphic in the values carried by the exception (= tag inputs) */
+
+            Throw(_) => {
+                let mut args = args!(tagIdx: I32);
+                args.extend(value_args(polymorphic_tys));
+                let js_args = &format!("tagIdx, {}", values_js_array(&args[1..]));
+                Hook::new(ll_name, args, "throw_", js_args)
+            }
+            Rethrow(_) => Hook::new(&ll_name, args!(targetLabel: I32, targetInstr: I32), &ll_name, "{label: targetLabel, location: {func, instr: targetInstr}}"),
+            // NOTE the begin hook for the catch block is called separately, see begin_catch()
+            Catch(_) => {
+                let mut args = args!(tryInstr: I32, tagIdx: I32);
+                args.extend(value_args(polymorphic_tys));
+                let js_args = &format!("{{func, instr: tryInstr}}, tagIdx, {}", values_js_array(&args[2..]));
+                Hook::new(ll_name, args, "catch_begin", js_args)
+            }
+            // tagIdx === undefined iff the handler is catch_all
+            CatchAll => Hook::new(&ll_name, args!(tryInstr: I32), "catch_begin", "{func, instr: tryInstr}, undefined, []"),
+
+            Block(_) | Loop(_) | Else | End | Try(_) | Delegate(_) => panic!("cannot get hook for block-type instruction with this method, please use the other methods specialized to the block type"),
+
+            /* reference and table instructions (reference types extension) do not have hooks (yet) */
+
+            RefNull(_) | RefIsNull | RefFunc(_)
+            | TableGet(_) | TableSet(_) | TableSize(_) | TableGrow(_) | TableFill(_)
+            | TableCopy { .. } | TableInit(_, _) | ElemDrop(_) => panic!("no hook for reference or table instruction {instr}"),
+
+            /* SIMD shuffle and bitselect instructions do not have hooks (yet) */
+
+            I8x16Shuffle(_) | V128Bitselect => panic!("no hook for SIMD instruction {instr}"),
             }
         };
 
@@ -297,86 +500,142 @@ impl HookMap {
 
     pub fn begin_function(&self) -> Instr {
         self.get_or_insert(LowLevelHookName::monomorphic("begin_function"), |ll_name| {
-            Hook::new(ll_name, vec![], "begin", "\"function\"")
+            Hook::new(ll_name, vec![], "begin", "\"function\", undefined, []")
         })
     }
 
-    pub fn begin_block(&self) -> Instr {
-        self.get_or_insert(LowLevelHookName::monomorphic("begin_block"), |ll_name| {
-            Hook::new(ll_name, vec![], "begin", "\"block\"")
-        })
+    /// input_tys: the block inputs, which are on the stack when the block begins (multi-value)
+    pub fn begin_block(&self, input_tys: &[ValType]) -> Instr {
+        self.get_or_insert(
+            LowLevelHookName::polymorphic("begin_block", input_tys),
+            |ll_name| {
+                let args = value_args(input_tys);
+                let js_args = &format!("\"block\", undefined, {}", values_js_array(&args));
+                Hook::new(ll_name, args, "begin", js_args)
+            },
+        )
     }
 
-    pub fn begin_loop(&self) -> Instr {
-        self.get_or_insert(LowLevelHookName::monomorphic("begin_loop"), |ll_name| {
-            Hook::new(ll_name, vec![], "begin", "\"loop\"")
-        })
+    pub fn begin_loop(&self, input_tys: &[ValType]) -> Instr {
+        self.get_or_insert(
+            LowLevelHookName::polymorphic("begin_loop", input_tys),
+            |ll_name| {
+                let args = value_args(input_tys);
+                let js_args = &format!("\"loop\", undefined, {}", values_js_array(&args));
+                Hook::new(ll_name, args, "begin", js_args)
+            },
+        )
     }
 
-    pub fn begin_if(&self) -> Instr {
-        self.get_or_insert(LowLevelHookName::monomorphic("begin_if"), |ll_name| {
-            Hook::new(ll_name, vec![], "begin", "\"if\"")
-        })
+    pub fn begin_if(&self, input_tys: &[ValType]) -> Instr {
+        self.get_or_insert(
+            LowLevelHookName::polymorphic("begin_if", input_tys),
+            |ll_name| {
+                let args = value_args(input_tys);
+                let js_args = &format!("\"if\", undefined, {}", values_js_array(&args));
+                Hook::new(ll_name, args, "begin", js_args)
+            },
+        )
     }
 
-    pub fn begin_else(&self) -> Instr {
-        self.get_or_insert(LowLevelHookName::monomorphic("begin_else"), |ll_name| {
-            Hook::new(
-                ll_name,
-                args!(ifInstr: I32),
-                "begin",
-                "\"else\", {func, instr: ifInstr}",
-            )
-        })
+    pub fn begin_else(&self, input_tys: &[ValType]) -> Instr {
+        self.get_or_insert(
+            LowLevelHookName::polymorphic("begin_else", input_tys),
+            |ll_name| {
+                let mut args = args!(ifInstr: I32);
+                args.extend(value_args(input_tys));
+                let js_args = &format!(
+                    "\"else\", {{func, instr: ifInstr}}, {}",
+                    values_js_array(&args[1..])
+                );
+                Hook::new(ll_name, args, "begin", js_args)
+            },
+        )
     }
 
-    pub fn end(&self, block: &BlockStackElement) -> Instr {
-        let (ll_name, generate_hook): (_, fn(String) -> Hook) = match *block {
-            BlockStackElement::Function { .. } => {
-                (LowLevelHookName::monomorphic("end_function"), |ll_name| {
-                    Hook::new(ll_name, vec![], "end", "\"function\", {func, instr: -1}")
-                })
-            }
-            BlockStackElement::Block { .. } => {
-                (LowLevelHookName::monomorphic("end_block"), |ll_name| {
-                    Hook::new(
-                        ll_name,
-                        args!(beginInstr: I32),
-                        "end",
-                        "\"block\", {func, instr: beginInstr}",
-                    )
-                })
-            }
-            BlockStackElement::Loop { .. } => {
-                (LowLevelHookName::monomorphic("end_loop"), |ll_name| {
-                    Hook::new(
-                        ll_name,
-                        args!(beginInstr: I32),
-                        "end",
-                        "\"loop\", {func, instr: beginInstr}",
-                    )
-                })
-            }
-            BlockStackElement::If { .. } => (LowLevelHookName::monomorphic("end_if"), |ll_name| {
-                Hook::new(
-                    ll_name,
-                    args!(beginInstr: I32),
-                    "end",
-                    "\"if\", {func, instr: beginInstr}",
-                )
-            }),
-            BlockStackElement::Else { .. } => {
-                (LowLevelHookName::monomorphic("end_else"), |ll_name| {
-                    Hook::new(
-                        ll_name,
-                        args!(elseInstr: I32, ifInstr: I32),
-                        "end",
-                        "\"else\", {func, instr: elseInstr}, {func, instr: ifInstr}",
-                    )
-                })
-            }
+    pub fn begin_try(&self, input_tys: &[ValType]) -> Instr {
+        self.get_or_insert(
+            LowLevelHookName::polymorphic("begin_try", input_tys),
+            |ll_name| {
+                let args = value_args(input_tys);
+                let js_args = &format!("\"try\", undefined, {}", values_js_array(&args));
+                Hook::new(ll_name, args, "begin", js_args)
+            },
+        )
+    }
+
+    /// exception_tys: the values carried by the caught exception, which are the inputs of the
+    /// catch block (empty for catch_all)
+    pub fn begin_catch(&self, exception_tys: &[ValType]) -> Instr {
+        self.get_or_insert(
+            LowLevelHookName::polymorphic("begin_catch", exception_tys),
+            |ll_name| {
+                let mut args = args!(tryInstr: I32);
+                args.extend(value_args(exception_tys));
+                let js_args = &format!(
+                    "\"catch\", {{func, instr: tryInstr}}, {}",
+                    values_js_array(&args[1..])
+                );
+                Hook::new(ll_name, args, "begin", js_args)
+            },
+        )
+    }
+
+    /// result_tys: the block results, which are on the stack when the block ends regularly
+    /// (i.e., via end, else, catch, or delegate, not via a branch or an exception, for which
+    /// result_tys should be empty)
+    pub fn end(&self, block: &BlockStackElement, result_tys: &[ValType]) -> Instr {
+        let (hook_stem, block_args, js_block_args) = match *block {
+            BlockStackElement::Function { .. } => (
+                "end_function",
+                args!(),
+                "\"function\", {func, instr: -1}, undefined",
+            ),
+            BlockStackElement::Block { .. } => (
+                "end_block",
+                args!(beginInstr: I32),
+                "\"block\", {func, instr: beginInstr}, undefined",
+            ),
+            BlockStackElement::Loop { .. } => (
+                "end_loop",
+                args!(beginInstr: I32),
+                "\"loop\", {func, instr: beginInstr}, undefined",
+            ),
+            BlockStackElement::If { .. } => (
+                "end_if",
+                args!(beginInstr: I32),
+                "\"if\", {func, instr: beginInstr}, undefined",
+            ),
+            BlockStackElement::Else { .. } => (
+                "end_else",
+                args!(elseInstr: I32, ifInstr: I32),
+                "\"else\", {func, instr: elseInstr}, {func, instr: ifInstr}",
+            ),
+            BlockStackElement::Try { .. } => (
+                "end_try",
+                args!(beginInstr: I32),
+                "\"try\", {func, instr: beginInstr}, undefined",
+            ),
+            BlockStackElement::Catch { .. } => (
+                "end_catch",
+                args!(catchInstr: I32, tryInstr: I32),
+                "\"catch\", {func, instr: catchInstr}, {func, instr: tryInstr}",
+            ),
         };
-        self.get_or_insert(ll_name, generate_hook)
+        self.get_or_insert(
+            LowLevelHookName::polymorphic(hook_stem, result_tys),
+            |ll_name| {
+                let mut args = block_args.clone();
+                let block_args_count = args.len();
+                args.extend(value_args(result_tys));
+                let js_args = &format!(
+                    "{}, {}",
+                    js_block_args,
+                    values_js_array(&args[block_args_count..])
+                );
+                Hook::new(ll_name, args, "end", js_args)
+            },
+        )
     }
 
     /// returns a Call instruction to the requested hook, which either
@@ -0,0 +1,120 @@
+use wasabi_wasm::Function;
+use wasabi_wasm::Idx;
+use wasabi_wasm::Instr;
+use wasabi_wasm::Offsets;
+
+/// Maps instructions of the instrumented module back to the instructions of the original module,
+/// e.g., to show the original location of a trap or stack trace in the instrumented binary.
+///
+/// Every instrumented instruction maps to the original instruction it was copied from or inserted
+/// for, e.g., all instructions that save the arguments of a `call` and call its hooks map to that
+/// `call`. Instructions inserted before the first original one (start and function begin hooks)
+/// and the added hook functions themselves have no original instruction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocationMap {
+    /// Indexed by function and then by instrumented instruction.
+    /// Function indices are the same in the original and instrumented module, since the
+    /// imported hook functions are appended at the end.
+    functions: Vec<Vec<Option<Idx<Instr>>>>,
+    /// The inverse, indexed by function and then by original instruction.
+    instrumented: Vec<Vec<Option<Idx<Instr>>>>,
+}
+
+impl LocationMap {
+    pub(crate) fn new(functions: Vec<Vec<Option<Idx<Instr>>>>) -> Self {
+        let instrumented = functions
+            .iter()
+            .map(|origins| {
+                let original_len = origins
+                    .iter()
+                    .flatten()
+                    .max()
+                    .map_or(0, |instr| instr.to_usize() + 1);
+                let mut instrumented = vec![None; original_len];
+                // Iterate in reverse, such that the first instrumented instruction wins.
+                for (instrumented_instr, origin) in origins.iter().enumerate().rev() {
+                    if let Some(original_instr) = origin {
+                        instrumented[original_instr.to_usize()] = Some(instrumented_instr.into());
+                    }
+                }
+                // Original instructions that were removed (e.g., `nop`s) continue at the next one.
+                for i in (0..original_len.saturating_sub(1)).rev() {
+                    if instrumented[i].is_none() {
+                        instrumented[i] = instrumented[i + 1];
+                    }
+                }
+                instrumented
+            })
+            .collect();
+        LocationMap {
+            functions,
+            instrumented,
+        }
+    }
+
+    /// Returns the original instruction of the given instruction in the instrumented module.
+    pub fn original_instr(
+        &self,
+        function: Idx<Function>,
+        instrumented_instr: Idx<Instr>,
+    ) -> Option<Idx<Instr>> {
+        *self
+            .functions
+            .get(function.to_usize())?
+            .get(instrumented_instr.to_usize())?
+    }
+
+    /// Returns the first instruction in the instrumented module that was copied from or inserted
+    /// for the given original instruction, e.g., the first instruction that saves the arguments of
+    /// a `call` for its hooks.
+    pub fn instrumented_instr(
+        &self,
+        function: Idx<Function>,
+        original_instr: Idx<Instr>,
+    ) -> Option<Idx<Instr>> {
+        *self
+            .instrumented
+            .get(function.to_usize())?
+            .get(original_instr.to_usize())?
+    }
+
+    /// Returns the offset of the original instruction in the original binary, given any offset
+    /// inside an instruction of the instrumented binary.
+    ///
+    /// `original` are the offsets from parsing the original binary with
+    /// `ParseOptions::instr_offsets`, `instrumented` from encoding the instrumented module
+    /// (`Module::to_bytes_with_offsets`).
+    pub fn original_offset(
+        &self,
+        instrumented_offset: usize,
+        original: &Offsets,
+        instrumented: &Offsets,
+    ) -> Option<usize> {
+        let (function, instrumented_instr) =
+            instrumented.instr_offset_to_idx(instrumented_offset)?;
+        let original_instr = self.original_instr(function, instrumented_instr)?;
+        original.instr_idx_to_offset(function, original_instr)
+    }
+
+    /// Returns pairs of instrumented and original instruction offsets, sorted by the former, for
+    /// all instrumented instructions that have an original instruction.
+    /// See `original_offset` for the arguments.
+    pub fn offset_map(&self, original: &Offsets, instrumented: &Offsets) -> Vec<(usize, usize)> {
+        let mut offset_map = Vec::new();
+        for (function, instrumented_offsets) in &instrumented.instrs {
+            for (instrumented_instr, &instrumented_offset) in
+                instrumented_offsets.iter().enumerate()
+            {
+                let original_offset = self
+                    .original_instr(*function, instrumented_instr.into())
+                    .and_then(|original_instr| {
+                        original.instr_idx_to_offset(*function, original_instr)
+                    });
+                if let Some(original_offset) = original_offset {
+                    offset_map.push((instrumented_offset, original_offset));
+                }
+            }
+        }
+        offset_map
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
 use std::convert::TryInto;
 
 use parking_lot::RwLock;
 use rayon::prelude::*;
 use serde_json;
+use wasabi_wasm::AtomicWaitNotifyOp;
+use wasabi_wasm::ExtractLaneOp;
 use wasabi_wasm::Function;
 use wasabi_wasm::FunctionType;
 use wasabi_wasm::GlobalOp;
@@ -11,11 +14,17 @@ use wasabi_wasm::Instr;
 use wasabi_wasm::Instr::*;
 use wasabi_wasm::Label;
 use wasabi_wasm::LocalOp::*;
+use wasabi_wasm::Memarg;
+use wasabi_wasm::Memory;
 use wasabi_wasm::MemoryOp;
 use wasabi_wasm::Module;
 use wasabi_wasm::Mutability;
+use wasabi_wasm::ParseError;
+use wasabi_wasm::Tag;
 use wasabi_wasm::Val;
+use wasabi_wasm::ValType;
 use wasabi_wasm::ValType::*;
+use wasabi_wasm::WasmExtension;
 
 use crate::options::Hook;
 use crate::options::HookSet;
@@ -25,30 +34,68 @@ use self::block_stack::BlockStackElement;
 use self::convert_i64::convert_i64_instr;
 use self::duplicate_stack::*;
 use self::hook_map::HookMap;
+pub use self::location_map::LocationMap;
 use self::static_info::*;
-use self::type_stack::TypeStack;
 
 pub mod block_stack;
 mod convert_i64;
 mod duplicate_stack;
 mod hook_map;
+mod location_map;
+pub mod source_map;
 mod static_info;
-pub mod type_stack;
+
+/// Extensions beyond the WebAssembly MVP that `add_hooks` can instrument.
+/// Modules using any other extension are rejected by the command-line tool.
+pub const SUPPORTED_EXTENSIONS: &[WasmExtension] = &[
+    WasmExtension::SignExtensionOps,
+    WasmExtension::NontrappingFloatToInt,
+    WasmExtension::BulkMemoryOperations,
+    WasmExtension::MultiValue,
+    WasmExtension::ReferenceTypes,
+    WasmExtension::Simd,
+    WasmExtension::ThreadsAtomics,
+    WasmExtension::Memory64,
+    WasmExtension::MultiMemory,
+    WasmExtension::ExceptionHandling,
+    WasmExtension::TailCalls,
+    WasmExtension::ExtendedNameSection,
+];
 
 /// Instruments every instruction in Jalangi-style with a callback that takes inputs, outputs, and
 /// other relevant information.
+/// Returns the generated JavaScript, the number of hooks, and where the instrumented
+/// instructions come from in the original module, or an error if the module was parsed lazily and
+/// a function body is malformed.
 #[allow(clippy::cognitive_complexity)]
 pub fn add_hooks(
     module: &mut Module,
     enabled_hooks: HookSet,
     node_js: bool,
-) -> Option<(String, usize)> {
-    // make sure table is exported, needed for Wasabi runtime to resolve table indices to function indices.
-    for table in &mut module.tables {
+) -> Result<(String, usize, LocationMap), ParseError> {
+    // decode lazily parsed bodies before modifying the module, so malformed ones are reported
+    // instead of panicking when they are accessed below
+    module.decode_lazy_bodies()?;
+
+    // make sure all tables are exported, needed for Wasabi runtime to resolve table indices to function indices.
+    for (table_idx, table) in module.tables.iter_mut().enumerate() {
         if table.export.is_empty() {
-            table.export.push("__wasabi_table".into());
+            // export names must be unique, so only the first table gets the name without suffix
+            let export_name = if table_idx == 0 {
+                "__wasabi_table".to_string()
+            } else {
+                format!("__wasabi_table_{table_idx}")
+            };
+            table.export.push(export_name);
         }
     }
+    // for typing the memory instructions (i64 addresses with the memory64 extension)
+    let memory_address_tys: Vec<ValType> =
+        module.memories.iter().map(Memory::address_type).collect();
+    let address_ty_of = |memory: Idx<Memory>| memory_address_tys[memory.to_usize()];
+    // for typing throw and catch (exception handling extension)
+    let tag_tys: Vec<FunctionType> = module.tags.iter().map(|tag| tag.type_).collect();
+    let tag_ty_of = |tag: Idx<Tag>| tag_tys[tag.to_usize()];
     // FIXME is this a valid workaround for wrong Firefox exported function .name property?
     //    if let Some(function) = module.functions.first_mut() {
     //        if function.export.is_empty() {
@@ -68,28 +115,51 @@ pub fn add_hooks(
         None
     };
 
-    module.functions.par_iter_mut().enumerate().for_each(|(fidx, function): (usize, &mut Function)| {
+    // concrete types of all instructions (including those in dead code), e.g., for monomorphizing
+    // drop and select, whose input types cannot be determined from the instruction alone
+    // NOTE computed upfront, because the type checker needs the module, which is mutably borrowed below
+    let instr_tys: Vec<Vec<FunctionType>> = module
+        .functions
+        .par_iter()
+        .enumerate()
+        .map(|(fidx, function)| {
+            let instrs = function
+                .instrs_with_concrete_types(module)
+                .unwrap_or_else(|err| {
+                    panic!(
+                        "cannot instrument function #{fidx}, because it is not well-typed: {err}"
+                    )
+                });
+            instrs.into_iter().map(|instr| instr.type_).collect()
+        })
+        .collect();
+
+    let instr_origins = module.functions.par_iter_mut().enumerate().map(|(fidx, function): (usize, &mut Function)| {
+        let instr_tys = &instr_tys[fidx];
         let fidx = fidx.into();
         // only instrument non-imported functions
         if function.code().is_none() {
-            return;
+            return Vec::new();
         }
 
         // move body out of function, so that function is not borrowed during iteration over the original body
         let original_body = {
             let dummy_body = Vec::new();
-            ::std::mem::replace(&mut function.code_mut().expect("internal error: function code should exist, see check above").body, dummy_body)
+            ::std::mem::replace(&mut *function.code_mut().expect("internal error: function code should exist, see check above").body, dummy_body)
         };
+        // label names are by position of the block among all blocks, which shifts when inserting blocks
+        let label_names = ::std::mem::take(&mut function.code_mut().unwrap().label_names);
+        let label_names = label_names_by_instr(&original_body, label_names);
 
         // allocate new instrumented body (i.e., do not modify in-place), since there are too many insertions anyway
         // there are at least 3 new instructions per original one (2 const for location + 1 hook call)
         // later increased to 6, since we saw a lot of re-allocations when analyzing Wasabi with heaptrack.
         let mut instrumented_body = Vec::with_capacity(6 * original_body.len());
+        // for every instrumented instruction, the original instruction it was inserted for (if any)
+        let mut instr_origins: Vec<Option<Idx<Instr>>> = Vec::with_capacity(instrumented_body.capacity());
 
         // for branch target resolution (i.e., relative labels -> instruction locations)
         let mut block_stack = BlockStack::new(&original_body);
-        // for drop/select monomorphization (cannot determine their input types only from instruction, but need this additional type information)
-        let mut type_stack = TypeStack::new();
 
         // execute start hook before anything else
         if module_info.read().start == Some(fidx)
@@ -118,73 +188,31 @@ pub fn add_hooks(
         }
 
         // remember implicit return for instrumentation: add "synthetic" return hook call to last end
-        let implicit_return = !original_body.ends_with(&[Return, End]);
-
-        // WebAssembly's type rules are weird with unreachable code (i.e., code after an
-        // "unreachable" instruction or after unconditional branches like return/br/br_table):
-        // - Dead code needs to be type-correct for "existing types" on the stack. E.g,
-        //   unreachable; i32.const 0; f32.abs
-        //   is wrong because the second instruction pops a type-incompatible argument (f32 vs i32).
-        // - But "missing types" on the stack are "synthesized" from nothing. E.g.,
-        //   unreachable; f32.abs
-        //   is type correct as per the spec because unreachable "produces" the f32 on the stack
-        //   for the f32.abs instruction out of nothing.
-        //
-        // (I don't know if any real-world compiler produces unreachable code that exploits (2),
-        // but the spec test suite certainly does.
-        // Also, I have read somewhere that this is supposedly to make implementing compilers easier,
-        // but to me it looks like it makes type checking more _complicated_!?
-        // Since we can't change the spec now, I guess we are stuck with it though...)
-        //
-        // Unfortunately, Wasabi's type checking (in type_stack) is not powerful enough
-        // to "produce" the missing types for unreachable code.
-        // (TODO I think this needs some form of primitive unification. E.g., checking
-        // [unreachable, f32] against [i32, f32, f32]
-        // should "expand" or unify the type of the unreachable instruction to/with [i32, f32].)
-        //
-        // Since I don't want to introduce this kind of complexity, and because unreachable code
-        // is by definition never executed, a workaround is to simply not instrument unreachable
-        // code. Instead, I just copy it over unaltered. This should still be type-correct
-        // because Wasabi's instrumentation always leaves the stack after an instruction + hook
-        // the same as without instrumentation.
-        //
-        // Conceptually, when we encounter an "unreachable" instruction (or br/return/br_table),
-        // we switch to "unreachable mode" and do not instrument. We stop the "unreachable mode"
-        // (and start to properly instrument again) when we see an end or else (which closes the
-        // current dead block).
-        //
-        // However, because unreachable code can itself contain more blocks, we must actually
-        // count the depth for "how far we are in unreachable mode" and only stop once we reach 0.
-        let mut unreachable_depth = 0;
+        // (tail calls also return explicitly, see ReturnCall below)
+        let implicit_return = !matches!(
+            original_body.as_slice(),
+            [.., Return | ReturnCall(_) | ReturnCallIndirect(_, _), End]
+        );
 
+        // NOTE unreachable code (e.g., after br or return) is instrumented just like reachable code:
+        // values that are "produced out of thin air" by stack-polymorphic instructions still get
+        // concrete types from the type checker, so the inserted locals and hook calls are type-correct
+        // even though they are never executed.
+
+        let original_len = original_body.len();
         for (iidx, instr) in original_body.into_iter().enumerate() {
+            // everything inserted since the previous original instruction belongs to it
+            let previous_iidx = iidx.checked_sub(1).map(Idx::from);
+            instr_origins.resize(instrumented_body.len(), previous_iidx);
 
-            // End or Else could end the current "unreachable" block.
-            if unreachable_depth > 0 {
-                match instr {
-                    Else | End => unreachable_depth -= 1,
-                    _ => {}
-                };
-            }
-            // Still unreachable, even after closing the current block?
-            if unreachable_depth > 0 {
-                // 1. Copy over the instruction unaltered
-                instrumented_body.push(instr.clone());
-                // 2. If the unreachable code itself contains even deeper blocks, increase the "unreachable depth".
-                match instr {
-                    // NOTE Else can also open a "deeper" unreachable block, but only if we were unreachable to begin with.
-                    Block(_) | Loop(_) | If(_) | Else => unreachable_depth += 1,
-                    _ => {}
-                };
-                // 3. DO NOT instrument unreachable code, since type_stack will throw an exception on
-                // instructions that pop types that are "magically produced" by unreachable code.
-                continue;
-            }
+            let instr_ty = instr_tys[iidx];
 
 //            println!("{:?}:{:?}: {:?}", fidx.0, iidx, instr);
 
             let iidx: Idx<Instr> = iidx.into();
             let location = (fidx.to_const(), iidx.to_const());
+            // only relevant for instructions that access memory (destination memory for memory.copy)
+            let address_ty = instr.memory().map_or(I32, address_ty_of);
 
             /*
              * add calls to hooks, typical instructions inserted for (not necessarily in this order if that saves us a local or so):
@@ -214,8 +242,6 @@ pub fn add_hooks(
                     }
 
                     instrumented_body.push(instr);
-
-                    unreachable_depth = 1;
                 }
 
 
@@ -223,36 +249,40 @@ pub fn add_hooks(
 
                 Block(block_ty) => {
                     block_stack.begin_block(iidx);
-                    type_stack.begin(block_ty);
 
                     instrumented_body.push(instr);
 
                     if enabled_hooks.contains(Hook::Begin) {
+                        let input_tmps = function.add_fresh_locals(block_ty.inputs());
+
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
                         instrumented_body.extend_from_slice(&[
                             location.0,
                             location.1,
-                            hooks.begin_block(),
-                        ])
+                        ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.begin_block(block_ty.inputs()));
                     }
                 }
                 Loop(block_ty) => {
                     block_stack.begin_loop(iidx);
-                    type_stack.begin(block_ty);
 
                     instrumented_body.push(instr);
 
                     if enabled_hooks.contains(Hook::Begin) {
+                        let input_tmps = function.add_fresh_locals(block_ty.inputs());
+
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
                         instrumented_body.extend_from_slice(&[
                             location.0,
                             location.1,
-                            hooks.begin_loop(),
-                        ])
+                        ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.begin_loop(block_ty.inputs()));
                     }
                 }
                 If(block_ty) => {
                     block_stack.begin_if(iidx);
-                    type_stack.instr(&FunctionType::new(&[I32], &[]));
-                    type_stack.begin(block_ty);
 
                     // if_ hook for the condition (always executed on either branch)
                     if enabled_hooks.contains(Hook::If) {
@@ -272,11 +302,15 @@ pub fn add_hooks(
 
                     // begin hook (not executed when condition implies else branch)
                     if enabled_hooks.contains(Hook::Begin) {
+                        let input_tmps = function.add_fresh_locals(block_ty.inputs());
+
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
                         instrumented_body.extend_from_slice(&[
                             location.0,
                             location.1,
-                            hooks.begin_if()
                         ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.begin_if(block_ty.inputs()));
                     }
                 }
                 Else => {
@@ -287,32 +321,44 @@ pub fn add_hooks(
                         unreachable!()
                     };
 
-                    type_stack.else_();
+                    // the type of else has the inputs and results of the if block
+                    let block_ty = instr_ty;
 
+                    // end hook for the if block, reports the results of the "then" branch
                     if enabled_hooks.contains(Hook::End) {
+                        let result_tmps = function.add_fresh_locals(block_ty.results());
+
+                        save_stack_to_locals(&mut instrumented_body, &result_tmps);
                         instrumented_body.extend_from_slice(&[
                             location.0.clone(),
                             location.1.clone(),
                             begin_if.to_const(),
-                            hooks.end(&if_block),
                         ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, result_tmps, function);
+                        instrumented_body.push(hooks.end(&if_block, block_ty.results()));
                     }
 
                     instrumented_body.push(instr);
 
+                    // begin hook for the else block, reports the same inputs as for the if block
                     if enabled_hooks.contains(Hook::Begin) {
+                        let input_tmps = function.add_fresh_locals(block_ty.inputs());
+
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
                         instrumented_body.extend_from_slice(&[
                             location.0,
                             location.1,
                             begin_if.to_const(),
-                            hooks.begin_else(),
-                        ])
+                        ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.begin_else(block_ty.inputs()));
                     }
                 }
-                End => {
+                // Delegate ends the try block just like End (exceptions are forwarded, see Throw below)
+                End | Delegate(_) => {
                     let block = block_stack.end();
                     assert_eq!(iidx, block.end());
-                    type_stack.end();
+                    let block_ty = instr_ty;
 
                     // add "synthetic" return hook call for implicit returns
                     if implicit_return
@@ -335,8 +381,167 @@ pub fn add_hooks(
                     // because the end hook that is inserted now is never called (dead code)
 
                     if enabled_hooks.contains(Hook::End) {
+                        let result_tmps = function.add_fresh_locals(block_ty.results());
+
+                        save_stack_to_locals(&mut instrumented_body, &result_tmps);
                         block.append_end_hook_args(&mut instrumented_body, fidx);
-                        instrumented_body.push(hooks.end(&block))
+                        restore_locals_with_i64_handling(&mut instrumented_body, result_tmps, function);
+                        instrumented_body.push(hooks.end(&block, block_ty.results()))
+                    }
+
+                    instrumented_body.push(instr);
+                }
+
+
+                /* Control Instructions: Exceptions */
+
+                Try(block_ty) => {
+                    block_stack.begin_try(iidx);
+
+                    instrumented_body.push(instr);
+
+                    if enabled_hooks.contains(Hook::Begin) {
+                        let input_tmps = function.add_fresh_locals(block_ty.inputs());
+
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                        ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.begin_try(block_ty.inputs()));
+                    }
+                }
+                Catch(_) | CatchAll => {
+                    let exception_ty = match instr {
+                        Catch(tag) => tag_ty_of(tag),
+                        _ => FunctionType::empty(),
+                    };
+                    let exception_tys = exception_ty.inputs();
+
+                    let ended_block = block_stack.catch_(iidx);
+                    let begin_try = match ended_block {
+                        BlockStackElement::Try { begin, .. } => begin,
+                        BlockStackElement::Catch { begin_try, .. } => begin_try,
+                        _ => unreachable!()
+                    };
+
+                    let block_ty = instr_ty;
+
+                    // end hook for the try (or previous catch) block, reports its results
+                    // (like else, this is only executed if the block was not left early)
+                    if enabled_hooks.contains(Hook::End) {
+                        let result_tmps = function.add_fresh_locals(block_ty.results());
+
+                        save_stack_to_locals(&mut instrumented_body, &result_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            location.0.clone(),
+                            location.1.clone(),
+                        ]);
+                        if let BlockStackElement::Catch { begin_catch, .. } = ended_block {
+                            instrumented_body.push(begin_catch.to_const());
+                        }
+                        instrumented_body.push(begin_try.to_const());
+                        restore_locals_with_i64_handling(&mut instrumented_body, result_tmps, function);
+                        instrumented_body.push(hooks.end(&ended_block, block_ty.results()));
+                    }
+
+                    instrumented_body.push(instr.clone());
+
+                    // the handler is only executed if an exception was caught, which unwound the
+                    // try block (the throw and rethrow hooks only report blocks _inside_ of it)
+                    // NOTE no values are reported for it, since it is not ended regularly
+                    if enabled_hooks.contains(Hook::End) {
+                        let try_block = BlockStackElement::Try {
+                            begin: begin_try,
+                            end: ended_block.end(),
+                        };
+                        try_block.append_end_hook_args(&mut instrumented_body, fidx);
+                        instrumented_body.push(hooks.end(&try_block, &[]));
+                    }
+
+                    // catch_begin hook, reports the values carried by the exception
+                    if enabled_hooks.contains(Hook::CatchBegin) {
+                        let value_tmps = function.add_fresh_locals(exception_tys);
+
+                        save_stack_to_locals(&mut instrumented_body, &value_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            location.0.clone(),
+                            location.1.clone(),
+                            begin_try.to_const(),
+                        ]);
+                        if let Catch(tag) = instr {
+                            instrumented_body.push(tag.to_const());
+                        }
+                        restore_locals_with_i64_handling(&mut instrumented_body, value_tmps, function);
+                        instrumented_body.push(hooks.instr(&instr, exception_tys));
+                    }
+
+                    // begin hook for the catch block, whose inputs are the same values
+                    if enabled_hooks.contains(Hook::Begin) {
+                        let input_tmps = function.add_fresh_locals(exception_tys);
+
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                            begin_try.to_const(),
+                        ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.begin_catch(exception_tys));
+                    }
+                }
+                Throw(tag) => {
+                    let exception_ty = tag_ty_of(tag);
+                    let exception_tys = exception_ty.inputs();
+
+                    // throw hook
+                    if enabled_hooks.contains(Hook::Throw) {
+                        let value_tmps = function.add_fresh_locals(exception_tys);
+
+                        save_stack_to_locals(&mut instrumented_body, &value_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                            tag.to_const(),
+                        ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, value_tmps, function);
+                        instrumented_body.push(hooks.instr(&instr, exception_tys));
+                    }
+
+                    // end hooks for all blocks that are unwound until the handler (or that are
+                    // left, if the exception is not caught in this function)
+                    // NOTE exceptions thrown in called functions are only reported by the
+                    // end hook for the try block, when the handler begins (see Catch above)
+                    if enabled_hooks.contains(Hook::End) {
+                        for block in block_stack.unwound_blocks(Some(tag)) {
+                            block.append_end_hook_args(&mut instrumented_body, fidx);
+                            instrumented_body.push(hooks.end(&block, &[]));
+                        }
+                    }
+
+                    instrumented_body.push(instr);
+                }
+                Rethrow(target_label) => {
+                    let rethrow_target = block_stack.rethrow_target(target_label);
+
+                    // rethrow hook
+                    if enabled_hooks.contains(Hook::Rethrow) {
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                            target_label.to_const(),
+                            rethrow_target.absolute_instr.to_const(),
+                            hooks.instr(&instr, &[])
+                        ])
+                    }
+
+                    // end hooks for all unwound blocks, see Throw above
+                    if enabled_hooks.contains(Hook::End) {
+                        for block in rethrow_target.ended_blocks {
+                            block.append_end_hook_args(&mut instrumented_body, fidx);
+                            instrumented_body.push(hooks.end(&block, &[]));
+                        }
                     }
 
                     instrumented_body.push(instr);
@@ -361,19 +566,17 @@ pub fn add_hooks(
                     }
 
                     // end hooks for all intermediate blocks that are "jumped over"
+                    // NOTE no values are reported for them, since they are not ended regularly
                     if enabled_hooks.contains(Hook::End) {
                         for block in br_target.ended_blocks {
                             block.append_end_hook_args(&mut instrumented_body, fidx);
-                            instrumented_body.push(hooks.end(&block));
+                            instrumented_body.push(hooks.end(&block, &[]));
                         }
                     }
 
                     instrumented_body.push(instr);
-
-                    unreachable_depth = 1;
                 }
                 BrIf(target_label) => {
-                    type_stack.instr(&FunctionType::new(&[I32], &[]));
 
                     let br_target = block_stack.br_target(target_label);
 
@@ -407,7 +610,7 @@ pub fn add_hooks(
                             ]);
                             for block in br_target.ended_blocks {
                                 block.append_end_hook_args(&mut instrumented_body, fidx);
-                                instrumented_body.push(hooks.end(&block));
+                                instrumented_body.push(hooks.end(&block, &[]));
                             }
                             // of the artificially inserted if block before
                             instrumented_body.push(End);
@@ -417,7 +620,6 @@ pub fn add_hooks(
                     instrumented_body.push(instr)
                 }
                 BrTable { ref table, default } => {
-                    type_stack.instr(&FunctionType::new(&[I32], &[]));
 
                     if enabled_hooks.contains(Hook::BrTable)
                         // because end hooks are called at runtime, we need to instrument even if br_table is not enabled
@@ -447,15 +649,12 @@ pub fn add_hooks(
                     }
 
                     instrumented_body.push(instr.clone());
-
-                    unreachable_depth = 1;
                 }
 
 
                 /* Control Instructions: Calls & Returns */
 
                 Return => {
-                    type_stack.instr(&FunctionType::new(&[], function.type_.results()));
 
                     // return hook
                     if enabled_hooks.contains(Hook::Return) {
@@ -475,17 +674,14 @@ pub fn add_hooks(
                     if enabled_hooks.contains(Hook::End) {
                         for block in block_stack.return_target().ended_blocks {
                             block.append_end_hook_args(&mut instrumented_body, fidx);
-                            instrumented_body.push(hooks.end(&block));
+                            instrumented_body.push(hooks.end(&block, &[]));
                         }
                     }
 
                     instrumented_body.push(instr);
-
-                    unreachable_depth = 1;
                 }
                 Call(target_func_idx) => {
                     let func_ty = &module_info.read().functions[target_func_idx.to_usize()].type_;
-                    type_stack.instr(func_ty);
 
                     if enabled_hooks.contains(Hook::Call) {
                         /* pre call hook */
@@ -519,8 +715,7 @@ pub fn add_hooks(
                         instrumented_body.push(instr);
                     }
                 }
-                CallIndirect(ref func_ty, _ /* table idx == 0 in WASM version 1 */) => {
-                    type_stack.instr(&instr.simple_type().unwrap());
+                CallIndirect(ref func_ty, table_idx) => {
 
                     if enabled_hooks.contains(Hook::Call) {
                         /* pre call hook */
@@ -534,6 +729,7 @@ pub fn add_hooks(
                             Local(Get, target_table_idx_tmp),
                             location.0.clone(),
                             location.1.clone(),
+                            table_idx.to_const(),
                             Local(Get, target_table_idx_tmp),
                         ]);
                         restore_locals_with_i64_handling(&mut instrumented_body, arg_tmps, function);
@@ -557,12 +753,70 @@ pub fn add_hooks(
                         instrumented_body.push(instr.clone());
                     }
                 }
+                // Tail calls (tail call extension): the current function is left before the
+                // callee runs, i.e., we call the pre call hook, followed by return and end hooks
+                // for the current function. There is no post call hook, because the callee returns
+                // directly to our caller.
+                ReturnCall(_) | ReturnCallIndirect(_, _) => {
+                    let func_ty = match instr {
+                        ReturnCall(target_func_idx) => module_info.read().functions[target_func_idx.to_usize()].type_,
+                        ReturnCallIndirect(func_ty, _) => func_ty,
+                        _ => unreachable!(),
+                    };
+
+                    /* pre call hook */
+                    if enabled_hooks.contains(Hook::Call) {
+                        let arg_tmps = function.add_fresh_locals(func_ty.inputs());
+
+                        if let ReturnCall(target_func_idx) = instr {
+                            save_stack_to_locals(&mut instrumented_body, &arg_tmps);
+                            instrumented_body.extend_from_slice(&[
+                                location.0.clone(),
+                                location.1.clone(),
+                                target_func_idx.to_const(),
+                            ]);
+                        } else if let ReturnCallIndirect(_, table_idx) = instr {
+                            let target_table_idx_tmp = function.add_fresh_local(I32);
+
+                            instrumented_body.push(Local(Set, target_table_idx_tmp));
+                            save_stack_to_locals(&mut instrumented_body, &arg_tmps);
+                            instrumented_body.extend_from_slice(&[
+                                Local(Get, target_table_idx_tmp),
+                                location.0.clone(),
+                                location.1.clone(),
+                                table_idx.to_const(),
+                                Local(Get, target_table_idx_tmp),
+                            ]);
+                        }
+                        restore_locals_with_i64_handling(&mut instrumented_body, arg_tmps, function);
+                        instrumented_body.push(hooks.instr(&instr, func_ty.inputs()));
+                    }
+
+                    /* return hook, without values, because those are only produced by the callee */
+                    if enabled_hooks.contains(Hook::Return) {
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                            hooks.instr(&Return, &[]),
+                        ]);
+                    }
+
+                    // end hooks for all intermediate blocks and the function itself
+                    if enabled_hooks.contains(Hook::End) {
+                        for block in block_stack.return_target().ended_blocks {
+                            block.append_end_hook_args(&mut instrumented_body, fidx);
+                            instrumented_body.push(hooks.end(&block, &[]));
+                        }
+                    }
+
+                    instrumented_body.push(instr);
+                }
 
 
                 /* Parametric Instructions */
 
                 Drop => {
-                    let ty = type_stack.pop_val();
+                    let ty = instr_ty.inputs()[0];
 
                     if enabled_hooks.contains(Hook::Drop) {
                         let tmp = function.add_fresh_local(ty);
@@ -579,11 +833,8 @@ pub fn add_hooks(
                         instrumented_body.push(instr);
                     }
                 }
-                Select => {
-                    assert_eq!(type_stack.pop_val(), I32, "select condition should be i32");
-                    let ty = type_stack.pop_val();
-                    assert_eq!(type_stack.pop_val(), ty, "select arguments should have same type");
-                    type_stack.push_val(ty);
+                Select | TypedSelect(_) => {
+                    let ty = instr_ty.results()[0];
 
                     if enabled_hooks.contains(Hook::Drop) {
                         let condition_tmp = function.add_fresh_local(I32);
@@ -607,11 +858,9 @@ pub fn add_hooks(
 
                 /* Variable Instructions */
 
-                Local(op, local_idx) => {
+                Local(_, local_idx) => {
                     let local_ty = function.param_or_local_type(local_idx);
 
-                    type_stack.instr(&op.to_type(local_ty));
-
                     instrumented_body.push(instr.clone());
 
                     // insert hook AFTER instruction, so that we can use local.get instead of duplicating the value through a new local
@@ -625,11 +874,9 @@ pub fn add_hooks(
                         instrumented_body.push(hooks.instr(&instr, &[local_ty]));
                     }
                 }
-                Global(op, global_idx) => {
+                Global(_, global_idx) => {
                     let global_ty = module_info.read().globals[global_idx.to_usize()];
 
-                    type_stack.instr(&op.to_type(global_ty));
-
                     instrumented_body.push(instr.clone());
 
                     // insert hook AFTER instruction, so that we can use global.get instead of duplicating the value through a new local
@@ -647,8 +894,7 @@ pub fn add_hooks(
 
                 /* Memory Instructions */
 
-                MemorySize(_ /* memory idx == 0 in WASM version 1 */) => {
-                    type_stack.instr(&instr.simple_type().unwrap());
+                MemorySize(memory_idx) => {
 
                     instrumented_body.push(instr.clone());
 
@@ -656,18 +902,18 @@ pub fn add_hooks(
                         instrumented_body.extend_from_slice(&[
                             location.0,
                             location.1,
-                            // optimization: just call memory_size again instead of duplicating result into local
-                            instr.clone(),
-                            hooks.instr(&instr, &[])
+                            memory_idx.to_const(),
                         ]);
+                        // optimization: just call memory_size again instead of duplicating result into local
+                        convert_i64_instr(&mut instrumented_body, instr.clone(), address_ty);
+                        instrumented_body.push(hooks.memory_instr(&instr, address_ty_of));
                     }
                 }
-                MemoryGrow(_ /* memory idx == 0 in WASM version 1 */) => {
-                    type_stack.instr(&instr.simple_type().unwrap());
+                MemoryGrow(memory_idx) => {
 
                     if enabled_hooks.contains(Hook::MemoryGrow) {
-                        let input_tmp = function.add_fresh_local(I32);
-                        let result_tmp = function.add_fresh_local(I32);
+                        let input_tmp = function.add_fresh_local(address_ty);
+                        let result_tmp = function.add_fresh_local(address_ty);
 
                         instrumented_body.extend_from_slice(&[
                             Local(Tee, input_tmp),
@@ -675,20 +921,80 @@ pub fn add_hooks(
                             Local(Tee, result_tmp),
                             location.0,
                             location.1,
-                            Local(Get, input_tmp),
-                            Local(Get, result_tmp),
-                            hooks.instr(&instr, &[])
+                            memory_idx.to_const(),
                         ]);
+                        restore_locals_with_i64_handling(&mut instrumented_body, [input_tmp, result_tmp], function);
+                        instrumented_body.push(hooks.memory_instr(&instr, address_ty_of));
                     } else {
                         instrumented_body.push(instr);
                     }
                 }
 
+                MemoryCopy { .. } | MemoryFill(_) | MemoryInit(_, _) => {
+                    let ty = instr.simple_type_with_memories(address_ty_of).unwrap();
+
+                    let hook_enabled = match instr {
+                        MemoryCopy { .. } => enabled_hooks.contains(Hook::MemoryCopy),
+                        MemoryFill(_) => enabled_hooks.contains(Hook::MemoryFill),
+                        _ => enabled_hooks.contains(Hook::MemoryInit),
+                    };
+                    if hook_enabled {
+                        // all three inputs are i32 (or i64 for addresses into 64-bit memories):
+                        // destination address, source address or value, and length
+                        let input_tmps = function.add_fresh_locals(ty.inputs());
+
+                        // hook after the instruction, such that it is only called if the instruction did not trap
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            instr.clone(),
+                            location.0,
+                            location.1,
+                        ]);
+                        match instr {
+                            MemoryCopy { dst, src } => instrumented_body.extend_from_slice(&[dst.to_const(), src.to_const()]),
+                            MemoryFill(memory_idx) => instrumented_body.push(memory_idx.to_const()),
+                            MemoryInit(memory_idx, data_idx) => instrumented_body.extend_from_slice(&[memory_idx.to_const(), data_idx.to_const()]),
+                            _ => unreachable!(),
+                        }
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps, function);
+                        instrumented_body.push(hooks.memory_instr(&instr, address_ty_of));
+                    } else {
+                        instrumented_body.push(instr);
+                    }
+                }
+                DataDrop(data_idx) => {
+
+                    instrumented_body.push(instr.clone());
+
+                    if enabled_hooks.contains(Hook::DataDrop) {
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                            data_idx.to_const(),
+                            hooks.instr(&instr, &[])
+                        ]);
+                    }
+                }
+
+                /* Reference and Table Instructions (reference types extension) */
+                // NOTE there are no hooks for these (yet), so just copy them over
+
+                RefNull(_) | RefFunc(_) | RefIsNull | TableSize(_) | TableCopy { .. } | TableInit(_, _) | ElemDrop(_)
+                | TableGet(_) | TableSet(_) | TableGrow(_) | TableFill(_) => {
+                    instrumented_body.push(instr);
+                }
+
+                /* SIMD Shuffle and Bitselect Instructions (SIMD extension) */
+                // NOTE there are no hooks for these (yet), so just copy them over
+
+                I8x16Shuffle(_) | V128Bitselect => {
+                    instrumented_body.push(instr);
+                }
+
                 /* rest are "grouped instructions", i.e., where many instructions can be handled in a similar manner */
 
-                Load(op, memarg) => {
-                    let ty = op.to_type();
-                    type_stack.instr(&ty);
+                Load(_, memarg) => {
+                    let ty = instr.simple_type_with_memories(address_ty_of).unwrap();
 
                     if enabled_hooks.contains(Hook::Load) {
                         let addr_tmp = function.add_fresh_local(ty.inputs()[0]);
@@ -700,18 +1006,16 @@ pub fn add_hooks(
                             Local(Tee, value_tmp),
                             location.0,
                             location.1,
-                            Const(Val::I32(memarg.offset as i32)),
-                            Const(Val::I32(memarg.alignment_exp as i32)),
                         ]);
+                        memarg_consts(&mut instrumented_body, memarg, address_ty);
                         restore_locals_with_i64_handling(&mut instrumented_body, [addr_tmp, value_tmp], function);
-                        instrumented_body.push(hooks.instr(&instr, &[]));
+                        instrumented_body.push(hooks.memory_instr(&instr, address_ty_of));
                     } else {
                         instrumented_body.push(instr);
                     }
                 }
-                Store(op, memarg) => {
-                    let ty = op.to_type();
-                    type_stack.instr(&ty);
+                Store(_, memarg) => {
+                    let ty = instr.simple_type_with_memories(address_ty_of).unwrap();
 
                     if enabled_hooks.contains(Hook::Store) {
                         let addr_tmp = function.add_fresh_local(ty.inputs()[0]);
@@ -722,21 +1026,122 @@ pub fn add_hooks(
                             instr.clone(),
                             location.0,
                             location.1,
-                            Const(Val::I32(memarg.offset as i32)),
-                            Const(Val::I32(memarg.alignment_exp as i32)),
                         ]);
+                        memarg_consts(&mut instrumented_body, memarg, address_ty);
                         restore_locals_with_i64_handling(&mut instrumented_body, [addr_tmp, value_tmp], function);
-                        instrumented_body.push(hooks.instr(&instr, &[]));
+                        instrumented_body.push(hooks.memory_instr(&instr, address_ty_of));
+                    } else {
+                        instrumented_body.push(instr);
+                    }
+                }
+
+                // Lane loads and stores (SIMD extension) report only the value of the accessed lane
+                // (i.e., the value that is actually read from or written to memory), plus the lane index.
+                LoadLane(op, memarg, lane) => {
+                    let ty = instr.simple_type_with_memories(address_ty_of).unwrap();
+
+                    if enabled_hooks.contains(Hook::Load) {
+                        let lane_op = lane_value_op(op.natural_alignment_exp());
+                        let addr_tmp = function.add_fresh_local(ty.inputs()[0]);
+                        let vector_tmp = function.add_fresh_local(V128);
+                        let value_tmp = function.add_fresh_local(lane_op.to_type().results()[0]);
+
+                        instrumented_body.extend_from_slice(&[
+                            Local(Set, vector_tmp),
+                            Local(Tee, addr_tmp),
+                            Local(Get, vector_tmp),
+                            instr.clone(),
+                            Local(Tee, vector_tmp),
+                            ExtractLane(lane_op, lane),
+                            Local(Set, value_tmp),
+                            Local(Get, vector_tmp),
+                            location.0,
+                            location.1,
+                        ]);
+                        memarg_consts(&mut instrumented_body, memarg, address_ty);
+                        restore_locals_with_i64_handling(&mut instrumented_body, [addr_tmp, value_tmp], function);
+                        instrumented_body.extend_from_slice(&[
+                            Const(Val::I32(lane.into())),
+                            hooks.memory_instr(&instr, address_ty_of),
+                        ]);
+                    } else {
+                        instrumented_body.push(instr);
+                    }
+                }
+                StoreLane(op, memarg, lane) => {
+                    let ty = instr.simple_type_with_memories(address_ty_of).unwrap();
+
+                    if enabled_hooks.contains(Hook::Store) {
+                        let lane_op = lane_value_op(op.natural_alignment_exp());
+                        let addr_tmp = function.add_fresh_local(ty.inputs()[0]);
+                        let vector_tmp = function.add_fresh_local(V128);
+                        let value_tmp = function.add_fresh_local(lane_op.to_type().results()[0]);
+
+                        save_stack_to_locals(&mut instrumented_body, &[addr_tmp, vector_tmp]);
+                        instrumented_body.extend_from_slice(&[
+                            instr.clone(),
+                            Local(Get, vector_tmp),
+                            ExtractLane(lane_op, lane),
+                            Local(Set, value_tmp),
+                            location.0,
+                            location.1,
+                        ]);
+                        memarg_consts(&mut instrumented_body, memarg, address_ty);
+                        restore_locals_with_i64_handling(&mut instrumented_body, [addr_tmp, value_tmp], function);
+                        instrumented_body.extend_from_slice(&[
+                            Const(Val::I32(lane.into())),
+                            hooks.memory_instr(&instr, address_ty_of),
+                        ]);
+                    } else {
+                        instrumented_body.push(instr);
+                    }
+                }
+
+                AtomicLoad(_, memarg) | AtomicStore(_, memarg) | AtomicRmw(_, memarg) | AtomicCmpxchg(_, memarg) | AtomicWaitNotify(_, memarg) => {
+                    let ty = instr.simple_type_with_memories(address_ty_of).unwrap();
+
+                    let hook_enabled = match instr {
+                        AtomicLoad(_, _) => enabled_hooks.contains(Hook::AtomicLoad),
+                        AtomicStore(_, _) => enabled_hooks.contains(Hook::AtomicStore),
+                        AtomicRmw(_, _) | AtomicCmpxchg(_, _) => enabled_hooks.contains(Hook::AtomicRmw),
+                        AtomicWaitNotify(AtomicWaitNotifyOp::MemoryAtomicNotify, _) => enabled_hooks.contains(Hook::AtomicNotify),
+                        _ => enabled_hooks.contains(Hook::AtomicWait),
+                    };
+                    if hook_enabled {
+                        let input_tmps = function.add_fresh_locals(ty.inputs());
+                        let result_tmps = function.add_fresh_locals(ty.results());
+
+                        // hook after the instruction, such that it is only called if the instruction did not trap
+                        save_stack_to_locals(&mut instrumented_body, &input_tmps);
+                        instrumented_body.push(instr.clone());
+                        save_stack_to_locals(&mut instrumented_body, &result_tmps);
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                        ]);
+                        memarg_consts(&mut instrumented_body, memarg, address_ty);
+                        restore_locals_with_i64_handling(&mut instrumented_body, input_tmps.iter().chain(result_tmps.iter()).copied(), function);
+                        instrumented_body.push(hooks.memory_instr(&instr, address_ty_of));
                     } else {
                         instrumented_body.push(instr);
                     }
                 }
+                AtomicFence => {
+                    instrumented_body.push(instr.clone());
+
+                    if enabled_hooks.contains(Hook::AtomicFence) {
+                        instrumented_body.extend_from_slice(&[
+                            location.0,
+                            location.1,
+                            hooks.instr(&instr, &[])
+                        ]);
+                    }
+                }
 
 
                 /* Numeric Instructions */
 
                 Const(val) => {
-                    type_stack.instr(&instr.simple_type().unwrap());
 
                     instrumented_body.push(instr.clone());
 
@@ -750,9 +1155,10 @@ pub fn add_hooks(
                         instrumented_body.push(hooks.instr(&instr, &[]));
                     }
                 }
-                Unary(_) | Binary(_) => {
+                // NOTE extract_lane and replace_lane (SIMD extension) are treated as unary and binary
+                // instructions, with the lane index as an additional argument of the hook
+                Unary(_) | Binary(_) | ExtractLane(_, _) | ReplaceLane(_, _) => {
                     let ty = instr.simple_type().unwrap();
-                    type_stack.instr(&ty);
 
                     if (enabled_hooks.contains(Hook::Unary) && ty.inputs().len() == 1)
                         || (enabled_hooks.contains(Hook::Binary) && ty.inputs().len() == 2) {
@@ -767,6 +1173,9 @@ pub fn add_hooks(
                             location.1,
                         ]);
                         restore_locals_with_i64_handling(&mut instrumented_body, input_tmps.iter().chain( result_tmps.iter()).copied(), function);
+                        if let ExtractLane(_, lane) | ReplaceLane(_, lane) = instr {
+                            instrumented_body.push(Const(Val::I32(lane.into())));
+                        }
                         instrumented_body.push(hooks.instr(&instr, &[]));
                     } else {
                         instrumented_body.push(instr);
@@ -775,9 +1184,15 @@ pub fn add_hooks(
             }
         }
 
+        let last_iidx = original_len.checked_sub(1).map(Idx::from);
+        instr_origins.resize(instrumented_body.len(), last_iidx);
+
         // finally, switch dummy body out against instrumented body
-        function.code_mut().unwrap().body = instrumented_body;
-    });
+        let code = function.code_mut().unwrap();
+        code.label_names = remap_label_names(&instrumented_body, &instr_origins, label_names);
+        code.body = instrumented_body.into();
+        instr_origins
+    }).collect();
 
     // actually add the hooks to module and check that inserted Idx is the one on the Hook struct
     let hooks = hooks.finish();
@@ -796,9 +1211,10 @@ pub fn add_hooks(
         module.functions.push(hook.wasm);
     }
 
-    Some((
+    Ok((
         generate_js(module_info.into_inner(), &js_hooks, node_js),
         hook_count,
+        LocationMap::new(instr_origins),
     ))
 }
 
@@ -828,6 +1244,7 @@ impl BlockStackElement {
             }
             BlockStackElement::Block { begin, end }
             | BlockStackElement::Loop { begin, end }
+            | BlockStackElement::Try { begin, end }
             | BlockStackElement::If {
                 begin_if: begin,
                 end,
@@ -843,6 +1260,16 @@ impl BlockStackElement {
                 begin_else.to_const(),
                 begin_if.to_const(),
             ]),
+            BlockStackElement::Catch {
+                begin_catch,
+                begin_try,
+                end,
+            } => append_to.extend_from_slice(&[
+                fidx.to_const(),
+                end.to_const(),
+                begin_catch.to_const(),
+                begin_try.to_const(),
+            ]),
         }
     }
     fn end(&self) -> Idx<Instr> {
@@ -852,11 +1279,87 @@ impl BlockStackElement {
             | Block { end, .. }
             | Loop { end, .. }
             | If { end, .. }
-            | Else { end, .. } => *end,
+            | Else { end, .. }
+            | Try { end, .. }
+            | Catch { end, .. } => *end,
         }
     }
 }
 
+fn starts_block(instr: &Instr) -> bool {
+    matches!(instr, Block(_) | Loop(_) | If(_) | Try(_))
+}
+
+/// Label names (from the extended name section) by the original instruction that starts the block.
+fn label_names_by_instr(
+    body: &[Instr],
+    label_names: Vec<Option<String>>,
+) -> HashMap<Idx<Instr>, String> {
+    if label_names.is_empty() {
+        return HashMap::new();
+    }
+    body.iter()
+        .enumerate()
+        .filter(|(_, instr)| starts_block(instr))
+        .zip(label_names)
+        .filter_map(|((iidx, _), name)| Some((iidx.into(), name?)))
+        .collect()
+}
+
+/// Label names by position of the block in the instrumented body, i.e., for the first block
+/// instruction that originates from an original block with a name.
+fn remap_label_names(
+    instrumented_body: &[Instr],
+    instr_origins: &[Option<Idx<Instr>>],
+    mut label_names: HashMap<Idx<Instr>, String>,
+) -> Vec<Option<String>> {
+    let mut new_label_names = Vec::new();
+    if label_names.is_empty() {
+        return new_label_names;
+    }
+    let block_origins = instrumented_body
+        .iter()
+        .zip(instr_origins)
+        .filter(|(instr, _)| starts_block(instr))
+        .map(|(_, origin)| *origin);
+    for (label_idx, origin) in block_origins.enumerate() {
+        if let Some(name) = origin.and_then(|origin| label_names.remove(&origin)) {
+            new_label_names.resize(label_idx, None);
+            new_label_names.push(Some(name));
+        }
+    }
+    new_label_names
+}
+
+/// Pushes the static offset, alignment, and memory index of a memory access as constants for the
+/// hook call.
+/// The offset has the address type of the memory, i.e., it is lowered to two i32's (low and high
+/// bits) for 64-bit memories (memory64 extension).
+fn memarg_consts(append_to: &mut Vec<Instr>, memarg: Memarg, address_ty: ValType) {
+    match address_ty {
+        I64 => append_to.extend_from_slice(&[
+            Const(Val::I32(memarg.offset as i32)),
+            Const(Val::I32((memarg.offset >> 32) as i32)),
+        ]),
+        _ => append_to.push(Const(Val::I32(memarg.offset as i32))),
+    }
+    append_to.extend_from_slice(&[
+        Const(Val::I32(memarg.alignment_exp as i32)),
+        memarg.memory.to_const(),
+    ]);
+}
+
+/// The lane (zero-extended to i32 for narrow lanes) that is loaded or stored by a
+/// `v128.loadN_lane` or `v128.storeN_lane` with the given natural alignment, i.e., lane width.
+fn lane_value_op(natural_alignment_exp: u8) -> ExtractLaneOp {
+    match natural_alignment_exp {
+        0 => ExtractLaneOp::I8x16ExtractLaneU,
+        1 => ExtractLaneOp::I16x8ExtractLaneU,
+        2 => ExtractLaneOp::I32x4ExtractLane,
+        _ => ExtractLaneOp::I64x2ExtractLane,
+    }
+}
+
 fn generate_js(module_info: ModuleInfo, hooks: &[String], node_js: bool) -> String {
     let mut result = r#"/*
 * Generated by Wasabi. DO NOT EDIT.
@@ -0,0 +1,204 @@
+//! Updating source maps (https://sourcemaps.info/spec.html) that are referenced by the
+//! `sourceMappingURL` custom section, e.g., as produced by Emscripten with `-gsource-map`.
+//! For WebAssembly, there is only a single generated "line" and the generated "column" of a
+//! mapping is the byte offset in the binary.
+
+use std::fs;
+use std::io;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::de::Error;
+use serde_json::Value;
+use wasabi_wasm::Module;
+
+pub const SOURCE_MAPPING_URL_SECTION: &str = "sourceMappingURL";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Returns the URL (often a path relative to the binary) of the source map of the module (if any).
+pub fn source_mapping_url(module: &Module) -> Option<String> {
+    let section = module
+        .custom_sections
+        .iter()
+        .find(|section| section.name == SOURCE_MAPPING_URL_SECTION)?;
+    // The content is a name, i.e., a LEB128 length followed by UTF-8 bytes.
+    let mut length = 0;
+    let mut shift = 0;
+    let mut bytes = section.content.iter();
+    loop {
+        let byte = *bytes.next()?;
+        length |= usize::from(byte & 0x7f).checked_shl(shift)?;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    let url = bytes.as_slice().get(..length)?;
+    String::from_utf8(url.to_vec()).ok()
+}
+
+/// The path of the source map file, if the URL from the `sourceMappingURL` section (which is
+/// untrusted input) is a relative path below the directory of the binary, i.e., not absolute, not
+/// with a scheme (such as `http://` or `data:`), and without `..` components.
+pub fn local_source_map_path(url: &str) -> Option<&Path> {
+    let has_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.contains('/'));
+    let path = Path::new(url);
+    let is_below = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    (!has_scheme && is_below && path.file_name().is_some()).then_some(path)
+}
+
+/// Reads the source map at `url` relative to `input_dir`, remaps it with `remap_source_map`, and
+/// writes it to the same relative path below `output_dir`, such that the (unchanged) URL in the
+/// instrumented binary still refers to it. Returns the path of the written source map.
+pub fn update_source_map_file(
+    url: &str,
+    input_dir: &Path,
+    output_dir: &Path,
+    map_offset: impl Fn(usize) -> Option<usize>,
+) -> io::Result<PathBuf> {
+    let path = local_source_map_path(url).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "URL is not a relative path below the directory of the binary",
+        )
+    })?;
+    let original_source_map = fs::read_to_string(input_dir.join(path))?;
+    let new_source_map = remap_source_map(&original_source_map, map_offset)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let output_path = output_dir.join(path);
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&output_path, new_source_map)?;
+    Ok(output_path)
+}
+
+/// Maps the generated offsets of all mappings in the `source_map` (JSON) to offsets in the
+/// instrumented binary with `map_offset`, e.g., `Offsets::map_offset` with
+/// `LocationMap::instrumented_instr`. Mappings at offsets that cannot be mapped are removed.
+pub fn remap_source_map(
+    source_map: &str,
+    map_offset: impl Fn(usize) -> Option<usize>,
+) -> Result<String, serde_json::Error> {
+    let mut source_map: Value = serde_json::from_str(source_map)?;
+    let mappings = source_map
+        .get("mappings")
+        .and_then(Value::as_str)
+        .ok_or_else(|| serde_json::Error::custom("source map has no \"mappings\""))?;
+
+    let mut lines = decode_mappings(mappings)?;
+    for segments in &mut lines {
+        segments.retain_mut(|segment| {
+            let new_offset = usize::try_from(segment[0])
+                .ok()
+                .and_then(&map_offset)
+                .and_then(|offset| i64::try_from(offset).ok());
+            match new_offset {
+                Some(offset) => {
+                    segment[0] = offset;
+                    true
+                }
+                None => false,
+            }
+        });
+        // Instrumentation does not reorder instructions, but be safe, since the format requires it.
+        segments.sort_by_key(|segment| segment[0]);
+    }
+    source_map["mappings"] = Value::String(encode_mappings(&lines));
+
+    serde_json::to_string(&source_map)
+}
+
+/// A segment has 1, 4, or 5 fields: generated column, source index, source line, source column,
+/// and name index. Here, all fields are absolute values (instead of relative to the previous
+/// segment, as in the encoded mappings).
+type Segment = Vec<i64>;
+
+fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Segment>>, serde_json::Error> {
+    let invalid = || serde_json::Error::custom("invalid \"mappings\" in source map");
+
+    // The generated column is relative within a line, all other fields across lines.
+    let mut previous = [0i64; 5];
+    let mut lines = Vec::new();
+    for line in mappings.split(';') {
+        previous[0] = 0;
+        let mut segments = Vec::new();
+        for encoded_segment in line.split(',').filter(|segment| !segment.is_empty()) {
+            let mut segment = Vec::with_capacity(5);
+            let mut value = 0i64;
+            let mut shift = 0;
+            for char in encoded_segment.bytes() {
+                let digit = BASE64.iter().position(|&c| c == char).ok_or_else(invalid)? as i64;
+                value |= (digit & 0x1f).checked_shl(shift).ok_or_else(invalid)?;
+                shift += 5;
+                if digit & 0x20 == 0 {
+                    // The lowest bit is the sign.
+                    let delta = if value & 1 == 1 {
+                        -(value >> 1)
+                    } else {
+                        value >> 1
+                    };
+                    let field = segment.len();
+                    let absolute = previous.get(field).ok_or_else(invalid)? + delta;
+                    segment.push(absolute);
+                    previous[field] = absolute;
+                    value = 0;
+                    shift = 0;
+                }
+            }
+            if shift != 0 || !matches!(segment.len(), 1 | 4 | 5) {
+                return Err(invalid());
+            }
+            segments.push(segment);
+        }
+        lines.push(segments);
+    }
+    Ok(lines)
+}
+
+fn encode_mappings(lines: &[Vec<Segment>]) -> String {
+    let mut mappings = String::new();
+    let mut previous = [0i64; 5];
+    for (i, segments) in lines.iter().enumerate() {
+        if i > 0 {
+            mappings.push(';');
+        }
+        previous[0] = 0;
+        for (j, segment) in segments.iter().enumerate() {
+            if j > 0 {
+                mappings.push(',');
+            }
+            for (field, &absolute) in segment.iter().enumerate() {
+                encode_vlq(&mut mappings, absolute - previous[field]);
+                previous[field] = absolute;
+            }
+        }
+    }
+    mappings
+}
+
+fn encode_vlq(output: &mut String, value: i64) {
+    let mut value = if value < 0 {
+        (value.unsigned_abs() << 1) | 1
+    } else {
+        (value as u64) << 1
+    };
+    loop {
+        let mut digit = value & 0x1f;
+        value >>= 5;
+        if value > 0 {
+            digit |= 0x20;
+        }
+        output.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
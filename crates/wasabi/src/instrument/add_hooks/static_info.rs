@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::Serialize;
 use serde::Serializer;
 use wasabi_wasm::Function;
@@ -23,11 +25,13 @@ pub struct ModuleInfo {
     #[serde(serialize_with = "serialize_types")]
     pub globals: Vec<ValType>,
     pub start: Option<Idx<Function>>,
-    pub table_export_name: Option<String>,
+    pub table_export_names: Vec<String>,
     pub br_tables: Vec<BrTableInfo>,
     // For mapping indices of indirectly called functions to the original indices, see
     // `resolveTableIdx` in `runtime.js`.
     pub original_function_imports_count: usize,
+    #[serde(skip_serializing_if = "NameInfo::is_empty")]
+    pub names: NameInfo,
 }
 
 impl<'a> From<&'a Module> for ModuleInfo {
@@ -36,17 +40,64 @@ impl<'a> From<&'a Module> for ModuleInfo {
             functions: module.functions.iter().map(Into::into).collect(),
             globals: module.globals.iter().map(|g| g.type_.0).collect(),
             start: module.start,
-            // if the module has no table, there cannot be a call_indirect, so this null will never be read from JS runtime
-            table_export_name: module
+            // all tables are exported by add_hooks before the static info is generated
+            table_export_names: module
                 .tables
-                .first()
-                .and_then(|table| table.export.first().cloned()),
+                .iter()
+                .map(|table| table.export[0].clone())
+                .collect(),
             br_tables: vec![],
             original_function_imports_count: module
                 .functions
                 .iter()
                 .filter_map(Function::import)
                 .count(),
+            names: module.into(),
+        }
+    }
+}
+
+/// Names of other items than functions (see `FunctionInfo::name`) from the (extended) name section.
+/// Sparse, i.e., only items with a name are included, by their (original) index.
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NameInfo {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub globals: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tables: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub memories: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub elements: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub data: BTreeMap<usize, String>,
+}
+
+impl NameInfo {
+    pub fn is_empty(&self) -> bool {
+        self.globals.is_empty()
+            && self.tables.is_empty()
+            && self.memories.is_empty()
+            && self.elements.is_empty()
+            && self.data.is_empty()
+    }
+}
+
+impl From<&Module> for NameInfo {
+    fn from(module: &Module) -> Self {
+        fn names<'a>(names: impl Iterator<Item = &'a Option<String>>) -> BTreeMap<usize, String> {
+            names
+                .enumerate()
+                .filter_map(|(idx, name)| Some((idx, name.clone()?)))
+                .collect()
+        }
+        NameInfo {
+            globals: names(module.globals.iter().map(|global| &global.name)),
+            tables: names(module.tables.iter().map(|table| &table.name)),
+            memories: names(module.memories.iter().map(|memory| &memory.name)),
+            elements: names(module.elements.iter().map(|element| &element.name)),
+            data: names(module.data.iter().map(|data| &data.name)),
         }
     }
 }
@@ -62,8 +113,9 @@ pub struct FunctionInfo {
     #[serde(serialize_with = "serialize_types")]
     pub locals: Vec<ValType>,
     pub instr_count: usize,
-    // TODO we could add name (from debug info) here, but it would be added for all functions,
-    // in the JSON as `"name": null`, which is a lot of overhead...
+    // From the name section (if any). Omitted instead of `"name": null` to keep the JSON small.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 impl<'a> From<&'a Function> for FunctionInfo {
@@ -81,6 +133,7 @@ impl<'a> From<&'a Function> for FunctionInfo {
                 .map(|local| local.type_)
                 .collect(),
             instr_count: function.instr_count(),
+            name: function.name.clone(),
         }
     }
 }
@@ -172,6 +225,12 @@ impl Serialize for BlockStackElement {
                 end,
                 begin_if,
             } => ("else", begin_else, end, begin_if).serialize(serializer),
+            Try { begin, end } => ("try", begin, end).serialize(serializer),
+            Catch {
+                begin_catch,
+                begin_try,
+                end,
+            } => ("catch", begin_catch, end, begin_try).serialize(serializer),
         }
     }
 }
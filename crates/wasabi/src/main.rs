@@ -1,11 +1,14 @@
 use std::fs;
 use std::io;
+use std::path::Path;
 
 use main_error::MainError;
 use structopt::StructOpt;
 use wasabi_wasm::Module;
+use wasabi_wasm::ParseOptions;
 
 use wasabi::instrument::add_hooks;
+use wasabi::instrument::add_hooks::source_map;
 use wasabi::options::HookSet;
 use wasabi::options::Options;
 
@@ -41,19 +44,57 @@ fn main() -> Result<(), MainError> {
     let output_file_wasabi_js = output_file_wasm.with_extension("wasabi.js");
 
     // instrument Wasm and generate JavaScript
-    let (mut module, _offsets, _warnings) = Module::from_file(opt.input_file)?;
-    if module.metadata.used_extensions().next().is_some() {
-        return Err(io_err(
-            "input file uses Wasm extensions, which are not supported yet by Wasabi",
-        )
+    // instruction offsets are needed for updating DWARF debug info and source maps (if any)
+    let parse_options = ParseOptions {
+        instr_offsets: true,
+        ..ParseOptions::default()
+    };
+    let (mut module, offsets, _warnings) =
+        Module::from_file_with_options(&opt.input_file, parse_options)?;
+    if let Some(extension) = module
+        .metadata
+        .used_extensions()
+        .find(|extension| !add_hooks::SUPPORTED_EXTENSIONS.contains(extension))
+    {
+        return Err(io_err(&format!(
+            "input file uses Wasm extension '{}', which is not supported yet by Wasabi",
+            extension.name()
+        ))
         .into());
     }
-    let (js, hook_count) = add_hooks(&mut module, enabled_hooks, opt.node_js).unwrap();
+    let (js, hook_count, location_map) = add_hooks(&mut module, enabled_hooks, opt.node_js)?;
     println!("inserted {hook_count} low-level hooks");
 
+    // update debug info, such that it maps the instrumented binary to the original sources
+    let (mut bytes, mut instrumented_offsets) = module.to_bytes_with_offsets()?;
+    let instrumented_instr = |function, instr| location_map.instrumented_instr(function, instr);
+    let has_dwarf = module
+        .custom_sections
+        .iter()
+        .any(|section| section.name.starts_with(".debug_"));
+    if has_dwarf {
+        match module.update_debug_info(&offsets, &instrumented_offsets, instrumented_instr) {
+            Ok(()) => (bytes, instrumented_offsets) = module.to_bytes_with_offsets()?,
+            Err(err) => eprintln!("warning: could not update DWARF debug info: {err}"),
+        }
+    }
+
     // write output files
     fs::create_dir_all(&opt.output_dir)?;
-    module.to_file(output_file_wasm)?;
+    fs::write(output_file_wasm, bytes)?;
+    if let Some(url) = source_map::source_mapping_url(&module) {
+        let input_dir = opt.input_file.parent().unwrap_or(Path::new(""));
+        let map_offset =
+            |offset| offsets.map_offset(offset, &instrumented_offsets, instrumented_instr);
+        if let Err(err) =
+            source_map::update_source_map_file(&url, input_dir, &opt.output_dir, map_offset)
+        {
+            // Like for DWARF, the instrumented binary is still useful without source map.
+            eprintln!(
+                "warning: could not update source map '{url}', it will not be written: {err}"
+            );
+        }
+    }
     fs::write(output_file_wasabi_js, js)?;
     if opt.node_js {
         let output_file_long_js = opt.output_dir.join("long.js");
@@ -87,6 +87,11 @@ pub enum Hook {
     Call,
     Return,
 
+    // exception handling extension
+    Throw,
+    Rethrow,
+    CatchBegin,
+
     Drop,
     Select,
 
@@ -100,6 +105,19 @@ pub enum Hook {
     MemorySize,
     MemoryGrow,
 
+    MemoryCopy,
+    MemoryFill,
+    MemoryInit,
+    DataDrop,
+
+    AtomicLoad,
+    AtomicStore,
+    // together for read-modify-write and compare-exchange operations
+    AtomicRmw,
+    AtomicWait,
+    AtomicNotify,
+    AtomicFence,
+
     Local,
     Global,
 }
@@ -1,10 +1,21 @@
 use test_utilities::*;
+use wasabi_wasm::Idx;
+use wasabi_wasm::Instr;
 use wasabi_wasm::Module;
+use wasabi_wasm::ParseOptions;
+use wasabi_wasm::Val;
 
 use crate::instrument::add_hooks;
+use crate::instrument::add_hooks::source_map;
 use crate::instrument::direct;
+use crate::options::Hook;
 use crate::options::HookSet;
 
+const WITH_INSTR_OFFSETS: ParseOptions = ParseOptions {
+    lazy: false,
+    instr_offsets: true,
+};
+
 #[test]
 fn add_empty_function_produces_valid_wasm() {
     test_instrument(
@@ -27,14 +38,408 @@ fn count_calls_instrumentation_produces_valid_wasm() {
     );
 }
 
+#[test]
+fn count_calls_instrumentation_on_wat() {
+    let mut module = Module::from_wat(
+        r#"(module
+            (func $f (call $g))
+            (func $g))"#,
+    )
+    .unwrap();
+    direct::count_calls(&mut module);
+
+    let expected = Module::from_wat(
+        r#"(module
+            (func $f (call 3) (call $g))
+            (func $g)
+            (global (mut i32) (i32.const 0))
+            (func (export "get_counter") (result i32) (global.get 0))
+            (func (global.set 0 (i32.add (global.get 0) (i32.const 1)))))"#,
+    )
+    .unwrap();
+    assert_eq!(module, expected);
+}
+
 #[test]
 fn add_hooks_instrumentation_produces_valid_wasm() {
     test_instrument(
-        |module| add_hooks(module, HookSet::all(), false).map(|opt| opt.0),
+        |module| Some(add_hooks(module, HookSet::all(), false).unwrap().0),
         "add-hooks",
     );
 }
 
+#[test]
+fn add_hooks_location_map_points_to_original_instrs() {
+    let original = Module::from_wat(
+        r#"(module
+            (func $f (param i32) (result i32)
+                (block (br_if 0 (local.get 0)) (nop))
+                (call $g (i32.add (local.get 0) (i32.const 1))))
+            (func $g (param i32) (result i32)
+                (return (local.get 0))
+                (unreachable))
+            (func $start)
+            (start $start))"#,
+    )
+    .unwrap();
+    let original_bytes = original.to_bytes().unwrap();
+    let (mut module, original_offsets, _warnings) =
+        Module::from_bytes_with_options(&original_bytes, WITH_INSTR_OFFSETS).unwrap();
+
+    let (_js, _hook_count, location_map) = add_hooks(&mut module, HookSet::all(), false).unwrap();
+    let (_bytes, instrumented_offsets) = module.to_bytes_with_offsets().unwrap();
+
+    for (function_idx, function) in original.functions() {
+        let instrumented_body = &module.function(function_idx).code().unwrap().body;
+        let origins: Vec<_> = (0..instrumented_body.len())
+            .map(|instr| location_map.original_instr(function_idx, instr.into()))
+            .collect();
+        // Hooks are inserted before and after instructions, but never reorder them.
+        assert!(origins.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        for (instr_idx, instr) in function.code().unwrap().body.iter().enumerate() {
+            let instr_idx = Some(instr_idx.into());
+            // Only `nop` is fully replaced by its hook, all others are copied over.
+            let copied =
+                instrumented_body
+                    .iter()
+                    .zip(&origins)
+                    .any(|(instrumented_instr, origin)| {
+                        *origin == instr_idx && instrumented_instr == instr
+                    });
+            assert!(copied || *instr == Instr::Nop, "{instr} was not copied");
+        }
+    }
+    // Start and begin hooks at the beginning of the function have no original instruction.
+    assert_eq!(location_map.original_instr(2u32.into(), 0u32.into()), None);
+
+    let offset_map = location_map.offset_map(&original_offsets, &instrumented_offsets);
+    assert!(!offset_map.is_empty());
+    for (instrumented_offset, original_offset) in offset_map {
+        assert_eq!(
+            location_map.original_offset(
+                instrumented_offset,
+                &original_offsets,
+                &instrumented_offsets
+            ),
+            Some(original_offset)
+        );
+        let (function, instrumented_instr) = instrumented_offsets
+            .instr_offset_to_idx(instrumented_offset)
+            .unwrap();
+        let original_instr = location_map.original_instr(function, instrumented_instr);
+        assert_eq!(
+            original_offsets.instr_offset_to_idx(original_offset),
+            original_instr.map(|instr| (function, instr))
+        );
+    }
+}
+
+#[test]
+fn add_hooks_keeps_names() {
+    let mut module = Module::from_wat(
+        r#"(module
+            (global $counter (mut i32) (i32.const 0))
+            (func $f (param i32)
+                (block $outer
+                    (loop $repeat
+                        (br_if $outer (local.get 0))
+                        (if $cond (local.get 0) (then (br $repeat)))))))"#,
+    )
+    .unwrap();
+
+    let (js, _hook_count, _location_map) = add_hooks(&mut module, HookSet::all(), false).unwrap();
+
+    // Label names refer to blocks by position, which changes when hooks insert blocks.
+    let code = module.functions[0].code().unwrap();
+    let named_blocks: Vec<_> = code
+        .body
+        .iter()
+        .filter(|instr| matches!(instr, Instr::Block(_) | Instr::Loop(_) | Instr::If(_)))
+        .zip(&code.label_names)
+        .filter_map(|(instr, name)| Some((instr.to_name(), name.as_deref()?)))
+        .collect();
+    assert_eq!(
+        named_blocks,
+        vec![("block", "outer"), ("loop", "repeat"), ("if", "cond")]
+    );
+
+    assert!(js.contains(r#""name":"f""#), "{js}");
+    assert!(
+        js.contains(r#""names":{"globals":{"0":"counter"}}"#),
+        "{js}"
+    );
+}
+
+#[test]
+fn add_hooks_instruments_dead_code() {
+    let mut module = Module::from_wat(
+        r#"(module
+            (func $f (param i32) (result i64)
+                unreachable
+                ;; The arguments are produced "out of thin air" and only constrained by the add.
+                select
+                i64.const 1
+                i64.add
+                drop
+                f32.abs
+                drop
+                i64.const 0))"#,
+    )
+    .unwrap();
+
+    add_hooks(&mut module, HookSet::all(), false).unwrap();
+    if let Err(errors) = module.validate() {
+        panic!("instrumenting dead code produces invalid Wasm\n{errors:#?}")
+    }
+
+    let body = module.functions[0].instrs();
+    let unreachable = body
+        .iter()
+        .position(|instr| matches!(instr, Instr::Unreachable))
+        .unwrap();
+    let hook_calls_in_dead_code = body[unreachable..]
+        .iter()
+        .filter(|instr| matches!(instr, Instr::Call(_)))
+        .count();
+    // select, const, add, drop, abs, drop, const, and the end of the function.
+    assert!(hook_calls_in_dead_code >= 8, "{hook_calls_in_dead_code}");
+}
+
+#[test]
+fn add_hooks_resolves_indirect_calls_in_all_tables() {
+    let mut module = Module::from_wat(
+        r#"(module
+            (table 1 funcref)
+            (table $t1 (export "t1") 2 funcref)
+            (table 1 externref)
+            (elem (table $t1) (i32.const 1) func $g)
+            (func $f (param externref) (result i32)
+                (table.set 2 (i32.const 0) (local.get 0))
+                (drop (table.grow $t1 (ref.func $g) (i32.const 1)))
+                (drop (ref.is_null (table.get 2 (i32.const 0))))
+                (call_indirect $t1 (result i32) (i32.const 1)))
+            (func $g (result i32)
+                (return_call_indirect $t1 (result i32) (i32.const 1))))"#,
+    )
+    .unwrap();
+
+    let (js, _hook_count, _location_map) = add_hooks(&mut module, HookSet::all(), false).unwrap();
+    if let Err(errors) = module.validate() {
+        panic!("instrumenting reference types produces invalid Wasm\n{errors:#?}")
+    }
+
+    // Every table is exported, existing exports are re-used.
+    assert!(
+        js.contains(r#""tableExportNames":["__wasabi_table","t1","__wasabi_table_2"]"#),
+        "{js}"
+    );
+    assert!(
+        js.contains("Wasabi.resolveTableIdx(tableIndex, table)"),
+        "{js}"
+    );
+    // The table of the indirect calls is passed to the hook right after the location.
+    let body = module.functions[0].instrs();
+    let call_indirect = body
+        .iter()
+        .position(|instr| matches!(instr, Instr::CallIndirect(_, _)))
+        .unwrap();
+    assert!(
+        body[..call_indirect].windows(3).any(|window| matches!(
+            window,
+            [
+                Instr::Const(Val::I32(1)),
+                Instr::Local(_, _),
+                Instr::Call(_)
+            ]
+        )),
+        "{body:?}"
+    );
+}
+
+#[test]
+fn add_hooks_lane_load_calls_load_hook_in_js_runtime() {
+    use std::process::Command;
+
+    let mut module = Module::from_wat(
+        r#"(module
+            (memory 1)
+            (data (i32.const 16) "\2a\00\00\00")
+            (func (export "main") (result i32)
+                (i32x4.extract_lane 1
+                    (v128.load32_lane offset=12 1 (i32.const 4) (v128.const i32x4 7 7 7 7))))
+            (func
+                (v128.store64_lane 1 (i32.const 0)
+                    (i64x2.replace_lane 0 (v128.const i64x2 1 2) (i64.const 3)))))"#,
+    )
+    .unwrap();
+    let (js, _hook_count, _location_map) = add_hooks(&mut module, HookSet::all(), false).unwrap();
+    if let Err(errors) = module.validate() {
+        panic!("instrumenting lane instructions produces invalid Wasm\n{errors:#?}")
+    }
+
+    let dir = std::path::Path::new("../../test-outputs/js-runtime");
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join("lane-load.wasm"), module.to_bytes().unwrap()).unwrap();
+    let analysis = r#"
+const calls = [];
+Wasabi.analysis = {
+    load(location, op, memarg, value, lane) { calls.push([op, memarg, value, lane]); },
+    unary(location, op, input, result, lane) { calls.push([op, Array.from(input), result, lane]); },
+};
+WebAssembly.instantiate(require("fs").readFileSync(__dirname + "/lane-load.wasm"))
+    .then(({instance}) => console.log(JSON.stringify([instance.exports.main(), calls])));
+"#;
+    std::fs::write(dir.join("lane-load.js"), js + analysis).unwrap();
+
+    let node_output = Command::new("node")
+        .arg(dir.join("lane-load.js"))
+        .output()
+        .expect("could not run node, is Node.js on $PATH?");
+    let stdout = String::from_utf8_lossy(&node_output.stdout);
+    assert!(
+        node_output.status.success(),
+        "{stdout}\n{}",
+        String::from_utf8_lossy(&node_output.stderr)
+    );
+    // The load hook gets only the loaded lane, the unary hook for extract_lane the whole vector.
+    assert_eq!(
+        stdout.lines().last(),
+        Some(concat!(
+            r#"[42,[["v128.load32_lane",{"addr":4,"offset":12,"align":2,"memory":0},42,1],"#,
+            r#"["i32x4.extract_lane",[7,42,7,7],42,1]]]"#
+        )),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn add_hooks_atomic_fence_calls_hook() {
+    let wat = r#"(module (memory 1 1 shared) (func atomic.fence))"#;
+    let mut module = Module::from_wat(wat).unwrap();
+    let (js, hook_count, _location_map) = add_hooks(&mut module, HookSet::all(), false).unwrap();
+    assert!(
+        js.contains("Wasabi.analysis.atomic_fence({func, instr}, )"),
+        "{js}"
+    );
+    module.validate().unwrap();
+
+    let mut module = Module::from_wat(wat).unwrap();
+    let (_js, hook_count_without_fence, _location_map) =
+        add_hooks(&mut module, HookSet::all() - Hook::AtomicFence, false).unwrap();
+    assert_eq!(hook_count_without_fence, hook_count - 1);
+}
+
+#[test]
+fn add_hooks_reports_malformed_lazily_parsed_body() {
+    let module = Module::from_wat("(module (func nop))").unwrap();
+    let mut bytes = module.to_bytes().unwrap();
+    // replace the `end` of the (last encoded) function body with an invalid opcode
+    *bytes.last_mut().unwrap() = 0xff;
+
+    let (mut module, _offsets, _warnings) = Module::from_bytes_lazy(&bytes).unwrap();
+    assert!(add_hooks(&mut module, HookSet::all(), false).is_err());
+}
+
+#[test]
+fn add_hooks_keeps_dwarf_source_locations() {
+    let (mut module, original_offsets, _warnings) = Module::from_file_with_options(
+        "../../test-inputs/wasm-feature-tests/name-section/extended-name-section/vuln.wasm",
+        WITH_INSTR_OFFSETS,
+    )
+    .unwrap();
+    let original = module.clone();
+    let original_debug_info = original.debug_info(&original_offsets).unwrap();
+
+    let (_js, _hook_count, location_map) = add_hooks(&mut module, HookSet::all(), false).unwrap();
+    let (_bytes, instrumented_offsets) = module.to_bytes_with_offsets().unwrap();
+    module
+        .update_debug_info(
+            &original_offsets,
+            &instrumented_offsets,
+            |function, instr| location_map.instrumented_instr(function, instr),
+        )
+        .unwrap();
+
+    // Not reparsed, since that would change the function indices (imported hooks come first).
+    let debug_info = module.debug_info(&instrumented_offsets).unwrap();
+    let mut source_locations = 0;
+    for (function_idx, function) in original.functions() {
+        assert_eq!(
+            debug_info.function_name(function_idx),
+            original_debug_info.function_name(function_idx)
+        );
+        for instr_idx in 0..function.instrs().len() {
+            let instr_idx = Idx::from(instr_idx);
+            let original_location = original_debug_info.source_location(function_idx, instr_idx);
+            let instrumented_instr = location_map
+                .instrumented_instr(function_idx, instr_idx)
+                .unwrap();
+            assert_eq!(
+                debug_info.source_location(function_idx, instrumented_instr),
+                original_location
+            );
+            source_locations += original_location.is_some() as usize;
+        }
+    }
+    assert!(source_locations > 0);
+}
+
+#[test]
+fn source_map_offsets_are_remapped() {
+    // Mappings at offsets 10, 20, and 300, the last with a name.
+    let original = r#"{"version":3,"sources":["a.c"],"names":["f"],"mappings":"UAAE,UAEF,wRACIA"}"#;
+    let remapped =
+        source_map::remap_source_map(original, |offset| (offset != 20).then_some(offset + 5))
+            .unwrap();
+    let expected = r#"{"mappings":"eAAE,kSAGEA","names":["f"],"sources":["a.c"],"version":3}"#;
+    assert_eq!(remapped, expected);
+
+    assert!(source_map::remap_source_map(r#"{"mappings":"U!"}"#, Some).is_err());
+}
+
+#[test]
+fn source_map_file_keeps_relative_path_of_url() {
+    let dir = std::path::Path::new("../../test-outputs/source-map");
+    let input_dir = dir.join("input");
+    let output_dir = dir.join("output");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(input_dir.join("maps")).unwrap();
+    let original = r#"{"version":3,"sources":["a.c"],"names":[],"mappings":"UAAE"}"#;
+    std::fs::write(input_dir.join("maps/a.wasm.map"), original).unwrap();
+
+    let written =
+        source_map::update_source_map_file("./maps/a.wasm.map", &input_dir, &output_dir, Some)
+            .unwrap();
+    assert_eq!(written, output_dir.join("./maps/a.wasm.map"));
+    let remapped = std::fs::read_to_string(output_dir.join("maps/a.wasm.map")).unwrap();
+    assert!(remapped.contains(r#""mappings":"UAAE""#), "{remapped}");
+
+    std::fs::write(dir.join("outside.map"), original).unwrap();
+    assert!(
+        source_map::update_source_map_file("../outside.map", &input_dir, &output_dir, Some)
+            .is_err()
+    );
+    // Not overwritten, even though it is at the same relative path from the output directory.
+    assert_eq!(
+        std::fs::read_to_string(dir.join("outside.map")).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn source_map_url_must_be_local_relative_path() {
+    let path = |url| source_map::local_source_map_path(url).map(|path| path.to_str().unwrap());
+    assert_eq!(path("a.wasm.map"), Some("a.wasm.map"));
+    assert_eq!(path("./maps/a.wasm.map"), Some("./maps/a.wasm.map"));
+    assert_eq!(path("/tmp/a.wasm.map"), None);
+    assert_eq!(path("../a.wasm.map"), None);
+    assert_eq!(path("maps/../../a.wasm.map"), None);
+    assert_eq!(path("http://example.com/a.wasm.map"), None);
+    assert_eq!(path("data:application/json;base64,e30="), None);
+    assert_eq!(path(""), None);
+}
+
 /// Utility function.
 fn test_instrument(instrument: fn(&mut Module) -> Option<String>, instrument_name: &'static str) {
     for_each_valid_wasm_binary_in_test_set(|path| {
@@ -47,6 +452,9 @@ fn test_instrument(instrument: fn(&mut Module) -> Option<String>, instrument_nam
             std::fs::write(output_path.with_extension("wasabi.js"), javascript).unwrap();
         }
 
+        if let Err(errors) = module.validate() {
+            panic!("instrumentation with {instrument_name} of input file '{}' produces invalid Wasm\n{errors:#?}", path.display())
+        }
         match wasm_validate(&output_path) {
             Ok(()) => {}
             Err(err @ WasmValidateError::InvalidWasmFile { .. }) => {
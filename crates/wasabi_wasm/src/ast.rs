@@ -10,21 +10,34 @@
 //!    functions, and locals).
 
 use core::fmt;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::hash;
 use std::marker::PhantomData;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
 use ordered_float::OrderedFloat;
+use rayon::prelude::*;
 use serde::Serialize;
 use smallvec::SmallVec;
 
 pub use crate::function_type::FunctionType;
 
 use crate::extensions::WasmExtension;
+use crate::parse::ParseOptions;
+use crate::parse::RawBody;
+use crate::types::InstrWithConcreteType;
+use crate::types::InstrsWithTypes;
+use crate::types::TypeError;
+use crate::CallGraph;
+use crate::DebugInfo;
 use crate::EncodeError;
 use crate::ParseError;
 use crate::ParseWarnings;
+use crate::ValidationError;
+use crate::WatParseError;
 
 /* Values and types. */
 
@@ -37,6 +50,8 @@ pub enum Val {
     // to make it possible, e.g., to put instructions in HashSets etc.
     F32(OrderedFloat<f32>),
     F64(OrderedFloat<f64>),
+    // SIMD extension.
+    V128(V128),
 }
 
 impl Val {
@@ -47,6 +62,7 @@ impl Val {
             Val::I64(_) => ValType::I64,
             Val::F32(_) => ValType::F32,
             Val::F64(_) => ValType::F64,
+            Val::V128(_) => ValType::V128,
         }
     }
 
@@ -59,6 +75,9 @@ impl Val {
             ValType::I64 => Val::I64(str.parse().map_err(|_| ())?),
             ValType::F32 => Val::F32(str.parse().map_err(|_| ())?),
             ValType::F64 => Val::F64(str.parse().map_err(|_| ())?),
+            ValType::V128 => Val::V128(str.parse()?),
+            // References cannot be written as literals.
+            ValType::FuncRef | ValType::ExternRef => return Err(()),
         })
     }
 }
@@ -70,10 +89,104 @@ impl fmt::Display for Val {
             Val::I64(v) => v.fmt(f),
             Val::F32(v) => v.into_inner().fmt(f),
             Val::F64(v) => v.into_inner().fmt(f),
+            Val::V128(v) => v.fmt(f),
         }
     }
 }
 
+/// A 128-bit value of the SIMD extension, as 16 bytes in little-endian order.
+/// How the bytes are interpreted (e.g., as four `i32` or two `f64` lanes) depends on the
+/// instruction that consumes the value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct V128(pub [u8; 16]);
+
+impl V128 {
+    pub fn from_i128(value: i128) -> Self {
+        V128(value.to_le_bytes())
+    }
+
+    pub fn to_i128(self) -> i128 {
+        i128::from_le_bytes(self.0)
+    }
+
+    /// The value interpreted as four `i32` lanes, lowest lane first.
+    pub fn to_i32x4(self) -> [i32; 4] {
+        let mut lanes = [0; 4];
+        for (lane, bytes) in lanes.iter_mut().zip(self.0.chunks_exact(4)) {
+            *lane = i32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        lanes
+    }
+}
+
+/// Formats as in the text format (without the `v128.const` mnemonic), as four hexadecimal
+/// `i32` lanes, e.g., `i32x4 0x00000001 0x00000000 0x00000000 0x00000000`.
+impl fmt::Display for V128 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("i32x4")?;
+        for lane in self.to_i32x4() {
+            write!(f, " {lane:#010x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the text format (without the `v128.const` mnemonic), i.e., a shape followed by the
+/// values of all lanes, e.g., `i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15` or `f64x2 1.5 -0.0`.
+impl FromStr for V128 {
+    type Err = ();
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        /// Parses a (decimal or hexadecimal, signed or unsigned) integer lane with `bits` width
+        /// and returns its little-endian bytes.
+        fn parse_int_lane(str: &str, bits: u32) -> Result<Vec<u8>, ()> {
+            let (negative, digits) = match str.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, str.strip_prefix('+').unwrap_or(str)),
+            };
+            let digits = digits.replace('_', "");
+            let magnitude = match digits.strip_prefix("0x") {
+                Some(hex_digits) => u64::from_str_radix(hex_digits, 16),
+                None => digits.parse(),
+            }
+            .map_err(|_| ())?;
+            let value = match negative {
+                false if magnitude <= u64::MAX >> (64 - bits) => magnitude,
+                true if magnitude <= 1 << (bits - 1) => magnitude.wrapping_neg(),
+                _ => return Err(()),
+            };
+            Ok(value.to_le_bytes()[..(bits / 8) as usize].to_vec())
+        }
+
+        let mut tokens = str.split_whitespace();
+        let shape = tokens.next().ok_or(())?;
+        let lanes = tokens.collect::<Vec<_>>();
+        type LaneParser = fn(&str) -> Result<Vec<u8>, ()>;
+        let (lane_count, parse_lane): (usize, LaneParser) = match shape {
+            "i8x16" => (16, |lane| parse_int_lane(lane, 8)),
+            "i16x8" => (8, |lane| parse_int_lane(lane, 16)),
+            "i32x4" => (4, |lane| parse_int_lane(lane, 32)),
+            "i64x2" => (2, |lane| parse_int_lane(lane, 64)),
+            "f32x4" => (4, |lane| {
+                Ok(lane.parse::<f32>().map_err(|_| ())?.to_le_bytes().to_vec())
+            }),
+            "f64x2" => (2, |lane| {
+                Ok(lane.parse::<f64>().map_err(|_| ())?.to_le_bytes().to_vec())
+            }),
+            _ => return Err(()),
+        };
+        if lanes.len() != lane_count {
+            return Err(());
+        }
+
+        let mut bytes = Vec::with_capacity(16);
+        for lane in lanes {
+            bytes.extend(parse_lane(lane)?);
+        }
+        Ok(V128(bytes.try_into().map_err(|_| ())?))
+    }
+}
+
 /// A WebAssembly value type, e.g., `i32` or `f64`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
 #[serde(rename_all = "lowercase")]
@@ -82,6 +195,11 @@ pub enum ValType {
     I64,
     F32,
     F64,
+    // Reference types extension.
+    FuncRef,
+    ExternRef,
+    // SIMD extension.
+    V128,
 }
 
 #[test]
@@ -91,12 +209,28 @@ fn val_type_is_small() {
 
 impl ValType {
     /// Produce a zero value (e.g., 0 or 0.0) for this type.
+    ///
+    /// Panics for reference types, which have no `Val` representation.
+    /// Use `Instr::RefNull` to produce a null reference instead.
     pub fn zero(self) -> Val {
         match self {
             ValType::I32 => Val::I32(0),
             ValType::I64 => Val::I64(0),
             ValType::F32 => Val::F32(OrderedFloat(0.0)),
             ValType::F64 => Val::F64(OrderedFloat(0.0)),
+            ValType::V128 => Val::V128(V128([0; 16])),
+            ValType::FuncRef | ValType::ExternRef => {
+                panic!("reference type {self} has no zero value")
+            }
+        }
+    }
+
+    /// Returns the reference type, if this is one, or `None` for numeric types.
+    pub fn to_ref_type(self) -> Option<RefType> {
+        match self {
+            ValType::FuncRef => Some(RefType::FuncRef),
+            ValType::ExternRef => Some(RefType::ExternRef),
+            ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64 | ValType::V128 => None,
         }
     }
 
@@ -108,18 +242,26 @@ impl ValType {
             ValType::I64 => "i64",
             ValType::F32 => "f32",
             ValType::F64 => "f64",
+            ValType::FuncRef => "funcref",
+            ValType::ExternRef => "externref",
+            ValType::V128 => "v128",
         }
     }
 
     /// Convert to a single character, e.g., as used by Emscripten.
     /// Lowercase is for 32 bit, uppercase is for 64 bit;
     /// `i` for integers, `f` for floats.
+    /// Reference types (not in Emscripten) use `r` for funcref and `e` for externref,
+    /// SIMD values (also not in Emscripten) use `v`.
     pub fn to_char(self) -> char {
         match self {
             ValType::I32 => 'i',
             ValType::I64 => 'I',
             ValType::F32 => 'f',
             ValType::F64 => 'F',
+            ValType::FuncRef => 'r',
+            ValType::ExternRef => 'e',
+            ValType::V128 => 'v',
         }
     }
 
@@ -130,6 +272,9 @@ impl ValType {
             'I' => Some(ValType::I64),
             'f' => Some(ValType::F32),
             'F' => Some(ValType::F64),
+            'r' => Some(ValType::FuncRef),
+            'e' => Some(ValType::ExternRef),
+            'v' => Some(ValType::V128),
             _ => None,
         }
     }
@@ -150,16 +295,67 @@ impl FromStr for ValType {
             "i64" => ValType::I64,
             "f32" => ValType::F32,
             "f64" => ValType::F64,
+            "funcref" => ValType::FuncRef,
+            "externref" => ValType::ExternRef,
+            "v128" => ValType::V128,
             _ => return Err(()),
         })
     }
 }
 
+/// A WebAssembly reference type (reference types extension), i.e., the type of table elements
+/// and of the operand of `ref.null`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefType {
+    FuncRef,
+    ExternRef,
+}
+
+impl RefType {
+    /// Convert to the standard string representation, as in the WebAssembly
+    /// specification and text format.
+    pub fn to_str(self) -> &'static str {
+        ValType::from(self).to_str()
+    }
+}
+
+impl From<RefType> for ValType {
+    fn from(ref_type: RefType) -> Self {
+        match ref_type {
+            RefType::FuncRef => ValType::FuncRef,
+            RefType::ExternRef => ValType::ExternRef,
+        }
+    }
+}
+
+impl fmt::Display for RefType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl FromStr for RefType {
+    type Err = ();
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        ValType::from_str(str)?.to_ref_type().ok_or(())
+    }
+}
+
 /// Limits for tables and memories.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Limits {
-    pub initial_size: u32,
-    pub max_size: Option<u32>,
+    // In pages for memories, in elements for tables.
+    // Only 64-bit memories (memory64 extension) can have sizes that do not fit into an u32.
+    pub initial_size: u64,
+    pub max_size: Option<u64>,
+    /// Whether the memory can be shared between threads (threads extension).
+    /// Always false for tables.
+    pub shared: bool,
+    /// Whether the memory is addressed with `i64` instead of `i32` (memory64 extension).
+    /// Always false for tables.
+    pub memory64: bool,
 }
 
 /// Type of global (scalar) variables.
@@ -315,6 +511,9 @@ impl Serialize for Label {
 pub struct Module {
     // From the name section, if present, e.g., compiler-generated debug info.
     pub name: Option<String>,
+    /// Names of function types from the (extended) name section. Unlike other names, these are
+    /// not stored with the types, because function types are not nodes of their own in the AST.
+    pub type_names: BTreeMap<FunctionType, String>,
 
     pub functions: Vec<Function>,
     pub globals: Vec<Global>,
@@ -323,8 +522,18 @@ pub struct Module {
     pub tables: Vec<Table>,
     pub memories: Vec<Memory>,
 
+    /// Exception tags, each with the types of the values carried by the exception (exception
+    /// handling extension).
+    pub tags: Vec<Tag>,
+
     pub start: Option<Idx<Function>>,
 
+    // Element and data segments are not stored with their table or memory, because passive
+    // segments (bulk memory extension) are not associated with any and because instructions
+    // such as `memory.init` refer to segments by their index in the module.
+    pub elements: Vec<Element>,
+    pub data: Vec<Data>,
+
     pub custom_sections: Vec<RawCustomSection>,
 
     pub metadata: ModuleMetadata,
@@ -333,6 +542,7 @@ pub struct Module {
 // TODO Crazy idea: make Module (and Function, Global) generic over InstrRepr, which
 // could even store the original WebAssembly bytes as a vector and only parse on demand,
 // which is more compact, requires less allocations, and is (obviously) faster on module creation.
+// (For function bodies, the latter is done by `LazyExpr`, but without making Module generic.)
 // Alternatively, the InstrRepr could also be a tree of blocks, or flat, or even a completely
 // diferent language, such as Wimpl or Stackl.
 
@@ -341,14 +551,85 @@ impl Module {
         Self::default()
     }
 
-    // TODO Generify this to work for any R: io::Read.
+    /// Note that the returned `Offsets` do not contain the offsets of instructions, see
+    /// `from_bytes_with_options` and `ParseOptions::instr_offsets` for that.
     pub fn from_bytes(bytes: &[u8]) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
-        crate::parse::parse_module(bytes)
+        Self::from_bytes_with_options(bytes, ParseOptions::default())
+    }
+
+    /// Like `from_bytes`, but keeps function bodies in their binary encoding and only decodes
+    /// them when accessed, see `LazyExpr`. Function bodies that are never mutated are re-emitted
+    /// byte-for-byte by the encoder, as long as the indices of functions, globals, etc. do not
+    /// change (e.g., by adding imports).
+    ///
+    /// Note that errors in function bodies are only reported when decoding them, and that
+    /// extensions used only inside function bodies are not recorded in `ModuleMetadata`.
+    pub fn from_bytes_lazy(bytes: &[u8]) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        let options = ParseOptions {
+            lazy: true,
+            ..ParseOptions::default()
+        };
+        Self::from_bytes_with_options(bytes, options)
+    }
+
+    /// Decodes all function bodies that were parsed lazily (see `from_bytes_lazy`) and returns
+    /// the first error, if any. Afterwards, accessing the bodies (e.g., via `Function::instrs`)
+    /// cannot panic anymore.
+    pub fn decode_lazy_bodies(&self) -> Result<(), ParseError> {
+        self.functions
+            .par_iter()
+            .filter_map(Function::code)
+            .try_for_each(|code| code.body.try_get().map(|_| ()))
+    }
+
+    pub fn from_bytes_with_options(
+        bytes: &[u8],
+        options: ParseOptions,
+    ) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        crate::parse::parse_module(bytes, options)
+    }
+
+    /// Parses a module while reading it incrementally, such that the binary never needs to be
+    /// fully resident in memory. See `parse_module_from_reader` in `parse.rs` for details.
+    pub fn from_reader(
+        reader: impl std::io::Read,
+    ) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        Self::from_reader_with_options(reader, ParseOptions::default())
+    }
+
+    pub fn from_reader_with_options(
+        reader: impl std::io::Read,
+        options: ParseOptions,
+    ) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        crate::parse::parse_module_from_reader(reader, options)
     }
 
     pub fn from_file(path: impl AsRef<Path>) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
-        let bytes = std::fs::read(path)?;
-        Self::from_bytes(&bytes)
+        Self::from_file_with_options(path, ParseOptions::default())
+    }
+
+    /// Lazy variant of `from_file`, see `from_bytes_lazy`.
+    pub fn from_file_lazy(
+        path: impl AsRef<Path>,
+    ) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        let options = ParseOptions {
+            lazy: true,
+            ..ParseOptions::default()
+        };
+        Self::from_file_with_options(path, options)
+    }
+
+    pub fn from_file_with_options(
+        path: impl AsRef<Path>,
+        options: ParseOptions,
+    ) -> Result<(Self, Offsets, ParseWarnings), ParseError> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader_with_options(file, options)
+    }
+
+    /// Parses a module in the WebAssembly text format, see `parse_wat.rs` for details.
+    pub fn from_wat(text: &str) -> Result<Self, WatParseError> {
+        crate::parse_wat::parse_module(text)
     }
 
     // TODO Some standard version that prints warnings?
@@ -365,18 +646,83 @@ impl Module {
     //     Ok((module, offsets))
     // }
 
-    // TODO Generify this to work for any W: io::Write.
-    // Unfortunately, wasm-encode only offers its `Encode` trait for `Vec<u8>`,
-    // so it is not quite so easy.
     pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
         crate::encode::encode_module(self)
     }
 
+    /// Validates the module according to the WebAssembly specification (including the supported
+    /// extensions), e.g., to check the result of instrumentation without an external validator.
+    /// Returns all errors found, each with the location (function, instruction, segment, etc.)
+    /// where it occurred.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        crate::validate::validate_module(self)
+    }
+
+    /// Computes the static call graph, with edges for direct calls and (over-approximated)
+    /// indirect calls through tables, and the exported and start functions as roots.
+    /// See the `call_graph` module for how indirect callees are resolved.
+    pub fn call_graph(&self) -> CallGraph {
+        CallGraph::new(self)
+    }
+
+    /// Parses the DWARF debug info in the `.debug_*` custom sections, e.g., to look up the source
+    /// location of functions and instructions.
+    /// DWARF refers to code by offsets, so this requires the `offsets` from parsing this module
+    /// with `ParseOptions::instr_offsets`, and the result is only valid as long as the module is
+    /// not modified.
+    pub fn debug_info(&self, offsets: &Offsets) -> Result<DebugInfo, ParseError> {
+        DebugInfo::parse(self, offsets)
+    }
+
+    /// Rewrites the code addresses in the DWARF debug info (address ranges and line programs in
+    /// the `.debug_*` custom sections) after instructions were inserted or removed, e.g., by
+    /// instrumentation, such that debuggers still show the original source locations.
+    ///
+    /// `original` are the offsets of the binary the debug info refers to (from parsing it with
+    /// `ParseOptions::instr_offsets`)
+    /// and `new` those from encoding this module (`to_bytes_with_offsets`). `map_instr` maps the
+    /// original instructions to the new instructions of the same function.
+    pub fn update_debug_info(
+        &mut self,
+        original: &Offsets,
+        new: &Offsets,
+        map_instr: impl Fn(Idx<Function>, Idx<Instr>) -> Option<Idx<Instr>>,
+    ) -> Result<(), EncodeError> {
+        let (Some(&original_code_offset), Some(&new_code_offset)) = (
+            original.section_offsets(SectionId::Code).first(),
+            new.section_offsets(SectionId::Code).first(),
+        ) else {
+            return Ok(());
+        };
+        // DWARF addresses are relative to the beginning of the code section contents.
+        crate::debug_info::update_addresses(self, |address| {
+            let offset = original_code_offset.checked_add(usize::try_from(address).ok()?)?;
+            let new_offset = original.map_offset(offset, new, &map_instr)?;
+            u64::try_from(new_offset.checked_sub(new_code_offset)?).ok()
+        })
+    }
+
+    /// Like `to_bytes`, but also returns the offsets of sections, function bodies, and
+    /// instructions in the encoded binary, e.g., to map them to the offsets in the original binary
+    /// (see `Offsets` returned by `from_bytes_with_options`).
+    pub fn to_bytes_with_offsets(&self) -> Result<(Vec<u8>, Offsets), EncodeError> {
+        crate::encode::encode_module_with_offsets(self)
+    }
+
+    /// Encodes the module and writes it section by section to `writer`, i.e., without first
+    /// building the complete binary in memory. Returns the number of bytes written.
+    pub fn write_to(&self, writer: impl std::io::Write) -> Result<usize, EncodeError> {
+        crate::encode::encode_module_to(self, writer)
+    }
+
     pub fn to_file(&self, path: impl AsRef<Path>) -> Result<usize, EncodeError> {
-        let bytes = self.to_bytes()?;
-        let len = bytes.len();
-        std::fs::write(path, bytes)?;
-        Ok(len)
+        let file = std::fs::File::create(path)?;
+        self.write_to(std::io::BufWriter::new(file))
+    }
+
+    /// Prints the module in the WebAssembly text format, see `print_wat.rs` for details.
+    pub fn to_wat(&self) -> String {
+        crate::print_wat::print_module(self)
     }
 }
 
@@ -428,24 +774,39 @@ pub struct Global {
     pub type_: GlobalType,
     pub init: ImportOrPresent<Expr>,
     pub export: Vec<String>,
-    // TODO name from name section.
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Table {
     pub limits: Limits,
-    // Unlike functions and globals, an imported table can still be initialized with elements.
+    /// Type of the references stored in this table, always `funcref` in the WebAssembly MVP.
+    pub elem_type: RefType,
+    // Unlike functions and globals, an imported table can still be initialized with elements,
+    // see the active segments in `Module::elements`.
     pub import: Option<(String, String)>,
-    pub elements: Vec<Element>,
     pub export: Vec<String>,
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Memory {
     pub limits: Limits,
-    // Unlike functions and globals, an imported memory can still be initialized with data elements.
+    // Unlike functions and globals, an imported memory can still be initialized with data,
+    // see the active segments in `Module::data`.
+    pub import: Option<(String, String)>,
+    pub export: Vec<String>,
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Tag {
+    /// The inputs are the values carried by exceptions with this tag, the results are always empty.
+    pub type_: FunctionType,
     pub import: Option<(String, String)>,
-    pub data: Vec<Data>,
     pub export: Vec<String>,
 }
 
@@ -454,7 +815,26 @@ pub struct Memory {
 pub struct Code {
     pub locals: Vec<Local>,
     // TODO rename to instrs
-    pub body: Expr,
+    pub body: LazyExpr,
+    /// Names of the labels of `block`, `loop`, `if`, and `try` instructions from the (extended)
+    /// name section, indexed by the order of these instructions in the body.
+    pub label_names: Vec<Option<String>>,
+}
+
+/// The instructions of a function body, which dereferences to `Expr`.
+///
+/// When parsed with `Module::from_bytes_lazy`, the body is kept in its original binary encoding
+/// and only decoded on first access. Read access (via `Deref`) caches the decoded instructions,
+/// but keeps the original bytes, such that the encoder can re-emit untouched bodies unchanged.
+/// Mutable access (via `DerefMut`) drops the original bytes, since they might no longer match.
+///
+/// Because `Deref` cannot return an error, it panics if the original bytes are invalid.
+/// Use `try_get` to handle such errors instead, or `Module::decode_lazy_bodies` to check all bodies
+/// upfront.
+#[derive(Clone)]
+pub struct LazyExpr {
+    decoded: OnceLock<Expr>,
+    raw: Option<RawBody>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -486,25 +866,64 @@ pub struct ParamRef<'a> {
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Element {
-    pub offset: Expr,
-    pub functions: Vec<Idx<Function>>,
+    pub mode: ElementMode,
+    /// Type of all items in this segment, always `funcref` in the WebAssembly MVP.
+    pub ref_type: RefType,
+    pub items: ElementItems,
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ElementMode {
+    /// Copied into the table during instantiation.
+    Active { table: Idx<Table>, offset: Expr },
+    /// Only copied into a table with `table.init` (bulk memory extension).
+    Passive,
+    /// Not available at runtime, only forward-declares functions referenced by `ref.func`
+    /// (reference types extension).
+    Declared,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ElementItems {
+    /// Compact MVP representation, where each item is a reference to the given function.
+    Functions(Vec<Idx<Function>>),
+    /// Each item is given by a constant expression, e.g., `ref.null func` or `ref.func 3`
+    /// (reference types extension).
+    Expressions(Vec<Expr>),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Data {
-    pub offset: Expr,
+    pub mode: DataMode,
     pub bytes: Vec<u8>,
+    // From the name section, if present, e.g., compiler-generated debug info.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum DataMode {
+    /// Copied into the memory during instantiation.
+    Active { memory: Idx<Memory>, offset: Expr },
+    /// Only copied into a memory with `memory.init` (bulk memory extension).
+    Passive,
 }
 
 /// Metainformation how low-level sections and function bodies map to byte offsets in the binary.
 // TODO Attach either directly to functions/sections or to the module (but rather the former, otherwise it can get easily lost).
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
 pub struct Offsets {
     /// Section offsets point to the beginning of the content of a section, i.e., after the size.
     pub sections: Vec<(SectionId, usize)>,
     /// Code offsets are only present for non-imported function, and also point to after the size
     /// in the code element (similar to section offsets).
     pub functions_code: Vec<(Idx<Function>, usize)>,
+    /// Offsets of all instructions of non-imported functions, indexed by `Idx<Instr>`, in the
+    /// same order as `functions_code`.
+    /// Empty unless requested with `ParseOptions::instr_offsets` when parsing (always present
+    /// when encoding with `Module::to_bytes_with_offsets`).
+    pub instrs: Vec<(Idx<Function>, Vec<usize>)>,
 }
 
 impl Offsets {
@@ -538,6 +957,71 @@ impl Offsets {
             .cloned()
             .find_map(|(func, offset)| if func == idx { Some(offset) } else { None })
     }
+
+    /// Returns the offset of the given instruction (if any).
+    pub fn instr_idx_to_offset(&self, function: Idx<Function>, instr: Idx<Instr>) -> Option<usize> {
+        self.function_instr_offsets(function)?
+            .get(instr.to_usize())
+            .copied()
+    }
+
+    /// Returns the instruction that contains the given offset, e.g., from a stack trace (if any).
+    /// Offsets inside an instruction (e.g., of an immediate) also return that instruction.
+    pub fn instr_offset_to_idx(&self, offset: usize) -> Option<(Idx<Function>, Idx<Instr>)> {
+        // Function bodies are in increasing order of offsets, so the last function that starts
+        // before the offset is the only one that can contain it.
+        let functions_before = self
+            .functions_code
+            .partition_point(|&(_, code_offset)| code_offset <= offset);
+        let (function, _) = *self.functions_code[..functions_before].last()?;
+        let instr_offsets = self.function_instr_offsets(function)?;
+        // The last instruction of a function is always an `end`, which is a single byte.
+        if offset > *instr_offsets.last()? {
+            return None;
+        }
+        // Offsets before the first instruction point into the local declarations.
+        let instrs_before = instr_offsets.partition_point(|&instr_offset| instr_offset <= offset);
+        let instr_idx = instrs_before.checked_sub(1)?;
+        Some((function, instr_idx.into()))
+    }
+
+    /// Maps an offset in the code of this binary to the corresponding offset in another binary
+    /// of the same (but modified) module, e.g., to update debug info after instrumentation.
+    /// `map_instr` maps instructions of this binary to instructions of the same function in `to`.
+    ///
+    /// Offsets inside an instruction map to the start of the mapped instruction, offsets in the
+    /// local declarations to the start of the function code, and the offset right after the last
+    /// instruction (i.e., the end of the function) to the end of the function in `to`.
+    /// Returns `None` for offsets outside of function bodies or unmapped instructions.
+    pub fn map_offset(
+        &self,
+        offset: usize,
+        to: &Offsets,
+        map_instr: impl Fn(Idx<Function>, Idx<Instr>) -> Option<Idx<Instr>>,
+    ) -> Option<usize> {
+        let functions_before = self
+            .functions_code
+            .partition_point(|&(_, code_offset)| code_offset <= offset);
+        let (function, _) = *self.functions_code[..functions_before].last()?;
+        let instr_offsets = self.function_instr_offsets(function)?;
+        if offset == instr_offsets.last()? + 1 {
+            return Some(to.function_instr_offsets(function)?.last()? + 1);
+        }
+        match self.instr_offset_to_idx(offset) {
+            Some((_, instr)) => to.instr_idx_to_offset(function, map_instr(function, instr)?),
+            None if offset < *instr_offsets.first()? => to.function_idx_to_offset(function),
+            None => None,
+        }
+    }
+
+    fn function_instr_offsets(&self, function: Idx<Function>) -> Option<&[usize]> {
+        // Functions are sorted by index, since that is the order of the code section.
+        let i = self
+            .instrs
+            .binary_search_by_key(&function, |(function, _)| *function)
+            .ok()?;
+        Some(&self.instrs[i].1)
+    }
 }
 
 /// A not-yet-parsed custom section.
@@ -563,10 +1047,13 @@ pub enum SectionId {
     Function,
     Table,
     Memory,
+    // Exception handling extension.
+    Tag,
     Global,
     Export,
     Start,
     Element,
+    DataCount,
     Code,
     Data,
     Custom(String),
@@ -596,7 +1083,12 @@ pub struct Memarg {
     /// and https://webassembly.github.io/spec/core/text/instructions.html#memory-instructions.
     pub alignment_exp: u8,
 
-    pub offset: u32,
+    // Only 64-bit memories (memory64 extension) can have offsets that do not fit into an u32.
+    pub offset: u64,
+
+    /// The accessed memory, which is always 0 for modules without multiple memories
+    /// (multi-memory extension).
+    pub memory: Idx<Memory>,
 }
 
 impl Memarg {
@@ -604,6 +1096,7 @@ impl Memarg {
         Self {
             offset: 0,
             alignment_exp: op.natural_alignment_exp(),
+            memory: Idx::from(0u32),
         }
     }
 
@@ -616,14 +1109,19 @@ impl Memarg {
     }
 
     /// Formats non-default fields, depends on natural alignment of `op`.
+    /// Like in the text format, the memory index comes first (and only if it is not 0).
     pub fn fmt(&self, f: &mut fmt::Formatter<'_>, op: impl MemoryOp) -> fmt::Result {
-        let is_natural_alignment = self.alignment_exp == op.natural_alignment_exp();
-        match (self.offset, is_natural_alignment) {
-            (0, true) => Ok(()),
-            (0, false) => write!(f, "align={}", self.alignment()),
-            (_, true) => write!(f, "offset={}", self.offset),
-            (_, false) => write!(f, "offset={} align={}", self.offset, self.alignment()),
+        let mut fields = Vec::new();
+        if self.memory.to_u32() != 0 {
+            fields.push(self.memory.to_u32().to_string());
         }
+        if self.offset != 0 {
+            fields.push(format!("offset={}", self.offset));
+        }
+        if self.alignment_exp != op.natural_alignment_exp() {
+            fields.push(format!("align={}", self.alignment()));
+        }
+        f.write_str(&fields.join(" "))
     }
 
     /// Parses Memarg, fills fields with defaults for `op`.
@@ -632,6 +1130,17 @@ impl Memarg {
     pub fn from_str(s: &str, op: impl MemoryOp) -> Result<Self, ()> {
         let mut result = Memarg::default(op);
 
+        let s = s.trim();
+        // The (optional) memory index is the only field that is not of the form `name=value`.
+        let s = match s.split_once(' ').unwrap_or((s, "")) {
+            (memory, rest) if !memory.contains('=') && !memory.is_empty() => {
+                let memory: u32 = memory.parse().map_err(|_| ())?;
+                result.memory = memory.into();
+                rest
+            }
+            _ => s,
+        };
+
         for field in s.split(' ') {
             // FIXME: Allows for the fields to appear multiple times.
 
@@ -676,11 +1185,12 @@ fn instr_size_should_not_be_too_large() {
     assert_eq!(std::mem::size_of::<Idx<Function>>(), 4);
     assert_eq!(std::mem::size_of::<Label>(), 4);
 
-    assert_eq!(std::mem::size_of::<Memarg>(), 8);
+    assert_eq!(std::mem::size_of::<Memarg>(), 16);
 
     // These are pretty large, but the only way to get it smaller is to store things out-of-line.
-    assert_eq!(std::mem::size_of::<Val>(), 16);
-    assert_eq!(std::mem::size_of::<Instr>(), 24);
+    // (The 16 bytes of `Val::V128` and `Instr::I8x16Shuffle` grow both by one word.)
+    assert_eq!(std::mem::size_of::<Val>(), 24);
+    assert_eq!(std::mem::size_of::<Instr>(), 32);
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -702,12 +1212,25 @@ pub enum Instr {
     // Block(FunctionType, Body), Loop(FunctionType, Body), If(FunctionType, Body, Option<Body>)
     // with
     // struct Body(Vec<Instr>, Option<TerminatorInstr>)
+    // See `nested::Body` for such a representation, which can be converted from and to the flat
+    // instruction sequence.
     Block(FunctionType),
     Loop(FunctionType),
     If(FunctionType),
     Else,
     End,
 
+    // Exception handling extension.
+    // `try` begins a block like `block`, `catch` and `catch_all` end the previous `try` or `catch`
+    // block (similar to `else`) and begin a handler block, which is ended by `end`.
+    // `delegate` ends a `try` block (instead of `end`) and forwards exceptions to the given label.
+    Try(FunctionType),
+    Catch(Idx<Tag>),
+    CatchAll,
+    Delegate(Label),
+    Throw(Idx<Tag>),
+    Rethrow(Label),
+
     Br(Label),
     // TODO: Replace with If(FunctionType, Body([], Some(Br(Label))), None)?
     BrIf(Label),
@@ -716,8 +1239,11 @@ pub enum Instr {
     // TODO: Replace with Br(toplevel)
     Return,
     Call(Idx<Function>),
-    // TODO: remove Idx<Table>, always 0 in MVP.
     CallIndirect(FunctionType, Idx<Table>),
+    // Tail calls extension: calls that replace the current function's frame, i.e., the current
+    // function returns with the results of the callee.
+    ReturnCall(Idx<Function>),
+    ReturnCallIndirect(FunctionType, Idx<Table>),
 
     // TODO: Include the type explicitly in the instruction to remove
     // value-polymorphism.
@@ -725,6 +1251,9 @@ pub enum Instr {
     Drop,
     // TODO: Replace with `If([ty, ty] -> [ty], ...)
     Select,
+    // Reference types extension: select with an explicit type annotation.
+    // Required for reference-typed operands.
+    TypedSelect(ValType),
 
     // TODO: Get rid of all locals by using block params and results only + a pick or copy
     // instruction, that copies the nth value on the stack to the top.
@@ -739,9 +1268,46 @@ pub enum Instr {
     MemorySize(Idx<Memory>),
     MemoryGrow(Idx<Memory>),
 
+    // Bulk memory extension.
+    MemoryCopy { dst: Idx<Memory>, src: Idx<Memory> },
+    MemoryFill(Idx<Memory>),
+    MemoryInit(Idx<Memory>, Idx<Data>),
+    DataDrop(Idx<Data>),
+
+    // Reference types extension.
+    RefNull(RefType),
+    RefIsNull,
+    RefFunc(Idx<Function>),
+
+    TableGet(Idx<Table>),
+    TableSet(Idx<Table>),
+    TableSize(Idx<Table>),
+    TableGrow(Idx<Table>),
+    TableFill(Idx<Table>),
+    TableCopy { dst: Idx<Table>, src: Idx<Table> },
+    TableInit(Idx<Table>, Idx<Element>),
+    ElemDrop(Idx<Element>),
+
     Const(Val),
     Unary(UnaryOp),
     Binary(BinaryOp),
+
+    // SIMD extension: instructions with a lane index immediate, shuffle, and the only ternary
+    // instruction. All other SIMD instructions are part of Load, Store, Const, Unary, and Binary.
+    LoadLane(LoadLaneOp, Memarg, u8),
+    StoreLane(StoreLaneOp, Memarg, u8),
+    ExtractLane(ExtractLaneOp, u8),
+    ReplaceLane(ReplaceLaneOp, u8),
+    I8x16Shuffle([u8; 16]),
+    V128Bitselect,
+
+    // Threads extension.
+    AtomicLoad(AtomicLoadOp, Memarg),
+    AtomicStore(AtomicStoreOp, Memarg),
+    AtomicRmw(AtomicRmwOp, Memarg),
+    AtomicCmpxchg(AtomicCmpxchgOp, Memarg),
+    AtomicWaitNotify(AtomicWaitNotifyOp, Memarg),
+    AtomicFence,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -794,6 +1360,21 @@ pub enum LoadOp {
     I64Load16U,
     I64Load32S,
     I64Load32U,
+
+    // SIMD extension.
+    V128Load,
+    V128Load8x8S,
+    V128Load8x8U,
+    V128Load16x4S,
+    V128Load16x4U,
+    V128Load32x2S,
+    V128Load32x2U,
+    V128Load8Splat,
+    V128Load16Splat,
+    V128Load32Splat,
+    V128Load64Splat,
+    V128Load32Zero,
+    V128Load64Zero,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -809,9 +1390,12 @@ pub enum StoreOp {
     I64Store8,
     I64Store16,
     I64Store32,
+
+    // SIMD extension.
+    V128Store,
 }
 
-/// Common trait for `LoadOp` and `StoreOp`.
+/// Common trait for all operations with a `Memarg` immediate, e.g., `LoadOp` and `StoreOp`.
 pub trait MemoryOp: Sized + Copy {
     fn to_name(self) -> &'static str;
     fn to_type(self) -> FunctionType;
@@ -843,6 +1427,20 @@ impl MemoryOp for LoadOp {
             I64Load16U => "i64.load16_u",
             I64Load32S => "i64.load32_s",
             I64Load32U => "i64.load32_u",
+
+            V128Load => "v128.load",
+            V128Load8x8S => "v128.load8x8_s",
+            V128Load8x8U => "v128.load8x8_u",
+            V128Load16x4S => "v128.load16x4_s",
+            V128Load16x4U => "v128.load16x4_u",
+            V128Load32x2S => "v128.load32x2_s",
+            V128Load32x2U => "v128.load32x2_u",
+            V128Load8Splat => "v128.load8_splat",
+            V128Load16Splat => "v128.load16_splat",
+            V128Load32Splat => "v128.load32_splat",
+            V128Load64Splat => "v128.load64_splat",
+            V128Load32Zero => "v128.load32_zero",
+            V128Load64Zero => "v128.load64_zero",
         }
     }
 
@@ -865,6 +1463,12 @@ impl MemoryOp for LoadOp {
             I64Load16U => FunctionType::new(&[I32], &[I64]),
             I64Load32S => FunctionType::new(&[I32], &[I64]),
             I64Load32U => FunctionType::new(&[I32], &[I64]),
+
+            V128Load | V128Load8x8S | V128Load8x8U | V128Load16x4S | V128Load16x4U
+            | V128Load32x2S | V128Load32x2U | V128Load8Splat | V128Load16Splat
+            | V128Load32Splat | V128Load64Splat | V128Load32Zero | V128Load64Zero => {
+                FunctionType::new(&[I32], &[V128])
+            }
         }
     }
 
@@ -886,6 +1490,20 @@ impl MemoryOp for LoadOp {
             I64Load16U => 1,
             I64Load32S => 2,
             I64Load32U => 2,
+
+            V128Load => 4,
+            V128Load8x8S => 3,
+            V128Load8x8U => 3,
+            V128Load16x4S => 3,
+            V128Load16x4U => 3,
+            V128Load32x2S => 3,
+            V128Load32x2U => 3,
+            V128Load8Splat => 0,
+            V128Load16Splat => 1,
+            V128Load32Splat => 2,
+            V128Load64Splat => 3,
+            V128Load32Zero => 2,
+            V128Load64Zero => 3,
         }
     }
 }
@@ -904,6 +1522,8 @@ impl MemoryOp for StoreOp {
             I64Store8 => "i64.store8",
             I64Store16 => "i64.store16",
             I64Store32 => "i64.store32",
+
+            V128Store => "v128.store",
         }
     }
 
@@ -921,78 +1541,839 @@ impl MemoryOp for StoreOp {
             I64Store8 => FunctionType::new(&[I32, I64], &[]),
             I64Store16 => FunctionType::new(&[I32, I64], &[]),
             I64Store32 => FunctionType::new(&[I32, I64], &[]),
+
+            V128Store => FunctionType::new(&[I32, V128], &[]),
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use StoreOp::*;
+        match self {
+            I32Store => 2,
+            I64Store => 3,
+            F32Store => 2,
+            F64Store => 3,
+
+            I32Store8 => 0,
+            I32Store16 => 1,
+            I64Store8 => 0,
+            I64Store16 => 1,
+            I64Store32 => 2,
+
+            V128Store => 4,
+        }
+    }
+}
+
+impl fmt::Display for LoadOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl fmt::Display for StoreOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for LoadOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use LoadOp::*;
+        Ok(match s {
+            "i32.load" => I32Load,
+            "i64.load" => I64Load,
+            "f32.load" => F32Load,
+            "f64.load" => F64Load,
+            "i32.load8_s" => I32Load8S,
+            "i32.load8_u" => I32Load8U,
+            "i32.load16_s" => I32Load16S,
+            "i32.load16_u" => I32Load16U,
+            "i64.load8_s" => I64Load8S,
+            "i64.load8_u" => I64Load8U,
+            "i64.load16_s" => I64Load16S,
+            "i64.load16_u" => I64Load16U,
+            "i64.load32_s" => I64Load32S,
+            "i64.load32_u" => I64Load32U,
+            "v128.load" => V128Load,
+            "v128.load8x8_s" => V128Load8x8S,
+            "v128.load8x8_u" => V128Load8x8U,
+            "v128.load16x4_s" => V128Load16x4S,
+            "v128.load16x4_u" => V128Load16x4U,
+            "v128.load32x2_s" => V128Load32x2S,
+            "v128.load32x2_u" => V128Load32x2U,
+            "v128.load8_splat" => V128Load8Splat,
+            "v128.load16_splat" => V128Load16Splat,
+            "v128.load32_splat" => V128Load32Splat,
+            "v128.load64_splat" => V128Load64Splat,
+            "v128.load32_zero" => V128Load32Zero,
+            "v128.load64_zero" => V128Load64Zero,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl FromStr for StoreOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use StoreOp::*;
+        Ok(match s {
+            "i32.store" => I32Store,
+            "i64.store" => I64Store,
+            "f32.store" => F32Store,
+            "f64.store" => F64Store,
+            "i32.store8" => I32Store8,
+            "i32.store16" => I32Store16,
+            "i64.store8" => I64Store8,
+            "i64.store16" => I64Store16,
+            "i64.store32" => I64Store32,
+            "v128.store" => V128Store,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Loads a single lane of a `v128` from memory (SIMD extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum LoadLaneOp {
+    V128Load8Lane,
+    V128Load16Lane,
+    V128Load32Lane,
+    V128Load64Lane,
+}
+
+/// Stores a single lane of a `v128` to memory (SIMD extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum StoreLaneOp {
+    V128Store8Lane,
+    V128Store16Lane,
+    V128Store32Lane,
+    V128Store64Lane,
+}
+
+impl MemoryOp for LoadLaneOp {
+    fn to_name(self) -> &'static str {
+        use LoadLaneOp::*;
+        match self {
+            V128Load8Lane => "v128.load8_lane",
+            V128Load16Lane => "v128.load16_lane",
+            V128Load32Lane => "v128.load32_lane",
+            V128Load64Lane => "v128.load64_lane",
+        }
+    }
+
+    /// Inputs are the address and the vector to replace the lane in.
+    fn to_type(self) -> FunctionType {
+        use ValType::*;
+        FunctionType::new(&[I32, V128], &[V128])
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use LoadLaneOp::*;
+        match self {
+            V128Load8Lane => 0,
+            V128Load16Lane => 1,
+            V128Load32Lane => 2,
+            V128Load64Lane => 3,
+        }
+    }
+}
+
+impl MemoryOp for StoreLaneOp {
+    fn to_name(self) -> &'static str {
+        use StoreLaneOp::*;
+        match self {
+            V128Store8Lane => "v128.store8_lane",
+            V128Store16Lane => "v128.store16_lane",
+            V128Store32Lane => "v128.store32_lane",
+            V128Store64Lane => "v128.store64_lane",
+        }
+    }
+
+    /// Inputs are the address and the vector to take the lane from.
+    fn to_type(self) -> FunctionType {
+        use ValType::*;
+        FunctionType::new(&[I32, V128], &[])
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use StoreLaneOp::*;
+        match self {
+            V128Store8Lane => 0,
+            V128Store16Lane => 1,
+            V128Store32Lane => 2,
+            V128Store64Lane => 3,
+        }
+    }
+}
+
+impl fmt::Display for LoadLaneOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl fmt::Display for StoreLaneOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for LoadLaneOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use LoadLaneOp::*;
+        Ok(match s {
+            "v128.load8_lane" => V128Load8Lane,
+            "v128.load16_lane" => V128Load16Lane,
+            "v128.load32_lane" => V128Load32Lane,
+            "v128.load64_lane" => V128Load64Lane,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl FromStr for StoreLaneOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use StoreLaneOp::*;
+        Ok(match s {
+            "v128.store8_lane" => V128Store8Lane,
+            "v128.store16_lane" => V128Store16Lane,
+            "v128.store32_lane" => V128Store32Lane,
+            "v128.store64_lane" => V128Store64Lane,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Atomic loads (threads extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum AtomicLoadOp {
+    I32AtomicLoad,
+    I64AtomicLoad,
+    I32AtomicLoad8U,
+    I32AtomicLoad16U,
+    I64AtomicLoad8U,
+    I64AtomicLoad16U,
+    I64AtomicLoad32U,
+}
+
+impl MemoryOp for AtomicLoadOp {
+    fn to_name(self) -> &'static str {
+        use AtomicLoadOp::*;
+        match self {
+            I32AtomicLoad => "i32.atomic.load",
+            I64AtomicLoad => "i64.atomic.load",
+            I32AtomicLoad8U => "i32.atomic.load8_u",
+            I32AtomicLoad16U => "i32.atomic.load16_u",
+            I64AtomicLoad8U => "i64.atomic.load8_u",
+            I64AtomicLoad16U => "i64.atomic.load16_u",
+            I64AtomicLoad32U => "i64.atomic.load32_u",
+        }
+    }
+
+    fn to_type(self) -> FunctionType {
+        use AtomicLoadOp::*;
+        use ValType::*;
+        match self {
+            I32AtomicLoad | I32AtomicLoad8U | I32AtomicLoad16U => FunctionType::new(&[I32], &[I32]),
+            I64AtomicLoad | I64AtomicLoad8U | I64AtomicLoad16U | I64AtomicLoad32U => {
+                FunctionType::new(&[I32], &[I64])
+            }
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use AtomicLoadOp::*;
+        match self {
+            I32AtomicLoad8U | I64AtomicLoad8U => 0,
+            I32AtomicLoad16U | I64AtomicLoad16U => 1,
+            I32AtomicLoad | I64AtomicLoad32U => 2,
+            I64AtomicLoad => 3,
+        }
+    }
+}
+
+impl fmt::Display for AtomicLoadOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for AtomicLoadOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AtomicLoadOp::*;
+        Ok(match s {
+            "i32.atomic.load" => I32AtomicLoad,
+            "i64.atomic.load" => I64AtomicLoad,
+            "i32.atomic.load8_u" => I32AtomicLoad8U,
+            "i32.atomic.load16_u" => I32AtomicLoad16U,
+            "i64.atomic.load8_u" => I64AtomicLoad8U,
+            "i64.atomic.load16_u" => I64AtomicLoad16U,
+            "i64.atomic.load32_u" => I64AtomicLoad32U,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Atomic stores (threads extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum AtomicStoreOp {
+    I32AtomicStore,
+    I64AtomicStore,
+    I32AtomicStore8,
+    I32AtomicStore16,
+    I64AtomicStore8,
+    I64AtomicStore16,
+    I64AtomicStore32,
+}
+
+impl MemoryOp for AtomicStoreOp {
+    fn to_name(self) -> &'static str {
+        use AtomicStoreOp::*;
+        match self {
+            I32AtomicStore => "i32.atomic.store",
+            I64AtomicStore => "i64.atomic.store",
+            I32AtomicStore8 => "i32.atomic.store8",
+            I32AtomicStore16 => "i32.atomic.store16",
+            I64AtomicStore8 => "i64.atomic.store8",
+            I64AtomicStore16 => "i64.atomic.store16",
+            I64AtomicStore32 => "i64.atomic.store32",
+        }
+    }
+
+    fn to_type(self) -> FunctionType {
+        use AtomicStoreOp::*;
+        use ValType::*;
+        match self {
+            I32AtomicStore | I32AtomicStore8 | I32AtomicStore16 => {
+                FunctionType::new(&[I32, I32], &[])
+            }
+            I64AtomicStore | I64AtomicStore8 | I64AtomicStore16 | I64AtomicStore32 => {
+                FunctionType::new(&[I32, I64], &[])
+            }
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use AtomicStoreOp::*;
+        match self {
+            I32AtomicStore8 | I64AtomicStore8 => 0,
+            I32AtomicStore16 | I64AtomicStore16 => 1,
+            I32AtomicStore | I64AtomicStore32 => 2,
+            I64AtomicStore => 3,
+        }
+    }
+}
+
+impl fmt::Display for AtomicStoreOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for AtomicStoreOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AtomicStoreOp::*;
+        Ok(match s {
+            "i32.atomic.store" => I32AtomicStore,
+            "i64.atomic.store" => I64AtomicStore,
+            "i32.atomic.store8" => I32AtomicStore8,
+            "i32.atomic.store16" => I32AtomicStore16,
+            "i64.atomic.store8" => I64AtomicStore8,
+            "i64.atomic.store16" => I64AtomicStore16,
+            "i64.atomic.store32" => I64AtomicStore32,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Atomic read-modify-write operations (threads extension), which combine the value in memory
+/// with an operand, write the result back, and return the previous value from memory.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum AtomicRmwOp {
+    I32AtomicRmwAdd,
+    I64AtomicRmwAdd,
+    I32AtomicRmw8AddU,
+    I32AtomicRmw16AddU,
+    I64AtomicRmw8AddU,
+    I64AtomicRmw16AddU,
+    I64AtomicRmw32AddU,
+    I32AtomicRmwSub,
+    I64AtomicRmwSub,
+    I32AtomicRmw8SubU,
+    I32AtomicRmw16SubU,
+    I64AtomicRmw8SubU,
+    I64AtomicRmw16SubU,
+    I64AtomicRmw32SubU,
+    I32AtomicRmwAnd,
+    I64AtomicRmwAnd,
+    I32AtomicRmw8AndU,
+    I32AtomicRmw16AndU,
+    I64AtomicRmw8AndU,
+    I64AtomicRmw16AndU,
+    I64AtomicRmw32AndU,
+    I32AtomicRmwOr,
+    I64AtomicRmwOr,
+    I32AtomicRmw8OrU,
+    I32AtomicRmw16OrU,
+    I64AtomicRmw8OrU,
+    I64AtomicRmw16OrU,
+    I64AtomicRmw32OrU,
+    I32AtomicRmwXor,
+    I64AtomicRmwXor,
+    I32AtomicRmw8XorU,
+    I32AtomicRmw16XorU,
+    I64AtomicRmw8XorU,
+    I64AtomicRmw16XorU,
+    I64AtomicRmw32XorU,
+    I32AtomicRmwXchg,
+    I64AtomicRmwXchg,
+    I32AtomicRmw8XchgU,
+    I32AtomicRmw16XchgU,
+    I64AtomicRmw8XchgU,
+    I64AtomicRmw16XchgU,
+    I64AtomicRmw32XchgU,
+}
+
+impl MemoryOp for AtomicRmwOp {
+    fn to_name(self) -> &'static str {
+        use AtomicRmwOp::*;
+        match self {
+            I32AtomicRmwAdd => "i32.atomic.rmw.add",
+            I64AtomicRmwAdd => "i64.atomic.rmw.add",
+            I32AtomicRmw8AddU => "i32.atomic.rmw8.add_u",
+            I32AtomicRmw16AddU => "i32.atomic.rmw16.add_u",
+            I64AtomicRmw8AddU => "i64.atomic.rmw8.add_u",
+            I64AtomicRmw16AddU => "i64.atomic.rmw16.add_u",
+            I64AtomicRmw32AddU => "i64.atomic.rmw32.add_u",
+            I32AtomicRmwSub => "i32.atomic.rmw.sub",
+            I64AtomicRmwSub => "i64.atomic.rmw.sub",
+            I32AtomicRmw8SubU => "i32.atomic.rmw8.sub_u",
+            I32AtomicRmw16SubU => "i32.atomic.rmw16.sub_u",
+            I64AtomicRmw8SubU => "i64.atomic.rmw8.sub_u",
+            I64AtomicRmw16SubU => "i64.atomic.rmw16.sub_u",
+            I64AtomicRmw32SubU => "i64.atomic.rmw32.sub_u",
+            I32AtomicRmwAnd => "i32.atomic.rmw.and",
+            I64AtomicRmwAnd => "i64.atomic.rmw.and",
+            I32AtomicRmw8AndU => "i32.atomic.rmw8.and_u",
+            I32AtomicRmw16AndU => "i32.atomic.rmw16.and_u",
+            I64AtomicRmw8AndU => "i64.atomic.rmw8.and_u",
+            I64AtomicRmw16AndU => "i64.atomic.rmw16.and_u",
+            I64AtomicRmw32AndU => "i64.atomic.rmw32.and_u",
+            I32AtomicRmwOr => "i32.atomic.rmw.or",
+            I64AtomicRmwOr => "i64.atomic.rmw.or",
+            I32AtomicRmw8OrU => "i32.atomic.rmw8.or_u",
+            I32AtomicRmw16OrU => "i32.atomic.rmw16.or_u",
+            I64AtomicRmw8OrU => "i64.atomic.rmw8.or_u",
+            I64AtomicRmw16OrU => "i64.atomic.rmw16.or_u",
+            I64AtomicRmw32OrU => "i64.atomic.rmw32.or_u",
+            I32AtomicRmwXor => "i32.atomic.rmw.xor",
+            I64AtomicRmwXor => "i64.atomic.rmw.xor",
+            I32AtomicRmw8XorU => "i32.atomic.rmw8.xor_u",
+            I32AtomicRmw16XorU => "i32.atomic.rmw16.xor_u",
+            I64AtomicRmw8XorU => "i64.atomic.rmw8.xor_u",
+            I64AtomicRmw16XorU => "i64.atomic.rmw16.xor_u",
+            I64AtomicRmw32XorU => "i64.atomic.rmw32.xor_u",
+            I32AtomicRmwXchg => "i32.atomic.rmw.xchg",
+            I64AtomicRmwXchg => "i64.atomic.rmw.xchg",
+            I32AtomicRmw8XchgU => "i32.atomic.rmw8.xchg_u",
+            I32AtomicRmw16XchgU => "i32.atomic.rmw16.xchg_u",
+            I64AtomicRmw8XchgU => "i64.atomic.rmw8.xchg_u",
+            I64AtomicRmw16XchgU => "i64.atomic.rmw16.xchg_u",
+            I64AtomicRmw32XchgU => "i64.atomic.rmw32.xchg_u",
+        }
+    }
+
+    /// Inputs are the address and the operand, the result is the previous value in memory.
+    fn to_type(self) -> FunctionType {
+        use AtomicRmwOp::*;
+        use ValType::*;
+        match self {
+            I32AtomicRmwAdd | I32AtomicRmw8AddU | I32AtomicRmw16AddU | I32AtomicRmwSub
+            | I32AtomicRmw8SubU | I32AtomicRmw16SubU | I32AtomicRmwAnd | I32AtomicRmw8AndU
+            | I32AtomicRmw16AndU | I32AtomicRmwOr | I32AtomicRmw8OrU | I32AtomicRmw16OrU
+            | I32AtomicRmwXor | I32AtomicRmw8XorU | I32AtomicRmw16XorU | I32AtomicRmwXchg
+            | I32AtomicRmw8XchgU | I32AtomicRmw16XchgU => FunctionType::new(&[I32, I32], &[I32]),
+            I64AtomicRmwAdd | I64AtomicRmw8AddU | I64AtomicRmw16AddU | I64AtomicRmw32AddU
+            | I64AtomicRmwSub | I64AtomicRmw8SubU | I64AtomicRmw16SubU | I64AtomicRmw32SubU
+            | I64AtomicRmwAnd | I64AtomicRmw8AndU | I64AtomicRmw16AndU | I64AtomicRmw32AndU
+            | I64AtomicRmwOr | I64AtomicRmw8OrU | I64AtomicRmw16OrU | I64AtomicRmw32OrU
+            | I64AtomicRmwXor | I64AtomicRmw8XorU | I64AtomicRmw16XorU | I64AtomicRmw32XorU
+            | I64AtomicRmwXchg | I64AtomicRmw8XchgU | I64AtomicRmw16XchgU | I64AtomicRmw32XchgU => {
+                FunctionType::new(&[I32, I64], &[I64])
+            }
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use AtomicRmwOp::*;
+        match self {
+            I32AtomicRmw8AddU | I64AtomicRmw8AddU | I32AtomicRmw8SubU | I64AtomicRmw8SubU
+            | I32AtomicRmw8AndU | I64AtomicRmw8AndU | I32AtomicRmw8OrU | I64AtomicRmw8OrU
+            | I32AtomicRmw8XorU | I64AtomicRmw8XorU | I32AtomicRmw8XchgU | I64AtomicRmw8XchgU => 0,
+            I32AtomicRmw16AddU | I64AtomicRmw16AddU | I32AtomicRmw16SubU | I64AtomicRmw16SubU
+            | I32AtomicRmw16AndU | I64AtomicRmw16AndU | I32AtomicRmw16OrU | I64AtomicRmw16OrU
+            | I32AtomicRmw16XorU | I64AtomicRmw16XorU | I32AtomicRmw16XchgU
+            | I64AtomicRmw16XchgU => 1,
+            I32AtomicRmwAdd | I64AtomicRmw32AddU | I32AtomicRmwSub | I64AtomicRmw32SubU
+            | I32AtomicRmwAnd | I64AtomicRmw32AndU | I32AtomicRmwOr | I64AtomicRmw32OrU
+            | I32AtomicRmwXor | I64AtomicRmw32XorU | I32AtomicRmwXchg | I64AtomicRmw32XchgU => 2,
+            I64AtomicRmwAdd | I64AtomicRmwSub | I64AtomicRmwAnd | I64AtomicRmwOr
+            | I64AtomicRmwXor | I64AtomicRmwXchg => 3,
+        }
+    }
+}
+
+impl fmt::Display for AtomicRmwOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for AtomicRmwOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AtomicRmwOp::*;
+        Ok(match s {
+            "i32.atomic.rmw.add" => I32AtomicRmwAdd,
+            "i64.atomic.rmw.add" => I64AtomicRmwAdd,
+            "i32.atomic.rmw8.add_u" => I32AtomicRmw8AddU,
+            "i32.atomic.rmw16.add_u" => I32AtomicRmw16AddU,
+            "i64.atomic.rmw8.add_u" => I64AtomicRmw8AddU,
+            "i64.atomic.rmw16.add_u" => I64AtomicRmw16AddU,
+            "i64.atomic.rmw32.add_u" => I64AtomicRmw32AddU,
+            "i32.atomic.rmw.sub" => I32AtomicRmwSub,
+            "i64.atomic.rmw.sub" => I64AtomicRmwSub,
+            "i32.atomic.rmw8.sub_u" => I32AtomicRmw8SubU,
+            "i32.atomic.rmw16.sub_u" => I32AtomicRmw16SubU,
+            "i64.atomic.rmw8.sub_u" => I64AtomicRmw8SubU,
+            "i64.atomic.rmw16.sub_u" => I64AtomicRmw16SubU,
+            "i64.atomic.rmw32.sub_u" => I64AtomicRmw32SubU,
+            "i32.atomic.rmw.and" => I32AtomicRmwAnd,
+            "i64.atomic.rmw.and" => I64AtomicRmwAnd,
+            "i32.atomic.rmw8.and_u" => I32AtomicRmw8AndU,
+            "i32.atomic.rmw16.and_u" => I32AtomicRmw16AndU,
+            "i64.atomic.rmw8.and_u" => I64AtomicRmw8AndU,
+            "i64.atomic.rmw16.and_u" => I64AtomicRmw16AndU,
+            "i64.atomic.rmw32.and_u" => I64AtomicRmw32AndU,
+            "i32.atomic.rmw.or" => I32AtomicRmwOr,
+            "i64.atomic.rmw.or" => I64AtomicRmwOr,
+            "i32.atomic.rmw8.or_u" => I32AtomicRmw8OrU,
+            "i32.atomic.rmw16.or_u" => I32AtomicRmw16OrU,
+            "i64.atomic.rmw8.or_u" => I64AtomicRmw8OrU,
+            "i64.atomic.rmw16.or_u" => I64AtomicRmw16OrU,
+            "i64.atomic.rmw32.or_u" => I64AtomicRmw32OrU,
+            "i32.atomic.rmw.xor" => I32AtomicRmwXor,
+            "i64.atomic.rmw.xor" => I64AtomicRmwXor,
+            "i32.atomic.rmw8.xor_u" => I32AtomicRmw8XorU,
+            "i32.atomic.rmw16.xor_u" => I32AtomicRmw16XorU,
+            "i64.atomic.rmw8.xor_u" => I64AtomicRmw8XorU,
+            "i64.atomic.rmw16.xor_u" => I64AtomicRmw16XorU,
+            "i64.atomic.rmw32.xor_u" => I64AtomicRmw32XorU,
+            "i32.atomic.rmw.xchg" => I32AtomicRmwXchg,
+            "i64.atomic.rmw.xchg" => I64AtomicRmwXchg,
+            "i32.atomic.rmw8.xchg_u" => I32AtomicRmw8XchgU,
+            "i32.atomic.rmw16.xchg_u" => I32AtomicRmw16XchgU,
+            "i64.atomic.rmw8.xchg_u" => I64AtomicRmw8XchgU,
+            "i64.atomic.rmw16.xchg_u" => I64AtomicRmw16XchgU,
+            "i64.atomic.rmw32.xchg_u" => I64AtomicRmw32XchgU,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Atomic compare-exchange operations (threads extension), which write the replacement value only
+/// if the value in memory is equal to the expected value, and return the previous value from memory.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum AtomicCmpxchgOp {
+    I32AtomicRmwCmpxchg,
+    I64AtomicRmwCmpxchg,
+    I32AtomicRmw8CmpxchgU,
+    I32AtomicRmw16CmpxchgU,
+    I64AtomicRmw8CmpxchgU,
+    I64AtomicRmw16CmpxchgU,
+    I64AtomicRmw32CmpxchgU,
+}
+
+impl MemoryOp for AtomicCmpxchgOp {
+    fn to_name(self) -> &'static str {
+        use AtomicCmpxchgOp::*;
+        match self {
+            I32AtomicRmwCmpxchg => "i32.atomic.rmw.cmpxchg",
+            I64AtomicRmwCmpxchg => "i64.atomic.rmw.cmpxchg",
+            I32AtomicRmw8CmpxchgU => "i32.atomic.rmw8.cmpxchg_u",
+            I32AtomicRmw16CmpxchgU => "i32.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw8CmpxchgU => "i64.atomic.rmw8.cmpxchg_u",
+            I64AtomicRmw16CmpxchgU => "i64.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw32CmpxchgU => "i64.atomic.rmw32.cmpxchg_u",
+        }
+    }
+
+    /// Inputs are the address, the expected value, and the replacement value.
+    fn to_type(self) -> FunctionType {
+        use AtomicCmpxchgOp::*;
+        use ValType::*;
+        match self {
+            I32AtomicRmwCmpxchg | I32AtomicRmw8CmpxchgU | I32AtomicRmw16CmpxchgU => {
+                FunctionType::new(&[I32, I32, I32], &[I32])
+            }
+            I64AtomicRmwCmpxchg
+            | I64AtomicRmw8CmpxchgU
+            | I64AtomicRmw16CmpxchgU
+            | I64AtomicRmw32CmpxchgU => FunctionType::new(&[I32, I64, I64], &[I64]),
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use AtomicCmpxchgOp::*;
+        match self {
+            I32AtomicRmw8CmpxchgU | I64AtomicRmw8CmpxchgU => 0,
+            I32AtomicRmw16CmpxchgU | I64AtomicRmw16CmpxchgU => 1,
+            I32AtomicRmwCmpxchg | I64AtomicRmw32CmpxchgU => 2,
+            I64AtomicRmwCmpxchg => 3,
+        }
+    }
+}
+
+impl fmt::Display for AtomicCmpxchgOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for AtomicCmpxchgOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AtomicCmpxchgOp::*;
+        Ok(match s {
+            "i32.atomic.rmw.cmpxchg" => I32AtomicRmwCmpxchg,
+            "i64.atomic.rmw.cmpxchg" => I64AtomicRmwCmpxchg,
+            "i32.atomic.rmw8.cmpxchg_u" => I32AtomicRmw8CmpxchgU,
+            "i32.atomic.rmw16.cmpxchg_u" => I32AtomicRmw16CmpxchgU,
+            "i64.atomic.rmw8.cmpxchg_u" => I64AtomicRmw8CmpxchgU,
+            "i64.atomic.rmw16.cmpxchg_u" => I64AtomicRmw16CmpxchgU,
+            "i64.atomic.rmw32.cmpxchg_u" => I64AtomicRmw32CmpxchgU,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Suspending and waking up threads that wait on an address in shared memory (threads extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum AtomicWaitNotifyOp {
+    MemoryAtomicNotify,
+    MemoryAtomicWait32,
+    MemoryAtomicWait64,
+}
+
+impl MemoryOp for AtomicWaitNotifyOp {
+    fn to_name(self) -> &'static str {
+        use AtomicWaitNotifyOp::*;
+        match self {
+            MemoryAtomicNotify => "memory.atomic.notify",
+            MemoryAtomicWait32 => "memory.atomic.wait32",
+            MemoryAtomicWait64 => "memory.atomic.wait64",
+        }
+    }
+
+    /// Inputs are the address followed by the number of threads to wake up (notify), or the expected
+    /// value and a timeout in nanoseconds (wait). The result is the number of woken up threads (notify),
+    /// or whether the wait returned because of a notify (0), the value was not expected (1), or a
+    /// timeout (2).
+    fn to_type(self) -> FunctionType {
+        use AtomicWaitNotifyOp::*;
+        use ValType::*;
+        match self {
+            MemoryAtomicNotify => FunctionType::new(&[I32, I32], &[I32]),
+            MemoryAtomicWait32 => FunctionType::new(&[I32, I32, I64], &[I32]),
+            MemoryAtomicWait64 => FunctionType::new(&[I32, I64, I64], &[I32]),
+        }
+    }
+
+    fn natural_alignment_exp(self) -> u8 {
+        use AtomicWaitNotifyOp::*;
+        match self {
+            MemoryAtomicNotify | MemoryAtomicWait32 => 2,
+            MemoryAtomicWait64 => 3,
+        }
+    }
+}
+
+impl fmt::Display for AtomicWaitNotifyOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_name())
+    }
+}
+
+impl FromStr for AtomicWaitNotifyOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AtomicWaitNotifyOp::*;
+        Ok(match s {
+            "memory.atomic.notify" => MemoryAtomicNotify,
+            "memory.atomic.wait32" => MemoryAtomicWait32,
+            "memory.atomic.wait64" => MemoryAtomicWait64,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Extracts a single lane of a `v128` as a scalar value (SIMD extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ExtractLaneOp {
+    I8x16ExtractLaneS,
+    I8x16ExtractLaneU,
+    I16x8ExtractLaneS,
+    I16x8ExtractLaneU,
+    I32x4ExtractLane,
+    I64x2ExtractLane,
+    F32x4ExtractLane,
+    F64x2ExtractLane,
+}
+
+/// Replaces a single lane of a `v128` with a scalar value (SIMD extension).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ReplaceLaneOp {
+    I8x16ReplaceLane,
+    I16x8ReplaceLane,
+    I32x4ReplaceLane,
+    I64x2ReplaceLane,
+    F32x4ReplaceLane,
+    F64x2ReplaceLane,
+}
+
+impl ExtractLaneOp {
+    pub fn to_name(self) -> &'static str {
+        use ExtractLaneOp::*;
+        match self {
+            I8x16ExtractLaneS => "i8x16.extract_lane_s",
+            I8x16ExtractLaneU => "i8x16.extract_lane_u",
+            I16x8ExtractLaneS => "i16x8.extract_lane_s",
+            I16x8ExtractLaneU => "i16x8.extract_lane_u",
+            I32x4ExtractLane => "i32x4.extract_lane",
+            I64x2ExtractLane => "i64x2.extract_lane",
+            F32x4ExtractLane => "f32x4.extract_lane",
+            F64x2ExtractLane => "f64x2.extract_lane",
+        }
+    }
+
+    pub fn to_type(self) -> FunctionType {
+        use ExtractLaneOp::*;
+        use ValType::*;
+        match self {
+            I8x16ExtractLaneS | I8x16ExtractLaneU | I16x8ExtractLaneS | I16x8ExtractLaneU
+            | I32x4ExtractLane => FunctionType::new(&[V128], &[I32]),
+            I64x2ExtractLane => FunctionType::new(&[V128], &[I64]),
+            F32x4ExtractLane => FunctionType::new(&[V128], &[F32]),
+            F64x2ExtractLane => FunctionType::new(&[V128], &[F64]),
+        }
+    }
+
+    pub fn lane_count(self) -> u8 {
+        use ExtractLaneOp::*;
+        match self {
+            I8x16ExtractLaneS | I8x16ExtractLaneU => 16,
+            I16x8ExtractLaneS | I16x8ExtractLaneU => 8,
+            I32x4ExtractLane | F32x4ExtractLane => 4,
+            I64x2ExtractLane | F64x2ExtractLane => 2,
+        }
+    }
+}
+
+impl ReplaceLaneOp {
+    pub fn to_name(self) -> &'static str {
+        use ReplaceLaneOp::*;
+        match self {
+            I8x16ReplaceLane => "i8x16.replace_lane",
+            I16x8ReplaceLane => "i16x8.replace_lane",
+            I32x4ReplaceLane => "i32x4.replace_lane",
+            I64x2ReplaceLane => "i64x2.replace_lane",
+            F32x4ReplaceLane => "f32x4.replace_lane",
+            F64x2ReplaceLane => "f64x2.replace_lane",
         }
     }
 
-    fn natural_alignment_exp(self) -> u8 {
-        use StoreOp::*;
+    pub fn to_type(self) -> FunctionType {
+        use ReplaceLaneOp::*;
+        use ValType::*;
         match self {
-            I32Store => 2,
-            I64Store => 3,
-            F32Store => 2,
-            F64Store => 3,
+            I8x16ReplaceLane | I16x8ReplaceLane | I32x4ReplaceLane => {
+                FunctionType::new(&[V128, I32], &[V128])
+            }
+            I64x2ReplaceLane => FunctionType::new(&[V128, I64], &[V128]),
+            F32x4ReplaceLane => FunctionType::new(&[V128, F32], &[V128]),
+            F64x2ReplaceLane => FunctionType::new(&[V128, F64], &[V128]),
+        }
+    }
 
-            I32Store8 => 0,
-            I32Store16 => 1,
-            I64Store8 => 0,
-            I64Store16 => 1,
-            I64Store32 => 2,
+    pub fn lane_count(self) -> u8 {
+        use ReplaceLaneOp::*;
+        match self {
+            I8x16ReplaceLane => 16,
+            I16x8ReplaceLane => 8,
+            I32x4ReplaceLane | F32x4ReplaceLane => 4,
+            I64x2ReplaceLane | F64x2ReplaceLane => 2,
         }
     }
 }
 
-impl fmt::Display for LoadOp {
+impl fmt::Display for ExtractLaneOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.to_name())
     }
 }
 
-impl fmt::Display for StoreOp {
+impl fmt::Display for ReplaceLaneOp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.to_name())
     }
 }
 
-impl FromStr for LoadOp {
+impl FromStr for ExtractLaneOp {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use LoadOp::*;
+        use ExtractLaneOp::*;
         Ok(match s {
-            "i32.load" => I32Load,
-            "i64.load" => I64Load,
-            "f32.load" => F32Load,
-            "f64.load" => F64Load,
-            "i32.load8_s" => I32Load8S,
-            "i32.load8_u" => I32Load8U,
-            "i32.load16_s" => I32Load16S,
-            "i32.load16_u" => I32Load16U,
-            "i64.load8_s" => I64Load8S,
-            "i64.load8_u" => I64Load8U,
-            "i64.load16_s" => I64Load16S,
-            "i64.load16_u" => I64Load16U,
-            "i64.load32_s" => I64Load32S,
-            "i64.load32_u" => I64Load32U,
+            "i8x16.extract_lane_s" => I8x16ExtractLaneS,
+            "i8x16.extract_lane_u" => I8x16ExtractLaneU,
+            "i16x8.extract_lane_s" => I16x8ExtractLaneS,
+            "i16x8.extract_lane_u" => I16x8ExtractLaneU,
+            "i32x4.extract_lane" => I32x4ExtractLane,
+            "i64x2.extract_lane" => I64x2ExtractLane,
+            "f32x4.extract_lane" => F32x4ExtractLane,
+            "f64x2.extract_lane" => F64x2ExtractLane,
             _ => return Err(()),
         })
     }
 }
 
-impl FromStr for StoreOp {
+impl FromStr for ReplaceLaneOp {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use StoreOp::*;
+        use ReplaceLaneOp::*;
         Ok(match s {
-            "i32.store" => I32Store,
-            "i64.store" => I64Store,
-            "f32.store" => F32Store,
-            "f64.store" => F64Store,
-            "i32.store8" => I32Store8,
-            "i32.store16" => I32Store16,
-            "i64.store8" => I64Store8,
-            "i64.store16" => I64Store16,
-            "i64.store32" => I64Store32,
+            "i8x16.replace_lane" => I8x16ReplaceLane,
+            "i16x8.replace_lane" => I16x8ReplaceLane,
+            "i32x4.replace_lane" => I32x4ReplaceLane,
+            "i64x2.replace_lane" => I64x2ReplaceLane,
+            "f32x4.replace_lane" => F32x4ReplaceLane,
+            "f64x2.replace_lane" => F64x2ReplaceLane,
             _ => return Err(()),
         })
     }
@@ -1056,6 +2437,90 @@ pub enum UnaryOp {
     I64ReinterpretF64,
     F32ReinterpretI32,
     F64ReinterpretI64,
+
+    // Sign-extension operators extension
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+
+    // Non-trapping float-to-int conversions extension
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+
+    // SIMD extension.
+    I8x16Splat,
+    I16x8Splat,
+    I32x4Splat,
+    I64x2Splat,
+    F32x4Splat,
+    F64x2Splat,
+    V128Not,
+    V128AnyTrue,
+    I8x16Abs,
+    I8x16Neg,
+    I8x16Popcnt,
+    I8x16AllTrue,
+    I8x16Bitmask,
+    I16x8ExtAddPairwiseI8x16S,
+    I16x8ExtAddPairwiseI8x16U,
+    I16x8Abs,
+    I16x8Neg,
+    I16x8AllTrue,
+    I16x8Bitmask,
+    I16x8ExtendLowI8x16S,
+    I16x8ExtendHighI8x16S,
+    I16x8ExtendLowI8x16U,
+    I16x8ExtendHighI8x16U,
+    I32x4ExtAddPairwiseI16x8S,
+    I32x4ExtAddPairwiseI16x8U,
+    I32x4Abs,
+    I32x4Neg,
+    I32x4AllTrue,
+    I32x4Bitmask,
+    I32x4ExtendLowI16x8S,
+    I32x4ExtendHighI16x8S,
+    I32x4ExtendLowI16x8U,
+    I32x4ExtendHighI16x8U,
+    I64x2Abs,
+    I64x2Neg,
+    I64x2AllTrue,
+    I64x2Bitmask,
+    I64x2ExtendLowI32x4S,
+    I64x2ExtendHighI32x4S,
+    I64x2ExtendLowI32x4U,
+    I64x2ExtendHighI32x4U,
+    F32x4Ceil,
+    F32x4Floor,
+    F32x4Trunc,
+    F32x4Nearest,
+    F32x4Abs,
+    F32x4Neg,
+    F32x4Sqrt,
+    F64x2Ceil,
+    F64x2Floor,
+    F64x2Trunc,
+    F64x2Nearest,
+    F64x2Abs,
+    F64x2Neg,
+    F64x2Sqrt,
+    I32x4TruncSatF32x4S,
+    I32x4TruncSatF32x4U,
+    F32x4ConvertI32x4S,
+    F32x4ConvertI32x4U,
+    I32x4TruncSatF64x2SZero,
+    I32x4TruncSatF64x2UZero,
+    F64x2ConvertLowI32x4S,
+    F64x2ConvertLowI32x4U,
+    F32x4DemoteF64x2Zero,
+    F64x2PromoteLowF32x4,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
@@ -1143,6 +2608,140 @@ pub enum BinaryOp {
     F64Min,
     F64Max,
     F64Copysign,
+
+    // SIMD extension.
+    I8x16Eq,
+    I8x16Ne,
+    I8x16LtS,
+    I8x16LtU,
+    I8x16GtS,
+    I8x16GtU,
+    I8x16LeS,
+    I8x16LeU,
+    I8x16GeS,
+    I8x16GeU,
+    I16x8Eq,
+    I16x8Ne,
+    I16x8LtS,
+    I16x8LtU,
+    I16x8GtS,
+    I16x8GtU,
+    I16x8LeS,
+    I16x8LeU,
+    I16x8GeS,
+    I16x8GeU,
+    I32x4Eq,
+    I32x4Ne,
+    I32x4LtS,
+    I32x4LtU,
+    I32x4GtS,
+    I32x4GtU,
+    I32x4LeS,
+    I32x4LeU,
+    I32x4GeS,
+    I32x4GeU,
+    I64x2Eq,
+    I64x2Ne,
+    I64x2LtS,
+    I64x2GtS,
+    I64x2LeS,
+    I64x2GeS,
+    F32x4Eq,
+    F32x4Ne,
+    F32x4Lt,
+    F32x4Gt,
+    F32x4Le,
+    F32x4Ge,
+    F64x2Eq,
+    F64x2Ne,
+    F64x2Lt,
+    F64x2Gt,
+    F64x2Le,
+    F64x2Ge,
+    V128And,
+    V128AndNot,
+    V128Or,
+    V128Xor,
+    I8x16NarrowI16x8S,
+    I8x16NarrowI16x8U,
+    I8x16Shl,
+    I8x16ShrS,
+    I8x16ShrU,
+    I8x16Add,
+    I8x16AddSatS,
+    I8x16AddSatU,
+    I8x16Sub,
+    I8x16SubSatS,
+    I8x16SubSatU,
+    I8x16MinS,
+    I8x16MinU,
+    I8x16MaxS,
+    I8x16MaxU,
+    I8x16AvgrU,
+    I8x16Swizzle,
+    I16x8Q15MulrSatS,
+    I16x8NarrowI32x4S,
+    I16x8NarrowI32x4U,
+    I16x8Shl,
+    I16x8ShrS,
+    I16x8ShrU,
+    I16x8Add,
+    I16x8AddSatS,
+    I16x8AddSatU,
+    I16x8Sub,
+    I16x8SubSatS,
+    I16x8SubSatU,
+    I16x8Mul,
+    I16x8MinS,
+    I16x8MinU,
+    I16x8MaxS,
+    I16x8MaxU,
+    I16x8AvgrU,
+    I16x8ExtMulLowI8x16S,
+    I16x8ExtMulHighI8x16S,
+    I16x8ExtMulLowI8x16U,
+    I16x8ExtMulHighI8x16U,
+    I32x4Shl,
+    I32x4ShrS,
+    I32x4ShrU,
+    I32x4Add,
+    I32x4Sub,
+    I32x4Mul,
+    I32x4MinS,
+    I32x4MinU,
+    I32x4MaxS,
+    I32x4MaxU,
+    I32x4DotI16x8S,
+    I32x4ExtMulLowI16x8S,
+    I32x4ExtMulHighI16x8S,
+    I32x4ExtMulLowI16x8U,
+    I32x4ExtMulHighI16x8U,
+    I64x2Shl,
+    I64x2ShrS,
+    I64x2ShrU,
+    I64x2Add,
+    I64x2Sub,
+    I64x2Mul,
+    I64x2ExtMulLowI32x4S,
+    I64x2ExtMulHighI32x4S,
+    I64x2ExtMulLowI32x4U,
+    I64x2ExtMulHighI32x4U,
+    F32x4Add,
+    F32x4Sub,
+    F32x4Mul,
+    F32x4Div,
+    F32x4Min,
+    F32x4Max,
+    F32x4PMin,
+    F32x4PMax,
+    F64x2Add,
+    F64x2Sub,
+    F64x2Mul,
+    F64x2Div,
+    F64x2Min,
+    F64x2Max,
+    F64x2PMin,
+    F64x2PMax,
 }
 
 impl fmt::Display for UnaryOp {
@@ -1208,6 +2807,84 @@ impl UnaryOp {
             I64ReinterpretF64 => "i64.reinterpret_f64",
             F32ReinterpretI32 => "f32.reinterpret_i32",
             F64ReinterpretI64 => "f64.reinterpret_i64",
+            I32Extend8S => "i32.extend8_s",
+            I32Extend16S => "i32.extend16_s",
+            I64Extend8S => "i64.extend8_s",
+            I64Extend16S => "i64.extend16_s",
+            I64Extend32S => "i64.extend32_s",
+            I32TruncSatF32S => "i32.trunc_sat_f32_s",
+            I32TruncSatF32U => "i32.trunc_sat_f32_u",
+            I32TruncSatF64S => "i32.trunc_sat_f64_s",
+            I32TruncSatF64U => "i32.trunc_sat_f64_u",
+            I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            I64TruncSatF32U => "i64.trunc_sat_f32_u",
+            I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            I64TruncSatF64U => "i64.trunc_sat_f64_u",
+            I8x16Splat => "i8x16.splat",
+            I16x8Splat => "i16x8.splat",
+            I32x4Splat => "i32x4.splat",
+            I64x2Splat => "i64x2.splat",
+            F32x4Splat => "f32x4.splat",
+            F64x2Splat => "f64x2.splat",
+            V128Not => "v128.not",
+            V128AnyTrue => "v128.any_true",
+            I8x16Abs => "i8x16.abs",
+            I8x16Neg => "i8x16.neg",
+            I8x16Popcnt => "i8x16.popcnt",
+            I8x16AllTrue => "i8x16.all_true",
+            I8x16Bitmask => "i8x16.bitmask",
+            I16x8ExtAddPairwiseI8x16S => "i16x8.extadd_pairwise_i8x16_s",
+            I16x8ExtAddPairwiseI8x16U => "i16x8.extadd_pairwise_i8x16_u",
+            I16x8Abs => "i16x8.abs",
+            I16x8Neg => "i16x8.neg",
+            I16x8AllTrue => "i16x8.all_true",
+            I16x8Bitmask => "i16x8.bitmask",
+            I16x8ExtendLowI8x16S => "i16x8.extend_low_i8x16_s",
+            I16x8ExtendHighI8x16S => "i16x8.extend_high_i8x16_s",
+            I16x8ExtendLowI8x16U => "i16x8.extend_low_i8x16_u",
+            I16x8ExtendHighI8x16U => "i16x8.extend_high_i8x16_u",
+            I32x4ExtAddPairwiseI16x8S => "i32x4.extadd_pairwise_i16x8_s",
+            I32x4ExtAddPairwiseI16x8U => "i32x4.extadd_pairwise_i16x8_u",
+            I32x4Abs => "i32x4.abs",
+            I32x4Neg => "i32x4.neg",
+            I32x4AllTrue => "i32x4.all_true",
+            I32x4Bitmask => "i32x4.bitmask",
+            I32x4ExtendLowI16x8S => "i32x4.extend_low_i16x8_s",
+            I32x4ExtendHighI16x8S => "i32x4.extend_high_i16x8_s",
+            I32x4ExtendLowI16x8U => "i32x4.extend_low_i16x8_u",
+            I32x4ExtendHighI16x8U => "i32x4.extend_high_i16x8_u",
+            I64x2Abs => "i64x2.abs",
+            I64x2Neg => "i64x2.neg",
+            I64x2AllTrue => "i64x2.all_true",
+            I64x2Bitmask => "i64x2.bitmask",
+            I64x2ExtendLowI32x4S => "i64x2.extend_low_i32x4_s",
+            I64x2ExtendHighI32x4S => "i64x2.extend_high_i32x4_s",
+            I64x2ExtendLowI32x4U => "i64x2.extend_low_i32x4_u",
+            I64x2ExtendHighI32x4U => "i64x2.extend_high_i32x4_u",
+            F32x4Ceil => "f32x4.ceil",
+            F32x4Floor => "f32x4.floor",
+            F32x4Trunc => "f32x4.trunc",
+            F32x4Nearest => "f32x4.nearest",
+            F32x4Abs => "f32x4.abs",
+            F32x4Neg => "f32x4.neg",
+            F32x4Sqrt => "f32x4.sqrt",
+            F64x2Ceil => "f64x2.ceil",
+            F64x2Floor => "f64x2.floor",
+            F64x2Trunc => "f64x2.trunc",
+            F64x2Nearest => "f64x2.nearest",
+            F64x2Abs => "f64x2.abs",
+            F64x2Neg => "f64x2.neg",
+            F64x2Sqrt => "f64x2.sqrt",
+            I32x4TruncSatF32x4S => "i32x4.trunc_sat_f32x4_s",
+            I32x4TruncSatF32x4U => "i32x4.trunc_sat_f32x4_u",
+            F32x4ConvertI32x4S => "f32x4.convert_i32x4_s",
+            F32x4ConvertI32x4U => "f32x4.convert_i32x4_u",
+            I32x4TruncSatF64x2SZero => "i32x4.trunc_sat_f64x2_s_zero",
+            I32x4TruncSatF64x2UZero => "i32x4.trunc_sat_f64x2_u_zero",
+            F64x2ConvertLowI32x4S => "f64x2.convert_low_i32x4_s",
+            F64x2ConvertLowI32x4U => "f64x2.convert_low_i32x4_u",
+            F32x4DemoteF64x2Zero => "f32x4.demote_f64x2_zero",
+            F64x2PromoteLowF32x4 => "f64x2.promote_low_f32x4",
         }
     }
 
@@ -1245,6 +2922,73 @@ impl UnaryOp {
             I64ReinterpretF64 => FunctionType::new(&[F64], &[I64]),
             F32ReinterpretI32 => FunctionType::new(&[I32], &[F32]),
             F64ReinterpretI64 => FunctionType::new(&[I64], &[F64]),
+
+            I32Extend8S | I32Extend16S => FunctionType::new(&[I32], &[I32]),
+            I64Extend8S | I64Extend16S | I64Extend32S => FunctionType::new(&[I64], &[I64]),
+
+            I32TruncSatF32S | I32TruncSatF32U => FunctionType::new(&[F32], &[I32]),
+            I32TruncSatF64S | I32TruncSatF64U => FunctionType::new(&[F64], &[I32]),
+            I64TruncSatF32S | I64TruncSatF32U => FunctionType::new(&[F32], &[I64]),
+            I64TruncSatF64S | I64TruncSatF64U => FunctionType::new(&[F64], &[I64]),
+
+            I8x16Splat | I16x8Splat | I32x4Splat => FunctionType::new(&[I32], &[V128]),
+            I64x2Splat => FunctionType::new(&[I64], &[V128]),
+            F32x4Splat => FunctionType::new(&[F32], &[V128]),
+            F64x2Splat => FunctionType::new(&[F64], &[V128]),
+            V128Not
+            | I8x16Abs
+            | I8x16Neg
+            | I8x16Popcnt
+            | I16x8ExtAddPairwiseI8x16S
+            | I16x8ExtAddPairwiseI8x16U
+            | I16x8Abs
+            | I16x8Neg
+            | I16x8ExtendLowI8x16S
+            | I16x8ExtendHighI8x16S
+            | I16x8ExtendLowI8x16U
+            | I16x8ExtendHighI8x16U
+            | I32x4ExtAddPairwiseI16x8S
+            | I32x4ExtAddPairwiseI16x8U
+            | I32x4Abs
+            | I32x4Neg
+            | I32x4ExtendLowI16x8S
+            | I32x4ExtendHighI16x8S
+            | I32x4ExtendLowI16x8U
+            | I32x4ExtendHighI16x8U
+            | I64x2Abs
+            | I64x2Neg
+            | I64x2ExtendLowI32x4S
+            | I64x2ExtendHighI32x4S
+            | I64x2ExtendLowI32x4U
+            | I64x2ExtendHighI32x4U
+            | F32x4Ceil
+            | F32x4Floor
+            | F32x4Trunc
+            | F32x4Nearest
+            | F32x4Abs
+            | F32x4Neg
+            | F32x4Sqrt
+            | F64x2Ceil
+            | F64x2Floor
+            | F64x2Trunc
+            | F64x2Nearest
+            | F64x2Abs
+            | F64x2Neg
+            | F64x2Sqrt
+            | I32x4TruncSatF32x4S
+            | I32x4TruncSatF32x4U
+            | F32x4ConvertI32x4S
+            | F32x4ConvertI32x4U
+            | I32x4TruncSatF64x2SZero
+            | I32x4TruncSatF64x2UZero
+            | F64x2ConvertLowI32x4S
+            | F64x2ConvertLowI32x4U
+            | F32x4DemoteF64x2Zero
+            | F64x2PromoteLowF32x4 => FunctionType::new(&[V128], &[V128]),
+            V128AnyTrue | I8x16AllTrue | I8x16Bitmask | I16x8AllTrue | I16x8Bitmask
+            | I32x4AllTrue | I32x4Bitmask | I64x2AllTrue | I64x2Bitmask => {
+                FunctionType::new(&[V128], &[I32])
+            }
         }
     }
 }
@@ -1302,6 +3046,84 @@ impl FromStr for UnaryOp {
             "i64.reinterpret_f64" => I64ReinterpretF64,
             "f32.reinterpret_i32" => F32ReinterpretI32,
             "f64.reinterpret_i64" => F64ReinterpretI64,
+            "i32.extend8_s" => I32Extend8S,
+            "i32.extend16_s" => I32Extend16S,
+            "i64.extend8_s" => I64Extend8S,
+            "i64.extend16_s" => I64Extend16S,
+            "i64.extend32_s" => I64Extend32S,
+            "i32.trunc_sat_f32_s" => I32TruncSatF32S,
+            "i32.trunc_sat_f32_u" => I32TruncSatF32U,
+            "i32.trunc_sat_f64_s" => I32TruncSatF64S,
+            "i32.trunc_sat_f64_u" => I32TruncSatF64U,
+            "i64.trunc_sat_f32_s" => I64TruncSatF32S,
+            "i64.trunc_sat_f32_u" => I64TruncSatF32U,
+            "i64.trunc_sat_f64_s" => I64TruncSatF64S,
+            "i64.trunc_sat_f64_u" => I64TruncSatF64U,
+            "i8x16.splat" => I8x16Splat,
+            "i16x8.splat" => I16x8Splat,
+            "i32x4.splat" => I32x4Splat,
+            "i64x2.splat" => I64x2Splat,
+            "f32x4.splat" => F32x4Splat,
+            "f64x2.splat" => F64x2Splat,
+            "v128.not" => V128Not,
+            "v128.any_true" => V128AnyTrue,
+            "i8x16.abs" => I8x16Abs,
+            "i8x16.neg" => I8x16Neg,
+            "i8x16.popcnt" => I8x16Popcnt,
+            "i8x16.all_true" => I8x16AllTrue,
+            "i8x16.bitmask" => I8x16Bitmask,
+            "i16x8.extadd_pairwise_i8x16_s" => I16x8ExtAddPairwiseI8x16S,
+            "i16x8.extadd_pairwise_i8x16_u" => I16x8ExtAddPairwiseI8x16U,
+            "i16x8.abs" => I16x8Abs,
+            "i16x8.neg" => I16x8Neg,
+            "i16x8.all_true" => I16x8AllTrue,
+            "i16x8.bitmask" => I16x8Bitmask,
+            "i16x8.extend_low_i8x16_s" => I16x8ExtendLowI8x16S,
+            "i16x8.extend_high_i8x16_s" => I16x8ExtendHighI8x16S,
+            "i16x8.extend_low_i8x16_u" => I16x8ExtendLowI8x16U,
+            "i16x8.extend_high_i8x16_u" => I16x8ExtendHighI8x16U,
+            "i32x4.extadd_pairwise_i16x8_s" => I32x4ExtAddPairwiseI16x8S,
+            "i32x4.extadd_pairwise_i16x8_u" => I32x4ExtAddPairwiseI16x8U,
+            "i32x4.abs" => I32x4Abs,
+            "i32x4.neg" => I32x4Neg,
+            "i32x4.all_true" => I32x4AllTrue,
+            "i32x4.bitmask" => I32x4Bitmask,
+            "i32x4.extend_low_i16x8_s" => I32x4ExtendLowI16x8S,
+            "i32x4.extend_high_i16x8_s" => I32x4ExtendHighI16x8S,
+            "i32x4.extend_low_i16x8_u" => I32x4ExtendLowI16x8U,
+            "i32x4.extend_high_i16x8_u" => I32x4ExtendHighI16x8U,
+            "i64x2.abs" => I64x2Abs,
+            "i64x2.neg" => I64x2Neg,
+            "i64x2.all_true" => I64x2AllTrue,
+            "i64x2.bitmask" => I64x2Bitmask,
+            "i64x2.extend_low_i32x4_s" => I64x2ExtendLowI32x4S,
+            "i64x2.extend_high_i32x4_s" => I64x2ExtendHighI32x4S,
+            "i64x2.extend_low_i32x4_u" => I64x2ExtendLowI32x4U,
+            "i64x2.extend_high_i32x4_u" => I64x2ExtendHighI32x4U,
+            "f32x4.ceil" => F32x4Ceil,
+            "f32x4.floor" => F32x4Floor,
+            "f32x4.trunc" => F32x4Trunc,
+            "f32x4.nearest" => F32x4Nearest,
+            "f32x4.abs" => F32x4Abs,
+            "f32x4.neg" => F32x4Neg,
+            "f32x4.sqrt" => F32x4Sqrt,
+            "f64x2.ceil" => F64x2Ceil,
+            "f64x2.floor" => F64x2Floor,
+            "f64x2.trunc" => F64x2Trunc,
+            "f64x2.nearest" => F64x2Nearest,
+            "f64x2.abs" => F64x2Abs,
+            "f64x2.neg" => F64x2Neg,
+            "f64x2.sqrt" => F64x2Sqrt,
+            "i32x4.trunc_sat_f32x4_s" => I32x4TruncSatF32x4S,
+            "i32x4.trunc_sat_f32x4_u" => I32x4TruncSatF32x4U,
+            "f32x4.convert_i32x4_s" => F32x4ConvertI32x4S,
+            "f32x4.convert_i32x4_u" => F32x4ConvertI32x4U,
+            "i32x4.trunc_sat_f64x2_s_zero" => I32x4TruncSatF64x2SZero,
+            "i32x4.trunc_sat_f64x2_u_zero" => I32x4TruncSatF64x2UZero,
+            "f64x2.convert_low_i32x4_s" => F64x2ConvertLowI32x4S,
+            "f64x2.convert_low_i32x4_u" => F64x2ConvertLowI32x4U,
+            "f32x4.demote_f64x2_zero" => F32x4DemoteF64x2Zero,
+            "f64x2.promote_low_f32x4" => F64x2PromoteLowF32x4,
             _ => return Err(()),
         })
     }
@@ -1387,6 +3209,138 @@ impl BinaryOp {
             F64Min => "f64.min",
             F64Max => "f64.max",
             F64Copysign => "f64.copysign",
+            I8x16Eq => "i8x16.eq",
+            I8x16Ne => "i8x16.ne",
+            I8x16LtS => "i8x16.lt_s",
+            I8x16LtU => "i8x16.lt_u",
+            I8x16GtS => "i8x16.gt_s",
+            I8x16GtU => "i8x16.gt_u",
+            I8x16LeS => "i8x16.le_s",
+            I8x16LeU => "i8x16.le_u",
+            I8x16GeS => "i8x16.ge_s",
+            I8x16GeU => "i8x16.ge_u",
+            I16x8Eq => "i16x8.eq",
+            I16x8Ne => "i16x8.ne",
+            I16x8LtS => "i16x8.lt_s",
+            I16x8LtU => "i16x8.lt_u",
+            I16x8GtS => "i16x8.gt_s",
+            I16x8GtU => "i16x8.gt_u",
+            I16x8LeS => "i16x8.le_s",
+            I16x8LeU => "i16x8.le_u",
+            I16x8GeS => "i16x8.ge_s",
+            I16x8GeU => "i16x8.ge_u",
+            I32x4Eq => "i32x4.eq",
+            I32x4Ne => "i32x4.ne",
+            I32x4LtS => "i32x4.lt_s",
+            I32x4LtU => "i32x4.lt_u",
+            I32x4GtS => "i32x4.gt_s",
+            I32x4GtU => "i32x4.gt_u",
+            I32x4LeS => "i32x4.le_s",
+            I32x4LeU => "i32x4.le_u",
+            I32x4GeS => "i32x4.ge_s",
+            I32x4GeU => "i32x4.ge_u",
+            I64x2Eq => "i64x2.eq",
+            I64x2Ne => "i64x2.ne",
+            I64x2LtS => "i64x2.lt_s",
+            I64x2GtS => "i64x2.gt_s",
+            I64x2LeS => "i64x2.le_s",
+            I64x2GeS => "i64x2.ge_s",
+            F32x4Eq => "f32x4.eq",
+            F32x4Ne => "f32x4.ne",
+            F32x4Lt => "f32x4.lt",
+            F32x4Gt => "f32x4.gt",
+            F32x4Le => "f32x4.le",
+            F32x4Ge => "f32x4.ge",
+            F64x2Eq => "f64x2.eq",
+            F64x2Ne => "f64x2.ne",
+            F64x2Lt => "f64x2.lt",
+            F64x2Gt => "f64x2.gt",
+            F64x2Le => "f64x2.le",
+            F64x2Ge => "f64x2.ge",
+            V128And => "v128.and",
+            V128AndNot => "v128.andnot",
+            V128Or => "v128.or",
+            V128Xor => "v128.xor",
+            I8x16NarrowI16x8S => "i8x16.narrow_i16x8_s",
+            I8x16NarrowI16x8U => "i8x16.narrow_i16x8_u",
+            I8x16Shl => "i8x16.shl",
+            I8x16ShrS => "i8x16.shr_s",
+            I8x16ShrU => "i8x16.shr_u",
+            I8x16Add => "i8x16.add",
+            I8x16AddSatS => "i8x16.add_sat_s",
+            I8x16AddSatU => "i8x16.add_sat_u",
+            I8x16Sub => "i8x16.sub",
+            I8x16SubSatS => "i8x16.sub_sat_s",
+            I8x16SubSatU => "i8x16.sub_sat_u",
+            I8x16MinS => "i8x16.min_s",
+            I8x16MinU => "i8x16.min_u",
+            I8x16MaxS => "i8x16.max_s",
+            I8x16MaxU => "i8x16.max_u",
+            I8x16AvgrU => "i8x16.avgr_u",
+            I8x16Swizzle => "i8x16.swizzle",
+            I16x8Q15MulrSatS => "i16x8.q15mulr_sat_s",
+            I16x8NarrowI32x4S => "i16x8.narrow_i32x4_s",
+            I16x8NarrowI32x4U => "i16x8.narrow_i32x4_u",
+            I16x8Shl => "i16x8.shl",
+            I16x8ShrS => "i16x8.shr_s",
+            I16x8ShrU => "i16x8.shr_u",
+            I16x8Add => "i16x8.add",
+            I16x8AddSatS => "i16x8.add_sat_s",
+            I16x8AddSatU => "i16x8.add_sat_u",
+            I16x8Sub => "i16x8.sub",
+            I16x8SubSatS => "i16x8.sub_sat_s",
+            I16x8SubSatU => "i16x8.sub_sat_u",
+            I16x8Mul => "i16x8.mul",
+            I16x8MinS => "i16x8.min_s",
+            I16x8MinU => "i16x8.min_u",
+            I16x8MaxS => "i16x8.max_s",
+            I16x8MaxU => "i16x8.max_u",
+            I16x8AvgrU => "i16x8.avgr_u",
+            I16x8ExtMulLowI8x16S => "i16x8.extmul_low_i8x16_s",
+            I16x8ExtMulHighI8x16S => "i16x8.extmul_high_i8x16_s",
+            I16x8ExtMulLowI8x16U => "i16x8.extmul_low_i8x16_u",
+            I16x8ExtMulHighI8x16U => "i16x8.extmul_high_i8x16_u",
+            I32x4Shl => "i32x4.shl",
+            I32x4ShrS => "i32x4.shr_s",
+            I32x4ShrU => "i32x4.shr_u",
+            I32x4Add => "i32x4.add",
+            I32x4Sub => "i32x4.sub",
+            I32x4Mul => "i32x4.mul",
+            I32x4MinS => "i32x4.min_s",
+            I32x4MinU => "i32x4.min_u",
+            I32x4MaxS => "i32x4.max_s",
+            I32x4MaxU => "i32x4.max_u",
+            I32x4DotI16x8S => "i32x4.dot_i16x8_s",
+            I32x4ExtMulLowI16x8S => "i32x4.extmul_low_i16x8_s",
+            I32x4ExtMulHighI16x8S => "i32x4.extmul_high_i16x8_s",
+            I32x4ExtMulLowI16x8U => "i32x4.extmul_low_i16x8_u",
+            I32x4ExtMulHighI16x8U => "i32x4.extmul_high_i16x8_u",
+            I64x2Shl => "i64x2.shl",
+            I64x2ShrS => "i64x2.shr_s",
+            I64x2ShrU => "i64x2.shr_u",
+            I64x2Add => "i64x2.add",
+            I64x2Sub => "i64x2.sub",
+            I64x2Mul => "i64x2.mul",
+            I64x2ExtMulLowI32x4S => "i64x2.extmul_low_i32x4_s",
+            I64x2ExtMulHighI32x4S => "i64x2.extmul_high_i32x4_s",
+            I64x2ExtMulLowI32x4U => "i64x2.extmul_low_i32x4_u",
+            I64x2ExtMulHighI32x4U => "i64x2.extmul_high_i32x4_u",
+            F32x4Add => "f32x4.add",
+            F32x4Sub => "f32x4.sub",
+            F32x4Mul => "f32x4.mul",
+            F32x4Div => "f32x4.div",
+            F32x4Min => "f32x4.min",
+            F32x4Max => "f32x4.max",
+            F32x4PMin => "f32x4.pmin",
+            F32x4PMax => "f32x4.pmax",
+            F64x2Add => "f64x2.add",
+            F64x2Sub => "f64x2.sub",
+            F64x2Mul => "f64x2.mul",
+            F64x2Div => "f64x2.div",
+            F64x2Min => "f64x2.min",
+            F64x2Max => "f64x2.max",
+            F64x2PMin => "f64x2.pmin",
+            F64x2PMax => "f64x2.pmax",
         }
     }
 
@@ -1416,6 +3370,132 @@ impl BinaryOp {
             F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => {
                 FunctionType::new(&[F64, F64], &[F64])
             }
+
+            I8x16Eq
+            | I8x16Ne
+            | I8x16LtS
+            | I8x16LtU
+            | I8x16GtS
+            | I8x16GtU
+            | I8x16LeS
+            | I8x16LeU
+            | I8x16GeS
+            | I8x16GeU
+            | I16x8Eq
+            | I16x8Ne
+            | I16x8LtS
+            | I16x8LtU
+            | I16x8GtS
+            | I16x8GtU
+            | I16x8LeS
+            | I16x8LeU
+            | I16x8GeS
+            | I16x8GeU
+            | I32x4Eq
+            | I32x4Ne
+            | I32x4LtS
+            | I32x4LtU
+            | I32x4GtS
+            | I32x4GtU
+            | I32x4LeS
+            | I32x4LeU
+            | I32x4GeS
+            | I32x4GeU
+            | I64x2Eq
+            | I64x2Ne
+            | I64x2LtS
+            | I64x2GtS
+            | I64x2LeS
+            | I64x2GeS
+            | F32x4Eq
+            | F32x4Ne
+            | F32x4Lt
+            | F32x4Gt
+            | F32x4Le
+            | F32x4Ge
+            | F64x2Eq
+            | F64x2Ne
+            | F64x2Lt
+            | F64x2Gt
+            | F64x2Le
+            | F64x2Ge
+            | V128And
+            | V128AndNot
+            | V128Or
+            | V128Xor
+            | I8x16NarrowI16x8S
+            | I8x16NarrowI16x8U
+            | I8x16Add
+            | I8x16AddSatS
+            | I8x16AddSatU
+            | I8x16Sub
+            | I8x16SubSatS
+            | I8x16SubSatU
+            | I8x16MinS
+            | I8x16MinU
+            | I8x16MaxS
+            | I8x16MaxU
+            | I8x16AvgrU
+            | I8x16Swizzle
+            | I16x8Q15MulrSatS
+            | I16x8NarrowI32x4S
+            | I16x8NarrowI32x4U
+            | I16x8Add
+            | I16x8AddSatS
+            | I16x8AddSatU
+            | I16x8Sub
+            | I16x8SubSatS
+            | I16x8SubSatU
+            | I16x8Mul
+            | I16x8MinS
+            | I16x8MinU
+            | I16x8MaxS
+            | I16x8MaxU
+            | I16x8AvgrU
+            | I16x8ExtMulLowI8x16S
+            | I16x8ExtMulHighI8x16S
+            | I16x8ExtMulLowI8x16U
+            | I16x8ExtMulHighI8x16U
+            | I32x4Add
+            | I32x4Sub
+            | I32x4Mul
+            | I32x4MinS
+            | I32x4MinU
+            | I32x4MaxS
+            | I32x4MaxU
+            | I32x4DotI16x8S
+            | I32x4ExtMulLowI16x8S
+            | I32x4ExtMulHighI16x8S
+            | I32x4ExtMulLowI16x8U
+            | I32x4ExtMulHighI16x8U
+            | I64x2Add
+            | I64x2Sub
+            | I64x2Mul
+            | I64x2ExtMulLowI32x4S
+            | I64x2ExtMulHighI32x4S
+            | I64x2ExtMulLowI32x4U
+            | I64x2ExtMulHighI32x4U
+            | F32x4Add
+            | F32x4Sub
+            | F32x4Mul
+            | F32x4Div
+            | F32x4Min
+            | F32x4Max
+            | F32x4PMin
+            | F32x4PMax
+            | F64x2Add
+            | F64x2Sub
+            | F64x2Mul
+            | F64x2Div
+            | F64x2Min
+            | F64x2Max
+            | F64x2PMin
+            | F64x2PMax => FunctionType::new(&[V128, V128], &[V128]),
+            // Shifts take the shift amount as a scalar i32.
+            I8x16Shl | I8x16ShrS | I8x16ShrU | I16x8Shl | I16x8ShrS | I16x8ShrU | I32x4Shl
+            | I32x4ShrS | I32x4ShrU | I64x2Shl | I64x2ShrS | I64x2ShrU => {
+                FunctionType::new(&[V128, I32], &[V128])
+            }
         }
     }
 }
@@ -1502,6 +3582,138 @@ impl FromStr for BinaryOp {
             "f64.min" => F64Min,
             "f64.max" => F64Max,
             "f64.copysign" => F64Copysign,
+            "i8x16.eq" => I8x16Eq,
+            "i8x16.ne" => I8x16Ne,
+            "i8x16.lt_s" => I8x16LtS,
+            "i8x16.lt_u" => I8x16LtU,
+            "i8x16.gt_s" => I8x16GtS,
+            "i8x16.gt_u" => I8x16GtU,
+            "i8x16.le_s" => I8x16LeS,
+            "i8x16.le_u" => I8x16LeU,
+            "i8x16.ge_s" => I8x16GeS,
+            "i8x16.ge_u" => I8x16GeU,
+            "i16x8.eq" => I16x8Eq,
+            "i16x8.ne" => I16x8Ne,
+            "i16x8.lt_s" => I16x8LtS,
+            "i16x8.lt_u" => I16x8LtU,
+            "i16x8.gt_s" => I16x8GtS,
+            "i16x8.gt_u" => I16x8GtU,
+            "i16x8.le_s" => I16x8LeS,
+            "i16x8.le_u" => I16x8LeU,
+            "i16x8.ge_s" => I16x8GeS,
+            "i16x8.ge_u" => I16x8GeU,
+            "i32x4.eq" => I32x4Eq,
+            "i32x4.ne" => I32x4Ne,
+            "i32x4.lt_s" => I32x4LtS,
+            "i32x4.lt_u" => I32x4LtU,
+            "i32x4.gt_s" => I32x4GtS,
+            "i32x4.gt_u" => I32x4GtU,
+            "i32x4.le_s" => I32x4LeS,
+            "i32x4.le_u" => I32x4LeU,
+            "i32x4.ge_s" => I32x4GeS,
+            "i32x4.ge_u" => I32x4GeU,
+            "i64x2.eq" => I64x2Eq,
+            "i64x2.ne" => I64x2Ne,
+            "i64x2.lt_s" => I64x2LtS,
+            "i64x2.gt_s" => I64x2GtS,
+            "i64x2.le_s" => I64x2LeS,
+            "i64x2.ge_s" => I64x2GeS,
+            "f32x4.eq" => F32x4Eq,
+            "f32x4.ne" => F32x4Ne,
+            "f32x4.lt" => F32x4Lt,
+            "f32x4.gt" => F32x4Gt,
+            "f32x4.le" => F32x4Le,
+            "f32x4.ge" => F32x4Ge,
+            "f64x2.eq" => F64x2Eq,
+            "f64x2.ne" => F64x2Ne,
+            "f64x2.lt" => F64x2Lt,
+            "f64x2.gt" => F64x2Gt,
+            "f64x2.le" => F64x2Le,
+            "f64x2.ge" => F64x2Ge,
+            "v128.and" => V128And,
+            "v128.andnot" => V128AndNot,
+            "v128.or" => V128Or,
+            "v128.xor" => V128Xor,
+            "i8x16.narrow_i16x8_s" => I8x16NarrowI16x8S,
+            "i8x16.narrow_i16x8_u" => I8x16NarrowI16x8U,
+            "i8x16.shl" => I8x16Shl,
+            "i8x16.shr_s" => I8x16ShrS,
+            "i8x16.shr_u" => I8x16ShrU,
+            "i8x16.add" => I8x16Add,
+            "i8x16.add_sat_s" => I8x16AddSatS,
+            "i8x16.add_sat_u" => I8x16AddSatU,
+            "i8x16.sub" => I8x16Sub,
+            "i8x16.sub_sat_s" => I8x16SubSatS,
+            "i8x16.sub_sat_u" => I8x16SubSatU,
+            "i8x16.min_s" => I8x16MinS,
+            "i8x16.min_u" => I8x16MinU,
+            "i8x16.max_s" => I8x16MaxS,
+            "i8x16.max_u" => I8x16MaxU,
+            "i8x16.avgr_u" => I8x16AvgrU,
+            "i8x16.swizzle" => I8x16Swizzle,
+            "i16x8.q15mulr_sat_s" => I16x8Q15MulrSatS,
+            "i16x8.narrow_i32x4_s" => I16x8NarrowI32x4S,
+            "i16x8.narrow_i32x4_u" => I16x8NarrowI32x4U,
+            "i16x8.shl" => I16x8Shl,
+            "i16x8.shr_s" => I16x8ShrS,
+            "i16x8.shr_u" => I16x8ShrU,
+            "i16x8.add" => I16x8Add,
+            "i16x8.add_sat_s" => I16x8AddSatS,
+            "i16x8.add_sat_u" => I16x8AddSatU,
+            "i16x8.sub" => I16x8Sub,
+            "i16x8.sub_sat_s" => I16x8SubSatS,
+            "i16x8.sub_sat_u" => I16x8SubSatU,
+            "i16x8.mul" => I16x8Mul,
+            "i16x8.min_s" => I16x8MinS,
+            "i16x8.min_u" => I16x8MinU,
+            "i16x8.max_s" => I16x8MaxS,
+            "i16x8.max_u" => I16x8MaxU,
+            "i16x8.avgr_u" => I16x8AvgrU,
+            "i16x8.extmul_low_i8x16_s" => I16x8ExtMulLowI8x16S,
+            "i16x8.extmul_high_i8x16_s" => I16x8ExtMulHighI8x16S,
+            "i16x8.extmul_low_i8x16_u" => I16x8ExtMulLowI8x16U,
+            "i16x8.extmul_high_i8x16_u" => I16x8ExtMulHighI8x16U,
+            "i32x4.shl" => I32x4Shl,
+            "i32x4.shr_s" => I32x4ShrS,
+            "i32x4.shr_u" => I32x4ShrU,
+            "i32x4.add" => I32x4Add,
+            "i32x4.sub" => I32x4Sub,
+            "i32x4.mul" => I32x4Mul,
+            "i32x4.min_s" => I32x4MinS,
+            "i32x4.min_u" => I32x4MinU,
+            "i32x4.max_s" => I32x4MaxS,
+            "i32x4.max_u" => I32x4MaxU,
+            "i32x4.dot_i16x8_s" => I32x4DotI16x8S,
+            "i32x4.extmul_low_i16x8_s" => I32x4ExtMulLowI16x8S,
+            "i32x4.extmul_high_i16x8_s" => I32x4ExtMulHighI16x8S,
+            "i32x4.extmul_low_i16x8_u" => I32x4ExtMulLowI16x8U,
+            "i32x4.extmul_high_i16x8_u" => I32x4ExtMulHighI16x8U,
+            "i64x2.shl" => I64x2Shl,
+            "i64x2.shr_s" => I64x2ShrS,
+            "i64x2.shr_u" => I64x2ShrU,
+            "i64x2.add" => I64x2Add,
+            "i64x2.sub" => I64x2Sub,
+            "i64x2.mul" => I64x2Mul,
+            "i64x2.extmul_low_i32x4_s" => I64x2ExtMulLowI32x4S,
+            "i64x2.extmul_high_i32x4_s" => I64x2ExtMulHighI32x4S,
+            "i64x2.extmul_low_i32x4_u" => I64x2ExtMulLowI32x4U,
+            "i64x2.extmul_high_i32x4_u" => I64x2ExtMulHighI32x4U,
+            "f32x4.add" => F32x4Add,
+            "f32x4.sub" => F32x4Sub,
+            "f32x4.mul" => F32x4Mul,
+            "f32x4.div" => F32x4Div,
+            "f32x4.min" => F32x4Min,
+            "f32x4.max" => F32x4Max,
+            "f32x4.pmin" => F32x4PMin,
+            "f32x4.pmax" => F32x4PMax,
+            "f64x2.add" => F64x2Add,
+            "f64x2.sub" => F64x2Sub,
+            "f64x2.mul" => F64x2Mul,
+            "f64x2.div" => F64x2Div,
+            "f64x2.min" => F64x2Min,
+            "f64x2.max" => F64x2Max,
+            "f64x2.pmin" => F64x2PMin,
+            "f64x2.pmax" => F64x2PMax,
             _ => return Err(()),
         })
     }
@@ -1523,6 +3735,13 @@ impl Instr {
             Else => "else",
             End => "end",
 
+            Try(_) => "try",
+            Catch(_) => "catch",
+            CatchAll => "catch_all",
+            Delegate(_) => "delegate",
+            Throw(_) => "throw",
+            Rethrow(_) => "rethrow",
+
             Br(_) => "br",
             BrIf(_) => "br_if",
             BrTable { .. } => "br_table",
@@ -1530,9 +3749,11 @@ impl Instr {
             Return => "return",
             Call(_) => "call",
             CallIndirect(_, _) => "call_indirect",
+            ReturnCall(_) => "return_call",
+            ReturnCallIndirect(_, _) => "return_call_indirect",
 
             Drop => "drop",
-            Select => "select",
+            Select | TypedSelect(_) => "select",
 
             Local(LocalOp::Get, _) => "local.get",
             Local(LocalOp::Set, _) => "local.set",
@@ -1542,16 +3763,48 @@ impl Instr {
 
             MemorySize(_) => "memory.size",
             MemoryGrow(_) => "memory.grow",
+            MemoryCopy { .. } => "memory.copy",
+            MemoryFill(_) => "memory.fill",
+            MemoryInit(_, _) => "memory.init",
+            DataDrop(_) => "data.drop",
+
+            RefNull(_) => "ref.null",
+            RefIsNull => "ref.is_null",
+            RefFunc(_) => "ref.func",
+
+            TableGet(_) => "table.get",
+            TableSet(_) => "table.set",
+            TableSize(_) => "table.size",
+            TableGrow(_) => "table.grow",
+            TableFill(_) => "table.fill",
+            TableCopy { .. } => "table.copy",
+            TableInit(_, _) => "table.init",
+            ElemDrop(_) => "elem.drop",
 
             Const(Val::I32(_)) => "i32.const",
             Const(Val::I64(_)) => "i64.const",
             Const(Val::F32(_)) => "f32.const",
             Const(Val::F64(_)) => "f64.const",
+            Const(Val::V128(_)) => "v128.const",
 
             Load(op, _) => op.to_name(),
             Store(op, _) => op.to_name(),
             Unary(op) => op.to_name(),
             Binary(op) => op.to_name(),
+
+            LoadLane(op, _, _) => op.to_name(),
+            StoreLane(op, _, _) => op.to_name(),
+            ExtractLane(op, _) => op.to_name(),
+            ReplaceLane(op, _) => op.to_name(),
+            I8x16Shuffle(_) => "i8x16.shuffle",
+            V128Bitselect => "v128.bitselect",
+
+            AtomicLoad(op, _) => op.to_name(),
+            AtomicStore(op, _) => op.to_name(),
+            AtomicRmw(op, _) => op.to_name(),
+            AtomicCmpxchg(op, _) => op.to_name(),
+            AtomicWaitNotify(op, _) => op.to_name(),
+            AtomicFence => "atomic.fence",
         }
     }
 
@@ -1568,9 +3821,34 @@ impl Instr {
             Store(ref op, _) => Some(op.to_type()),
             MemorySize(_) => Some(FunctionType::new(&[], &[I32])),
             MemoryGrow(_) => Some(FunctionType::new(&[I32], &[I32])),
+            // Inputs are destination address, source address or value, and length in bytes.
+            MemoryCopy { .. } | MemoryFill(_) | MemoryInit(_, _) => {
+                Some(FunctionType::new(&[I32, I32, I32], &[]))
+            }
+            DataDrop(_) => Some(FunctionType::new(&[], &[])),
+            RefNull(ty) => Some(FunctionType::new(&[], &[ty.into()])),
+            RefFunc(_) => Some(FunctionType::new(&[], &[FuncRef])),
+            TypedSelect(ty) => Some(FunctionType::new(&[ty, ty, I32], &[ty])),
+            TableSize(_) => Some(FunctionType::new(&[], &[I32])),
+            // Inputs are destination index, source index, and number of elements.
+            TableCopy { .. } | TableInit(_, _) => Some(FunctionType::new(&[I32, I32, I32], &[])),
+            ElemDrop(_) => Some(FunctionType::new(&[], &[])),
             Const(ref val) => Some(FunctionType::new(&[], &[val.to_type()])),
             Unary(ref op) => Some(op.to_type()),
             Binary(ref op) => Some(op.to_type()),
+            LoadLane(op, _, _) => Some(op.to_type()),
+            StoreLane(op, _, _) => Some(op.to_type()),
+            ExtractLane(op, _) => Some(op.to_type()),
+            ReplaceLane(op, _) => Some(op.to_type()),
+            I8x16Shuffle(_) => Some(FunctionType::new(&[V128, V128], &[V128])),
+            // Inputs are the two vectors to select bits from and the selection mask.
+            V128Bitselect => Some(FunctionType::new(&[V128, V128, V128], &[V128])),
+            AtomicLoad(op, _) => Some(op.to_type()),
+            AtomicStore(op, _) => Some(op.to_type()),
+            AtomicRmw(op, _) => Some(op.to_type()),
+            AtomicCmpxchg(op, _) => Some(op.to_type()),
+            AtomicWaitNotify(op, _) => Some(op.to_type()),
+            AtomicFence => Some(FunctionType::new(&[], &[])),
             CallIndirect(ref func_ty, _) => Some(FunctionType::from_iter(
                 func_ty.inputs().iter().copied().chain(std::iter::once(I32)),
                 func_ty.results().iter().copied(),
@@ -1578,17 +3856,90 @@ impl Instr {
 
             // Difficult because of nesting and block types.
             Block(_) | Loop(_) | If(_) | Else | End => None,
+            Try(_) | Catch(_) | CatchAll | Delegate(_) => None,
+            // Need lookup of the tag type.
+            Throw(_) => None,
             // Depends on the branch target block.
             Br(_) | BrIf(_) | BrTable { .. } => None,
             // Need to inspect the current/called function type.
-            Return | Call(_) => None,
+            Return | Call(_) | ReturnCall(_) | ReturnCallIndirect(_, _) => None,
             // Need lookup in locals/globals
             Local(_, _) | Global(_, _) => None,
             // Value-polymorphic, need abstract type stack.
-            Drop | Select => None,
+            Drop | Select | RefIsNull => None,
+            // Depend on the element type of the table.
+            TableGet(_) | TableSet(_) | TableGrow(_) | TableFill(_) => None,
             // Stack-polymorphic, needs type inference (br* above as well).
-            Unreachable => None,
+            Unreachable | Rethrow(_) => None,
+        }
+    }
+
+    /// Returns the memory that this instruction accesses (or grows, queries the size of, etc.),
+    /// or `None` if it doesn't access memory at all.
+    /// For `memory.copy`, this is the destination memory.
+    pub fn memory(&self) -> Option<Idx<Memory>> {
+        use Instr::*;
+        match *self {
+            Load(_, memarg)
+            | Store(_, memarg)
+            | LoadLane(_, memarg, _)
+            | StoreLane(_, memarg, _)
+            | AtomicLoad(_, memarg)
+            | AtomicStore(_, memarg)
+            | AtomicRmw(_, memarg)
+            | AtomicCmpxchg(_, memarg)
+            | AtomicWaitNotify(_, memarg) => Some(memarg.memory),
+            MemorySize(memory)
+            | MemoryGrow(memory)
+            | MemoryFill(memory)
+            | MemoryInit(memory, _)
+            | MemoryCopy { dst: memory, .. } => Some(memory),
+            _ => None,
+        }
+    }
+
+    /// Like `simple_type`, but with all addresses, memory sizes, and lengths in bytes typed by
+    /// the address type of the accessed memory, which is looked up with `address_ty`.
+    /// This is only different from `simple_type` for 64-bit memories (memory64 extension).
+    pub fn simple_type_with_memories(
+        &self,
+        address_ty: impl Fn(Idx<Memory>) -> ValType,
+    ) -> Option<FunctionType> {
+        use Instr::*;
+        let ty = self.simple_type()?;
+        let address_ty_dst = match self.memory() {
+            Some(memory) => address_ty(memory),
+            None => return Some(ty),
+        };
+        let mut inputs = ty.inputs().to_vec();
+        let mut results = ty.results().to_vec();
+        match *self {
+            MemorySize(_) => results[0] = address_ty_dst,
+            MemoryGrow(_) => {
+                inputs[0] = address_ty_dst;
+                results[0] = address_ty_dst;
+            }
+            // Destination address and length, but not the fill value.
+            MemoryFill(_) => {
+                inputs[0] = address_ty_dst;
+                inputs[2] = address_ty_dst;
+            }
+            // The length must fit into both memories, so it is only i64 if both are 64-bit.
+            MemoryCopy { src, .. } => {
+                let address_ty_src = address_ty(src);
+                inputs[0] = address_ty_dst;
+                inputs[1] = address_ty_src;
+                inputs[2] = if address_ty_src == address_ty_dst {
+                    address_ty_dst
+                } else {
+                    ValType::I32
+                };
+            }
+            // Only the destination address, source offset and length are within the data segment.
+            // All other instructions accessing memory take the address as their first input.
+            _ => inputs[0] = address_ty_dst,
         }
+        Some(FunctionType::new(&inputs, &results))
     }
 }
 
@@ -1607,6 +3958,24 @@ impl FromStr for Instr {
             let u: usize = str.parse().map_err(|_| ())?;
             Ok(u.into())
         }
+        // The memory index is optional and 0 if not given.
+        fn parse_memory_idx(str: &str) -> Result<Idx<Memory>, ()> {
+            match str.trim() {
+                "" => Ok(Idx::from(0u32)),
+                str => parse_idx(str),
+            }
+        }
+        // The lane index is the last argument, after the (optional) memarg.
+        fn parse_memarg_lane(str: &str, op: impl MemoryOp) -> Result<(Memarg, u8), ()> {
+            let (memarg, lane) = str
+                .trim()
+                .rsplit_once(char::is_whitespace)
+                .unwrap_or(("", str));
+            Ok((
+                Memarg::from_str(memarg, op)?,
+                lane.trim().parse().map_err(|_| ())?,
+            ))
+        }
 
         let (operator, rest) = str.split_once(char::is_whitespace).ok_or(())?;
         Ok(match operator {
@@ -1620,6 +3989,13 @@ impl FromStr for Instr {
             "else" => Else,
             "end" => End,
 
+            "try" => Try(FunctionType::from_str(rest)?),
+            "catch" => Catch(parse_idx(rest)?),
+            "catch_all" => CatchAll,
+            "delegate" => Delegate(parse_label(rest)?),
+            "throw" => Throw(parse_idx(rest)?),
+            "rethrow" => Rethrow(parse_label(rest)?),
+
             "br" => Br(parse_label(rest)?),
             "br_if" => BrIf(parse_label(rest)?),
             "br_table" => {
@@ -1640,16 +4016,27 @@ impl FromStr for Instr {
                 let func_idx = parse_idx(rest)?;
                 Call(func_idx)
             }
-            "call_indirect" => {
-                let ty = FunctionType::from_str(rest)?;
-                // For the WebAssembly MVP there is only a single table, so the
-                // table index was not printed. Instead assume 0.
-                let table_idx = Idx::from(0u32);
-                CallIndirect(ty, table_idx)
+            "call_indirect" | "return_call_indirect" => {
+                // The table index is only printed if it is not 0, e.g., the only table in the
+                // WebAssembly MVP, see `Display` below.
+                let (table_idx, ty) = match rest.trim().split_once(char::is_whitespace) {
+                    Some((table_idx, ty)) if table_idx.parse::<u32>().is_ok() => {
+                        (parse_idx(table_idx)?, ty)
+                    }
+                    _ => (Idx::from(0u32), rest),
+                };
+                let ty = FunctionType::from_str(ty)?;
+                if operator == "call_indirect" {
+                    CallIndirect(ty, table_idx)
+                } else {
+                    ReturnCallIndirect(ty, table_idx)
+                }
             }
+            "return_call" => ReturnCall(parse_idx(rest)?),
 
             "drop" => Drop,
-            "select" => Select,
+            "select" if rest.trim().is_empty() => Select,
+            "select" => TypedSelect(ValType::from_str(rest)?),
 
             "local.get" => Local(LocalOp::Get, parse_idx(rest)?),
             "local.set" => Local(LocalOp::Set, parse_idx(rest)?),
@@ -1658,14 +4045,61 @@ impl FromStr for Instr {
             "global.set" => Global(GlobalOp::Set, parse_idx(rest)?),
 
             // For the WebAssembly MVP there is only a single memory, so the
-            // memory index was not printed. Instead assume 0.
-            "memory.size" => MemorySize(Idx::from(0u32)),
-            "memory.grow" => MemoryGrow(Idx::from(0u32)),
+            // memory index is only printed if it is not 0 (multi-memory extension).
+            // Otherwise, assume 0.
+            "memory.size" => MemorySize(parse_memory_idx(rest)?),
+            "memory.grow" => MemoryGrow(parse_memory_idx(rest)?),
+            "memory.copy" => match rest.trim().split_once(char::is_whitespace) {
+                Some((dst, src)) => MemoryCopy {
+                    dst: parse_idx(dst)?,
+                    src: parse_idx(src.trim())?,
+                },
+                None if rest.trim().is_empty() => MemoryCopy {
+                    dst: Idx::from(0u32),
+                    src: Idx::from(0u32),
+                },
+                None => Err(())?,
+            },
+            "memory.fill" => MemoryFill(parse_memory_idx(rest)?),
+            "memory.init" => match rest.trim().split_once(char::is_whitespace) {
+                Some((memory_idx, data_idx)) => {
+                    MemoryInit(parse_idx(memory_idx)?, parse_idx(data_idx.trim())?)
+                }
+                None => MemoryInit(Idx::from(0u32), parse_idx(rest.trim())?),
+            },
+            "data.drop" => DataDrop(parse_idx(rest)?),
+
+            "ref.null" => RefNull(match rest.trim() {
+                "func" => RefType::FuncRef,
+                "extern" => RefType::ExternRef,
+                _ => return Err(()),
+            }),
+            "ref.is_null" => RefIsNull,
+            "ref.func" => RefFunc(parse_idx(rest)?),
+
+            "table.get" => TableGet(parse_idx(rest)?),
+            "table.set" => TableSet(parse_idx(rest)?),
+            "table.size" => TableSize(parse_idx(rest)?),
+            "table.grow" => TableGrow(parse_idx(rest)?),
+            "table.fill" => TableFill(parse_idx(rest)?),
+            "table.copy" => {
+                let (dst, src) = rest.trim().split_once(char::is_whitespace).ok_or(())?;
+                TableCopy {
+                    dst: parse_idx(dst)?,
+                    src: parse_idx(src.trim())?,
+                }
+            }
+            "table.init" => {
+                let (table, elem) = rest.trim().split_once(char::is_whitespace).ok_or(())?;
+                TableInit(parse_idx(table)?, parse_idx(elem.trim())?)
+            }
+            "elem.drop" => ElemDrop(parse_idx(rest)?),
 
             "i32.const" => Const(Val::from_str(rest, ValType::I32)?),
             "i64.const" => Const(Val::from_str(rest, ValType::I64)?),
             "f32.const" => Const(Val::from_str(rest, ValType::F32)?),
             "f64.const" => Const(Val::from_str(rest, ValType::F64)?),
+            "v128.const" => Const(Val::from_str(rest, ValType::V128)?),
 
             op if LoadOp::from_str(op).is_ok() => {
                 let op = LoadOp::from_str(op).unwrap();
@@ -1679,6 +4113,55 @@ impl FromStr for Instr {
             op if UnaryOp::from_str(op).is_ok() => UnaryOp::from_str(op).map(Unary)?,
             op if BinaryOp::from_str(op).is_ok() => BinaryOp::from_str(op).map(Binary)?,
 
+            op if LoadLaneOp::from_str(op).is_ok() => {
+                let op = LoadLaneOp::from_str(op).unwrap();
+                let (memarg, lane) = parse_memarg_lane(rest, op)?;
+                LoadLane(op, memarg, lane)
+            }
+            op if StoreLaneOp::from_str(op).is_ok() => {
+                let op = StoreLaneOp::from_str(op).unwrap();
+                let (memarg, lane) = parse_memarg_lane(rest, op)?;
+                StoreLane(op, memarg, lane)
+            }
+            op if ExtractLaneOp::from_str(op).is_ok() => ExtractLane(
+                ExtractLaneOp::from_str(op)?,
+                rest.trim().parse().map_err(|_| ())?,
+            ),
+            op if ReplaceLaneOp::from_str(op).is_ok() => ReplaceLane(
+                ReplaceLaneOp::from_str(op)?,
+                rest.trim().parse().map_err(|_| ())?,
+            ),
+            "i8x16.shuffle" => {
+                let lanes = rest
+                    .split_whitespace()
+                    .map(|lane| lane.parse().map_err(|_| ()))
+                    .collect::<Result<Vec<u8>, _>>()?;
+                I8x16Shuffle(lanes.try_into().map_err(|_| ())?)
+            }
+            "v128.bitselect" => V128Bitselect,
+
+            op if AtomicLoadOp::from_str(op).is_ok() => {
+                let op = AtomicLoadOp::from_str(op).unwrap();
+                AtomicLoad(op, Memarg::from_str(rest, op)?)
+            }
+            op if AtomicStoreOp::from_str(op).is_ok() => {
+                let op = AtomicStoreOp::from_str(op).unwrap();
+                AtomicStore(op, Memarg::from_str(rest, op)?)
+            }
+            op if AtomicRmwOp::from_str(op).is_ok() => {
+                let op = AtomicRmwOp::from_str(op).unwrap();
+                AtomicRmw(op, Memarg::from_str(rest, op)?)
+            }
+            op if AtomicCmpxchgOp::from_str(op).is_ok() => {
+                let op = AtomicCmpxchgOp::from_str(op).unwrap();
+                AtomicCmpxchg(op, Memarg::from_str(rest, op)?)
+            }
+            op if AtomicWaitNotifyOp::from_str(op).is_ok() => {
+                let op = AtomicWaitNotifyOp::from_str(op).unwrap();
+                AtomicWaitNotify(op, Memarg::from_str(rest, op)?)
+            }
+            "atomic.fence" => AtomicFence,
+
             _ => return Err(()),
         })
     }
@@ -1693,10 +4176,13 @@ impl fmt::Display for Instr {
         use self::Instr::*;
         match self {
             // instructions without arguments
-            Unreachable | Nop | Drop | Select | Return | Else | End | MemorySize(_)
-            | MemoryGrow(_) | Unary(_) | Binary(_) => Ok(()),
+            Unreachable | Nop | Drop | Select | Return | Else | End | CatchAll | RefIsNull
+            | Unary(_) | Binary(_) | V128Bitselect | AtomicFence => Ok(()),
+
+            Block(ty) | Loop(ty) | If(ty) | Try(ty) => write!(f, " {ty}"),
 
-            Block(ty) | Loop(ty) | If(ty) => write!(f, " {ty}"),
+            Catch(tag_idx) | Throw(tag_idx) => write!(f, " {}", tag_idx.to_u32()),
+            Delegate(label) | Rethrow(label) => write!(f, " {}", label.to_u32()),
 
             Br(label) => write!(f, " {}", label.to_u32()),
             BrIf(label) => write!(f, " {}", label.to_u32()),
@@ -1707,14 +4193,54 @@ impl fmt::Display for Instr {
                 write!(f, " {}", default.to_u32())
             }
 
-            Call(func_idx) => write!(f, " {}", func_idx.to_u32()),
-            // We don't print the table index, because we also don't for memory.size and memory.grow,
-            // and because in the MVP the table index is going to be 0 anyway.
-            CallIndirect(func_ty, _table_idx) => write!(f, " {func_ty}"),
+            Call(func_idx) | ReturnCall(func_idx) => write!(f, " {}", func_idx.to_u32()),
+            // We don't print the table index if it is 0 (the only table in the MVP), similar to
+            // memory.size and memory.grow.
+            CallIndirect(func_ty, table_idx) | ReturnCallIndirect(func_ty, table_idx)
+                if table_idx.to_u32() == 0 =>
+            {
+                write!(f, " {func_ty}")
+            }
+            CallIndirect(func_ty, table_idx) | ReturnCallIndirect(func_ty, table_idx) => {
+                write!(f, " {} {func_ty}", table_idx.to_u32())
+            }
+            TypedSelect(ty) => write!(f, " {ty}"),
 
             Local(_, local_idx) => write!(f, " {}", local_idx.to_u32()),
             Global(_, global_idx) => write!(f, " {}", global_idx.to_u32()),
 
+            // We don't print the memory index if it is 0 (the only memory in the MVP), similar to
+            // call_indirect.
+            MemorySize(memory_idx) | MemoryGrow(memory_idx) | MemoryFill(memory_idx)
+                if memory_idx.to_u32() == 0 =>
+            {
+                Ok(())
+            }
+            MemorySize(memory_idx) | MemoryGrow(memory_idx) | MemoryFill(memory_idx) => {
+                write!(f, " {}", memory_idx.to_u32())
+            }
+            MemoryCopy { dst, src } if dst.to_u32() == 0 && src.to_u32() == 0 => Ok(()),
+            MemoryCopy { dst, src } => write!(f, " {} {}", dst.to_u32(), src.to_u32()),
+            MemoryInit(memory_idx, data_idx) if memory_idx.to_u32() == 0 => {
+                write!(f, " {}", data_idx.to_u32())
+            }
+            MemoryInit(memory_idx, data_idx) => {
+                write!(f, " {} {}", memory_idx.to_u32(), data_idx.to_u32())
+            }
+            DataDrop(data_idx) => write!(f, " {}", data_idx.to_u32()),
+
+            RefNull(RefType::FuncRef) => write!(f, " func"),
+            RefNull(RefType::ExternRef) => write!(f, " extern"),
+            RefFunc(func_idx) => write!(f, " {}", func_idx.to_u32()),
+
+            TableGet(table_idx) | TableSet(table_idx) | TableSize(table_idx)
+            | TableGrow(table_idx) | TableFill(table_idx) => write!(f, " {}", table_idx.to_u32()),
+            TableCopy { dst, src } => write!(f, " {} {}", dst.to_u32(), src.to_u32()),
+            TableInit(table_idx, elem_idx) => {
+                write!(f, " {} {}", table_idx.to_u32(), elem_idx.to_u32())
+            }
+            ElemDrop(elem_idx) => write!(f, " {}", elem_idx.to_u32()),
+
             Load(op, memarg) => {
                 if !memarg.is_default(*op) {
                     f.write_str(" ")?;
@@ -1729,6 +4255,59 @@ impl fmt::Display for Instr {
             }
 
             Const(val) => write!(f, " {val}"),
+
+            LoadLane(op, memarg, lane) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)?;
+                write!(f, " {lane}")
+            }
+            StoreLane(op, memarg, lane) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)?;
+                write!(f, " {lane}")
+            }
+            ExtractLane(_, lane) | ReplaceLane(_, lane) => write!(f, " {lane}"),
+            I8x16Shuffle(lanes) => {
+                for lane in lanes {
+                    write!(f, " {lane}")?;
+                }
+                Ok(())
+            }
+
+            AtomicLoad(op, memarg) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)
+            }
+            AtomicStore(op, memarg) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)
+            }
+            AtomicRmw(op, memarg) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)
+            }
+            AtomicCmpxchg(op, memarg) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)
+            }
+            AtomicWaitNotify(op, memarg) => {
+                if !memarg.is_default(*op) {
+                    f.write_str(" ")?;
+                }
+                memarg.fmt(f, *op)
+            }
         }
     }
 }
@@ -1766,8 +4345,19 @@ impl Module {
         self.memories.iter().enumerate().map(|(i, m)| (i.into(), m))
     }
 
+    pub fn tags(&self) -> impl Iterator<Item = (Idx<Tag>, &Tag)> {
+        self.tags.iter().enumerate().map(|(i, t)| (i.into(), t))
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = (Idx<Element>, &Element)> {
+        self.elements.iter().enumerate().map(|(i, e)| (i.into(), e))
+    }
+
+    pub fn data(&self) -> impl Iterator<Item = (Idx<Data>, &Data)> {
+        self.data.iter().enumerate().map(|(i, d)| (i.into(), d))
+    }
+
     // Convenient accessors of functions for the typed, high-level index.
-    // TODO Add the same for globals, tables, and memories, if needed.
 
     pub fn function(&self, idx: Idx<Function>) -> &Function {
         &self.functions[idx.to_usize()]
@@ -1785,6 +4375,18 @@ impl Module {
         &mut self.globals[idx.to_usize()]
     }
 
+    pub fn table(&self, idx: Idx<Table>) -> &Table {
+        &self.tables[idx.to_usize()]
+    }
+
+    pub fn memory(&self, idx: Idx<Memory>) -> &Memory {
+        &self.memories[idx.to_usize()]
+    }
+
+    pub fn tag(&self, idx: Idx<Tag>) -> &Tag {
+        &self.tags[idx.to_usize()]
+    }
+
     pub fn add_function(
         &mut self,
         type_: FunctionType,
@@ -1795,7 +4397,8 @@ impl Module {
             type_,
             Code {
                 locals: locals.into_iter().map(Local::new).collect(),
-                body,
+                body: body.into(),
+                label_names: Vec::new(),
             },
             Vec::new(),
         ));
@@ -1823,6 +4426,7 @@ impl Module {
             type_: GlobalType(type_, mut_),
             init: ImportOrPresent::Present(init),
             export: Vec::new(),
+            name: None,
         });
         (self.globals.len() - 1).into()
     }
@@ -1886,18 +4490,55 @@ impl Function {
         }
     }
 
+    /// Panics if the body was parsed lazily and is malformed, use `try_instrs` to get the error
+    /// instead (or decode all bodies upfront with `Module::decode_lazy_bodies`).
     pub fn instrs(&self) -> &[Instr] {
         self.code().map(|code| code.body.as_slice()).unwrap_or(&[])
     }
 
+    /// Like `instrs`, but returns an error instead of panicking if the body is malformed.
+    pub fn try_instrs(&self) -> Result<&[Instr], ParseError> {
+        match self.code() {
+            Some(code) => Ok(code.body.try_get()?.as_slice()),
+            None => Ok(&[]),
+        }
+    }
+
     pub fn instrs_mut(&mut self) -> Option<&mut Vec<Instr>> {
-        self.code_mut().map(|code| &mut code.body)
+        self.code_mut().map(|code| &mut *code.body)
+    }
+
+    /// Returns the instructions together with their types and the type stack before each
+    /// instruction, as inferred by the type checker, such that analyses and instrumentation do not
+    /// have to model the type stack themselves.
+    ///
+    /// This is streaming, i.e., each item is produced as soon as the instruction is checked, so
+    /// instructions in dead code have no concrete type (see the module comment in `types` for why).
+    /// Use `instrs_with_concrete_types` instead if those are required.
+    pub fn instrs_with_types<'a>(&'a self, module: &'a Module) -> InstrsWithTypes<'a> {
+        InstrsWithTypes::new(self, module)
+    }
+
+    /// Like `instrs_with_types`, but also resolves the types of instructions in dead code, which
+    /// requires to first type check the whole function.
+    pub fn instrs_with_concrete_types<'a>(
+        &'a self,
+        module: &'a Module,
+    ) -> Result<Vec<InstrWithConcreteType<'a>>, TypeError> {
+        crate::types::instrs_with_concrete_types(self, module)
     }
 
     pub fn instr_count(&self) -> usize {
         self.code().map(|code| code.body.len()).unwrap_or(0)
     }
 
+    /// Prints the function in the WebAssembly text format.
+    /// Without the surrounding module, all indices (e.g., of called functions) are unchanged,
+    /// which is different from `Module::to_wat` if the module has imports after other functions.
+    pub fn to_wat(&self) -> String {
+        crate::print_wat::print_function(self)
+    }
+
     pub fn modify_instrs(&mut self, f: impl Fn(Instr) -> Vec<Instr>) {
         if let Some(body) = self.instrs_mut() {
             let new_body = Vec::with_capacity(body.len());
@@ -2104,7 +4745,133 @@ impl Code {
     pub fn new() -> Self {
         Code {
             locals: Vec::new(),
-            body: Vec::new(),
+            body: LazyExpr::default(),
+            label_names: Vec::new(),
+        }
+    }
+}
+
+impl LazyExpr {
+    pub(crate) fn from_raw(raw: RawBody) -> Self {
+        LazyExpr {
+            decoded: OnceLock::new(),
+            raw: Some(raw),
+        }
+    }
+
+    /// The original binary encoding, if the body was parsed lazily and not mutated since.
+    pub(crate) fn raw(&self) -> Option<&RawBody> {
+        self.raw.as_ref()
+    }
+
+    pub fn is_decoded(&self) -> bool {
+        self.decoded.get().is_some()
+    }
+
+    /// Returns the instructions, decoding (and caching) them if necessary.
+    pub fn try_get(&self) -> Result<&Expr, ParseError> {
+        if let Some(instrs) = self.decoded.get() {
+            return Ok(instrs);
+        }
+        let instrs = self
+            .raw
+            .as_ref()
+            .expect("either decoded instructions or raw bytes must be present")
+            .decode()?;
+        // If another thread decoded concurrently, both results are the same anyway.
+        Ok(self.decoded.get_or_init(|| instrs))
+    }
+
+    /// Like `try_get`, but does not cache the decoded instructions, e.g., for encoding, such that
+    /// not all bodies of a lazily parsed module stay decoded afterwards.
+    pub(crate) fn get_uncached(&self) -> Result<Cow<'_, Expr>, ParseError> {
+        match (self.decoded.get(), &self.raw) {
+            (Some(instrs), _) => Ok(Cow::Borrowed(instrs)),
+            (None, Some(raw)) => Ok(Cow::Owned(raw.decode()?)),
+            (None, None) => {
+                unreachable!("either decoded instructions or raw bytes must be present")
+            }
+        }
+    }
+}
+
+/// An empty, but already decoded body, to which instructions can be added directly.
+impl Default for LazyExpr {
+    fn default() -> Self {
+        Vec::new().into()
+    }
+}
+
+impl From<Expr> for LazyExpr {
+    fn from(instrs: Expr) -> Self {
+        LazyExpr {
+            decoded: OnceLock::from(instrs),
+            raw: None,
+        }
+    }
+}
+
+impl std::ops::Deref for LazyExpr {
+    type Target = Expr;
+
+    fn deref(&self) -> &Expr {
+        self.try_get()
+            .unwrap_or_else(|err| panic!("invalid lazily parsed function body: {err}"))
+    }
+}
+
+impl std::ops::DerefMut for LazyExpr {
+    fn deref_mut(&mut self) -> &mut Expr {
+        self.try_get()
+            .unwrap_or_else(|err| panic!("invalid lazily parsed function body: {err}"));
+        self.raw = None;
+        self.decoded
+            .get_mut()
+            .expect("instructions were decoded right above")
+    }
+}
+
+impl<'a> IntoIterator for &'a LazyExpr {
+    type Item = &'a Instr;
+    type IntoIter = std::slice::Iter<'a, Instr>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// Compare, hash, and print the instructions, independent of whether they are decoded already.
+impl PartialEq for LazyExpr {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for LazyExpr {}
+
+impl PartialOrd for LazyExpr {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LazyExpr {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl hash::Hash for LazyExpr {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl fmt::Debug for LazyExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_get() {
+            Ok(instrs) => instrs.fmt(f),
+            Err(err) => write!(f, "<invalid lazily parsed function body: {err}>"),
         }
     }
 }
@@ -2137,6 +4904,7 @@ impl Global {
             type_,
             init: ImportOrPresent::Present(init),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2145,6 +4913,7 @@ impl Global {
             type_,
             init: ImportOrPresent::Import(import_module, import_name),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2166,21 +4935,28 @@ impl Global {
 }
 
 impl Table {
-    pub fn new(limits: Limits) -> Table {
+    pub fn new(limits: Limits, elem_type: RefType) -> Table {
         Table {
             limits,
+            elem_type,
             import: None,
-            elements: Vec::new(),
             export: Vec::new(),
+            name: None,
         }
     }
 
-    pub fn new_imported(limits: Limits, import_module: String, import_name: String) -> Table {
+    pub fn new_imported(
+        limits: Limits,
+        elem_type: RefType,
+        import_module: String,
+        import_name: String,
+    ) -> Table {
         Table {
             limits,
+            elem_type,
             import: Some((import_module, import_name)),
-            elements: Vec::new(),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2196,8 +4972,8 @@ impl Memory {
         Memory {
             limits,
             import: None,
-            data: Vec::new(),
             export: Vec::new(),
+            name: None,
         }
     }
 
@@ -2205,7 +4981,41 @@ impl Memory {
         Memory {
             limits,
             import: Some((import_module, import_name)),
-            data: Vec::new(),
+            export: Vec::new(),
+            name: None,
+        }
+    }
+
+    pub fn import(&self) -> Option<(&str, &str)> {
+        self.import
+            .as_ref()
+            .map(|(module, name)| (module.as_str(), name.as_str()))
+    }
+
+    /// Type of addresses into this memory, i.e., `i64` for 64-bit memories (memory64 extension)
+    /// and `i32` otherwise.
+    pub fn address_type(&self) -> ValType {
+        if self.limits.memory64 {
+            ValType::I64
+        } else {
+            ValType::I32
+        }
+    }
+}
+
+impl Tag {
+    pub fn new(type_: FunctionType) -> Tag {
+        Tag {
+            type_,
+            import: None,
+            export: Vec::new(),
+        }
+    }
+
+    pub fn new_imported(type_: FunctionType, import_module: String, import_name: String) -> Tag {
+        Tag {
+            type_,
+            import: Some((import_module, import_name)),
             export: Vec::new(),
         }
     }
@@ -0,0 +1,273 @@
+//! Static call graph of a module, see `Module::call_graph`.
+//!
+//! Direct calls (`call`, `return_call`) have exactly one callee. Indirect calls
+//! (`call_indirect`, `return_call_indirect`) are over-approximated by all functions that may be
+//! in the table at runtime and have the type of the call:
+//!  - The functions in active element segments for the table.
+//!  - If the table is modified by instructions (`table.set`, `table.grow`, etc.), also every
+//!    function of which a reference is taken, i.e., that appears in any element segment or in a
+//!    `ref.func` instruction. For `table.init`, the functions in the initialized segment suffice.
+//!  - If the table is imported or exported, the host can put arbitrary references into it, which
+//!    are approximated by all referenced and all exported functions.
+//!
+//! Roots (i.e., entry points) are the exported functions and the start function, plus all
+//! functions in imported or exported tables, since the host can also call those.
+//! Calls from imported functions back into the module are not modeled beyond that, i.e., they
+//! can only call roots.
+
+use std::collections::BTreeSet;
+
+use nohash_hasher::IntSet;
+use rayon::prelude::*;
+use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
+
+use crate::*;
+
+/// Nodes are all functions of the module (imported or not), identified by their index.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CallGraph {
+    roots: Vec<Idx<Function>>,
+    /// For every function, its outgoing edges, sorted and without duplicates.
+    callees: Vec<Vec<CallEdge>>,
+    /// For every function, the functions that (may) call it, sorted and without duplicates.
+    callers: Vec<Vec<Idx<Function>>>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct CallEdge {
+    pub callee: Idx<Function>,
+    pub kind: CallKind,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum CallKind {
+    /// `call` or `return_call`, i.e., the callee is known statically.
+    Direct,
+    /// `call_indirect` or `return_call_indirect`, i.e., the callee may or may not be called.
+    Indirect,
+}
+
+impl CallGraph {
+    pub(crate) fn new(module: &Module) -> Self {
+        let referenced_functions = referenced_functions(module);
+        let exported_functions = module
+            .functions()
+            .filter(|(_, function)| !function.export.is_empty())
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        let mut roots: BTreeSet<Idx<Function>> = exported_functions.iter().copied().collect();
+        roots.extend(module.start);
+
+        // Which functions may be in each table, regardless of the type.
+        let mut table_functions = vec![BTreeSet::new(); module.tables.len()];
+        for (_, element) in module.elements() {
+            if let ElementMode::Active { table, .. } = element.mode {
+                table_functions[table.to_usize()].extend(element_functions(element));
+            }
+        }
+        let modified_tables = modified_tables(module);
+        for (table_idx, table) in module.tables() {
+            let functions = &mut table_functions[table_idx.to_usize()];
+            let host_accessible = table.import.is_some() || !table.export.is_empty();
+            if host_accessible || modified_tables.contains(&table_idx) {
+                functions.extend(referenced_functions.iter().copied());
+            }
+            if host_accessible {
+                functions.extend(exported_functions.iter().copied());
+                roots.extend(functions.iter().copied());
+            }
+        }
+        for (table_idx, element_idx) in initialized_tables(module) {
+            let functions = element_functions(&module.elements[element_idx.to_usize()]);
+            table_functions[table_idx.to_usize()].extend(functions);
+        }
+
+        // Indirect callees by the type of the call and the table, cached since there are often
+        // many indirect calls of the same type.
+        let indirect_callees: FxHashMap<(&FunctionType, Idx<Table>), Vec<Idx<Function>>> = module
+            .functions
+            .par_iter()
+            .flat_map_iter(|function| function.instrs())
+            .filter_map(|instr| match instr {
+                Instr::CallIndirect(type_, table) | Instr::ReturnCallIndirect(type_, table) => {
+                    Some((type_, *table))
+                }
+                _ => None,
+            })
+            .collect::<FxHashSet<_>>()
+            .into_iter()
+            .map(|(type_, table)| {
+                let callees = table_functions[table.to_usize()]
+                    .iter()
+                    .copied()
+                    .filter(|callee| module.function(*callee).type_ == *type_)
+                    .collect();
+                ((type_, table), callees)
+            })
+            .collect();
+
+        let callees: Vec<Vec<CallEdge>> = module
+            .functions
+            .par_iter()
+            .map(|function| {
+                let mut edges = BTreeSet::new();
+                for instr in function.instrs() {
+                    match instr {
+                        Instr::Call(callee) | Instr::ReturnCall(callee) => {
+                            edges.insert(CallEdge {
+                                callee: *callee,
+                                kind: CallKind::Direct,
+                            });
+                        }
+                        Instr::CallIndirect(type_, table)
+                        | Instr::ReturnCallIndirect(type_, table) => {
+                            edges.extend(indirect_callees[&(type_, *table)].iter().map(|callee| {
+                                CallEdge {
+                                    callee: *callee,
+                                    kind: CallKind::Indirect,
+                                }
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+                edges.into_iter().collect()
+            })
+            .collect();
+
+        let mut callers = vec![Vec::new(); module.functions.len()];
+        for (caller, edges) in callees.iter().enumerate() {
+            for edge in edges {
+                let callers = &mut callers[edge.callee.to_usize()];
+                // Edges are sorted, so duplicates (direct and indirect) are adjacent.
+                if callers.last() != Some(&Idx::from(caller)) {
+                    callers.push(Idx::from(caller));
+                }
+            }
+        }
+
+        CallGraph {
+            roots: roots.into_iter().collect(),
+            callees,
+            callers,
+        }
+    }
+
+    /// Entry points of the module, sorted.
+    pub fn roots(&self) -> &[Idx<Function>] {
+        &self.roots
+    }
+
+    pub fn callees(&self, caller: Idx<Function>) -> &[CallEdge] {
+        &self.callees[caller.to_usize()]
+    }
+
+    pub fn callers(&self, callee: Idx<Function>) -> &[Idx<Function>] {
+        &self.callers[callee.to_usize()]
+    }
+
+    /// All edges as `(caller, edge)` pairs, sorted.
+    pub fn edges(&self) -> impl Iterator<Item = (Idx<Function>, CallEdge)> + '_ {
+        self.callees
+            .iter()
+            .enumerate()
+            .flat_map(|(caller, edges)| edges.iter().map(move |edge| (caller.into(), *edge)))
+    }
+
+    /// Whether the static call graph contains a (direct or indirect) edge from `caller` to
+    /// `callee`, e.g., to check a call that was observed dynamically. Should always be the case,
+    /// except for callbacks from imported functions.
+    pub fn may_call(&self, caller: Idx<Function>, callee: Idx<Function>) -> bool {
+        self.callees(caller)
+            .iter()
+            .any(|edge| edge.callee == callee)
+    }
+
+    /// Functions that are transitively called from the roots, sorted.
+    pub fn reachable_functions(&self) -> Vec<Idx<Function>> {
+        let mut reachable = vec![false; self.callees.len()];
+        let mut worklist = self.roots.clone();
+        while let Some(function) = worklist.pop() {
+            if !std::mem::replace(&mut reachable[function.to_usize()], true) {
+                worklist.extend(self.callees(function).iter().map(|edge| edge.callee));
+            }
+        }
+        reachable
+            .into_iter()
+            .enumerate()
+            .filter(|(_, reachable)| *reachable)
+            .map(|(function, _)| function.into())
+            .collect()
+    }
+
+    /// Functions that are never called, neither directly nor indirectly, starting from the roots,
+    /// i.e., dead code (unless imported functions call them via some other way), sorted.
+    pub fn unreachable_functions(&self) -> Vec<Idx<Function>> {
+        let reachable: IntSet<_> = self.reachable_functions().into_iter().collect();
+        (0..self.callees.len())
+            .map(Idx::from)
+            .filter(|function| !reachable.contains(function))
+            .collect()
+    }
+}
+
+/// Functions in an element segment, ignoring null references.
+fn element_functions(element: &Element) -> Vec<Idx<Function>> {
+    match &element.items {
+        ElementItems::Functions(functions) => functions.clone(),
+        ElementItems::Expressions(exprs) => exprs.iter().flat_map(|expr| ref_funcs(expr)).collect(),
+    }
+}
+
+fn ref_funcs(instrs: &[Instr]) -> impl Iterator<Item = Idx<Function>> + '_ {
+    instrs.iter().filter_map(|instr| match instr {
+        Instr::RefFunc(function) => Some(*function),
+        _ => None,
+    })
+}
+
+/// Functions of which a reference is taken somewhere in the module.
+fn referenced_functions(module: &Module) -> BTreeSet<Idx<Function>> {
+    let mut functions: BTreeSet<_> = module.elements.iter().flat_map(element_functions).collect();
+    for global in &module.globals {
+        if let ImportOrPresent::Present(init) = &global.init {
+            functions.extend(ref_funcs(init));
+        }
+    }
+    for function in &module.functions {
+        functions.extend(ref_funcs(function.instrs()));
+    }
+    functions
+}
+
+/// Tables that instructions may put arbitrary function references into.
+fn modified_tables(module: &Module) -> IntSet<Idx<Table>> {
+    let mut tables = IntSet::default();
+    for instr in module.functions.iter().flat_map(Function::instrs) {
+        match instr {
+            Instr::TableSet(table)
+            | Instr::TableGrow(table)
+            | Instr::TableFill(table)
+            | Instr::TableCopy { dst: table, .. } => {
+                tables.insert(*table);
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+/// Tables initialized with passive element segments by `table.init`.
+fn initialized_tables(module: &Module) -> BTreeSet<(Idx<Table>, Idx<Element>)> {
+    module
+        .functions
+        .iter()
+        .flat_map(Function::instrs)
+        .filter_map(|instr| match instr {
+            Instr::TableInit(table, element) => Some((*table, *element)),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,834 @@
+//! Control-flow graph (CFG) of a function body, i.e., its basic blocks and the edges between them,
+//! plus the analyses that are typically computed on top of it: dominator and post-dominator trees
+//! and (natural) loops.
+//!
+//! Basic blocks are ranges of instructions in the flat function body, and are numbered in the order
+//! of their first instruction, i.e., the entry block is always the first block.
+//! Branch targets are the instructions where execution continues, i.e., the `end` of a `block`,
+//! `if`, or `try` (or its `delegate`), and the `loop` instruction itself for loops.
+//! There is an additional, empty _exit_ block, which is the successor of all blocks that leave the
+//! function, i.e., via `return` (also tail calls), the final `end`, or by trapping or throwing.
+//!
+//! Exceptions are only approximated: Because (almost) any instruction in a `try` block could throw,
+//! there are edges from the `try` instruction to all of its `catch` and `catch_all` handlers.
+//! Calls are not treated as branches, since they return to the next instruction (unless they
+//! trap or throw, which is also not modeled).
+//!
+//! Unreachable code (e.g., after a `br`) forms basic blocks without predecessors, which are also
+//! not dominated by any other block.
+
+use std::ops::Range;
+
+use nohash_hasher::IntMap;
+use nohash_hasher::IntSet;
+
+use crate::nested::NestingError;
+use crate::Idx;
+use crate::Instr;
+use crate::Label;
+
+/// A maximal sequence of instructions that is only entered at the first instruction and only left
+/// after the last instruction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BasicBlock {
+    /// Indices of the instructions in the function body, empty only for the exit block.
+    pub instrs: Range<usize>,
+    pub successors: Vec<Idx<BasicBlock>>,
+    pub predecessors: Vec<Idx<BasicBlock>>,
+}
+
+impl BasicBlock {
+    pub fn instr_idxs(&self) -> impl Iterator<Item = Idx<Instr>> {
+        self.instrs.clone().map(Idx::from)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ControlFlowGraph {
+    /// The last block is the (empty) exit block.
+    blocks: Vec<BasicBlock>,
+    /// For every instruction, the basic block that contains it.
+    block_of_instr: Vec<Idx<BasicBlock>>,
+}
+
+/// Where control flow continues after an instruction that ends a basic block.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Target {
+    Instr(usize),
+    Exit,
+}
+
+/// Blocks that are begun but not yet ended, for resolving labels.
+#[derive(Debug, Clone, Copy)]
+enum OpenBlock {
+    Function,
+    Block(usize),
+    Loop(usize),
+    If(usize),
+    Try(usize),
+}
+
+/// Where the parts of a block (`else`, handlers, `end`) are, by the index of the block instruction.
+#[derive(Debug, Default, Clone)]
+struct BlockParts {
+    else_: Option<usize>,
+    handlers: Vec<usize>,
+    /// Index of the `end` (or `delegate`) instruction.
+    end: usize,
+}
+
+impl ControlFlowGraph {
+    /// Builds the control-flow graph for a function body, which must be ended by an `end`
+    /// instruction, e.g., `ControlFlowGraph::new(function.instrs())`.
+    pub fn new(instrs: &[Instr]) -> Result<Self, NestingError> {
+        use crate::Instr::*;
+
+        let parts = block_parts(instrs)?;
+
+        // Compute the successors of all instructions that end a basic block (for all other
+        // instructions, control just falls through to the next instruction).
+        let mut open_blocks = vec![OpenBlock::Function];
+        let mut terminators: Vec<(usize, Vec<Target>)> = Vec::new();
+        for (instr_idx, instr) in instrs.iter().enumerate() {
+            let label_target = |label: &Label| -> Result<Target, NestingError> {
+                let open_block =
+                    open_blocks
+                        .iter()
+                        .rev()
+                        .nth(label.to_usize())
+                        .ok_or(NestingError {
+                            message: "invalid branch target label",
+                            instruction_idx: instr_idx.into(),
+                        })?;
+                Ok(match *open_block {
+                    OpenBlock::Function => Target::Exit,
+                    OpenBlock::Loop(begin) => Target::Instr(begin),
+                    OpenBlock::Block(begin) | OpenBlock::If(begin) | OpenBlock::Try(begin) => {
+                        Target::Instr(parts[&begin].end)
+                    }
+                })
+            };
+            let next = Target::Instr(instr_idx + 1);
+
+            let targets = match instr {
+                Block(_) => {
+                    open_blocks.push(OpenBlock::Block(instr_idx));
+                    continue;
+                }
+                Loop(_) => {
+                    open_blocks.push(OpenBlock::Loop(instr_idx));
+                    continue;
+                }
+                If(_) => {
+                    open_blocks.push(OpenBlock::If(instr_idx));
+                    let parts = &parts[&instr_idx];
+                    let false_target = parts.else_.map_or(parts.end, |else_| else_ + 1);
+                    vec![next, Target::Instr(false_target)]
+                }
+                Try(_) => {
+                    open_blocks.push(OpenBlock::Try(instr_idx));
+                    let handlers = parts[&instr_idx].handlers.iter();
+                    std::iter::once(next)
+                        .chain(handlers.map(|handler| Target::Instr(handler + 1)))
+                        .collect()
+                }
+                // The end of the previous body jumps to the end of the whole if or try block.
+                Else | Catch(_) | CatchAll => match open_blocks.last() {
+                    Some(OpenBlock::If(begin) | OpenBlock::Try(begin)) => {
+                        vec![Target::Instr(parts[begin].end)]
+                    }
+                    _ => unreachable!("checked by block_parts()"),
+                },
+                End | Delegate(_) => match open_blocks.pop() {
+                    Some(OpenBlock::Function) => vec![Target::Exit],
+                    _ => continue,
+                },
+
+                Br(label) => vec![label_target(label)?],
+                BrIf(label) => vec![label_target(label)?, next],
+                BrTable { table, default } => {
+                    let mut targets = Vec::with_capacity(table.len() + 1);
+                    for label in table.iter().chain(std::iter::once(default)) {
+                        targets.push(label_target(label)?);
+                    }
+                    targets
+                }
+                Return
+                | ReturnCall(_)
+                | ReturnCallIndirect(_, _)
+                | Unreachable
+                | Throw(_)
+                | Rethrow(_) => vec![Target::Exit],
+
+                _ => continue,
+            };
+            terminators.push((instr_idx, targets));
+        }
+
+        // Basic blocks begin at the first instruction, at branch targets, and after terminators.
+        let mut leaders = vec![0];
+        for (instr_idx, targets) in &terminators {
+            leaders.push(instr_idx + 1);
+            leaders.extend(targets.iter().filter_map(|target| match target {
+                Target::Instr(instr_idx) => Some(*instr_idx),
+                Target::Exit => None,
+            }));
+        }
+        leaders.retain(|&leader| leader < instrs.len());
+        leaders.sort_unstable();
+        leaders.dedup();
+
+        let mut blocks: Vec<BasicBlock> = leaders
+            .iter()
+            .zip(leaders.iter().skip(1).chain(std::iter::once(&instrs.len())))
+            .map(|(&begin, &end)| BasicBlock {
+                instrs: begin..end,
+                successors: Vec::new(),
+                predecessors: Vec::new(),
+            })
+            .collect();
+        let exit = Idx::from(blocks.len());
+        blocks.push(BasicBlock {
+            instrs: instrs.len()..instrs.len(),
+            successors: Vec::new(),
+            predecessors: Vec::new(),
+        });
+        let mut block_of_instr = Vec::with_capacity(instrs.len());
+        for (block_idx, block) in blocks.iter().enumerate() {
+            block_of_instr.extend(block.instrs.clone().map(|_| Idx::from(block_idx)));
+        }
+
+        // Add edges from the last instruction of each block.
+        let mut terminators = terminators.into_iter().peekable();
+        for block_idx in 0..exit.to_usize() {
+            let last_instr = blocks[block_idx].instrs.end - 1;
+            let targets = match terminators.next_if(|(instr_idx, _)| *instr_idx == last_instr) {
+                Some((_, targets)) => targets,
+                None => vec![Target::Instr(last_instr + 1)],
+            };
+            let mut successors = Vec::with_capacity(targets.len());
+            for target in targets {
+                let successor = match target {
+                    Target::Instr(instr_idx) => block_of_instr[instr_idx],
+                    Target::Exit => exit,
+                };
+                if !successors.contains(&successor) {
+                    successors.push(successor);
+                }
+            }
+            for &successor in &successors {
+                blocks[successor.to_usize()]
+                    .predecessors
+                    .push(block_idx.into());
+            }
+            blocks[block_idx].successors = successors;
+        }
+
+        Ok(ControlFlowGraph {
+            blocks,
+            block_of_instr,
+        })
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = (Idx<BasicBlock>, &BasicBlock)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(idx, block)| (idx.into(), block))
+    }
+
+    pub fn block(&self, idx: Idx<BasicBlock>) -> &BasicBlock {
+        &self.blocks[idx.to_usize()]
+    }
+
+    /// Number of basic blocks, including the exit block.
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn entry(&self) -> Idx<BasicBlock> {
+        Idx::from(0u32)
+    }
+
+    pub fn exit(&self) -> Idx<BasicBlock> {
+        Idx::from(self.blocks.len() - 1)
+    }
+
+    /// The basic block that contains the instruction.
+    pub fn block_of(&self, instr: Idx<Instr>) -> Idx<BasicBlock> {
+        self.block_of_instr[instr.to_usize()]
+    }
+
+    /// A block `a` dominates block `b` if every path from the entry to `b` goes through `a`.
+    pub fn dominators(&self) -> DominatorTree {
+        DominatorTree::new(
+            self.entry(),
+            self.blocks.len(),
+            |block| &self.block(block).successors,
+            |block| &self.block(block).predecessors,
+        )
+    }
+
+    /// A block `a` post-dominates block `b` if every path from `b` to the exit goes through `a`.
+    pub fn post_dominators(&self) -> DominatorTree {
+        DominatorTree::new(
+            self.exit(),
+            self.blocks.len(),
+            |block| &self.block(block).predecessors,
+            |block| &self.block(block).successors,
+        )
+    }
+
+    /// Natural loops, i.e., for every loop header (the target of a back edge, which dominates its
+    /// source), all blocks from which the back edge can be reached without going through the
+    /// header. In WebAssembly, loop headers are always `loop` instructions.
+    pub fn loops(&self, dominators: &DominatorTree) -> Loops {
+        // Find back edges and collect the blocks of the loop by walking backwards from their
+        // sources until the header. Back edges to the same header belong to the same loop.
+        // Unreachable blocks are never part of a loop, even if they fall through into one, since
+        // they are not dominated by the header.
+        let mut loop_of_header: IntMap<Idx<BasicBlock>, usize> = IntMap::default();
+        let mut loop_blocks: Vec<(Idx<BasicBlock>, IntSet<Idx<BasicBlock>>)> = Vec::new();
+        for (source, block) in self.blocks() {
+            if !dominators.is_reachable(source) {
+                continue;
+            }
+            for &header in &block.successors {
+                if !dominators.dominates(header, source) {
+                    continue;
+                }
+                let loop_idx = *loop_of_header.entry(header).or_insert_with(|| {
+                    loop_blocks.push((header, IntSet::from_iter([header])));
+                    loop_blocks.len() - 1
+                });
+                let blocks = &mut loop_blocks[loop_idx].1;
+                let mut worklist = Vec::new();
+                if blocks.insert(source) {
+                    worklist.push(source);
+                }
+                while let Some(block) = worklist.pop() {
+                    for &predecessor in &self.block(block).predecessors {
+                        if dominators.is_reachable(predecessor) && blocks.insert(predecessor) {
+                            worklist.push(predecessor);
+                        }
+                    }
+                }
+            }
+        }
+        let mut loops: Vec<Loop> = loop_blocks
+            .into_iter()
+            .map(|(header, blocks)| {
+                let mut blocks: Vec<_> = blocks.into_iter().collect();
+                blocks.sort_unstable();
+                Loop {
+                    header,
+                    blocks,
+                    parent: None,
+                    depth: 1,
+                }
+            })
+            .collect();
+
+        // Outer loops come first, since their headers come first in the function body.
+        loops.sort_by_key(|loop_| loop_.header);
+
+        // Natural loops are either nested or disjoint, so the innermost surrounding loop of a loop
+        // (or block) is the last loop (in this order) that contains it.
+        let mut innermost = vec![None; self.blocks.len()];
+        for loop_idx in 0..loops.len() {
+            let parent = innermost[loops[loop_idx].header.to_usize()];
+            if let Some(parent) = parent {
+                let parent: Idx<Loop> = parent;
+                loops[loop_idx].parent = Some(parent);
+                loops[loop_idx].depth = loops[parent.to_usize()].depth + 1;
+            }
+            for block in &loops[loop_idx].blocks {
+                innermost[block.to_usize()] = Some(Idx::from(loop_idx));
+            }
+        }
+
+        Loops { loops, innermost }
+    }
+}
+
+/// For every instruction that begins a block, where its `else`, handlers, and `end` are.
+fn block_parts(instrs: &[Instr]) -> Result<IntMap<usize, BlockParts>, NestingError> {
+    use crate::Instr::*;
+
+    let mut parts = IntMap::default();
+    let mut open_blocks = vec![None];
+    for (instr_idx, instr) in instrs.iter().enumerate() {
+        let error = |message| NestingError {
+            message,
+            instruction_idx: instr_idx.into(),
+        };
+
+        let open_block = *open_blocks
+            .last()
+            .ok_or_else(|| error("instruction after the final end"))?;
+        match (
+            instr,
+            open_block.map(|begin: usize| (begin, &instrs[begin])),
+        ) {
+            (Block(_) | Loop(_) | If(_) | Try(_), _) => {
+                parts.insert(instr_idx, BlockParts::default());
+                open_blocks.push(Some(instr_idx));
+            }
+            (Else, Some((begin, If(_)))) if parts[&begin].else_.is_none() => {
+                parts.get_mut(&begin).unwrap().else_ = Some(instr_idx);
+            }
+            (Else, _) => return Err(error("else without matching if")),
+            (Catch(_) | CatchAll, Some((begin, Try(_)))) => {
+                parts.get_mut(&begin).unwrap().handlers.push(instr_idx);
+            }
+            (Catch(_) | CatchAll, _) => return Err(error("catch without matching try")),
+            (Delegate(_), Some((begin, Try(_)))) if parts[&begin].handlers.is_empty() => {
+                parts.get_mut(&begin).unwrap().end = instr_idx;
+                open_blocks.pop();
+            }
+            (Delegate(_), _) => return Err(error("delegate without matching try")),
+            (End, Some((begin, _))) => {
+                parts.get_mut(&begin).unwrap().end = instr_idx;
+                open_blocks.pop();
+            }
+            (End, None) => {
+                open_blocks.pop();
+            }
+            _ => {}
+        }
+    }
+    if !open_blocks.is_empty() {
+        return Err(NestingError {
+            message: "missing end",
+            instruction_idx: instrs.len().into(),
+        });
+    }
+    Ok(parts)
+}
+
+/// Dominator (or post-dominator) tree of a control-flow graph.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DominatorTree {
+    root: Idx<BasicBlock>,
+    /// `None` for the root and for blocks that are not reachable from the root.
+    immediate_dominators: Vec<Option<Idx<BasicBlock>>>,
+}
+
+impl DominatorTree {
+    /// Computes the dominator tree with the iterative algorithm from "A Simple, Fast Dominance
+    /// Algorithm" by Cooper, Harvey, and Kennedy, see
+    /// https://www.cs.rice.edu/~keith/EMBED/dom.pdf
+    fn new<'a>(
+        root: Idx<BasicBlock>,
+        block_count: usize,
+        successors: impl Fn(Idx<BasicBlock>) -> &'a [Idx<BasicBlock>],
+        predecessors: impl Fn(Idx<BasicBlock>) -> &'a [Idx<BasicBlock>],
+    ) -> Self {
+        // Postorder by iterative depth-first search, since function bodies can be large.
+        let mut postorder = Vec::with_capacity(block_count);
+        let mut visited = IntSet::default();
+        visited.insert(root);
+        let mut stack = vec![(root, 0)];
+        while let Some((block, successor_idx)) = stack.last_mut() {
+            match successors(*block).get(*successor_idx) {
+                Some(&successor) => {
+                    *successor_idx += 1;
+                    if visited.insert(successor) {
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    postorder.push(*block);
+                    stack.pop();
+                }
+            }
+        }
+        let mut postorder_number = vec![None; block_count];
+        for (number, block) in postorder.iter().enumerate() {
+            postorder_number[block.to_usize()] = Some(number);
+        }
+
+        let mut idoms: Vec<Option<Idx<BasicBlock>>> = vec![None; block_count];
+        idoms[root.to_usize()] = Some(root);
+        let intersect = |idoms: &[Option<Idx<BasicBlock>>], mut block1, mut block2| {
+            let number = |block: Idx<BasicBlock>| postorder_number[block.to_usize()];
+            while block1 != block2 {
+                while number(block1) < number(block2) {
+                    block1 = idoms[block1.to_usize()].expect("processed before");
+                }
+                while number(block2) < number(block1) {
+                    block2 = idoms[block2.to_usize()].expect("processed before");
+                }
+            }
+            block1
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            // Reverse postorder, without the root.
+            for &block in postorder.iter().rev().skip(1) {
+                let mut processed_predecessors = predecessors(block)
+                    .iter()
+                    .copied()
+                    .filter(|predecessor| idoms[predecessor.to_usize()].is_some());
+                let first = processed_predecessors
+                    .next()
+                    .expect("in reverse postorder, some predecessor is processed before");
+                let new_idom = processed_predecessors.fold(first, |idom, predecessor| {
+                    intersect(&idoms, predecessor, idom)
+                });
+                if idoms[block.to_usize()] != Some(new_idom) {
+                    idoms[block.to_usize()] = Some(new_idom);
+                    changed = true;
+                }
+            }
+        }
+        idoms[root.to_usize()] = None;
+
+        DominatorTree {
+            root,
+            immediate_dominators: idoms,
+        }
+    }
+
+    pub fn root(&self) -> Idx<BasicBlock> {
+        self.root
+    }
+
+    /// The parent of `block` in the tree, or `None` for the root and for unreachable blocks.
+    pub fn immediate_dominator(&self, block: Idx<BasicBlock>) -> Option<Idx<BasicBlock>> {
+        self.immediate_dominators[block.to_usize()]
+    }
+
+    /// Whether `block` is reachable from the root (for post-dominators: whether the exit is
+    /// reachable from `block`, which is not the case for infinite loops).
+    pub fn is_reachable(&self, block: Idx<BasicBlock>) -> bool {
+        block == self.root || self.immediate_dominator(block).is_some()
+    }
+
+    /// Whether `dominator` dominates `block`. Every block dominates itself, and blocks that are
+    /// not reachable are dominated by no other block.
+    pub fn dominates(&self, dominator: Idx<BasicBlock>, mut block: Idx<BasicBlock>) -> bool {
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match self.immediate_dominator(block) {
+                Some(idom) => block = idom,
+                None => return false,
+            }
+        }
+    }
+
+    /// The blocks immediately dominated by `block`, i.e., its children in the tree.
+    pub fn children(&self, block: Idx<BasicBlock>) -> impl Iterator<Item = Idx<BasicBlock>> + '_ {
+        self.immediate_dominators
+            .iter()
+            .enumerate()
+            .filter(move |(_, idom)| **idom == Some(block))
+            .map(|(child, _)| child.into())
+    }
+}
+
+/// A natural loop in the control-flow graph, see `ControlFlowGraph::loops()`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Loop {
+    pub header: Idx<BasicBlock>,
+    /// All blocks of the loop, including the header and the blocks of nested loops, sorted.
+    pub blocks: Vec<Idx<BasicBlock>>,
+    /// The innermost loop that contains this loop, if any.
+    pub parent: Option<Idx<Loop>>,
+    /// Nesting depth, starting with 1 for outermost loops.
+    pub depth: usize,
+}
+
+/// All natural loops of a control-flow graph and their nesting.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Loops {
+    /// Outer loops come before inner loops.
+    loops: Vec<Loop>,
+    /// For every basic block, the innermost loop that contains it.
+    innermost: Vec<Option<Idx<Loop>>>,
+}
+
+impl Loops {
+    pub fn iter(&self) -> impl Iterator<Item = (Idx<Loop>, &Loop)> {
+        self.loops
+            .iter()
+            .enumerate()
+            .map(|(idx, loop_)| (idx.into(), loop_))
+    }
+
+    pub fn get(&self, idx: Idx<Loop>) -> &Loop {
+        &self.loops[idx.to_usize()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.loops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loops.is_empty()
+    }
+
+    pub fn innermost_loop(&self, block: Idx<BasicBlock>) -> Option<Idx<Loop>> {
+        self.innermost[block.to_usize()]
+    }
+
+    /// Number of loops that contain the block, 0 if it is not in any loop.
+    pub fn loop_depth(&self, block: Idx<BasicBlock>) -> usize {
+        self.innermost_loop(block)
+            .map_or(0, |loop_| self.get(loop_).depth)
+    }
+
+    pub fn is_loop_header(&self, block: Idx<BasicBlock>) -> bool {
+        self.innermost_loop(block)
+            .is_some_and(|loop_| self.get(loop_).header == block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FunctionType;
+    use crate::Instr::*;
+    use crate::Label;
+    use crate::Val;
+
+    use super::*;
+
+    fn successors(cfg: &ControlFlowGraph) -> Vec<Vec<usize>> {
+        cfg.blocks()
+            .map(|(_, block)| {
+                block
+                    .successors
+                    .iter()
+                    .map(|successor| successor.to_usize())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn if_else_diamond() {
+        let instrs = vec![
+            Const(Val::I32(0)),
+            If(FunctionType::empty()),
+            Nop,
+            Else,
+            Nop,
+            End,
+            Nop,
+            End,
+        ];
+        let cfg = ControlFlowGraph::new(&instrs).unwrap();
+        let instr_ranges: Vec<_> = cfg
+            .blocks()
+            .map(|(_, block)| block.instrs.clone())
+            .collect();
+        assert_eq!(instr_ranges, vec![0..2, 2..4, 4..5, 5..8, 8..8]);
+        assert_eq!(
+            successors(&cfg),
+            vec![vec![1, 2], vec![3], vec![3], vec![4], vec![]]
+        );
+        assert_eq!(cfg.block_of(Idx::from(3u32)), Idx::from(1u32));
+
+        let dominators = cfg.dominators();
+        let idom = |block: u32| dominators.immediate_dominator(Idx::from(block));
+        assert_eq!(idom(0), None);
+        assert_eq!(idom(1), Some(Idx::from(0u32)));
+        assert_eq!(idom(2), Some(Idx::from(0u32)));
+        assert_eq!(idom(3), Some(Idx::from(0u32)));
+        assert!(dominators.dominates(Idx::from(0u32), cfg.exit()));
+        assert!(!dominators.dominates(Idx::from(1u32), Idx::from(3u32)));
+
+        let post_dominators = cfg.post_dominators();
+        assert_eq!(post_dominators.root(), cfg.exit());
+        assert_eq!(
+            post_dominators.immediate_dominator(Idx::from(0u32)),
+            Some(Idx::from(3u32))
+        );
+        assert!(post_dominators.dominates(Idx::from(3u32), Idx::from(1u32)));
+        assert!(cfg.loops(&dominators).is_empty());
+    }
+
+    #[test]
+    pub fn branches_and_dead_code() {
+        let instrs = vec![
+            Block(FunctionType::empty()),
+            Block(FunctionType::empty()),
+            Const(Val::I32(0)),
+            BrTable {
+                table: vec![Label::from(0u32), Label::from(2u32)].into_boxed_slice(),
+                default: Label::from(1u32),
+            },
+            // Dead code.
+            Nop,
+            End,
+            Return,
+            End,
+            End,
+        ];
+        let cfg = ControlFlowGraph::new(&instrs).unwrap();
+        let instr_ranges: Vec<_> = cfg
+            .blocks()
+            .map(|(_, block)| block.instrs.clone())
+            .collect();
+        assert_eq!(instr_ranges, vec![0..4, 4..5, 5..7, 7..9, 9..9]);
+        assert_eq!(
+            successors(&cfg),
+            vec![vec![2, 4, 3], vec![2], vec![4], vec![4], vec![]]
+        );
+
+        let dominators = cfg.dominators();
+        assert!(!dominators.is_reachable(Idx::from(1u32)));
+        assert!(cfg.block(Idx::from(1u32)).predecessors.is_empty());
+        assert_eq!(
+            dominators.immediate_dominator(Idx::from(2u32)),
+            Some(Idx::from(0u32))
+        );
+        let children: Vec<_> = dominators.children(Idx::from(0u32)).collect();
+        assert_eq!(
+            children,
+            vec![Idx::from(2u32), Idx::from(3u32), Idx::from(4u32)]
+        );
+    }
+
+    #[test]
+    pub fn nested_loops() {
+        let instrs = vec![
+            Loop(FunctionType::empty()),
+            Nop,
+            Loop(FunctionType::empty()),
+            Const(Val::I32(0)),
+            BrIf(Label::from(0u32)),
+            Const(Val::I32(0)),
+            BrIf(Label::from(1u32)),
+            End,
+            End,
+            Loop(FunctionType::empty()),
+            Br(Label::from(0u32)),
+            End,
+            End,
+        ];
+        let cfg = ControlFlowGraph::new(&instrs).unwrap();
+        let instr_ranges: Vec<_> = cfg
+            .blocks()
+            .map(|(_, block)| block.instrs.clone())
+            .collect();
+        assert_eq!(
+            instr_ranges,
+            vec![0..2, 2..5, 5..7, 7..9, 9..11, 11..13, 13..13]
+        );
+
+        let loops = cfg.loops(&cfg.dominators());
+        let loops: Vec<_> = loops
+            .iter()
+            .map(|(_, loop_)| {
+                (
+                    loop_.header.to_usize(),
+                    loop_.blocks.iter().map(|block| block.to_usize()).collect(),
+                    loop_.parent.map(|parent| parent.to_usize()),
+                    loop_.depth,
+                )
+            })
+            .collect::<Vec<(_, Vec<_>, _, _)>>();
+        assert_eq!(
+            loops,
+            vec![
+                (0, vec![0, 1, 2], None, 1),
+                (1, vec![1], Some(0), 2),
+                (4, vec![4], None, 1),
+            ]
+        );
+
+        let loops = cfg.loops(&cfg.dominators());
+        assert_eq!(loops.loop_depth(Idx::from(1u32)), 2);
+        assert_eq!(loops.loop_depth(Idx::from(2u32)), 1);
+        assert_eq!(loops.loop_depth(Idx::from(3u32)), 0);
+        assert!(loops.is_loop_header(Idx::from(4u32)));
+        assert!(!loops.is_loop_header(Idx::from(2u32)));
+
+        // Infinite loops never reach the exit.
+        assert!(!cfg.post_dominators().is_reachable(Idx::from(4u32)));
+    }
+
+    #[test]
+    pub fn dead_code_is_not_part_of_loops() {
+        let instrs = vec![
+            Loop(FunctionType::empty()),
+            Block(FunctionType::empty()),
+            Br(Label::from(0u32)),
+            // Dead code, which falls through into the loop.
+            Nop,
+            End,
+            Br(Label::from(0u32)),
+            End,
+            // Dead code with a self loop.
+            Loop(FunctionType::empty()),
+            Br(Label::from(0u32)),
+            End,
+            End,
+        ];
+        let cfg = ControlFlowGraph::new(&instrs).unwrap();
+        let instr_ranges: Vec<_> = cfg
+            .blocks()
+            .map(|(_, block)| block.instrs.clone())
+            .collect();
+        assert_eq!(
+            instr_ranges,
+            vec![0..3, 3..4, 4..6, 6..7, 7..9, 9..11, 11..11]
+        );
+        assert!(cfg
+            .block(Idx::from(1u32))
+            .successors
+            .contains(&Idx::from(2u32)));
+        assert!(cfg
+            .block(Idx::from(4u32))
+            .successors
+            .contains(&Idx::from(4u32)));
+
+        let loops = cfg.loops(&cfg.dominators());
+        assert_eq!(loops.len(), 1);
+        let (loop_idx, loop_) = loops.iter().next().unwrap();
+        assert_eq!(loop_.blocks, vec![Idx::from(0u32), Idx::from(2u32)]);
+        assert_eq!(loops.innermost_loop(Idx::from(2u32)), Some(loop_idx));
+        assert_eq!(loops.innermost_loop(Idx::from(1u32)), None);
+        assert_eq!(loops.loop_depth(Idx::from(1u32)), 0);
+        assert!(!loops.is_loop_header(Idx::from(4u32)));
+    }
+
+    #[test]
+    pub fn try_catch_edges() {
+        let instrs = vec![
+            Try(FunctionType::empty()),
+            Call(Idx::from(0u32)),
+            Catch(Idx::from(0u32)),
+            Nop,
+            CatchAll,
+            Nop,
+            End,
+            End,
+        ];
+        let cfg = ControlFlowGraph::new(&instrs).unwrap();
+        let instr_ranges: Vec<_> = cfg
+            .blocks()
+            .map(|(_, block)| block.instrs.clone())
+            .collect();
+        assert_eq!(instr_ranges, vec![0..1, 1..3, 3..5, 5..6, 6..8, 8..8]);
+        assert_eq!(
+            successors(&cfg),
+            vec![vec![1, 2, 3], vec![4], vec![4], vec![4], vec![5], vec![]]
+        );
+    }
+
+    #[test]
+    pub fn invalid_nesting() {
+        let error =
+            |instrs: Vec<Instr>| ControlFlowGraph::new(&instrs).unwrap_err().instruction_idx;
+        assert_eq!(error(vec![]), Idx::from(0u32));
+        assert_eq!(error(vec![Nop]), Idx::from(1u32));
+        assert_eq!(error(vec![Else, End]), Idx::from(0u32));
+        assert_eq!(error(vec![Br(Label::from(1u32)), End]), Idx::from(0u32));
+        assert_eq!(error(vec![End, Nop]), Idx::from(1u32));
+    }
+}
@@ -0,0 +1,689 @@
+//! Source locations (file, line, column) of functions and instructions from the DWARF debug
+//! info in `.debug_*` custom sections, e.g., as produced by Emscripten or rustc with `-g`.
+//! Uses `gimli` for the actual parsing of the DWARF sections.
+//!
+//! DWARF for WebAssembly refers to code by byte offsets relative to the beginning of the code
+//! section contents, see https://yurydelendik.github.io/webassembly-dwarf/#pc
+//! Mapping those to functions and instructions thus requires the `Offsets` from parsing the
+//! original binary, and the debug info is only valid for that binary.
+//! After modifying the code, `update_addresses` rewrites the DWARF for the new binary.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use gimli::write;
+use gimli::write::ConvertError;
+use gimli::write::LineString;
+use gimli::AttributeValue;
+use gimli::EndianSlice;
+use gimli::LittleEndian;
+use gimli::Reader as _;
+use nohash_hasher::IntMap;
+use rustc_hash::FxHashMap;
+
+use crate::*;
+
+type Reader<'a> = EndianSlice<'a, LittleEndian>;
+
+/// For every function, the instructions at which a new source location starts (`None` if the
+/// following instructions have no source location), sorted by instruction index.
+type Lines = IntMap<Idx<Function>, Vec<(Idx<Instr>, Option<SourceLocation>)>>;
+
+/// Source locations parsed from DWARF, see `Module::debug_info`.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// Deduplicated paths of all source files referenced by `SourceLocation::file`.
+    files: Vec<String>,
+    lines: Lines,
+    /// From `DW_TAG_subprogram` entries, i.e., the function definitions in the source code.
+    functions: IntMap<Idx<Function>, Subprogram>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SourceLocation {
+    file: u32,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Subprogram {
+    name: Option<String>,
+    location: Option<SourceLocation>,
+}
+
+impl DebugInfo {
+    /// Returns the source file, line, and column of the instruction (if any).
+    /// Lines and columns are 1-based, a column of 0 means the column is unknown (as in DWARF).
+    pub fn source_location(
+        &self,
+        function: Idx<Function>,
+        instr: Idx<Instr>,
+    ) -> Option<(&str, u32, u32)> {
+        let lines = self.lines.get(&function)?;
+        let lines_before = lines.partition_point(|(line_instr, _)| *line_instr <= instr);
+        let (_, location) = lines[..lines_before].last()?;
+        self.resolve(location.as_ref()?)
+    }
+
+    /// Returns the name of the function in the source code (if any), i.e., not mangled.
+    pub fn function_name(&self, function: Idx<Function>) -> Option<&str> {
+        self.functions.get(&function)?.name.as_deref()
+    }
+
+    /// Returns the source file, line, and column where the function is declared (if any).
+    pub fn function_location(&self, function: Idx<Function>) -> Option<(&str, u32, u32)> {
+        self.resolve(self.functions.get(&function)?.location.as_ref()?)
+    }
+
+    /// Whether there is no source location for any function or instruction.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.functions.is_empty()
+    }
+
+    fn resolve(&self, location: &SourceLocation) -> Option<(&str, u32, u32)> {
+        let file = self.files.get(location.file as usize)?;
+        Some((file, location.line, location.column))
+    }
+
+    pub(crate) fn parse(module: &Module, offsets: &Offsets) -> Result<Self, ParseError> {
+        let sections: FxHashMap<&str, &[u8]> = module
+            .custom_sections
+            .iter()
+            .filter(|section| section.name.starts_with(".debug_"))
+            .map(|section| (section.name.as_str(), &section.content[..]))
+            .collect();
+        let code_section_offset = match offsets.section_offsets(SectionId::Code).first() {
+            Some(offset) if !sections.is_empty() => *offset,
+            _ => return Ok(DebugInfo::default()),
+        };
+        let code = CodeOffsets {
+            code_section_offset,
+            offsets,
+        };
+
+        let dwarf = load_dwarf(|name| sections.get(name).copied().unwrap_or_default())?;
+        let mut files = Vec::new();
+        let mut file_ids = FxHashMap::default();
+        let mut lines = IntMap::default();
+        let mut functions = IntMap::default();
+
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let mut unit_files = UnitFiles {
+                dwarf: &dwarf,
+                unit: &unit,
+                file_ids: &mut file_ids,
+                files: &mut files,
+                indices: FxHashMap::default(),
+            };
+
+            if let Some(program) = unit.line_program.clone() {
+                let mut rows = program.rows();
+                let mut sequence = Vec::new();
+                while let Some((header, row)) = rows.next_row()? {
+                    let location = if row.end_sequence() {
+                        None
+                    } else {
+                        Some(SourceLocation {
+                            file: unit_files.file(header, row.file_index())?,
+                            line: row.line().map_or(0, |line| to_u32(line.get())),
+                            column: match row.column() {
+                                gimli::ColumnType::LeftEdge => 0,
+                                gimli::ColumnType::Column(column) => to_u32(column.get()),
+                            },
+                        })
+                    };
+                    sequence.push((row.address(), location));
+
+                    if row.end_sequence() {
+                        add_sequence(&mut lines, &sequence, &code);
+                        sequence.clear();
+                    }
+                }
+            }
+
+            let mut entries = unit.entries();
+            while let Some((_depth, entry)) = entries.next_dfs()? {
+                if entry.tag() != gimli::DW_TAG_subprogram {
+                    continue;
+                }
+                let low_pc = match entry.attr_value(gimli::DW_AT_low_pc)? {
+                    Some(low_pc) => dwarf.attr_address(&unit, low_pc)?,
+                    None => None,
+                };
+                let Some(function) = low_pc.and_then(|low_pc| code.function(low_pc)) else {
+                    continue;
+                };
+
+                let name = match entry.attr_value(gimli::DW_AT_name)? {
+                    Some(name) => Some(dwarf.attr_string(&unit, name)?.to_string_lossy()),
+                    None => None,
+                };
+                let file = match (
+                    entry.attr_value(gimli::DW_AT_decl_file)?,
+                    &unit.line_program,
+                ) {
+                    (Some(AttributeValue::FileIndex(file)), Some(program)) => {
+                        Some(unit_files.file(program.header(), file)?)
+                    }
+                    _ => None,
+                };
+                let line = entry
+                    .attr_value(gimli::DW_AT_decl_line)?
+                    .and_then(|line| line.udata_value());
+                let location = file.map(|file| SourceLocation {
+                    file,
+                    line: line.map_or(0, to_u32),
+                    column: 0,
+                });
+
+                functions.insert(
+                    function,
+                    Subprogram {
+                        name: name.map(Cow::into_owned),
+                        location,
+                    },
+                );
+            }
+        }
+
+        Ok(DebugInfo {
+            files,
+            lines,
+            functions,
+        })
+    }
+}
+
+fn load_dwarf<'a>(
+    section: impl Fn(&str) -> &'a [u8],
+) -> Result<gimli::Dwarf<Reader<'a>>, gimli::Error> {
+    gimli::Dwarf::load(|id| -> Result<Reader, gimli::Error> {
+        Ok(EndianSlice::new(section(id.name()), LittleEndian))
+    })
+}
+
+/// Adds the rows of a single line number sequence, i.e., a contiguous range of code.
+fn add_sequence(lines: &mut Lines, sequence: &[(u64, Option<SourceLocation>)], code: &CodeOffsets) {
+    // Code that was removed by the linker still has its line numbers, but at placeholder
+    // addresses (e.g., starting at 0), which must not be attributed to other functions.
+    let starts_in_function = sequence
+        .first()
+        .is_some_and(|(address, _)| code.instr(*address).is_some());
+    if !starts_in_function {
+        return;
+    }
+
+    // The end of the sequence is one byte past the last instruction, so it is skipped.
+    for (address, location) in sequence {
+        if let Some((function, instr)) = code.instr(*address) {
+            let lines = lines.entry(function).or_default();
+            match lines.last_mut() {
+                // For several rows at the same instruction, the last one applies.
+                Some((last_instr, last_location)) if *last_instr == instr => {
+                    *last_location = *location
+                }
+                _ => lines.push((instr, *location)),
+            }
+        }
+    }
+}
+
+/// Rewrites all code addresses in the DWARF sections with `map_address`, see
+/// `Module::update_debug_info`. Addresses without a mapping (e.g., placeholders of code that was
+/// removed by the linker) are kept as is.
+pub(crate) fn update_addresses(
+    module: &mut Module,
+    map_address: impl Fn(u64) -> Option<u64>,
+) -> Result<(), EncodeError> {
+    let is_debug_section = |section: &RawCustomSection| section.name.starts_with(".debug_");
+    let Some(first_debug_section) = module.custom_sections.iter().position(is_debug_section) else {
+        return Ok(());
+    };
+
+    let mut dwarf = convert(module, &map_address)?;
+    let mut sections = write::Sections::new(write::EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections)?;
+
+    // Replace the original debug sections, at the same position in the binary. Sections that
+    // gimli does not write (e.g., `.debug_aranges`) are dropped, since they would be invalid.
+    let mut previous_section = module.custom_sections[first_debug_section]
+        .previous_section
+        .clone();
+    let mut new_sections = Vec::new();
+    sections.for_each(|id, section| -> Result<(), EncodeError> {
+        if !section.slice().is_empty() {
+            new_sections.push(RawCustomSection {
+                name: id.name().to_string(),
+                content: section.slice().to_vec(),
+                previous_section: previous_section.clone(),
+            });
+            previous_section = Some(SectionId::Custom(id.name().to_string()));
+        }
+        Ok(())
+    })?;
+    module
+        .custom_sections
+        .retain(|section| !is_debug_section(section));
+    for section in &mut module.custom_sections {
+        let after_debug_section = matches!(
+            &section.previous_section,
+            Some(SectionId::Custom(name)) if name.starts_with(".debug_")
+        );
+        if after_debug_section {
+            section.previous_section = previous_section.clone();
+        }
+    }
+    module
+        .custom_sections
+        .splice(first_debug_section..first_debug_section, new_sections);
+    Ok(())
+}
+
+fn convert(
+    module: &Module,
+    map_address: &impl Fn(u64) -> Option<u64>,
+) -> Result<write::Dwarf, ConvertError> {
+    let mut sections: FxHashMap<&str, &[u8]> = module
+        .custom_sections
+        .iter()
+        .filter(|section| section.name.starts_with(".debug_"))
+        .map(|section| (section.name.as_str(), &section.content[..]))
+        .collect();
+    let original_dwarf = load_dwarf(|name| sections.get(name).copied().unwrap_or_default())?;
+    let debug_line = sections.get(".debug_line").copied().unwrap_or_default();
+    let debug_line = normalize_line_programs(&original_dwarf, debug_line)?;
+    sections.insert(".debug_line", &debug_line);
+    let read_dwarf = load_dwarf(|name| sections.get(name).copied().unwrap_or_default())?;
+
+    // Remember the original addresses, to fix the sizes of address ranges below. Several
+    // addresses can map to the start of a function (those of the local declarations), of which
+    // the function start itself is the smallest.
+    let original_addresses = RefCell::new(FxHashMap::default());
+    let convert_address = |address: u64| match map_address(address) {
+        Some(new_address) => {
+            original_addresses
+                .borrow_mut()
+                .entry(new_address)
+                .and_modify(|original: &mut u64| *original = (*original).min(address))
+                .or_insert(address);
+            Some(write::Address::Constant(new_address))
+        }
+        None => Some(write::Address::Constant(address)),
+    };
+    let mut dwarf = write::Dwarf::from(&read_dwarf, &convert_address)?;
+
+    // gimli only converts the start address of line sequences and ranges, but keeps the other
+    // rows at their original distance to the start and the original size in `DW_AT_high_pc`.
+    let mut read_units = read_dwarf.units();
+    let mut unit_index = 0;
+    while let Some(header) = read_units.next()? {
+        let read_unit = read_dwarf.unit(header)?;
+        let unit_id = dwarf.units.id(unit_index);
+        unit_index += 1;
+
+        if let Some(read_program) = read_unit.line_program.clone() {
+            let program = convert_line_program(
+                &read_dwarf,
+                &read_unit,
+                read_program,
+                &mut dwarf,
+                map_address,
+            )?;
+            dwarf.units.get_mut(unit_id).line_program = program;
+        }
+        update_high_pcs(
+            dwarf.units.get_mut(unit_id),
+            &original_addresses.borrow(),
+            map_address,
+        );
+    }
+
+    Ok(dwarf)
+}
+
+fn convert_line_program(
+    read_dwarf: &gimli::Dwarf<Reader>,
+    read_unit: &gimli::Unit<Reader>,
+    read_program: gimli::IncompleteLineProgram<Reader>,
+    dwarf: &mut write::Dwarf,
+    map_address: &impl Fn(u64) -> Option<u64>,
+) -> Result<write::LineProgram, ConvertError> {
+    // gimli's conversion already resolves directories and files (also those referenced by
+    // `DW_AT_decl_file`), so only the rows need to be added again with the new addresses.
+    let (converted, files) = write::LineProgram::from(
+        read_program.clone(),
+        read_dwarf,
+        &mut dwarf.line_strings,
+        &mut dwarf.strings,
+        &|address| Some(write::Address::Constant(address)),
+    )?;
+
+    let header = read_program.header();
+    let comp_dir = converted.get_directory(converted.default_directory());
+    let (comp_file, comp_file_info) = match header.file(0) {
+        Some(file) => {
+            let name = read_dwarf.attr_string(read_unit, file.path_name())?;
+            // The file names must all have the same form, so use that of the other files.
+            let form = match files.get(1) {
+                Some(&file) => converted.get_file(file).0,
+                None => comp_dir,
+            };
+            let info = write::FileInfo {
+                timestamp: file.timestamp(),
+                size: file.size(),
+                md5: *file.md5(),
+            };
+            (line_string(name.to_vec(), form, dwarf), Some(info))
+        }
+        None => (
+            LineString::new(&[][..], converted.encoding(), &mut dwarf.line_strings),
+            None,
+        ),
+    };
+    let mut program = write::LineProgram::new(
+        converted.encoding(),
+        header.line_encoding(),
+        comp_dir.clone(),
+        comp_file,
+        comp_file_info,
+    );
+    program.file_has_timestamp = converted.file_has_timestamp;
+    program.file_has_size = converted.file_has_size;
+    program.file_has_md5 = converted.file_has_md5;
+    // Adding the files in the same order results in the same `FileId`s.
+    let mut new_files = vec![files[0]];
+    for &file in &files[1..] {
+        let (name, directory) = converted.get_file(file);
+        let directory = program.add_directory(converted.get_directory(directory).clone());
+        let info = *converted.get_file_info(file);
+        new_files.push(program.add_file(name.clone(), directory, Some(info)));
+    }
+
+    let mut rows = read_program.rows();
+    let mut sequence_start = 0;
+    let mut previous_offset = 0;
+    while let Some((_, row)) = rows.next_row()? {
+        let address = map_address(row.address()).unwrap_or(row.address());
+        if !program.in_sequence() {
+            program.begin_sequence(Some(write::Address::Constant(address)));
+            sequence_start = address;
+            previous_offset = 0;
+        }
+        let address_offset = address
+            .checked_sub(sequence_start)
+            .filter(|offset| *offset >= previous_offset)
+            .ok_or(ConvertError::InvalidAddress)?;
+        previous_offset = address_offset;
+
+        if row.end_sequence() {
+            program.end_sequence(address_offset);
+            continue;
+        }
+        let new_row = program.row();
+        new_row.address_offset = address_offset;
+        new_row.op_index = row.op_index();
+        new_row.file = *new_files
+            .get(row.file_index() as usize)
+            .ok_or(ConvertError::InvalidFileIndex)?;
+        new_row.line = row.line().map_or(0, |line| line.get());
+        new_row.column = match row.column() {
+            gimli::ColumnType::LeftEdge => 0,
+            gimli::ColumnType::Column(column) => column.get(),
+        };
+        new_row.discriminator = row.discriminator();
+        new_row.is_statement = row.is_stmt();
+        new_row.basic_block = row.basic_block();
+        new_row.prologue_end = row.prologue_end();
+        new_row.epilogue_begin = row.epilogue_begin();
+        new_row.isa = row.isa();
+        program.generate_row();
+    }
+
+    Ok(program)
+}
+
+/// gimli cannot convert line programs that set the address in the middle of a sequence, which
+/// LLVM does for WebAssembly. Replaces those `DW_LNE_set_address` with a `DW_LNS_advance_pc` of
+/// the same length, such that the offsets of all line programs (e.g., in `DW_AT_stmt_list`) stay
+/// the same. Returns the modified `.debug_line` section.
+fn normalize_line_programs(
+    dwarf: &gimli::Dwarf<Reader>,
+    debug_line: &[u8],
+) -> Result<Vec<u8>, ConvertError> {
+    let mut normalized = debug_line.to_vec();
+    let section_start = EndianSlice::new(debug_line, LittleEndian);
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let Some(program) = &unit.line_program else {
+            continue;
+        };
+        let header = program.header();
+        if header.minimum_instruction_length() != 1
+            || header.maximum_operations_per_instruction() != 1
+        {
+            continue;
+        }
+        let opcode_base = header.opcode_base();
+        let line_range = header.line_range();
+
+        let mut instrs = header.raw_program_buf();
+        let mut address = 0;
+        let mut in_sequence = false;
+        while !instrs.is_empty() {
+            let instr_start = instrs.offset_from(section_start);
+            let opcode = instrs.read_u8()?;
+            if opcode >= opcode_base {
+                address += u64::from((opcode - opcode_base) / line_range);
+                in_sequence = true;
+                continue;
+            }
+            match gimli::DwLns(opcode) {
+                // Extended opcode.
+                gimli::DwLns(0) => {
+                    let length = instrs.read_uleb128()?;
+                    let mut extended =
+                        instrs.split(usize::try_from(length).unwrap_or(usize::MAX))?;
+                    let instr_end = instrs.offset_from(section_start);
+                    match gimli::DwLne(extended.read_u8()?) {
+                        gimli::DW_LNE_set_address => {
+                            let new_address = extended.read_address(header.address_size())?;
+                            if in_sequence {
+                                let advance = new_address
+                                    .checked_sub(address)
+                                    .ok_or(ConvertError::InvalidAddress)?;
+                                normalized[instr_start] = gimli::DW_LNS_advance_pc.0;
+                                write_padded_uleb128(
+                                    &mut normalized[instr_start + 1..instr_end],
+                                    advance,
+                                )?;
+                            }
+                            address = new_address;
+                        }
+                        gimli::DW_LNE_end_sequence => {
+                            address = 0;
+                            in_sequence = false;
+                        }
+                        _ => {}
+                    }
+                }
+                gimli::DW_LNS_copy => in_sequence = true,
+                gimli::DW_LNS_advance_pc => address += instrs.read_uleb128()?,
+                gimli::DW_LNS_const_add_pc => {
+                    address += u64::from((255 - opcode_base) / line_range)
+                }
+                gimli::DW_LNS_fixed_advance_pc => address += u64::from(instrs.read_u16()?),
+                _ => {
+                    let arguments = header
+                        .standard_opcode_lengths()
+                        .slice()
+                        .get(usize::from(opcode) - 1)
+                        .ok_or(ConvertError::UnsupportedLineInstruction)?;
+                    for _ in 0..*arguments {
+                        instrs.read_uleb128()?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+fn write_padded_uleb128(bytes: &mut [u8], mut value: u64) -> Result<(), ConvertError> {
+    let (last, rest) = bytes
+        .split_last_mut()
+        .ok_or(ConvertError::UnsupportedLineInstruction)?;
+    for byte in rest {
+        *byte = (value & 0x7f) as u8 | 0x80;
+        value >>= 7;
+    }
+    if value > 0x7f {
+        return Err(ConvertError::UnsupportedLineInstruction);
+    }
+    *last = value as u8;
+    Ok(())
+}
+
+/// Creates a string with the same form (e.g., `DW_FORM_line_strp`) as `like`.
+fn line_string(string: Vec<u8>, like: &LineString, dwarf: &mut write::Dwarf) -> LineString {
+    match like {
+        LineString::String(_) => LineString::String(string),
+        LineString::StringRef(_) => LineString::StringRef(dwarf.strings.add(string)),
+        LineString::LineStringRef(_) => LineString::LineStringRef(dwarf.line_strings.add(string)),
+    }
+}
+
+/// Updates the end of ranges given by `DW_AT_low_pc` and the size in `DW_AT_high_pc`.
+fn update_high_pcs(
+    unit: &mut write::Unit,
+    original_addresses: &FxHashMap<u64, u64>,
+    map_address: &impl Fn(u64) -> Option<u64>,
+) {
+    let mut entries = vec![unit.root()];
+    while let Some(id) = entries.pop() {
+        let entry = unit.get_mut(id);
+        let low_pc = match entry.get(gimli::DW_AT_low_pc) {
+            Some(write::AttributeValue::Address(write::Address::Constant(low_pc))) => Some(*low_pc),
+            _ => None,
+        };
+        let size = match entry.get(gimli::DW_AT_high_pc) {
+            Some(write::AttributeValue::Udata(size)) => Some(*size),
+            Some(write::AttributeValue::Data1(size)) => Some(u64::from(*size)),
+            Some(write::AttributeValue::Data2(size)) => Some(u64::from(*size)),
+            Some(write::AttributeValue::Data4(size)) => Some(u64::from(*size)),
+            Some(write::AttributeValue::Data8(size)) => Some(*size),
+            _ => None,
+        };
+        if let (Some(low_pc), Some(size)) = (low_pc, size) {
+            if let Some(&original_low_pc) = original_addresses.get(&low_pc) {
+                let original_high_pc = original_low_pc.saturating_add(size);
+                let high_pc = map_address(original_high_pc).unwrap_or(original_high_pc);
+                let size = high_pc.saturating_sub(low_pc);
+                entry.set(gimli::DW_AT_high_pc, write::AttributeValue::Udata(size));
+            }
+        }
+        entries.extend(entry.children().copied());
+    }
+}
+
+/// Maps DWARF addresses to functions and instructions.
+struct CodeOffsets<'a> {
+    code_section_offset: usize,
+    offsets: &'a Offsets,
+}
+
+impl CodeOffsets<'_> {
+    fn offset(&self, address: u64) -> Option<usize> {
+        self.code_section_offset
+            .checked_add(usize::try_from(address).ok()?)
+    }
+
+    fn function(&self, address: u64) -> Option<Idx<Function>> {
+        self.offsets.function_offset_to_idx(self.offset(address)?)
+    }
+
+    /// Addresses of the local declarations are attributed to the first instruction.
+    fn instr(&self, address: u64) -> Option<(Idx<Function>, Idx<Instr>)> {
+        let offset = self.offset(address)?;
+        self.offsets.instr_offset_to_idx(offset).or_else(|| {
+            let functions_before = self
+                .offsets
+                .functions_code
+                .partition_point(|&(_, code_offset)| code_offset <= offset);
+            let (function, _) = *self.offsets.functions_code[..functions_before].last()?;
+            let first_instr = self
+                .offsets
+                .instr_idx_to_offset(function, Idx::from(0u32))?;
+            (offset < first_instr).then_some((function, Idx::from(0u32)))
+        })
+    }
+}
+
+/// Resolves file indices of a single compilation unit to deduplicated paths in `DebugInfo`.
+struct UnitFiles<'a, 'input> {
+    dwarf: &'a gimli::Dwarf<Reader<'input>>,
+    unit: &'a gimli::Unit<Reader<'input>>,
+    file_ids: &'a mut FxHashMap<String, u32>,
+    files: &'a mut Vec<String>,
+    /// Cache, since typically many rows refer to the same few files.
+    indices: FxHashMap<u64, u32>,
+}
+
+impl<'input> UnitFiles<'_, 'input> {
+    fn file(
+        &mut self,
+        header: &gimli::LineProgramHeader<Reader<'input>>,
+        index: u64,
+    ) -> Result<u32, gimli::Error> {
+        if let Some(id) = self.indices.get(&index) {
+            return Ok(*id);
+        }
+
+        let path = match header.file(index) {
+            Some(file) => {
+                let name = self.string(file.path_name())?;
+                let directory = match file.directory(header) {
+                    Some(directory) => self.string(directory)?,
+                    None => String::new(),
+                };
+                let comp_dir = self
+                    .unit
+                    .comp_dir
+                    .map(|comp_dir| comp_dir.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                join_path(&comp_dir, &join_path(&directory, &name))
+            }
+            None => String::new(),
+        };
+
+        let next_id = self.files.len() as u32;
+        let id = *self.file_ids.entry(path).or_insert_with_key(|path| {
+            self.files.push(path.clone());
+            next_id
+        });
+        self.indices.insert(index, id);
+        Ok(id)
+    }
+
+    fn string(&self, attr: AttributeValue<Reader<'input>>) -> Result<String, gimli::Error> {
+        let string = self.dwarf.attr_string(self.unit, attr)?;
+        Ok(string.to_string_lossy().into_owned())
+    }
+}
+
+fn join_path(directory: &str, path: &str) -> String {
+    if directory.is_empty() || path.starts_with('/') {
+        path.to_string()
+    } else if path.is_empty() || directory.ends_with('/') {
+        format!("{directory}{path}")
+    } else {
+        format!("{directory}/{path}")
+    }
+}
+
+fn to_u32(value: u64) -> u32 {
+    value.try_into().unwrap_or(u32::MAX)
+}
@@ -1,6 +1,7 @@
 //! Code for encoding our AST back to the WebAssembly binary format.
 //! Uses `wasm-encoder` for the actual low-level work.
 
+use std::io;
 use std::sync::RwLock;
 
 use nohash_hasher::IntMap;
@@ -10,6 +11,7 @@ use rustc_hash::FxHashMap;
 use wasm_encoder as we;
 use wasm_encoder::Encode;
 
+use crate::parse::RawBody;
 use crate::*;
 
 /// Add marker types for type-safe `Idx<T>` for the low-level binary format.
@@ -22,6 +24,7 @@ mod marker {
         pub struct Global;
         pub struct Table;
         pub struct Memory;
+        pub struct Tag;
     }
 }
 
@@ -38,6 +41,7 @@ struct EncodeState {
     global_idx: IntMap<Idx<Global>, Idx<marker::we::Global>>,
     table_idx: IntMap<Idx<Table>, Idx<marker::we::Table>>,
     memory_idx: IntMap<Idx<Memory>, Idx<marker::we::Memory>>,
+    tag_idx: IntMap<Idx<Tag>, Idx<marker::we::Tag>>,
 
     last_encoded_section: Option<SectionId>,
     custom_sections_encoded: usize,
@@ -94,6 +98,22 @@ impl EncodeState {
         Memory,
         "memory"
     );
+    encode_state_idx_fns!(insert_tag_idx, map_tag_idx, tag_idx, Tag, "tag");
+
+    /// Whether all high-level indices are equal to their low-level indices, i.e., the original
+    /// binary encoding of lazily parsed function bodies still refers to the right items.
+    fn has_identity_idx_mappings(&self) -> bool {
+        fn is_identity<T, U>(mapping: &IntMap<Idx<T>, Idx<U>>) -> bool {
+            mapping.iter().all(|(highlevel_idx, lowlevel_idx)| {
+                highlevel_idx.to_u32() == lowlevel_idx.to_u32()
+            })
+        }
+        is_identity(&self.function_idx)
+            && is_identity(&self.global_idx)
+            && is_identity(&self.table_idx)
+            && is_identity(&self.memory_idx)
+            && is_identity(&self.tag_idx)
+    }
     encode_state_idx_fns!(
         insert_global_idx,
         map_global_idx,
@@ -104,9 +124,40 @@ impl EncodeState {
 }
 
 pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
-    let mut encoder = wasm_encoder::Module::new();
+    let mut bytes = Vec::new();
+    encode_module_to(module, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Encodes the module and writes each section to `writer` as soon as it is encoded, such that
+/// the complete binary is never resident in memory. Returns the number of bytes written.
+pub fn encode_module_to(module: &Module, writer: impl io::Write) -> Result<usize, EncodeError> {
+    let (bytes_written, _offsets) = encode(module, writer, false)?;
+    Ok(bytes_written)
+}
+
+/// Like `encode_module`, but also returns where sections, function bodies, and (unlike for
+/// `encode_module_to`) instructions ended up in the encoded binary.
+/// Functions are identified by their index in the high-level `module`.
+pub fn encode_module_with_offsets(module: &Module) -> Result<(Vec<u8>, Offsets), EncodeError> {
+    let mut bytes = Vec::new();
+    let (_bytes_written, offsets) = encode(module, &mut bytes, true)?;
+    Ok((bytes, offsets))
+}
+
+fn encode(
+    module: &Module,
+    writer: impl io::Write,
+    instr_offsets: bool,
+) -> Result<(usize, Offsets), EncodeError> {
+    let mut writer = SectionWriter::new(writer)?;
     let mut state = EncodeState::default();
 
+    // Lazily parsed function bodies (see `Module::from_bytes_lazy`) that were not mutated can be
+    // copied verbatim, if type indices (seeded here) and all other indices (checked below after
+    // all index spaces are known) are the same as in the original binary.
+    let raw_body_types = seed_raw_body_types(module, &mut state);
+
     // Note that the order in which the high-level AST is traversed is not equal to the order
     // in which low-level sections are written out to the binary.
     // Consider the type section: It must be the FIRST section of the low-level binary, but the
@@ -115,8 +166,10 @@ pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
     // Alternatively, one could iterate also twice over the high-level module. Once to collect
     // all types, then write the type section, and then once again to encode the rest of the
     // module. This would make the lifetime of the allocated sections a bit shorter.
-    // In principle, however, all sections must be fully resident in memory before one can write
-    // them out anyway, because the section size in bytes is prepended to its contents.
+    // In principle, however, a section must be fully resident in memory before one can write
+    // it out anyway, because the section size in bytes is prepended to its contents.
+    // So the (typically largest) code section is buffered until the type section is written,
+    // whereas the data section is only encoded after the code section was written and freed.
 
     // First, traverse all imported functions, globals, etc., such that they are at the
     // beginning of all index spaces.
@@ -125,94 +178,217 @@ pub fn encode_module(module: &Module) -> Result<Vec<u8>, EncodeError> {
     // Then traverse all non-imported functions, globals, etc., such that their indices and
     // types are in `state`.
     let function_section = encode_functions(module, &mut state);
-    let (table_section, element_section) = encode_tables(module, &mut state)?;
-    let (memory_section, data_section) = encode_memories(module, &mut state)?;
+    let table_section = encode_tables(module, &mut state);
+    let memory_section = encode_memories(module, &mut state);
+    let tag_section = encode_tags(module, &mut state);
     let global_section = encode_globals(module, &mut state)?;
 
+    // Element segments refer to tables, functions, and (in their offset expressions) globals,
+    // so they must come after all of those.
+    let element_section = encode_elements(module, &mut state)?;
+    let data_count_section = encode_data_count(module)?;
+    let raw_body_types = raw_body_types.filter(|_| state.has_identity_idx_mappings());
+
     // The code section can also contain types we haven't seen so far (e.g., in `call_indirect`),
     // so it must be processed before encoding the type section.
     // However the functions, globals, tables, etc. referred to in instructions should all
     // already be known from processing the sections above. If NOT, this is an error in the
     // input highlevel module and we report it.
-    let code_section = encode_code(module, &mut state)?;
+    let code_section = encode_code(module, &mut state, raw_body_types, instr_offsets)?;
 
     // Now, `state` contains all types that appear in the module, so we are ready encode the
     // type section.
     let type_section = encode_types(&state);
+    let type_count = type_section.len();
 
     // Then, write all sections in the correct order into the binary.
     // For the section order, see https://webassembly.github.io/spec/core/binary/modules.html#binary-module
     // Intersperse the correct custom sections in between as well.
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !type_section.is_empty() {
-        encoder.section(&type_section);
+        writer.section(SectionId::Type, &type_section)?;
     }
     state.last_encoded_section = Some(SectionId::Type);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !import_section.is_empty() {
-        encoder.section(&import_section);
+        writer.section(SectionId::Import, &import_section)?;
     }
     state.last_encoded_section = Some(SectionId::Import);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !function_section.is_empty() {
-        encoder.section(&function_section);
+        writer.section(SectionId::Function, &function_section)?;
     }
     state.last_encoded_section = Some(SectionId::Function);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !table_section.is_empty() {
-        encoder.section(&table_section);
+        writer.section(SectionId::Table, &table_section)?;
     }
     state.last_encoded_section = Some(SectionId::Table);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !memory_section.is_empty() {
-        encoder.section(&memory_section);
+        writer.section(SectionId::Memory, &memory_section)?;
     }
     state.last_encoded_section = Some(SectionId::Memory);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
+    if !tag_section.is_empty() {
+        writer.section(SectionId::Tag, &tag_section)?;
+    }
+    state.last_encoded_section = Some(SectionId::Tag);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !global_section.is_empty() {
-        encoder.section(&global_section);
+        writer.section(SectionId::Global, &global_section)?;
     }
     state.last_encoded_section = Some(SectionId::Global);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     let export_section = encode_exports(module, &mut state)?;
     if !export_section.is_empty() {
-        encoder.section(&export_section);
+        writer.section(SectionId::Export, &export_section)?;
     }
     state.last_encoded_section = Some(SectionId::Export);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if let Some(function_idx) = module.start {
         let start_section = we::StartSection {
             function_index: state.map_function_idx(function_idx)?.to_u32(),
         };
-        encoder.section(&start_section);
+        writer.section(SectionId::Start, &start_section)?;
     }
     state.last_encoded_section = Some(SectionId::Start);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !element_section.is_empty() {
-        encoder.section(&element_section);
+        writer.section(SectionId::Element, &element_section)?;
     }
     state.last_encoded_section = Some(SectionId::Element);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
+    if let Some(data_count_section) = data_count_section {
+        writer.section(SectionId::DataCount, &data_count_section)?;
+    }
+    state.last_encoded_section = Some(SectionId::DataCount);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     if !code_section.is_empty() {
-        encoder.section(&code_section);
+        writer.code_section(code_section)?;
     }
     state.last_encoded_section = Some(SectionId::Code);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
+    // Data segments refer only to memories and (in their offset expressions) globals, so they
+    // can be encoded this late. Their offset expressions must not contain any new types though,
+    // since the type section is already written at this point.
+    let data_section = encode_data(module, &mut state)?;
+    if state.types_idx.read().unwrap().len() != type_count as usize {
+        return Err(EncodeError::message(
+            "data segment offset expression contains a type not used anywhere else".to_string(),
+        ));
+    }
     if !data_section.is_empty() {
-        encoder.section(&data_section);
+        writer.section(SectionId::Data, &data_section)?;
     }
     state.last_encoded_section = Some(SectionId::Data);
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
     // Custom name section is only valid after data section, see
     // https://webassembly.github.io/spec/core/appendix/custom.html#name-section
     let name_section = encode_names(module, &state)?;
     if let Some(name_section) = name_section {
-        encoder.section(&name_section);
+        writer.section(SectionId::Custom("name".to_string()), &name_section)?;
         state.last_encoded_section = Some(SectionId::Custom("name".to_string()));
     }
-    encode_and_insert_custom(&mut encoder, &mut state, module);
+    encode_and_insert_custom(&mut writer, &mut state, module)?;
+
+    writer.finish()
+}
+
+/// Similar to `wasm_encoder::Module`, but writes sections directly to the underlying writer
+/// instead of appending them to a single, growing byte vector.
+struct SectionWriter<W> {
+    writer: W,
+    bytes_written: usize,
+    /// Re-used across sections to avoid an allocation for every section.
+    buffer: Vec<u8>,
+    offsets: Offsets,
+}
+
+impl<W: io::Write> SectionWriter<W> {
+    fn new(writer: W) -> Result<Self, EncodeError> {
+        let mut writer = SectionWriter {
+            writer,
+            bytes_written: 0,
+            buffer: Vec::new(),
+            offsets: Offsets::default(),
+        };
+        // Magic number `\0asm` and version 1.
+        writer.write(&[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00])?;
+        Ok(writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        self.writer.write_all(bytes)?;
+        self.bytes_written += bytes.len();
+        Ok(())
+    }
+
+    fn section(&mut self, id: SectionId, section: &impl we::Section) -> Result<(), EncodeError> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        buffer.push(section.id());
+        section.encode(&mut buffer);
+        let content_offset = self.bytes_written + 1 + leb128_len(&buffer[1..]);
+        self.offsets.sections.push((id, content_offset));
+        self.write(&buffer)?;
+        self.buffer = buffer;
+        Ok(())
+    }
+
+    /// Writes the code section from the already encoded function bodies (see `encode_code`),
+    /// without copying all of them into an intermediate `wasm_encoder::CodeSection` first.
+    fn code_section(&mut self, functions: Vec<EncodedBody>) -> Result<(), EncodeError> {
+        let mut header = vec![we::SectionId::Code as u8];
+        let mut count = Vec::new();
+        functions.len().encode(&mut count);
+        let size = count.len() + functions.iter().map(|f| f.bytes.len()).sum::<usize>();
+        size.encode(&mut header);
+        let content_offset = self.bytes_written + header.len();
+        self.offsets
+            .sections
+            .push((SectionId::Code, content_offset));
+        header.extend(count);
+        self.write(&header)?;
+
+        for function in functions {
+            let code_offset = self.bytes_written + leb128_len(&function.bytes);
+            self.offsets
+                .functions_code
+                .push((function.function, code_offset));
+            if let Some(instr_offsets) = function.instr_offsets {
+                let instr_offsets = instr_offsets
+                    .into_iter()
+                    .map(|offset| code_offset + offset)
+                    .collect();
+                self.offsets.instrs.push((function.function, instr_offsets));
+            }
+            self.write(&function.bytes)?;
+        }
+        Ok(())
+    }
 
-    Ok(encoder.finish())
+    fn finish(mut self) -> Result<(usize, Offsets), EncodeError> {
+        self.writer.flush()?;
+        Ok((self.bytes_written, self.offsets))
+    }
+}
+
+/// Number of bytes of the LEB128-encoded integer at the beginning of `bytes`.
+fn leb128_len(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .position(|byte| byte & 0x80 == 0)
+        .map_or(bytes.len(), |last_byte| last_byte + 1)
+}
+
+/// A function body that is encoded (see `encode_code`), but not yet written to the code section.
+struct EncodedBody {
+    function: Idx<Function>,
+    /// Including the size prefix of the code section entry.
+    bytes: Vec<u8>,
+    /// Only computed if requested, relative to the start of the body, i.e., after the size.
+    instr_offsets: Option<Vec<usize>>,
 }
 
 fn encode_imports(module: &Module, state: &mut EncodeState) -> we::ImportSection {
@@ -237,7 +413,7 @@ fn encode_imports(module: &Module, state: &mut EncodeState) -> we::ImportSection
         state.get_or_insert_type(f.type_).to_u32()
     });
     add_imports!(tables, insert_table_idx, Table, |t: &Table| {
-        we::TableType::from(t.limits)
+        we::TableType::from(t)
     });
     add_imports!(memories, insert_memory_idx, Memory, |m: &Memory| {
         we::MemoryType::from(m.limits)
@@ -245,6 +421,9 @@ fn encode_imports(module: &Module, state: &mut EncodeState) -> we::ImportSection
     add_imports!(globals, insert_global_idx, Global, |g: &Global| {
         we::GlobalType::from(g.type_)
     });
+    add_imports!(tags, insert_tag_idx, Tag, |t: &Tag| {
+        encode_tag_type(t, state)
+    });
 
     import_section
 }
@@ -273,6 +452,7 @@ fn encode_exports(
     add_exports!(tables, Table, map_table_idx);
     add_exports!(memories, Memory, map_memory_idx);
     add_exports!(globals, Global, map_global_idx);
+    add_exports!(tags, Tag, map_tag_idx);
 
     Ok(export_section)
 }
@@ -318,66 +498,149 @@ fn encode_functions(module: &Module, state: &mut EncodeState) -> we::FunctionSec
     function_section
 }
 
-fn encode_tables(
-    module: &Module,
-    state: &mut EncodeState,
-) -> Result<(we::TableSection, we::ElementSection), EncodeError> {
+fn encode_tables(module: &Module, state: &mut EncodeState) -> we::TableSection {
     let mut table_section = we::TableSection::new();
-    let mut element_section = we::ElementSection::new();
 
-    for (hl_table_idx, table) in module.tables() {
-        let ll_table_idx = if table.import.is_none() {
-            table_section.table(we::TableType::from(table.limits));
-            state.insert_table_idx(hl_table_idx)
-        } else {
-            state.map_table_idx(hl_table_idx)?
-        };
+    for (table_idx, table) in module.tables() {
+        if table.import.is_none() {
+            state.insert_table_idx(table_idx);
+            table_section.table(we::TableType::from(table));
+        }
+    }
 
-        for hl_element in &table.elements {
-            // `wasm-encoder` uses None as the table index to signify the MVP binary format.
-            // Use that whenever possible, to avoid producing a binary using extensions.
-            let ll_table_idx = if ll_table_idx.to_u32() == 0 {
-                None
-            } else {
-                Some(ll_table_idx.to_u32())
-            };
-            let ll_offset = encode_single_instruction_with_end(&hl_element.offset, state)?;
-            let ll_elements = hl_element
-                .functions
-                .iter()
-                .map(|function_idx| state.map_function_idx(*function_idx).map(Idx::to_u32))
-                .collect::<Result<Vec<u32>, _>>()?;
-            let ll_elements = we::Elements::Functions(ll_elements.as_slice());
-            element_section.active(ll_table_idx, &ll_offset, we::ValType::FuncRef, ll_elements);
+    table_section
+}
+
+fn encode_memories(module: &Module, state: &mut EncodeState) -> we::MemorySection {
+    let mut memory_section = we::MemorySection::new();
+
+    for (memory_idx, memory) in module.memories() {
+        if memory.import.is_none() {
+            state.insert_memory_idx(memory_idx);
+            memory_section.memory(we::MemoryType::from(memory.limits));
         }
     }
 
-    Ok((table_section, element_section))
+    memory_section
+}
+
+fn encode_tags(module: &Module, state: &mut EncodeState) -> we::TagSection {
+    let mut tag_section = we::TagSection::new();
+
+    for (tag_idx, tag) in module.tags() {
+        if tag.import.is_none() {
+            state.insert_tag_idx(tag_idx);
+            tag_section.tag(encode_tag_type(tag, state));
+        }
+    }
+
+    tag_section
+}
+
+fn encode_tag_type(tag: &Tag, state: &EncodeState) -> we::TagType {
+    we::TagType {
+        kind: we::TagKind::Exception,
+        func_type_idx: state.get_or_insert_type(tag.type_).to_u32(),
+    }
 }
 
-fn encode_memories(
+fn encode_elements(
     module: &Module,
     state: &mut EncodeState,
-) -> Result<(we::MemorySection, we::DataSection), EncodeError> {
-    let mut memory_section = we::MemorySection::new();
-    let mut data_section = we::DataSection::new();
+) -> Result<we::ElementSection, EncodeError> {
+    let mut element_section = we::ElementSection::new();
 
-    for (hl_memory_idx, memory) in module.memories() {
-        let ll_memory_idx = if memory.import.is_none() {
-            memory_section.memory(we::MemoryType::from(memory.limits));
-            state.insert_memory_idx(hl_memory_idx)
-        } else {
-            state.map_memory_idx(hl_memory_idx)?
+    // Element segments are encoded in order, so their high-level and low-level indices are equal.
+    for element in &module.elements {
+        // Only one of the two is initialized, depending on the representation of the items.
+        let ll_functions: Vec<u32>;
+        let ll_exprs: Vec<we::ConstExpr>;
+        let ll_elements = match &element.items {
+            ElementItems::Functions(functions) => {
+                ll_functions = functions
+                    .iter()
+                    .map(|function_idx| state.map_function_idx(*function_idx).map(Idx::to_u32))
+                    .collect::<Result<_, _>>()?;
+                we::Elements::Functions(ll_functions.as_slice())
+            }
+            ElementItems::Expressions(exprs) => {
+                ll_exprs = exprs
+                    .iter()
+                    .map(|expr| encode_single_instruction_with_end(expr, state))
+                    .collect::<Result<_, _>>()?;
+                we::Elements::Expressions(ll_exprs.as_slice())
+            }
         };
+        let ll_ref_type = ValType::from(element.ref_type).into();
+        match &element.mode {
+            ElementMode::Active { table, offset } => {
+                let ll_table_idx = state.map_table_idx(*table)?;
+                // `wasm-encoder` uses None as the table index to signify the MVP binary format.
+                // Use that whenever possible, to avoid producing a binary using extensions.
+                let ll_table_idx = if ll_table_idx.to_u32() == 0 {
+                    None
+                } else {
+                    Some(ll_table_idx.to_u32())
+                };
+                let ll_offset = encode_single_instruction_with_end(offset, state)?;
+                element_section.active(ll_table_idx, &ll_offset, ll_ref_type, ll_elements);
+            }
+            ElementMode::Passive => {
+                element_section.passive(ll_ref_type, ll_elements);
+            }
+            ElementMode::Declared => {
+                element_section.declared(ll_ref_type, ll_elements);
+            }
+        }
+    }
+
+    Ok(element_section)
+}
+
+fn encode_data(module: &Module, state: &mut EncodeState) -> Result<we::DataSection, EncodeError> {
+    let mut data_section = we::DataSection::new();
 
-        for data in &memory.data {
-            let ll_offset = encode_single_instruction_with_end(&data.offset, state)?;
-            let ll_data = data.bytes.iter().copied();
-            data_section.active(ll_memory_idx.to_u32(), &ll_offset, ll_data);
+    // Data segments are encoded in order, so their high-level and low-level indices are equal.
+    for data in &module.data {
+        let ll_data = data.bytes.iter().copied();
+        match &data.mode {
+            DataMode::Active { memory, offset } => {
+                let ll_memory_idx = state.map_memory_idx(*memory)?;
+                let ll_offset = encode_single_instruction_with_end(offset, state)?;
+                data_section.active(ll_memory_idx.to_u32(), &ll_offset, ll_data);
+            }
+            DataMode::Passive => {
+                data_section.passive(ll_data);
+            }
         }
     }
 
-    Ok((memory_section, data_section))
+    Ok(data_section)
+}
+
+/// The data count section is required (only) if data segments are referenced from code,
+/// see https://webassembly.github.io/spec/core/binary/modules.html#data-count-section
+fn encode_data_count(module: &Module) -> Result<Option<we::DataCountSection>, EncodeError> {
+    let data_referenced_in_code = module
+        .functions
+        .par_iter()
+        .filter_map(Function::code)
+        .map(|code| match code.body.raw() {
+            // Scanning the binary encoding is cheaper than decoding lazily parsed bodies.
+            Some(raw) if !code.body.is_decoded() => raw.references_data(),
+            _ => Ok(code
+                .body
+                .iter()
+                .any(|instr| matches!(instr, Instr::MemoryInit(_, _) | Instr::DataDrop(_)))),
+        })
+        .try_reduce(|| false, |any, referenced| Ok(any || referenced))?;
+    Ok(data_referenced_in_code.then(|| we::DataCountSection {
+        count: module
+            .data
+            .len()
+            .try_into()
+            .expect("too many data segments for wasm32"),
+    }))
 }
 
 fn encode_globals(
@@ -397,41 +660,115 @@ fn encode_globals(
     Ok(global_section)
 }
 
-fn encode_code(module: &Module, state: &mut EncodeState) -> Result<we::CodeSection, EncodeError> {
-    let mut code_section = we::CodeSection::new();
-
+/// Returns the encoded function bodies (including their size prefix), which are written
+/// directly by `SectionWriter::code_section`.
+fn encode_code(
+    module: &Module,
+    state: &mut EncodeState,
+    raw_body_types: Option<&RawBody>,
+    instr_offsets: bool,
+) -> Result<Vec<EncodedBody>, EncodeError> {
     // Encode function bodies in parallel.
-    let ll_functions = module
+    module
         .functions
         .par_iter()
-        .filter_map(Function::code)
-        .map(|code| -> Result<we::Function, EncodeError> {
+        .enumerate()
+        .filter_map(|(function_idx, function)| Some((function_idx.into(), function.code()?)))
+        .map(|(function, code)| -> Result<EncodedBody, EncodeError> {
+            if let Some(raw) = code.body.raw() {
+                if raw_body_types.is_some_and(|types| raw.same_types(types)) {
+                    return encode_raw_body(function, code, raw, instr_offsets);
+                }
+            }
+
             let ll_locals_iter = code
                 .locals
                 .iter()
                 .map(|local| we::ValType::from(local.type_));
             let mut ll_function = we::Function::new_with_locals_types(ll_locals_iter);
-            for instr in &code.body {
+            let body = code.body.get_uncached()?;
+            let mut offsets = Vec::with_capacity(if instr_offsets { body.len() } else { 0 });
+            for instr in body.iter() {
+                if instr_offsets {
+                    offsets.push(ll_function.byte_len());
+                }
                 ll_function.instruction(&encode_instruction(instr, state)?);
             }
-            Ok(ll_function)
+            let mut bytes = Vec::with_capacity(ll_function.byte_len() + 5);
+            ll_function.encode(&mut bytes);
+            Ok(EncodedBody {
+                function,
+                bytes,
+                instr_offsets: instr_offsets.then_some(offsets),
+            })
         })
-        .collect::<Result<Vec<we::Function>, _>>()?;
-    for ll_function in ll_functions {
-        code_section.function(&ll_function);
+        .collect()
+}
+
+/// Copies a lazily parsed function body verbatim, or only its instructions if the locals changed.
+fn encode_raw_body(
+    function: Idx<Function>,
+    code: &Code,
+    raw: &RawBody,
+    instr_offsets: bool,
+) -> Result<EncodedBody, EncodeError> {
+    let mut bytes = Vec::with_capacity(raw.bytes().len() + 5);
+    if raw.locals_match(&code.locals)? {
+        raw.bytes().len().encode(&mut bytes);
+        bytes.extend_from_slice(raw.bytes());
+    } else {
+        let ll_locals_iter = code
+            .locals
+            .iter()
+            .map(|local| we::ValType::from(local.type_));
+        let mut ll_function = we::Function::new_with_locals_types(ll_locals_iter);
+        ll_function.raw(raw.instr_bytes()?.iter().copied());
+        ll_function.encode(&mut bytes);
     }
+    let instr_offsets = if instr_offsets {
+        // The instructions are at the very end of the body, after the size and locals.
+        let instrs_start = bytes.len() - leb128_len(&bytes) - raw.instr_bytes()?.len();
+        let offsets = raw.instr_offsets()?;
+        Some(
+            offsets
+                .into_iter()
+                .map(|offset| instrs_start + offset)
+                .collect(),
+        )
+    } else {
+        None
+    };
+    Ok(EncodedBody {
+        function,
+        bytes,
+        instr_offsets,
+    })
+}
 
-    Ok(code_section)
+/// Gives the types of the original binary of lazily parsed function bodies the same indices as
+/// in that binary. Returns one of those bodies, or `None` if there are no lazily parsed bodies,
+/// or their type indices cannot be preserved because the original type section had duplicates.
+fn seed_raw_body_types<'a>(module: &'a Module, state: &mut EncodeState) -> Option<&'a RawBody> {
+    let raw = module
+        .functions
+        .iter()
+        .filter_map(Function::code)
+        .find_map(|code| code.body.raw())?;
+    for type_ in raw.types() {
+        state.get_or_insert_type(*type_);
+    }
+    let no_duplicates = state.types_idx.read().unwrap().len() == raw.types().len();
+    no_duplicates.then_some(raw)
 }
 
 // TODO generify to include all sections, not just custom sections
 // fn insert_section<T>(encoder: &mut wasm_encoder::Module, state: &mut EncodeState, section: T, module: &Module, previous_section: Option<SectionId>)
 //     where T: wasm_encoder::Section {
 fn encode_and_insert_custom(
-    encoder: &mut wasm_encoder::Module,
+    writer: &mut SectionWriter<impl io::Write>,
     state: &mut EncodeState,
     module: &Module,
-) {
+) -> Result<(), EncodeError> {
     for custom in module
         .custom_sections
         .iter()
@@ -440,14 +777,18 @@ fn encode_and_insert_custom(
         // FIXME what if the reference .after section is no longer present?
         // Right now, this would drop the custom section.
         if state.last_encoded_section == custom.previous_section {
-            encoder.section(&wasm_encoder::CustomSection {
-                name: &custom.name,
-                data: &custom.content[..],
-            });
+            writer.section(
+                SectionId::Custom(custom.name.clone()),
+                &wasm_encoder::CustomSection {
+                    name: &custom.name,
+                    data: &custom.content[..],
+                },
+            )?;
             state.custom_sections_encoded += 1;
             state.last_encoded_section = Some(SectionId::Custom(custom.name.clone()));
         }
     }
+    Ok(())
 }
 
 fn encode_single_instruction_with_end(
@@ -478,6 +819,13 @@ fn encode_instruction(
         Instr::Else => we::Instruction::Else,
         Instr::End => we::Instruction::End,
 
+        Instr::Try(block_type) => we::Instruction::Try(encode_block_type(block_type, state)),
+        Instr::Catch(tag_idx) => we::Instruction::Catch(state.map_tag_idx(tag_idx)?.to_u32()),
+        Instr::CatchAll => we::Instruction::CatchAll,
+        Instr::Delegate(label) => we::Instruction::Delegate(label.to_u32()),
+        Instr::Throw(tag_idx) => we::Instruction::Throw(state.map_tag_idx(tag_idx)?.to_u32()),
+        Instr::Rethrow(label) => we::Instruction::Rethrow(label.to_u32()),
+
         Instr::Br(label) => we::Instruction::Br(label.to_u32()),
         Instr::BrIf(label) => we::Instruction::BrIf(label.to_u32()),
         Instr::BrTable { ref table, default } => we::Instruction::BrTable(
@@ -493,9 +841,19 @@ fn encode_instruction(
             ty: state.get_or_insert_type(*function_type).to_u32(),
             table: state.map_table_idx(table_idx)?.to_u32(),
         },
+        Instr::ReturnCall(function_idx) => {
+            we::Instruction::ReturnCall(state.map_function_idx(function_idx)?.to_u32())
+        }
+        Instr::ReturnCallIndirect(ref function_type, table_idx) => {
+            we::Instruction::ReturnCallIndirect {
+                ty: state.get_or_insert_type(*function_type).to_u32(),
+                table: state.map_table_idx(table_idx)?.to_u32(),
+            }
+        }
 
         Instr::Drop => we::Instruction::Drop,
         Instr::Select => we::Instruction::Select,
+        Instr::TypedSelect(val_type) => we::Instruction::TypedSelect(val_type.into()),
 
         Instr::Local(LocalOp::Get, local_idx) => we::Instruction::LocalGet(local_idx.to_u32()),
         Instr::Local(LocalOp::Set, local_idx) => we::Instruction::LocalSet(local_idx.to_u32()),
@@ -507,30 +865,118 @@ fn encode_instruction(
             we::Instruction::GlobalSet(state.map_global_idx(global_idx)?.to_u32())
         }
 
-        Instr::Load(LoadOp::I32Load, memarg) => we::Instruction::I32Load(memarg.into()),
-        Instr::Load(LoadOp::I64Load, memarg) => we::Instruction::I64Load(memarg.into()),
-        Instr::Load(LoadOp::F32Load, memarg) => we::Instruction::F32Load(memarg.into()),
-        Instr::Load(LoadOp::F64Load, memarg) => we::Instruction::F64Load(memarg.into()),
-        Instr::Load(LoadOp::I32Load8S, memarg) => we::Instruction::I32Load8S(memarg.into()),
-        Instr::Load(LoadOp::I32Load8U, memarg) => we::Instruction::I32Load8U(memarg.into()),
-        Instr::Load(LoadOp::I32Load16S, memarg) => we::Instruction::I32Load16S(memarg.into()),
-        Instr::Load(LoadOp::I32Load16U, memarg) => we::Instruction::I32Load16U(memarg.into()),
-        Instr::Load(LoadOp::I64Load8S, memarg) => we::Instruction::I64Load8S(memarg.into()),
-        Instr::Load(LoadOp::I64Load8U, memarg) => we::Instruction::I64Load8U(memarg.into()),
-        Instr::Load(LoadOp::I64Load16S, memarg) => we::Instruction::I64Load16S(memarg.into()),
-        Instr::Load(LoadOp::I64Load16U, memarg) => we::Instruction::I64Load16U(memarg.into()),
-        Instr::Load(LoadOp::I64Load32S, memarg) => we::Instruction::I64Load32S(memarg.into()),
-        Instr::Load(LoadOp::I64Load32U, memarg) => we::Instruction::I64Load32U(memarg.into()),
-
-        Instr::Store(StoreOp::I32Store, memarg) => we::Instruction::I32Store(memarg.into()),
-        Instr::Store(StoreOp::I64Store, memarg) => we::Instruction::I64Store(memarg.into()),
-        Instr::Store(StoreOp::F32Store, memarg) => we::Instruction::F32Store(memarg.into()),
-        Instr::Store(StoreOp::F64Store, memarg) => we::Instruction::F64Store(memarg.into()),
-        Instr::Store(StoreOp::I32Store8, memarg) => we::Instruction::I32Store8(memarg.into()),
-        Instr::Store(StoreOp::I32Store16, memarg) => we::Instruction::I32Store16(memarg.into()),
-        Instr::Store(StoreOp::I64Store8, memarg) => we::Instruction::I64Store8(memarg.into()),
-        Instr::Store(StoreOp::I64Store16, memarg) => we::Instruction::I64Store16(memarg.into()),
-        Instr::Store(StoreOp::I64Store32, memarg) => we::Instruction::I64Store32(memarg.into()),
+        Instr::Load(LoadOp::I32Load, memarg) => {
+            we::Instruction::I32Load(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load, memarg) => {
+            we::Instruction::I64Load(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::F32Load, memarg) => {
+            we::Instruction::F32Load(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::F64Load, memarg) => {
+            we::Instruction::F64Load(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I32Load8S, memarg) => {
+            we::Instruction::I32Load8S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I32Load8U, memarg) => {
+            we::Instruction::I32Load8U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I32Load16S, memarg) => {
+            we::Instruction::I32Load16S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I32Load16U, memarg) => {
+            we::Instruction::I32Load16U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load8S, memarg) => {
+            we::Instruction::I64Load8S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load8U, memarg) => {
+            we::Instruction::I64Load8U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load16S, memarg) => {
+            we::Instruction::I64Load16S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load16U, memarg) => {
+            we::Instruction::I64Load16U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load32S, memarg) => {
+            we::Instruction::I64Load32S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::I64Load32U, memarg) => {
+            we::Instruction::I64Load32U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load, memarg) => {
+            we::Instruction::V128Load(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load8x8S, memarg) => {
+            we::Instruction::V128Load8x8S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load8x8U, memarg) => {
+            we::Instruction::V128Load8x8U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load16x4S, memarg) => {
+            we::Instruction::V128Load16x4S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load16x4U, memarg) => {
+            we::Instruction::V128Load16x4U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load32x2S, memarg) => {
+            we::Instruction::V128Load32x2S(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load32x2U, memarg) => {
+            we::Instruction::V128Load32x2U(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load8Splat, memarg) => {
+            we::Instruction::V128Load8Splat(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load16Splat, memarg) => {
+            we::Instruction::V128Load16Splat(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load32Splat, memarg) => {
+            we::Instruction::V128Load32Splat(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load64Splat, memarg) => {
+            we::Instruction::V128Load64Splat(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load32Zero, memarg) => {
+            we::Instruction::V128Load32Zero(encode_memarg(memarg, state)?)
+        }
+        Instr::Load(LoadOp::V128Load64Zero, memarg) => {
+            we::Instruction::V128Load64Zero(encode_memarg(memarg, state)?)
+        }
+
+        Instr::Store(StoreOp::I32Store, memarg) => {
+            we::Instruction::I32Store(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::I64Store, memarg) => {
+            we::Instruction::I64Store(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::F32Store, memarg) => {
+            we::Instruction::F32Store(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::F64Store, memarg) => {
+            we::Instruction::F64Store(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::I32Store8, memarg) => {
+            we::Instruction::I32Store8(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::I32Store16, memarg) => {
+            we::Instruction::I32Store16(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::I64Store8, memarg) => {
+            we::Instruction::I64Store8(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::I64Store16, memarg) => {
+            we::Instruction::I64Store16(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::I64Store32, memarg) => {
+            we::Instruction::I64Store32(encode_memarg(memarg, state)?)
+        }
+        Instr::Store(StoreOp::V128Store, memarg) => {
+            we::Instruction::V128Store(encode_memarg(memarg, state)?)
+        }
 
         Instr::MemorySize(memory_idx) => {
             we::Instruction::MemorySize(state.map_memory_idx(memory_idx)?.to_u32())
@@ -538,11 +984,57 @@ fn encode_instruction(
         Instr::MemoryGrow(memory_idx) => {
             we::Instruction::MemoryGrow(state.map_memory_idx(memory_idx)?.to_u32())
         }
+        Instr::MemoryCopy { dst, src } => we::Instruction::MemoryCopy {
+            dst_mem: state.map_memory_idx(dst)?.to_u32(),
+            src_mem: state.map_memory_idx(src)?.to_u32(),
+        },
+        Instr::MemoryFill(memory_idx) => {
+            we::Instruction::MemoryFill(state.map_memory_idx(memory_idx)?.to_u32())
+        }
+        // Data segments are encoded in order, so no mapping of indices is necessary.
+        Instr::MemoryInit(memory_idx, data_idx) => we::Instruction::MemoryInit {
+            mem: state.map_memory_idx(memory_idx)?.to_u32(),
+            data_index: data_idx.to_u32(),
+        },
+        Instr::DataDrop(data_idx) => we::Instruction::DataDrop(data_idx.to_u32()),
+
+        Instr::RefNull(ref_type) => we::Instruction::RefNull(ValType::from(ref_type).into()),
+        Instr::RefIsNull => we::Instruction::RefIsNull,
+        Instr::RefFunc(function_idx) => {
+            we::Instruction::RefFunc(state.map_function_idx(function_idx)?.to_u32())
+        }
+
+        Instr::TableGet(table_idx) => {
+            we::Instruction::TableGet(state.map_table_idx(table_idx)?.to_u32())
+        }
+        Instr::TableSet(table_idx) => {
+            we::Instruction::TableSet(state.map_table_idx(table_idx)?.to_u32())
+        }
+        Instr::TableSize(table_idx) => {
+            we::Instruction::TableSize(state.map_table_idx(table_idx)?.to_u32())
+        }
+        Instr::TableGrow(table_idx) => {
+            we::Instruction::TableGrow(state.map_table_idx(table_idx)?.to_u32())
+        }
+        Instr::TableFill(table_idx) => {
+            we::Instruction::TableFill(state.map_table_idx(table_idx)?.to_u32())
+        }
+        Instr::TableCopy { dst, src } => we::Instruction::TableCopy {
+            dst_table: state.map_table_idx(dst)?.to_u32(),
+            src_table: state.map_table_idx(src)?.to_u32(),
+        },
+        // Element segments are encoded in order, so no mapping of indices is necessary.
+        Instr::TableInit(table_idx, element_idx) => we::Instruction::TableInit {
+            table: state.map_table_idx(table_idx)?.to_u32(),
+            elem_index: element_idx.to_u32(),
+        },
+        Instr::ElemDrop(element_idx) => we::Instruction::ElemDrop(element_idx.to_u32()),
 
         Instr::Const(Val::I32(value)) => we::Instruction::I32Const(value),
         Instr::Const(Val::I64(value)) => we::Instruction::I64Const(value),
         Instr::Const(Val::F32(value)) => we::Instruction::F32Const(value.into_inner()),
         Instr::Const(Val::F64(value)) => we::Instruction::F64Const(value.into_inner()),
+        Instr::Const(Val::V128(value)) => we::Instruction::V128Const(value.to_i128()),
 
         Instr::Unary(UnaryOp::I32Eqz) => we::Instruction::I32Eqz,
         Instr::Unary(UnaryOp::I64Eqz) => we::Instruction::I64Eqz,
@@ -591,6 +1083,92 @@ fn encode_instruction(
         Instr::Unary(UnaryOp::I64ReinterpretF64) => we::Instruction::I64ReinterpretF64,
         Instr::Unary(UnaryOp::F32ReinterpretI32) => we::Instruction::F32ReinterpretI32,
         Instr::Unary(UnaryOp::F64ReinterpretI64) => we::Instruction::F64ReinterpretI64,
+        Instr::Unary(UnaryOp::I32Extend8S) => we::Instruction::I32Extend8S,
+        Instr::Unary(UnaryOp::I32Extend16S) => we::Instruction::I32Extend16S,
+        Instr::Unary(UnaryOp::I64Extend8S) => we::Instruction::I64Extend8S,
+        Instr::Unary(UnaryOp::I64Extend16S) => we::Instruction::I64Extend16S,
+        Instr::Unary(UnaryOp::I64Extend32S) => we::Instruction::I64Extend32S,
+        Instr::Unary(UnaryOp::I32TruncSatF32S) => we::Instruction::I32TruncSatF32S,
+        Instr::Unary(UnaryOp::I32TruncSatF32U) => we::Instruction::I32TruncSatF32U,
+        Instr::Unary(UnaryOp::I32TruncSatF64S) => we::Instruction::I32TruncSatF64S,
+        Instr::Unary(UnaryOp::I32TruncSatF64U) => we::Instruction::I32TruncSatF64U,
+        Instr::Unary(UnaryOp::I64TruncSatF32S) => we::Instruction::I64TruncSatF32S,
+        Instr::Unary(UnaryOp::I64TruncSatF32U) => we::Instruction::I64TruncSatF32U,
+        Instr::Unary(UnaryOp::I64TruncSatF64S) => we::Instruction::I64TruncSatF64S,
+        Instr::Unary(UnaryOp::I64TruncSatF64U) => we::Instruction::I64TruncSatF64U,
+        Instr::Unary(UnaryOp::I8x16Splat) => we::Instruction::I8x16Splat,
+        Instr::Unary(UnaryOp::I16x8Splat) => we::Instruction::I16x8Splat,
+        Instr::Unary(UnaryOp::I32x4Splat) => we::Instruction::I32x4Splat,
+        Instr::Unary(UnaryOp::I64x2Splat) => we::Instruction::I64x2Splat,
+        Instr::Unary(UnaryOp::F32x4Splat) => we::Instruction::F32x4Splat,
+        Instr::Unary(UnaryOp::F64x2Splat) => we::Instruction::F64x2Splat,
+        Instr::Unary(UnaryOp::V128Not) => we::Instruction::V128Not,
+        Instr::Unary(UnaryOp::V128AnyTrue) => we::Instruction::V128AnyTrue,
+        Instr::Unary(UnaryOp::I8x16Abs) => we::Instruction::I8x16Abs,
+        Instr::Unary(UnaryOp::I8x16Neg) => we::Instruction::I8x16Neg,
+        Instr::Unary(UnaryOp::I8x16Popcnt) => we::Instruction::I8x16Popcnt,
+        Instr::Unary(UnaryOp::I8x16AllTrue) => we::Instruction::I8x16AllTrue,
+        Instr::Unary(UnaryOp::I8x16Bitmask) => we::Instruction::I8x16Bitmask,
+        Instr::Unary(UnaryOp::I16x8ExtAddPairwiseI8x16S) => {
+            we::Instruction::I16x8ExtAddPairwiseI8x16S
+        }
+        Instr::Unary(UnaryOp::I16x8ExtAddPairwiseI8x16U) => {
+            we::Instruction::I16x8ExtAddPairwiseI8x16U
+        }
+        Instr::Unary(UnaryOp::I16x8Abs) => we::Instruction::I16x8Abs,
+        Instr::Unary(UnaryOp::I16x8Neg) => we::Instruction::I16x8Neg,
+        Instr::Unary(UnaryOp::I16x8AllTrue) => we::Instruction::I16x8AllTrue,
+        Instr::Unary(UnaryOp::I16x8Bitmask) => we::Instruction::I16x8Bitmask,
+        Instr::Unary(UnaryOp::I16x8ExtendLowI8x16S) => we::Instruction::I16x8ExtendLowI8x16S,
+        Instr::Unary(UnaryOp::I16x8ExtendHighI8x16S) => we::Instruction::I16x8ExtendHighI8x16S,
+        Instr::Unary(UnaryOp::I16x8ExtendLowI8x16U) => we::Instruction::I16x8ExtendLowI8x16U,
+        Instr::Unary(UnaryOp::I16x8ExtendHighI8x16U) => we::Instruction::I16x8ExtendHighI8x16U,
+        Instr::Unary(UnaryOp::I32x4ExtAddPairwiseI16x8S) => {
+            we::Instruction::I32x4ExtAddPairwiseI16x8S
+        }
+        Instr::Unary(UnaryOp::I32x4ExtAddPairwiseI16x8U) => {
+            we::Instruction::I32x4ExtAddPairwiseI16x8U
+        }
+        Instr::Unary(UnaryOp::I32x4Abs) => we::Instruction::I32x4Abs,
+        Instr::Unary(UnaryOp::I32x4Neg) => we::Instruction::I32x4Neg,
+        Instr::Unary(UnaryOp::I32x4AllTrue) => we::Instruction::I32x4AllTrue,
+        Instr::Unary(UnaryOp::I32x4Bitmask) => we::Instruction::I32x4Bitmask,
+        Instr::Unary(UnaryOp::I32x4ExtendLowI16x8S) => we::Instruction::I32x4ExtendLowI16x8S,
+        Instr::Unary(UnaryOp::I32x4ExtendHighI16x8S) => we::Instruction::I32x4ExtendHighI16x8S,
+        Instr::Unary(UnaryOp::I32x4ExtendLowI16x8U) => we::Instruction::I32x4ExtendLowI16x8U,
+        Instr::Unary(UnaryOp::I32x4ExtendHighI16x8U) => we::Instruction::I32x4ExtendHighI16x8U,
+        Instr::Unary(UnaryOp::I64x2Abs) => we::Instruction::I64x2Abs,
+        Instr::Unary(UnaryOp::I64x2Neg) => we::Instruction::I64x2Neg,
+        Instr::Unary(UnaryOp::I64x2AllTrue) => we::Instruction::I64x2AllTrue,
+        Instr::Unary(UnaryOp::I64x2Bitmask) => we::Instruction::I64x2Bitmask,
+        Instr::Unary(UnaryOp::I64x2ExtendLowI32x4S) => we::Instruction::I64x2ExtendLowI32x4S,
+        Instr::Unary(UnaryOp::I64x2ExtendHighI32x4S) => we::Instruction::I64x2ExtendHighI32x4S,
+        Instr::Unary(UnaryOp::I64x2ExtendLowI32x4U) => we::Instruction::I64x2ExtendLowI32x4U,
+        Instr::Unary(UnaryOp::I64x2ExtendHighI32x4U) => we::Instruction::I64x2ExtendHighI32x4U,
+        Instr::Unary(UnaryOp::F32x4Ceil) => we::Instruction::F32x4Ceil,
+        Instr::Unary(UnaryOp::F32x4Floor) => we::Instruction::F32x4Floor,
+        Instr::Unary(UnaryOp::F32x4Trunc) => we::Instruction::F32x4Trunc,
+        Instr::Unary(UnaryOp::F32x4Nearest) => we::Instruction::F32x4Nearest,
+        Instr::Unary(UnaryOp::F32x4Abs) => we::Instruction::F32x4Abs,
+        Instr::Unary(UnaryOp::F32x4Neg) => we::Instruction::F32x4Neg,
+        Instr::Unary(UnaryOp::F32x4Sqrt) => we::Instruction::F32x4Sqrt,
+        Instr::Unary(UnaryOp::F64x2Ceil) => we::Instruction::F64x2Ceil,
+        Instr::Unary(UnaryOp::F64x2Floor) => we::Instruction::F64x2Floor,
+        Instr::Unary(UnaryOp::F64x2Trunc) => we::Instruction::F64x2Trunc,
+        Instr::Unary(UnaryOp::F64x2Nearest) => we::Instruction::F64x2Nearest,
+        Instr::Unary(UnaryOp::F64x2Abs) => we::Instruction::F64x2Abs,
+        Instr::Unary(UnaryOp::F64x2Neg) => we::Instruction::F64x2Neg,
+        Instr::Unary(UnaryOp::F64x2Sqrt) => we::Instruction::F64x2Sqrt,
+        Instr::Unary(UnaryOp::I32x4TruncSatF32x4S) => we::Instruction::I32x4TruncSatF32x4S,
+        Instr::Unary(UnaryOp::I32x4TruncSatF32x4U) => we::Instruction::I32x4TruncSatF32x4U,
+        Instr::Unary(UnaryOp::F32x4ConvertI32x4S) => we::Instruction::F32x4ConvertI32x4S,
+        Instr::Unary(UnaryOp::F32x4ConvertI32x4U) => we::Instruction::F32x4ConvertI32x4U,
+        Instr::Unary(UnaryOp::I32x4TruncSatF64x2SZero) => we::Instruction::I32x4TruncSatF64x2SZero,
+        Instr::Unary(UnaryOp::I32x4TruncSatF64x2UZero) => we::Instruction::I32x4TruncSatF64x2UZero,
+        Instr::Unary(UnaryOp::F64x2ConvertLowI32x4S) => we::Instruction::F64x2ConvertLowI32x4S,
+        Instr::Unary(UnaryOp::F64x2ConvertLowI32x4U) => we::Instruction::F64x2ConvertLowI32x4U,
+        Instr::Unary(UnaryOp::F32x4DemoteF64x2Zero) => we::Instruction::F32x4DemoteF64x2Zero,
+        Instr::Unary(UnaryOp::F64x2PromoteLowF32x4) => we::Instruction::F64x2PromoteLowF32x4,
 
         Instr::Binary(BinaryOp::I32Eq) => we::Instruction::I32Eq,
         Instr::Binary(BinaryOp::I32Ne) => we::Instruction::I32Ne,
@@ -668,6 +1246,431 @@ fn encode_instruction(
         Instr::Binary(BinaryOp::F64Min) => we::Instruction::F64Min,
         Instr::Binary(BinaryOp::F64Max) => we::Instruction::F64Max,
         Instr::Binary(BinaryOp::F64Copysign) => we::Instruction::F64Copysign,
+        Instr::Binary(BinaryOp::I8x16Eq) => we::Instruction::I8x16Eq,
+        Instr::Binary(BinaryOp::I8x16Ne) => we::Instruction::I8x16Ne,
+        Instr::Binary(BinaryOp::I8x16LtS) => we::Instruction::I8x16LtS,
+        Instr::Binary(BinaryOp::I8x16LtU) => we::Instruction::I8x16LtU,
+        Instr::Binary(BinaryOp::I8x16GtS) => we::Instruction::I8x16GtS,
+        Instr::Binary(BinaryOp::I8x16GtU) => we::Instruction::I8x16GtU,
+        Instr::Binary(BinaryOp::I8x16LeS) => we::Instruction::I8x16LeS,
+        Instr::Binary(BinaryOp::I8x16LeU) => we::Instruction::I8x16LeU,
+        Instr::Binary(BinaryOp::I8x16GeS) => we::Instruction::I8x16GeS,
+        Instr::Binary(BinaryOp::I8x16GeU) => we::Instruction::I8x16GeU,
+        Instr::Binary(BinaryOp::I16x8Eq) => we::Instruction::I16x8Eq,
+        Instr::Binary(BinaryOp::I16x8Ne) => we::Instruction::I16x8Ne,
+        Instr::Binary(BinaryOp::I16x8LtS) => we::Instruction::I16x8LtS,
+        Instr::Binary(BinaryOp::I16x8LtU) => we::Instruction::I16x8LtU,
+        Instr::Binary(BinaryOp::I16x8GtS) => we::Instruction::I16x8GtS,
+        Instr::Binary(BinaryOp::I16x8GtU) => we::Instruction::I16x8GtU,
+        Instr::Binary(BinaryOp::I16x8LeS) => we::Instruction::I16x8LeS,
+        Instr::Binary(BinaryOp::I16x8LeU) => we::Instruction::I16x8LeU,
+        Instr::Binary(BinaryOp::I16x8GeS) => we::Instruction::I16x8GeS,
+        Instr::Binary(BinaryOp::I16x8GeU) => we::Instruction::I16x8GeU,
+        Instr::Binary(BinaryOp::I32x4Eq) => we::Instruction::I32x4Eq,
+        Instr::Binary(BinaryOp::I32x4Ne) => we::Instruction::I32x4Ne,
+        Instr::Binary(BinaryOp::I32x4LtS) => we::Instruction::I32x4LtS,
+        Instr::Binary(BinaryOp::I32x4LtU) => we::Instruction::I32x4LtU,
+        Instr::Binary(BinaryOp::I32x4GtS) => we::Instruction::I32x4GtS,
+        Instr::Binary(BinaryOp::I32x4GtU) => we::Instruction::I32x4GtU,
+        Instr::Binary(BinaryOp::I32x4LeS) => we::Instruction::I32x4LeS,
+        Instr::Binary(BinaryOp::I32x4LeU) => we::Instruction::I32x4LeU,
+        Instr::Binary(BinaryOp::I32x4GeS) => we::Instruction::I32x4GeS,
+        Instr::Binary(BinaryOp::I32x4GeU) => we::Instruction::I32x4GeU,
+        Instr::Binary(BinaryOp::I64x2Eq) => we::Instruction::I64x2Eq,
+        Instr::Binary(BinaryOp::I64x2Ne) => we::Instruction::I64x2Ne,
+        Instr::Binary(BinaryOp::I64x2LtS) => we::Instruction::I64x2LtS,
+        Instr::Binary(BinaryOp::I64x2GtS) => we::Instruction::I64x2GtS,
+        Instr::Binary(BinaryOp::I64x2LeS) => we::Instruction::I64x2LeS,
+        Instr::Binary(BinaryOp::I64x2GeS) => we::Instruction::I64x2GeS,
+        Instr::Binary(BinaryOp::F32x4Eq) => we::Instruction::F32x4Eq,
+        Instr::Binary(BinaryOp::F32x4Ne) => we::Instruction::F32x4Ne,
+        Instr::Binary(BinaryOp::F32x4Lt) => we::Instruction::F32x4Lt,
+        Instr::Binary(BinaryOp::F32x4Gt) => we::Instruction::F32x4Gt,
+        Instr::Binary(BinaryOp::F32x4Le) => we::Instruction::F32x4Le,
+        Instr::Binary(BinaryOp::F32x4Ge) => we::Instruction::F32x4Ge,
+        Instr::Binary(BinaryOp::F64x2Eq) => we::Instruction::F64x2Eq,
+        Instr::Binary(BinaryOp::F64x2Ne) => we::Instruction::F64x2Ne,
+        Instr::Binary(BinaryOp::F64x2Lt) => we::Instruction::F64x2Lt,
+        Instr::Binary(BinaryOp::F64x2Gt) => we::Instruction::F64x2Gt,
+        Instr::Binary(BinaryOp::F64x2Le) => we::Instruction::F64x2Le,
+        Instr::Binary(BinaryOp::F64x2Ge) => we::Instruction::F64x2Ge,
+        Instr::Binary(BinaryOp::V128And) => we::Instruction::V128And,
+        Instr::Binary(BinaryOp::V128AndNot) => we::Instruction::V128AndNot,
+        Instr::Binary(BinaryOp::V128Or) => we::Instruction::V128Or,
+        Instr::Binary(BinaryOp::V128Xor) => we::Instruction::V128Xor,
+        Instr::Binary(BinaryOp::I8x16NarrowI16x8S) => we::Instruction::I8x16NarrowI16x8S,
+        Instr::Binary(BinaryOp::I8x16NarrowI16x8U) => we::Instruction::I8x16NarrowI16x8U,
+        Instr::Binary(BinaryOp::I8x16Shl) => we::Instruction::I8x16Shl,
+        Instr::Binary(BinaryOp::I8x16ShrS) => we::Instruction::I8x16ShrS,
+        Instr::Binary(BinaryOp::I8x16ShrU) => we::Instruction::I8x16ShrU,
+        Instr::Binary(BinaryOp::I8x16Add) => we::Instruction::I8x16Add,
+        Instr::Binary(BinaryOp::I8x16AddSatS) => we::Instruction::I8x16AddSatS,
+        Instr::Binary(BinaryOp::I8x16AddSatU) => we::Instruction::I8x16AddSatU,
+        Instr::Binary(BinaryOp::I8x16Sub) => we::Instruction::I8x16Sub,
+        Instr::Binary(BinaryOp::I8x16SubSatS) => we::Instruction::I8x16SubSatS,
+        Instr::Binary(BinaryOp::I8x16SubSatU) => we::Instruction::I8x16SubSatU,
+        Instr::Binary(BinaryOp::I8x16MinS) => we::Instruction::I8x16MinS,
+        Instr::Binary(BinaryOp::I8x16MinU) => we::Instruction::I8x16MinU,
+        Instr::Binary(BinaryOp::I8x16MaxS) => we::Instruction::I8x16MaxS,
+        Instr::Binary(BinaryOp::I8x16MaxU) => we::Instruction::I8x16MaxU,
+        Instr::Binary(BinaryOp::I8x16AvgrU) => we::Instruction::I8x16AvgrU,
+        Instr::Binary(BinaryOp::I8x16Swizzle) => we::Instruction::I8x16Swizzle,
+        Instr::Binary(BinaryOp::I16x8Q15MulrSatS) => we::Instruction::I16x8Q15MulrSatS,
+        Instr::Binary(BinaryOp::I16x8NarrowI32x4S) => we::Instruction::I16x8NarrowI32x4S,
+        Instr::Binary(BinaryOp::I16x8NarrowI32x4U) => we::Instruction::I16x8NarrowI32x4U,
+        Instr::Binary(BinaryOp::I16x8Shl) => we::Instruction::I16x8Shl,
+        Instr::Binary(BinaryOp::I16x8ShrS) => we::Instruction::I16x8ShrS,
+        Instr::Binary(BinaryOp::I16x8ShrU) => we::Instruction::I16x8ShrU,
+        Instr::Binary(BinaryOp::I16x8Add) => we::Instruction::I16x8Add,
+        Instr::Binary(BinaryOp::I16x8AddSatS) => we::Instruction::I16x8AddSatS,
+        Instr::Binary(BinaryOp::I16x8AddSatU) => we::Instruction::I16x8AddSatU,
+        Instr::Binary(BinaryOp::I16x8Sub) => we::Instruction::I16x8Sub,
+        Instr::Binary(BinaryOp::I16x8SubSatS) => we::Instruction::I16x8SubSatS,
+        Instr::Binary(BinaryOp::I16x8SubSatU) => we::Instruction::I16x8SubSatU,
+        Instr::Binary(BinaryOp::I16x8Mul) => we::Instruction::I16x8Mul,
+        Instr::Binary(BinaryOp::I16x8MinS) => we::Instruction::I16x8MinS,
+        Instr::Binary(BinaryOp::I16x8MinU) => we::Instruction::I16x8MinU,
+        Instr::Binary(BinaryOp::I16x8MaxS) => we::Instruction::I16x8MaxS,
+        Instr::Binary(BinaryOp::I16x8MaxU) => we::Instruction::I16x8MaxU,
+        Instr::Binary(BinaryOp::I16x8AvgrU) => we::Instruction::I16x8AvgrU,
+        Instr::Binary(BinaryOp::I16x8ExtMulLowI8x16S) => we::Instruction::I16x8ExtMulLowI8x16S,
+        Instr::Binary(BinaryOp::I16x8ExtMulHighI8x16S) => we::Instruction::I16x8ExtMulHighI8x16S,
+        Instr::Binary(BinaryOp::I16x8ExtMulLowI8x16U) => we::Instruction::I16x8ExtMulLowI8x16U,
+        Instr::Binary(BinaryOp::I16x8ExtMulHighI8x16U) => we::Instruction::I16x8ExtMulHighI8x16U,
+        Instr::Binary(BinaryOp::I32x4Shl) => we::Instruction::I32x4Shl,
+        Instr::Binary(BinaryOp::I32x4ShrS) => we::Instruction::I32x4ShrS,
+        Instr::Binary(BinaryOp::I32x4ShrU) => we::Instruction::I32x4ShrU,
+        Instr::Binary(BinaryOp::I32x4Add) => we::Instruction::I32x4Add,
+        Instr::Binary(BinaryOp::I32x4Sub) => we::Instruction::I32x4Sub,
+        Instr::Binary(BinaryOp::I32x4Mul) => we::Instruction::I32x4Mul,
+        Instr::Binary(BinaryOp::I32x4MinS) => we::Instruction::I32x4MinS,
+        Instr::Binary(BinaryOp::I32x4MinU) => we::Instruction::I32x4MinU,
+        Instr::Binary(BinaryOp::I32x4MaxS) => we::Instruction::I32x4MaxS,
+        Instr::Binary(BinaryOp::I32x4MaxU) => we::Instruction::I32x4MaxU,
+        Instr::Binary(BinaryOp::I32x4DotI16x8S) => we::Instruction::I32x4DotI16x8S,
+        Instr::Binary(BinaryOp::I32x4ExtMulLowI16x8S) => we::Instruction::I32x4ExtMulLowI16x8S,
+        Instr::Binary(BinaryOp::I32x4ExtMulHighI16x8S) => we::Instruction::I32x4ExtMulHighI16x8S,
+        Instr::Binary(BinaryOp::I32x4ExtMulLowI16x8U) => we::Instruction::I32x4ExtMulLowI16x8U,
+        Instr::Binary(BinaryOp::I32x4ExtMulHighI16x8U) => we::Instruction::I32x4ExtMulHighI16x8U,
+        Instr::Binary(BinaryOp::I64x2Shl) => we::Instruction::I64x2Shl,
+        Instr::Binary(BinaryOp::I64x2ShrS) => we::Instruction::I64x2ShrS,
+        Instr::Binary(BinaryOp::I64x2ShrU) => we::Instruction::I64x2ShrU,
+        Instr::Binary(BinaryOp::I64x2Add) => we::Instruction::I64x2Add,
+        Instr::Binary(BinaryOp::I64x2Sub) => we::Instruction::I64x2Sub,
+        Instr::Binary(BinaryOp::I64x2Mul) => we::Instruction::I64x2Mul,
+        Instr::Binary(BinaryOp::I64x2ExtMulLowI32x4S) => we::Instruction::I64x2ExtMulLowI32x4S,
+        Instr::Binary(BinaryOp::I64x2ExtMulHighI32x4S) => we::Instruction::I64x2ExtMulHighI32x4S,
+        Instr::Binary(BinaryOp::I64x2ExtMulLowI32x4U) => we::Instruction::I64x2ExtMulLowI32x4U,
+        Instr::Binary(BinaryOp::I64x2ExtMulHighI32x4U) => we::Instruction::I64x2ExtMulHighI32x4U,
+        Instr::Binary(BinaryOp::F32x4Add) => we::Instruction::F32x4Add,
+        Instr::Binary(BinaryOp::F32x4Sub) => we::Instruction::F32x4Sub,
+        Instr::Binary(BinaryOp::F32x4Mul) => we::Instruction::F32x4Mul,
+        Instr::Binary(BinaryOp::F32x4Div) => we::Instruction::F32x4Div,
+        Instr::Binary(BinaryOp::F32x4Min) => we::Instruction::F32x4Min,
+        Instr::Binary(BinaryOp::F32x4Max) => we::Instruction::F32x4Max,
+        Instr::Binary(BinaryOp::F32x4PMin) => we::Instruction::F32x4PMin,
+        Instr::Binary(BinaryOp::F32x4PMax) => we::Instruction::F32x4PMax,
+        Instr::Binary(BinaryOp::F64x2Add) => we::Instruction::F64x2Add,
+        Instr::Binary(BinaryOp::F64x2Sub) => we::Instruction::F64x2Sub,
+        Instr::Binary(BinaryOp::F64x2Mul) => we::Instruction::F64x2Mul,
+        Instr::Binary(BinaryOp::F64x2Div) => we::Instruction::F64x2Div,
+        Instr::Binary(BinaryOp::F64x2Min) => we::Instruction::F64x2Min,
+        Instr::Binary(BinaryOp::F64x2Max) => we::Instruction::F64x2Max,
+        Instr::Binary(BinaryOp::F64x2PMin) => we::Instruction::F64x2PMin,
+        Instr::Binary(BinaryOp::F64x2PMax) => we::Instruction::F64x2PMax,
+
+        Instr::LoadLane(LoadLaneOp::V128Load8Lane, memarg, lane) => {
+            we::Instruction::V128Load8Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::LoadLane(LoadLaneOp::V128Load16Lane, memarg, lane) => {
+            we::Instruction::V128Load16Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::LoadLane(LoadLaneOp::V128Load32Lane, memarg, lane) => {
+            we::Instruction::V128Load32Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::LoadLane(LoadLaneOp::V128Load64Lane, memarg, lane) => {
+            we::Instruction::V128Load64Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::StoreLane(StoreLaneOp::V128Store8Lane, memarg, lane) => {
+            we::Instruction::V128Store8Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::StoreLane(StoreLaneOp::V128Store16Lane, memarg, lane) => {
+            we::Instruction::V128Store16Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::StoreLane(StoreLaneOp::V128Store32Lane, memarg, lane) => {
+            we::Instruction::V128Store32Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::StoreLane(StoreLaneOp::V128Store64Lane, memarg, lane) => {
+            we::Instruction::V128Store64Lane {
+                memarg: encode_memarg(memarg, state)?,
+                lane,
+            }
+        }
+        Instr::ExtractLane(ExtractLaneOp::I8x16ExtractLaneS, lane) => {
+            we::Instruction::I8x16ExtractLaneS(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::I8x16ExtractLaneU, lane) => {
+            we::Instruction::I8x16ExtractLaneU(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::I16x8ExtractLaneS, lane) => {
+            we::Instruction::I16x8ExtractLaneS(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::I16x8ExtractLaneU, lane) => {
+            we::Instruction::I16x8ExtractLaneU(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::I32x4ExtractLane, lane) => {
+            we::Instruction::I32x4ExtractLane(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::I64x2ExtractLane, lane) => {
+            we::Instruction::I64x2ExtractLane(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::F32x4ExtractLane, lane) => {
+            we::Instruction::F32x4ExtractLane(lane)
+        }
+        Instr::ExtractLane(ExtractLaneOp::F64x2ExtractLane, lane) => {
+            we::Instruction::F64x2ExtractLane(lane)
+        }
+        Instr::ReplaceLane(ReplaceLaneOp::I8x16ReplaceLane, lane) => {
+            we::Instruction::I8x16ReplaceLane(lane)
+        }
+        Instr::ReplaceLane(ReplaceLaneOp::I16x8ReplaceLane, lane) => {
+            we::Instruction::I16x8ReplaceLane(lane)
+        }
+        Instr::ReplaceLane(ReplaceLaneOp::I32x4ReplaceLane, lane) => {
+            we::Instruction::I32x4ReplaceLane(lane)
+        }
+        Instr::ReplaceLane(ReplaceLaneOp::I64x2ReplaceLane, lane) => {
+            we::Instruction::I64x2ReplaceLane(lane)
+        }
+        Instr::ReplaceLane(ReplaceLaneOp::F32x4ReplaceLane, lane) => {
+            we::Instruction::F32x4ReplaceLane(lane)
+        }
+        Instr::ReplaceLane(ReplaceLaneOp::F64x2ReplaceLane, lane) => {
+            we::Instruction::F64x2ReplaceLane(lane)
+        }
+        Instr::I8x16Shuffle(lanes) => we::Instruction::I8x16Shuffle(lanes),
+        Instr::V128Bitselect => we::Instruction::V128Bitselect,
+
+        Instr::AtomicLoad(AtomicLoadOp::I32AtomicLoad, memarg) => {
+            we::Instruction::I32AtomicLoad(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicLoad(AtomicLoadOp::I64AtomicLoad, memarg) => {
+            we::Instruction::I64AtomicLoad(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicLoad(AtomicLoadOp::I32AtomicLoad8U, memarg) => {
+            we::Instruction::I32AtomicLoad8U(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicLoad(AtomicLoadOp::I32AtomicLoad16U, memarg) => {
+            we::Instruction::I32AtomicLoad16U(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicLoad(AtomicLoadOp::I64AtomicLoad8U, memarg) => {
+            we::Instruction::I64AtomicLoad8U(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicLoad(AtomicLoadOp::I64AtomicLoad16U, memarg) => {
+            we::Instruction::I64AtomicLoad16U(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicLoad(AtomicLoadOp::I64AtomicLoad32U, memarg) => {
+            we::Instruction::I64AtomicLoad32U(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I32AtomicStore, memarg) => {
+            we::Instruction::I32AtomicStore(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I64AtomicStore, memarg) => {
+            we::Instruction::I64AtomicStore(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I32AtomicStore8, memarg) => {
+            we::Instruction::I32AtomicStore8(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I32AtomicStore16, memarg) => {
+            we::Instruction::I32AtomicStore16(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I64AtomicStore8, memarg) => {
+            we::Instruction::I64AtomicStore8(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I64AtomicStore16, memarg) => {
+            we::Instruction::I64AtomicStore16(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicStore(AtomicStoreOp::I64AtomicStore32, memarg) => {
+            we::Instruction::I64AtomicStore32(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmwAdd, memarg) => {
+            we::Instruction::I32AtomicRmwAdd(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmwAdd, memarg) => {
+            we::Instruction::I64AtomicRmwAdd(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw8AddU, memarg) => {
+            we::Instruction::I32AtomicRmw8AddU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw16AddU, memarg) => {
+            we::Instruction::I32AtomicRmw16AddU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw8AddU, memarg) => {
+            we::Instruction::I64AtomicRmw8AddU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw16AddU, memarg) => {
+            we::Instruction::I64AtomicRmw16AddU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw32AddU, memarg) => {
+            we::Instruction::I64AtomicRmw32AddU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmwSub, memarg) => {
+            we::Instruction::I32AtomicRmwSub(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmwSub, memarg) => {
+            we::Instruction::I64AtomicRmwSub(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw8SubU, memarg) => {
+            we::Instruction::I32AtomicRmw8SubU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw16SubU, memarg) => {
+            we::Instruction::I32AtomicRmw16SubU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw8SubU, memarg) => {
+            we::Instruction::I64AtomicRmw8SubU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw16SubU, memarg) => {
+            we::Instruction::I64AtomicRmw16SubU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw32SubU, memarg) => {
+            we::Instruction::I64AtomicRmw32SubU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmwAnd, memarg) => {
+            we::Instruction::I32AtomicRmwAnd(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmwAnd, memarg) => {
+            we::Instruction::I64AtomicRmwAnd(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw8AndU, memarg) => {
+            we::Instruction::I32AtomicRmw8AndU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw16AndU, memarg) => {
+            we::Instruction::I32AtomicRmw16AndU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw8AndU, memarg) => {
+            we::Instruction::I64AtomicRmw8AndU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw16AndU, memarg) => {
+            we::Instruction::I64AtomicRmw16AndU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw32AndU, memarg) => {
+            we::Instruction::I64AtomicRmw32AndU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmwOr, memarg) => {
+            we::Instruction::I32AtomicRmwOr(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmwOr, memarg) => {
+            we::Instruction::I64AtomicRmwOr(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw8OrU, memarg) => {
+            we::Instruction::I32AtomicRmw8OrU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw16OrU, memarg) => {
+            we::Instruction::I32AtomicRmw16OrU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw8OrU, memarg) => {
+            we::Instruction::I64AtomicRmw8OrU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw16OrU, memarg) => {
+            we::Instruction::I64AtomicRmw16OrU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw32OrU, memarg) => {
+            we::Instruction::I64AtomicRmw32OrU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmwXor, memarg) => {
+            we::Instruction::I32AtomicRmwXor(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmwXor, memarg) => {
+            we::Instruction::I64AtomicRmwXor(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw8XorU, memarg) => {
+            we::Instruction::I32AtomicRmw8XorU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw16XorU, memarg) => {
+            we::Instruction::I32AtomicRmw16XorU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw8XorU, memarg) => {
+            we::Instruction::I64AtomicRmw8XorU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw16XorU, memarg) => {
+            we::Instruction::I64AtomicRmw16XorU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw32XorU, memarg) => {
+            we::Instruction::I64AtomicRmw32XorU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmwXchg, memarg) => {
+            we::Instruction::I32AtomicRmwXchg(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmwXchg, memarg) => {
+            we::Instruction::I64AtomicRmwXchg(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw8XchgU, memarg) => {
+            we::Instruction::I32AtomicRmw8XchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I32AtomicRmw16XchgU, memarg) => {
+            we::Instruction::I32AtomicRmw16XchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw8XchgU, memarg) => {
+            we::Instruction::I64AtomicRmw8XchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw16XchgU, memarg) => {
+            we::Instruction::I64AtomicRmw16XchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicRmw(AtomicRmwOp::I64AtomicRmw32XchgU, memarg) => {
+            we::Instruction::I64AtomicRmw32XchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I32AtomicRmwCmpxchg, memarg) => {
+            we::Instruction::I32AtomicRmwCmpxchg(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I64AtomicRmwCmpxchg, memarg) => {
+            we::Instruction::I64AtomicRmwCmpxchg(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I32AtomicRmw8CmpxchgU, memarg) => {
+            we::Instruction::I32AtomicRmw8CmpxchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I32AtomicRmw16CmpxchgU, memarg) => {
+            we::Instruction::I32AtomicRmw16CmpxchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I64AtomicRmw8CmpxchgU, memarg) => {
+            we::Instruction::I64AtomicRmw8CmpxchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I64AtomicRmw16CmpxchgU, memarg) => {
+            we::Instruction::I64AtomicRmw16CmpxchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicCmpxchg(AtomicCmpxchgOp::I64AtomicRmw32CmpxchgU, memarg) => {
+            we::Instruction::I64AtomicRmw32CmpxchgU(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicWaitNotify(AtomicWaitNotifyOp::MemoryAtomicNotify, memarg) => {
+            we::Instruction::MemoryAtomicNotify(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicWaitNotify(AtomicWaitNotifyOp::MemoryAtomicWait32, memarg) => {
+            we::Instruction::MemoryAtomicWait32(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicWaitNotify(AtomicWaitNotifyOp::MemoryAtomicWait64, memarg) => {
+            we::Instruction::MemoryAtomicWait64(encode_memarg(memarg, state)?)
+        }
+        Instr::AtomicFence => we::Instruction::AtomicFence,
     })
 }
 
@@ -680,6 +1683,7 @@ fn encode_names(
     // lazily initialize on access. Then, write them only if they are not `None`.
     let mut functions_subsection: Option<we::NameMap> = None;
     let mut locals_subsection: Option<we::IndirectNameMap> = None;
+    let mut labels_subsection: Option<we::IndirectNameMap> = None;
     for (hl_function_idx, function) in module.functions() {
         let ll_function_idx = state.map_function_idx(hl_function_idx)?.to_u32();
 
@@ -702,8 +1706,69 @@ fn encode_names(
                 .get_or_insert_with(Default::default)
                 .append(ll_function_idx, &local_names);
         }
+
+        let label_names = function.code().and_then(|code| {
+            name_map(
+                code.label_names
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(label_idx, name)| Some((label_idx as u32, name.as_deref()?))),
+            )
+        });
+        if let Some(label_names) = label_names {
+            labels_subsection
+                .get_or_insert_with(Default::default)
+                .append(ll_function_idx, &label_names);
+        }
     }
 
+    // Subsections of the extended name section.
+    // Types that are not used anywhere in the module are not in the type index space, so their
+    // names are dropped.
+    let types_idx = state.types_idx.read().unwrap();
+    let types_subsection = name_map(
+        module
+            .type_names
+            .iter()
+            .filter_map(|(type_, name)| Some((types_idx.get(type_)?.to_u32(), name.as_str()))),
+    );
+    let tables_subsection = name_map(
+        module
+            .tables()
+            .filter_map(|(idx, table)| Some((idx, table.name.as_deref()?)))
+            .map(|(idx, name)| Ok((state.map_table_idx(idx)?.to_u32(), name)))
+            .collect::<Result<Vec<_>, EncodeError>>()?,
+    );
+    let memories_subsection = name_map(
+        module
+            .memories()
+            .filter_map(|(idx, memory)| Some((idx, memory.name.as_deref()?)))
+            .map(|(idx, name)| Ok((state.map_memory_idx(idx)?.to_u32(), name)))
+            .collect::<Result<Vec<_>, EncodeError>>()?,
+    );
+    let globals_subsection = name_map(
+        module
+            .globals()
+            .filter_map(|(idx, global)| Some((idx, global.name.as_deref()?)))
+            .map(|(idx, name)| Ok((state.map_global_idx(idx)?.to_u32(), name)))
+            .collect::<Result<Vec<_>, EncodeError>>()?,
+    );
+    // Element and data segments are encoded in the order of the AST, so no mapping is necessary.
+    let elements_subsection = name_map(
+        module
+            .elements
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, element)| Some((idx as u32, element.name.as_deref()?))),
+    );
+    let data_subsection = name_map(
+        module
+            .data
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, data)| Some((idx as u32, data.name.as_deref()?))),
+    );
+
     let mut name_section: Option<we::NameSection> = None;
     if let Some(module_name) = &module.name {
         name_section
@@ -720,10 +1785,60 @@ fn encode_names(
             .get_or_insert_with(Default::default)
             .locals(locals_subsection);
     }
+    // The order of subsections is prescribed by the (extended) name section specification.
+    if let Some(labels_subsection) = &labels_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .labels(labels_subsection);
+    }
+    if let Some(types_subsection) = &types_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .types(types_subsection);
+    }
+    if let Some(tables_subsection) = &tables_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .tables(tables_subsection);
+    }
+    if let Some(memories_subsection) = &memories_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .memories(memories_subsection);
+    }
+    if let Some(globals_subsection) = &globals_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .globals(globals_subsection);
+    }
+    if let Some(elements_subsection) = &elements_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .elements(elements_subsection);
+    }
+    if let Some(data_subsection) = &data_subsection {
+        name_section
+            .get_or_insert_with(Default::default)
+            .data(data_subsection);
+    }
 
     Ok(name_section)
 }
 
+/// Name map with entries sorted by index (as required by the name section), or `None` if empty.
+fn name_map<'a>(names: impl IntoIterator<Item = (u32, &'a str)>) -> Option<we::NameMap> {
+    let mut names: Vec<_> = names.into_iter().collect();
+    if names.is_empty() {
+        return None;
+    }
+    names.sort_unstable_by_key(|(idx, _name)| *idx);
+    let mut name_map = we::NameMap::new();
+    for (idx, name) in names {
+        name_map.append(idx, name);
+    }
+    Some(name_map)
+}
+
 fn encode_block_type(func_or_block_ty: FunctionType, state: &EncodeState) -> we::BlockType {
     match (func_or_block_ty.inputs(), func_or_block_ty.results()) {
         // Prefer the more compact inline encoding for Wasm MVP block types.
@@ -736,6 +1851,14 @@ fn encode_block_type(func_or_block_ty: FunctionType, state: &EncodeState) -> we:
     }
 }
 
+fn encode_memarg(hl_memarg: Memarg, state: &EncodeState) -> Result<we::MemArg, EncodeError> {
+    Ok(we::MemArg {
+        offset: hl_memarg.offset,
+        align: hl_memarg.alignment_exp.into(),
+        memory_index: state.map_memory_idx(hl_memarg.memory)?.to_u32(),
+    })
+}
+
 impl From<GlobalType> for we::GlobalType {
     fn from(hl_global_type: GlobalType) -> Self {
         Self {
@@ -748,12 +1871,19 @@ impl From<GlobalType> for we::GlobalType {
     }
 }
 
-impl From<Limits> for we::TableType {
-    fn from(limits: Limits) -> Self {
+impl From<&Table> for we::TableType {
+    fn from(table: &Table) -> Self {
         Self {
-            element_type: we::ValType::FuncRef,
-            minimum: limits.initial_size,
-            maximum: limits.max_size,
+            element_type: ValType::from(table.elem_type).into(),
+            minimum: table
+                .limits
+                .initial_size
+                .try_into()
+                .expect("table sizes are always 32-bit"),
+            maximum: table
+                .limits
+                .max_size
+                .map(|u| u.try_into().expect("table sizes are always 32-bit")),
         }
     }
 }
@@ -761,10 +1891,10 @@ impl From<Limits> for we::TableType {
 impl From<Limits> for we::MemoryType {
     fn from(limits: Limits) -> Self {
         Self {
-            minimum: limits.initial_size.into(),
-            maximum: limits.max_size.map(|u32| u32.into()),
-            memory64: false,
-            shared: false,
+            minimum: limits.initial_size,
+            maximum: limits.max_size,
+            memory64: limits.memory64,
+            shared: limits.shared,
         }
     }
 }
@@ -777,16 +1907,9 @@ impl From<ValType> for we::ValType {
             I64 => we::ValType::I64,
             F32 => we::ValType::F32,
             F64 => we::ValType::F64,
-        }
-    }
-}
-
-impl From<Memarg> for we::MemArg {
-    fn from(hl_memarg: Memarg) -> Self {
-        Self {
-            offset: hl_memarg.offset.into(),
-            align: hl_memarg.alignment_exp.into(),
-            memory_index: 0,
+            FuncRef => we::ValType::FuncRef,
+            ExternRef => we::ValType::ExternRef,
+            V128 => we::ValType::V128,
         }
     }
 }
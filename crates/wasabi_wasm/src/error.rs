@@ -1,6 +1,17 @@
 //! Typed errors and warnings when parsing/encoding of modules.
 
+use std::fmt;
+
 use crate::extensions::WasmExtension;
+use crate::Data;
+use crate::Element;
+use crate::Function;
+use crate::Global;
+use crate::Idx;
+use crate::Instr;
+use crate::Memory;
+use crate::Table;
+use crate::Tag;
 
 /// Used only for errors (not recoverable, i.e., parsing stops and does not return an AST).
 #[derive(Debug, thiserror::Error)]
@@ -49,6 +60,9 @@ pub enum ParseIssue {
         extension: WasmExtension,
     },
 
+    #[error("error parsing DWARF debug info: {0}")]
+    Dwarf(#[from] gimli::Error),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
@@ -81,6 +95,7 @@ impl ParseIssue {
             ParseIssue::Message { offset, .. } => Some(*offset),
             ParseIssue::Index { offset, .. } => Some(*offset),
             ParseIssue::Unsupported { offset, .. } => Some(*offset),
+            ParseIssue::Dwarf(_) => None,
             ParseIssue::Io(_) => None,
         }
     }
@@ -107,6 +122,113 @@ where
     }
 }
 
+/// Error when parsing the WebAssembly text format, with the position of the offending token.
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "error parsing WebAssembly text at line {}, column {}: {}",
+    line,
+    column,
+    message
+)]
+pub struct WatParseError {
+    /// Both line and column are 1-based, the column is counted in characters (not bytes).
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl WatParseError {
+    /// Computes line and column from the byte `offset` into `text`.
+    pub fn new(text: &str, offset: usize, message: impl Into<String>) -> Self {
+        let before = &text[..offset.min(text.len())];
+        let line_start = before.rfind('\n').map(|newline| newline + 1).unwrap_or(0);
+        WatParseError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+}
+
+/// Error when validating a module, see `Module::validate`.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("invalid WebAssembly module, {}: {}", location, message)]
+pub struct ValidationError {
+    pub location: ValidationLocation,
+    pub message: String,
+}
+
+/// Where in the module a validation error was found.
+/// Indices are those of the AST, not of the binary (where imported items come first).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ValidationLocation {
+    Function(Idx<Function>),
+    /// Instruction in the body of a function.
+    Instr(Idx<Function>, Idx<Instr>),
+    Global(Idx<Global>),
+    /// Instruction in the initialization expression of a global.
+    GlobalInit(Idx<Global>, Idx<Instr>),
+    Table(Idx<Table>),
+    Memory(Idx<Memory>),
+    Tag(Idx<Tag>),
+    Element(Idx<Element>),
+    /// Instruction in the offset expression of an element segment.
+    ElementOffset(Idx<Element>, Idx<Instr>),
+    /// The n-th item of an element segment, and for expressions, the instruction in it.
+    ElementItem(Idx<Element>, usize, Option<Idx<Instr>>),
+    Data(Idx<Data>),
+    /// Instruction in the offset expression of a data segment.
+    DataOffset(Idx<Data>, Idx<Instr>),
+    Start,
+}
+
+impl fmt::Display for ValidationLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ValidationLocation::*;
+        match *self {
+            Function(function) => write!(f, "function #{}", function.to_usize()),
+            Instr(function, instr) => write!(
+                f,
+                "function #{}, instruction #{}",
+                function.to_usize(),
+                instr.to_usize()
+            ),
+            Global(global) => write!(f, "global #{}", global.to_usize()),
+            GlobalInit(global, instr) => write!(
+                f,
+                "global #{}, init expression instruction #{}",
+                global.to_usize(),
+                instr.to_usize()
+            ),
+            Table(table) => write!(f, "table #{}", table.to_usize()),
+            Memory(memory) => write!(f, "memory #{}", memory.to_usize()),
+            Tag(tag) => write!(f, "tag #{}", tag.to_usize()),
+            Element(element) => write!(f, "element segment #{}", element.to_usize()),
+            ElementOffset(element, instr) => write!(
+                f,
+                "element segment #{}, offset expression instruction #{}",
+                element.to_usize(),
+                instr.to_usize()
+            ),
+            ElementItem(element, item, instr) => {
+                write!(f, "element segment #{}, item #{item}", element.to_usize())?;
+                if let Some(instr) = instr {
+                    write!(f, ", instruction #{}", instr.to_usize())?;
+                }
+                Ok(())
+            }
+            Data(data) => write!(f, "data segment #{}", data.to_usize()),
+            DataOffset(data, instr) => write!(
+                f,
+                "data segment #{}, offset expression instruction #{}",
+                data.to_usize(),
+                instr.to_usize()
+            ),
+            Start => f.write_str("start function"),
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
 pub struct EncodeError(
@@ -125,6 +247,15 @@ pub enum EncodeErrorInner {
         index_space: &'static str,
     },
 
+    #[error("error decoding lazily parsed function body: {}", .0)]
+    LazyBody(#[from] ParseError),
+
+    #[error("error converting DWARF debug info: {}", .0)]
+    DwarfConvert(#[from] gimli::write::ConvertError),
+
+    #[error("error writing DWARF debug info: {}", .0)]
+    DwarfWrite(#[from] gimli::write::Error),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
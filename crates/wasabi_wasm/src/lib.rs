@@ -9,9 +9,24 @@ pub use crate::error::*;
 
 pub mod types;
 
+pub mod nested;
+
+pub mod cfg;
+
+mod call_graph;
+pub use crate::call_graph::CallEdge;
+pub use crate::call_graph::CallGraph;
+pub use crate::call_graph::CallKind;
+mod debug_info;
+pub use crate::debug_info::DebugInfo;
 mod encode;
 mod extensions;
+pub use crate::extensions::WasmExtension;
 mod parse;
+pub use crate::parse::ParseOptions;
+mod parse_wat;
+mod print_wat;
+mod validate;
 
 #[cfg(test)]
 mod tests;
@@ -25,6 +40,6 @@ static GLOBAL: MiMalloc = MiMalloc;
 
 /*
  TODO WHEN CONTINUING
- - support multi-table, multi-memory because they are anyway pretty much supported (and make for less special cases)
  - make AST blocks nested, remove end/else opcodes
+   (see `nested` for a nested representation, which is not yet used in `Module` itself)
 */
@@ -0,0 +1,454 @@
+//! Nested representation of instruction sequences, as an alternative to the flat `Vec<Instr>` in
+//! `Code::body`:
+//!  - Structured control-flow instructions (`block`, `loop`, `if`, and `try`) contain their bodies,
+//!    so there are no `else`, `end`, `catch`, `catch_all`, or `delegate` instructions, and
+//!    transformations do not have to track the nesting of blocks manually.
+//!  - Every body has an optional _terminator_, i.e., an instruction that unconditionally transfers
+//!    control somewhere else (`unreachable`, `br`, `br_table`, `return`, etc.), if this is the
+//!    last instruction in the body.
+//!
+//! The conversion from and to the flat representation is lossless, i.e.,
+//! `Body::from_flat(instrs)?.to_flat() == instrs` for every well-nested instruction sequence.
+//! In particular, unreachable code after a terminator is kept: In that case, the terminator is a
+//! regular `NestedInstr::Simple` instruction in the middle of the body, followed by the dead code.
+//!
+//! Labels of branch instructions are unchanged, i.e., they are still relative to the enclosing
+//! blocks, just as in the flat representation.
+
+use crate::Function;
+use crate::FunctionType;
+use crate::Idx;
+use crate::Instr;
+use crate::Label;
+use crate::Table;
+use crate::Tag;
+
+/// A sequence of (nested) instructions, optionally ended by a terminator instruction.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Body {
+    pub instrs: Vec<NestedInstr>,
+    pub terminator: Option<Terminator>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum NestedInstr {
+    Block(FunctionType, Body),
+    Loop(FunctionType, Body),
+    /// The else body is `None` if there is no `else` instruction at all, and an empty body for an
+    /// `else` directly followed by `end` (which is different in the binary format).
+    If(FunctionType, Body, Option<Body>),
+    // Exception handling extension.
+    Try(FunctionType, Body, TryHandlers),
+    /// All other instructions, which do not contain bodies themselves.
+    /// Must not be one of the structured control-flow instructions (`block`, `else`, `end`, etc.).
+    Simple(Instr),
+}
+
+/// What happens to exceptions that are thrown in the body of a `try` block.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TryHandlers {
+    /// `catch` and `catch_all` handlers, in the order in which they appear.
+    /// The tag is `None` for `catch_all`.
+    Catch(Vec<(Option<Idx<Tag>>, Body)>),
+    /// `delegate` to the block with the given label.
+    Delegate(Label),
+}
+
+/// Instructions that unconditionally transfer control flow somewhere else, i.e., the subsequent
+/// instructions in the same body are never executed.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Terminator {
+    Unreachable,
+    Br(Label),
+    BrTable { table: Box<[Label]>, default: Label },
+    Return,
+
+    // Exception handling extension.
+    Throw(Idx<Tag>),
+    Rethrow(Label),
+
+    // Tail calls extension.
+    ReturnCall(Idx<Function>),
+    ReturnCallIndirect(FunctionType, Idx<Table>),
+}
+
+impl TryFrom<Instr> for Terminator {
+    /// Returns back the instruction if it is not a terminator.
+    type Error = Instr;
+
+    fn try_from(instr: Instr) -> Result<Self, Self::Error> {
+        Ok(match instr {
+            Instr::Unreachable => Terminator::Unreachable,
+            Instr::Br(label) => Terminator::Br(label),
+            Instr::BrTable { table, default } => Terminator::BrTable { table, default },
+            Instr::Return => Terminator::Return,
+            Instr::Throw(tag_idx) => Terminator::Throw(tag_idx),
+            Instr::Rethrow(label) => Terminator::Rethrow(label),
+            Instr::ReturnCall(func_idx) => Terminator::ReturnCall(func_idx),
+            Instr::ReturnCallIndirect(func_ty, table_idx) => {
+                Terminator::ReturnCallIndirect(func_ty, table_idx)
+            }
+            instr => return Err(instr),
+        })
+    }
+}
+
+impl From<Terminator> for Instr {
+    fn from(terminator: Terminator) -> Self {
+        match terminator {
+            Terminator::Unreachable => Instr::Unreachable,
+            Terminator::Br(label) => Instr::Br(label),
+            Terminator::BrTable { table, default } => Instr::BrTable { table, default },
+            Terminator::Return => Instr::Return,
+            Terminator::Throw(tag_idx) => Instr::Throw(tag_idx),
+            Terminator::Rethrow(label) => Instr::Rethrow(label),
+            Terminator::ReturnCall(func_idx) => Instr::ReturnCall(func_idx),
+            Terminator::ReturnCallIndirect(func_ty, table_idx) => {
+                Instr::ReturnCallIndirect(func_ty, table_idx)
+            }
+        }
+    }
+}
+
+/// Error when converting a flat instruction sequence that is not properly nested, e.g., with an
+/// `else` without matching `if`, or a missing `end`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid nesting at instruction #{}: {message}", .instruction_idx.to_usize())]
+pub struct NestingError {
+    pub message: &'static str,
+    pub instruction_idx: Idx<Instr>,
+}
+
+/// Blocks that are begun but not yet ended during conversion from the flat representation.
+enum OpenBlock {
+    Function,
+    Block(FunctionType),
+    Loop(FunctionType),
+    If(FunctionType),
+    Else(FunctionType, Body),
+    Try(FunctionType),
+    Catch {
+        type_: FunctionType,
+        try_body: Body,
+        previous_handlers: Vec<(Option<Idx<Tag>>, Body)>,
+        tag: Option<Idx<Tag>>,
+    },
+}
+
+impl Body {
+    pub fn new(instrs: Vec<NestedInstr>, terminator: Option<Terminator>) -> Self {
+        Body { instrs, terminator }
+    }
+
+    /// Converts a flat instruction sequence, such as a function body or a constant expression, to
+    /// the nested representation. The instruction sequence must be ended by an `end` instruction.
+    pub fn from_flat(instrs: impl IntoIterator<Item = Instr>) -> Result<Self, NestingError> {
+        use crate::Instr::*;
+
+        // Use an explicit stack instead of recursion, since real-world code can be nested very
+        // deeply (e.g., thousands of blocks for a single `br_table`).
+        let mut open_blocks = vec![(OpenBlock::Function, Vec::new())];
+        let mut function_body = None;
+        let mut instr_count = 0usize;
+        for (instr_idx, instr) in instrs.into_iter().enumerate() {
+            instr_count += 1;
+            let error = |message| NestingError {
+                message,
+                instruction_idx: instr_idx.into(),
+            };
+
+            let (open_block, nested_instrs) = open_blocks
+                .last_mut()
+                .ok_or_else(|| error("instruction after the final end"))?;
+            match instr {
+                Block(type_) => open_blocks.push((OpenBlock::Block(type_), Vec::new())),
+                Loop(type_) => open_blocks.push((OpenBlock::Loop(type_), Vec::new())),
+                If(type_) => open_blocks.push((OpenBlock::If(type_), Vec::new())),
+                Try(type_) => open_blocks.push((OpenBlock::Try(type_), Vec::new())),
+                Else => match *open_block {
+                    OpenBlock::If(type_) => {
+                        let if_body = Body::from_nested_instrs(std::mem::take(nested_instrs));
+                        *open_block = OpenBlock::Else(type_, if_body);
+                    }
+                    _ => return Err(error("else without matching if")),
+                },
+                Catch(_) | CatchAll => {
+                    let tag = match instr {
+                        Catch(tag_idx) => Some(tag_idx),
+                        _ => None,
+                    };
+                    let body = Body::from_nested_instrs(std::mem::take(nested_instrs));
+                    let new_open_block = match std::mem::replace(open_block, OpenBlock::Function) {
+                        OpenBlock::Try(type_) => OpenBlock::Catch {
+                            type_,
+                            try_body: body,
+                            previous_handlers: Vec::new(),
+                            tag,
+                        },
+                        OpenBlock::Catch {
+                            type_,
+                            try_body,
+                            mut previous_handlers,
+                            tag: previous_tag,
+                        } => {
+                            previous_handlers.push((previous_tag, body));
+                            OpenBlock::Catch {
+                                type_,
+                                try_body,
+                                previous_handlers,
+                                tag,
+                            }
+                        }
+                        _ => return Err(error("catch without matching try")),
+                    };
+                    *open_block = new_open_block;
+                }
+                End | Delegate(_) => {
+                    let (open_block, nested_instrs) =
+                        open_blocks.pop().expect("checked non-empty above");
+                    let body = Body::from_nested_instrs(nested_instrs);
+                    let nested_instr = match (open_block, instr) {
+                        (OpenBlock::Function, End) => {
+                            // Subsequent instructions produce an error, since the stack is empty.
+                            function_body = Some(body);
+                            continue;
+                        }
+                        (OpenBlock::Block(type_), End) => NestedInstr::Block(type_, body),
+                        (OpenBlock::Loop(type_), End) => NestedInstr::Loop(type_, body),
+                        (OpenBlock::If(type_), End) => NestedInstr::If(type_, body, None),
+                        (OpenBlock::Else(type_, if_body), End) => {
+                            NestedInstr::If(type_, if_body, Some(body))
+                        }
+                        (OpenBlock::Try(type_), End) => {
+                            NestedInstr::Try(type_, body, TryHandlers::Catch(Vec::new()))
+                        }
+                        (
+                            OpenBlock::Catch {
+                                type_,
+                                try_body,
+                                mut previous_handlers,
+                                tag,
+                            },
+                            End,
+                        ) => {
+                            previous_handlers.push((tag, body));
+                            NestedInstr::Try(type_, try_body, TryHandlers::Catch(previous_handlers))
+                        }
+                        (OpenBlock::Try(type_), Delegate(label)) => {
+                            NestedInstr::Try(type_, body, TryHandlers::Delegate(label))
+                        }
+                        _ => return Err(error("delegate without matching try")),
+                    };
+                    open_blocks
+                        .last_mut()
+                        .expect("function block is only popped by the final end")
+                        .1
+                        .push(nested_instr);
+                }
+                instr => nested_instrs.push(NestedInstr::Simple(instr)),
+            }
+        }
+        function_body.ok_or(NestingError {
+            message: "missing end",
+            instruction_idx: instr_count.into(),
+        })
+    }
+
+    /// Converts back to the flat representation, including the final `end` instruction.
+    pub fn to_flat(&self) -> Vec<Instr> {
+        let mut instrs = Vec::new();
+        self.append_flat(&mut instrs);
+        instrs.push(Instr::End);
+        instrs
+    }
+
+    /// Appends the instructions of this body (without a final `end`) to `instrs`.
+    fn append_flat(&self, instrs: &mut Vec<Instr>) {
+        for instr in &self.instrs {
+            match instr {
+                NestedInstr::Block(type_, body) => {
+                    instrs.push(Instr::Block(*type_));
+                    body.append_flat(instrs);
+                    instrs.push(Instr::End);
+                }
+                NestedInstr::Loop(type_, body) => {
+                    instrs.push(Instr::Loop(*type_));
+                    body.append_flat(instrs);
+                    instrs.push(Instr::End);
+                }
+                NestedInstr::If(type_, if_body, else_body) => {
+                    instrs.push(Instr::If(*type_));
+                    if_body.append_flat(instrs);
+                    if let Some(else_body) = else_body {
+                        instrs.push(Instr::Else);
+                        else_body.append_flat(instrs);
+                    }
+                    instrs.push(Instr::End);
+                }
+                NestedInstr::Try(type_, try_body, handlers) => {
+                    instrs.push(Instr::Try(*type_));
+                    try_body.append_flat(instrs);
+                    match handlers {
+                        TryHandlers::Catch(handlers) => {
+                            for (tag, handler_body) in handlers {
+                                instrs.push(match tag {
+                                    Some(tag_idx) => Instr::Catch(*tag_idx),
+                                    None => Instr::CatchAll,
+                                });
+                                handler_body.append_flat(instrs);
+                            }
+                            instrs.push(Instr::End);
+                        }
+                        TryHandlers::Delegate(label) => instrs.push(Instr::Delegate(*label)),
+                    }
+                }
+                NestedInstr::Simple(instr) => instrs.push(instr.clone()),
+            }
+        }
+        if let Some(terminator) = &self.terminator {
+            instrs.push(terminator.clone().into());
+        }
+    }
+
+    /// Splits off the last instruction as the terminator, if it is one.
+    fn from_nested_instrs(mut instrs: Vec<NestedInstr>) -> Self {
+        let terminator = match instrs.pop() {
+            Some(NestedInstr::Simple(instr)) => match Terminator::try_from(instr) {
+                Ok(terminator) => Some(terminator),
+                Err(instr) => {
+                    instrs.push(NestedInstr::Simple(instr));
+                    None
+                }
+            },
+            Some(instr) => {
+                instrs.push(instr);
+                None
+            }
+            None => None,
+        };
+        Body::new(instrs, terminator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FunctionType;
+    use crate::Idx;
+    use crate::Instr::*;
+    use crate::Label;
+    use crate::Val;
+    use crate::ValType::*;
+
+    use super::*;
+
+    #[test]
+    pub fn nested_blocks_and_terminators() {
+        let ty = FunctionType::new(&[], &[I32]);
+        let flat = vec![
+            Block(ty),
+            Const(Val::I32(0)),
+            If(FunctionType::empty()),
+            Br(Label::from(1u32)),
+            End,
+            Const(Val::I32(1)),
+            End,
+            Return,
+            End,
+        ];
+        let nested = Body::from_flat(flat.clone()).unwrap();
+        assert_eq!(
+            nested,
+            Body::new(
+                vec![NestedInstr::Block(
+                    ty,
+                    Body::new(
+                        vec![
+                            NestedInstr::Simple(Const(Val::I32(0))),
+                            NestedInstr::If(
+                                FunctionType::empty(),
+                                Body::new(vec![], Some(Terminator::Br(Label::from(1u32)))),
+                                None
+                            ),
+                            NestedInstr::Simple(Const(Val::I32(1))),
+                        ],
+                        None
+                    )
+                )],
+                Some(Terminator::Return)
+            )
+        );
+        assert_eq!(nested.to_flat(), flat);
+    }
+
+    #[test]
+    pub fn dead_code_and_empty_else_are_kept() {
+        let flat = vec![If(FunctionType::empty()), Else, End, Unreachable, Nop, End];
+        let nested = Body::from_flat(flat.clone()).unwrap();
+        assert_eq!(
+            nested.instrs[0],
+            NestedInstr::If(
+                FunctionType::empty(),
+                Body::default(),
+                Some(Body::default())
+            )
+        );
+        assert_eq!(nested.instrs[1], NestedInstr::Simple(Unreachable));
+        assert_eq!(nested.terminator, None);
+        assert_eq!(nested.to_flat(), flat);
+    }
+
+    #[test]
+    pub fn try_catch_delegate() {
+        let tag = Idx::from(0u32);
+        let flat = vec![
+            Try(FunctionType::empty()),
+            Try(FunctionType::empty()),
+            Throw(tag),
+            Delegate(Label::from(0u32)),
+            Catch(tag),
+            Drop,
+            CatchAll,
+            Rethrow(Label::from(0u32)),
+            End,
+            End,
+        ];
+        let nested = Body::from_flat(flat.clone()).unwrap();
+        match &nested.instrs[..] {
+            [NestedInstr::Try(_, try_body, TryHandlers::Catch(handlers))] => {
+                assert!(matches!(
+                    try_body.instrs[..],
+                    [NestedInstr::Try(_, _, TryHandlers::Delegate(_))]
+                ));
+                assert_eq!(handlers.len(), 2);
+                assert_eq!(handlers[0].0, Some(tag));
+                assert_eq!(handlers[1].0, None);
+                assert_eq!(
+                    handlers[1].1.terminator,
+                    Some(Terminator::Rethrow(Label::from(0u32)))
+                );
+            }
+            _ => panic!("expected single try instruction, got {nested:?}"),
+        }
+        assert_eq!(nested.to_flat(), flat);
+    }
+
+    #[test]
+    pub fn invalid_nesting() {
+        let error = |instrs: Vec<Instr>| Body::from_flat(instrs).unwrap_err().instruction_idx;
+        assert_eq!(error(vec![Nop]), Idx::from(1u32));
+        assert_eq!(error(vec![Else, End]), Idx::from(0u32));
+        assert_eq!(
+            error(vec![Block(FunctionType::empty()), CatchAll, End, End]),
+            Idx::from(1u32)
+        );
+        assert_eq!(
+            error(vec![
+                Block(FunctionType::empty()),
+                Delegate(Label::from(0u32)),
+                End
+            ]),
+            Idx::from(1u32)
+        );
+        assert_eq!(error(vec![End, Nop]), Idx::from(1u32));
+    }
+}
@@ -2,7 +2,8 @@
 //! Uses `wasmparser` crate for the actual low-level work.
 
 use std::convert::TryInto;
-use std::sync::RwLock;
+use std::io::{self, Read};
+use std::sync::{Arc, RwLock};
 
 use ordered_float::OrderedFloat;
 use rayon::prelude::*;
@@ -13,30 +14,178 @@ use wasmparser as wp;
 use crate::extensions::WasmExtension;
 use crate::*;
 
-// The streaming API of wasmparser is a bit cumbersome, so implement reading
-// from bytes fully resident in memory first.
-// TODO Add a second API from streaming sources, i.e., `io::Read` like here:
-// https://docs.rs/wasmparser/latest/wasmparser/struct.Parser.html#examples
-pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), ParseError> {
-    let mut warnings = Vec::new();
-
-    // The final module to return.
-    let mut module = Module::default();
-
-    // State during module parsing.
-    let mut types = Types::none();
-    let mut imported_function_count = 0;
-    let mut current_code_index = 0;
-    let mut section_offsets = Vec::with_capacity(16);
-    let mut function_offsets = Vec::new();
+/// Options for parsing the binary format, see `Module::from_bytes_with_options`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ParseOptions {
+    /// Keep function bodies in their binary encoding instead of decoding them, see
+    /// `Module::from_bytes_lazy`.
+    pub lazy: bool,
+    /// Record the offset of every instruction in `Offsets::instrs`, which is required for mapping
+    /// DWARF debug info or source maps. Off by default, since it costs a `usize` per instruction.
+    pub instr_offsets: bool,
+}
+
+/// Parses a module from bytes fully resident in memory.
+pub fn parse_module(
+    bytes: &[u8],
+    options: ParseOptions,
+) -> Result<(Module, Offsets, ParseWarnings), ParseError> {
+    let mut parser = ModuleParser::new(options);
+
     // Put the function bodies in their own vector, such that parallel processing of the
     // code section doesn't require synchronization on the shared `module` variable.
     let mut function_bodies = Vec::new();
-    let mut code_entries_count = 0;
-    let metadata = RwLock::new(ModuleMetadata::default());
 
     for payload in wp::Parser::new(0).parse_all(bytes) {
-        match payload? {
+        if let Some(function_body) = parser.payload(payload?)? {
+            function_bodies.push(function_body);
+            if parser.code_section_complete() {
+                parser.parse_bodies(std::mem::take(&mut function_bodies))?;
+            }
+        }
+    }
+
+    Ok(parser.finish())
+}
+
+/// Bytes requested from the reader at once. Independent of wasmparser's hint for how many bytes
+/// are missing, since that is often just a single byte, e.g., for the next section header.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Function bodies are copied out of the read buffer until they exceed this many bytes in total,
+/// and are then converted to our AST in parallel and freed again.
+const FUNCTION_BODIES_BATCH_SIZE: usize = 16 * 1024 * 1024;
+
+/// Parses a module from a streaming source, e.g., a file or network connection, without first
+/// reading the whole binary into memory.
+/// Sections are parsed as soon as their bytes have arrived, so besides the AST itself, only the
+/// current (non-code) section and a batch of function bodies are buffered at any time.
+pub fn parse_module_from_reader(
+    mut reader: impl io::Read,
+    options: ParseOptions,
+) -> Result<(Module, Offsets, ParseWarnings), ParseError> {
+    let mut parser = ModuleParser::new(options);
+    let mut wasmparser = wp::Parser::new(0);
+
+    // Bytes that were read, but not yet consumed by wasmparser start at `buffer[buffer_start]`.
+    // Consumed bytes are only removed from the front before reading more, to avoid moving the
+    // rest of the buffer after every (small) payload.
+    let mut buffer = Vec::with_capacity(READ_CHUNK_SIZE);
+    let mut buffer_start = 0;
+    let mut eof = false;
+
+    // Unlike for `parse_module`, the function bodies need to be owned, since the read buffer is
+    // overwritten while the code section is still streaming in.
+    let mut function_bodies: Vec<(u32, usize, Vec<u8>)> = Vec::new();
+    let mut function_bodies_size = 0;
+
+    loop {
+        let (payload, consumed) = match wasmparser.parse(&buffer[buffer_start..], eof)? {
+            wp::Chunk::NeedMoreData(_hint) => {
+                buffer.drain(..buffer_start);
+                buffer_start = 0;
+
+                let read = (&mut reader)
+                    .take(READ_CHUNK_SIZE as u64)
+                    .read_to_end(&mut buffer)?;
+                eof = read == 0;
+                continue;
+            }
+            wp::Chunk::Parsed { consumed, payload } => (payload, consumed),
+        };
+        let end = matches!(payload, wp::Payload::End(_));
+
+        if let Some((func_idx, body)) = parser.payload(payload)? {
+            let body_bytes = function_body_bytes(&body)?;
+            function_bodies_size += body_bytes.len();
+            function_bodies.push((func_idx, body.range().start, body_bytes.to_vec()));
+
+            if function_bodies_size >= FUNCTION_BODIES_BATCH_SIZE || parser.code_section_complete()
+            {
+                parser.parse_bodies(
+                    function_bodies
+                        .iter()
+                        .map(|(func_idx, offset, bytes)| {
+                            (*func_idx, wp::FunctionBody::new(*offset, bytes))
+                        })
+                        .collect(),
+                )?;
+                function_bodies.clear();
+                function_bodies_size = 0;
+            }
+        }
+
+        buffer_start += consumed;
+        if end {
+            break;
+        }
+    }
+
+    Ok(parser.finish())
+}
+
+/// State during module parsing, independent of whether the binary is fully resident in memory
+/// (`parse_module`) or read incrementally (`parse_module_from_reader`).
+struct ModuleParser {
+    options: ParseOptions,
+    /// The final module to return.
+    module: Module,
+    warnings: ParseWarnings,
+    types: Types,
+    imported_function_count: u32,
+    current_code_index: u32,
+    code_entries_count: u32,
+    section_offsets: Vec<(SectionId, usize)>,
+    function_offsets: Vec<(Idx<Function>, usize)>,
+    instr_offsets: Vec<(Idx<Function>, Vec<usize>)>,
+    metadata: RwLock<ModuleMetadata>,
+}
+
+impl ModuleParser {
+    fn new(options: ParseOptions) -> Self {
+        ModuleParser {
+            options,
+            module: Module::default(),
+            warnings: Vec::new(),
+            types: Types::none(),
+            imported_function_count: 0,
+            current_code_index: 0,
+            code_entries_count: 0,
+            section_offsets: Vec::with_capacity(16),
+            function_offsets: Vec::new(),
+            instr_offsets: Vec::new(),
+            metadata: RwLock::new(ModuleMetadata::default()),
+        }
+    }
+
+    /// Whether the last entry of the code section was returned from `payload`.
+    fn code_section_complete(&self) -> bool {
+        self.current_code_index == self.code_entries_count
+    }
+
+    /// Converts a single payload and adds it to the module.
+    /// Code section entries are not converted here, but returned together with their function
+    /// index, such that the caller can collect them and convert them in parallel with
+    /// `parse_bodies`.
+    fn payload<'a>(
+        &mut self,
+        payload: wp::Payload<'a>,
+    ) -> Result<Option<(u32, wp::FunctionBody<'a>)>, ParseError> {
+        let ModuleParser {
+            options: _,
+            module,
+            warnings,
+            types,
+            imported_function_count,
+            current_code_index,
+            code_entries_count,
+            section_offsets,
+            function_offsets,
+            instr_offsets,
+            metadata,
+        } = self;
+
+        match payload {
             wp::Payload::Version {
                 num: _,
                 encoding,
@@ -60,9 +209,9 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
 
                 types.new_type_section(reader.count(), type_offset)?;
 
-                for elem in reader.into_iter_with_offsets() {
-                    let (offset, wp::Type::Func(type_)) = elem?;
-                    let type_ = parse_func_ty(type_, offset)?;
+                for elem in reader {
+                    let wp::Type::Func(type_) = elem?;
+                    let type_ = parse_func_ty(type_, metadata);
                     types.add(type_);
                 }
             }
@@ -77,7 +226,7 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
 
                     match import.ty {
                         wp::TypeRef::Func(ty_index) => {
-                            imported_function_count += 1;
+                            *imported_function_count += 1;
                             module.functions.push(Function::new_imported(
                                 // The `import_offset` is not actually the offset of the type index,
                                 // but wasmparser doesn't offer a way to get the latter.
@@ -91,33 +240,36 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                         wp::TypeRef::Global(ty) => module.globals.push(
                             // Same issue regarding `import_offset`.
                             Global::new_imported(
-                                parse_global_ty(ty, import_offset)?,
+                                parse_global_ty(ty, metadata),
                                 import_module,
                                 import_name,
                             ),
                         ),
-                        wp::TypeRef::Table(ty) => module.tables.push(
+                        wp::TypeRef::Table(ty) => {
                             // Same issue regarding `import_offset`.
-                            Table::new_imported(
-                                parse_table_ty(ty, import_offset)?,
+                            let (limits, elem_type) = parse_table_ty(ty, import_offset)?;
+                            module.tables.push(Table::new_imported(
+                                limits,
+                                elem_type,
                                 import_module,
                                 import_name,
-                            ),
-                        ),
+                            ));
+                            mark_reference_types_tables(&module.tables, metadata);
+                        }
                         wp::TypeRef::Memory(ty) => {
-                            // Same issue regarding `import_offset`.
                             module.memories.push(Memory::new_imported(
-                                parse_memory_ty(ty, import_offset)?,
+                                parse_memory_ty(ty, metadata),
                                 import_module,
                                 import_name,
-                            ))
+                            ));
+                            mark_multi_memory(&module.memories, metadata);
                         }
-                        wp::TypeRef::Tag(_) => {
+                        wp::TypeRef::Tag(ty) => {
                             // Same issue regarding `import_offset`.
-                            Err(ParseIssue::unsupported(
-                                import_offset,
-                                WasmExtension::ExceptionHandling,
-                            ))?
+                            let type_ = parse_tag_ty(ty, import_offset, types, metadata)?;
+                            module
+                                .tags
+                                .push(Tag::new_imported(type_, import_module, import_name));
                         }
                     }
                 }
@@ -145,10 +297,10 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, table_ty) = elem?;
-                    let table_ty = parse_table_ty(table_ty, offset)?;
-                    // Fill in the elements of the table later with the element section.
-                    module.tables.push(Table::new(table_ty));
+                    let (limits, elem_type) = parse_table_ty(table_ty, offset)?;
+                    module.tables.push(Table::new(limits, elem_type));
                 }
+                mark_reference_types_tables(&module.tables, metadata);
             }
             wp::Payload::MemorySection(reader) => {
                 section_offsets.push((SectionId::Memory, reader.range().start));
@@ -156,17 +308,25 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                 let memory_count = reader.count();
                 module.memories.reserve(u32_to_usize(memory_count));
 
-                for elem in reader.into_iter_with_offsets() {
-                    let (offset, memory_ty) = elem?;
-                    let memory_ty = parse_memory_ty(memory_ty, offset)?;
+                for memory_ty in reader {
+                    let memory_ty = parse_memory_ty(memory_ty?, metadata);
                     // Fill in the data of the memory later with the data section.
                     module.memories.push(Memory::new(memory_ty));
                 }
+                mark_multi_memory(&module.memories, metadata);
+            }
+            wp::Payload::TagSection(reader) => {
+                section_offsets.push((SectionId::Tag, reader.range().start));
+
+                let tag_count = reader.count();
+                module.tags.reserve(u32_to_usize(tag_count));
+
+                for elem in reader.into_iter_with_offsets() {
+                    let (offset, tag_ty) = elem?;
+                    let type_ = parse_tag_ty(tag_ty, offset, types, metadata)?;
+                    module.tags.push(Tag::new(type_));
+                }
             }
-            wp::Payload::TagSection(reader) => Err(ParseIssue::unsupported(
-                reader.range().start,
-                WasmExtension::ExceptionHandling,
-            ))?,
             wp::Payload::GlobalSection(reader) => {
                 section_offsets.push((SectionId::Global, reader.range().start));
 
@@ -175,14 +335,14 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
 
                 for elem in reader.into_iter_with_offsets() {
                     let (offset, global) = elem?;
-                    let type_ = parse_global_ty(global.ty, offset)?;
+                    let type_ = parse_global_ty(global.ty, metadata);
 
                     // Most initialization expressions have just a constant and the end instruction.
                     let mut init = Vec::with_capacity(2);
                     for op in global.init_expr.get_operators_reader() {
                         // The `offset` will be slightly off, because it points to the beginning of the
                         // whole global entry, not the initialization expression.
-                        init.push(parse_instr(op?, offset, &types, &metadata)?)
+                        init.push(parse_instr(op?, offset, types, metadata)?)
                     }
 
                     module.globals.push(Global::new(type_, init));
@@ -230,13 +390,13 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                             .ok_or_else(|| ParseIssue::index(export_offset, index_u32, "global"))?
                             .export
                             .push(name),
-                        ExternalKind::Tag => {
+                        ExternalKind::Tag => module
+                            .tags
+                            .get_mut(index)
                             // Same issue regarding `export_offset`.
-                            Err(ParseIssue::unsupported(
-                                export_offset,
-                                WasmExtension::ExceptionHandling,
-                            ))?
-                        }
+                            .ok_or_else(|| ParseIssue::index(export_offset, index_u32, "tag"))?
+                            .export
+                            .push(name),
                     };
                 }
             }
@@ -257,30 +417,51 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
 
                 for elem in reader.into_iter_with_offsets() {
                     let (element_offset, element) = elem?;
-                    parse_elem_ty(element.ty, element_offset)?;
+                    let ref_type = parse_ref_ty(element.ty, element_offset)?;
 
                     let items = match element.items {
-                        wp::ElementItems::Functions(items_reader) => items_reader
-                            .into_iter()
-                            .map(|func_idx| func_idx.map(|func_idx| u32_to_usize(func_idx).into()))
-                            .collect::<Result<Vec<Idx<Function>>, _>>()?,
-                        wp::ElementItems::Expressions(reader) => Err(ParseIssue::unsupported(
-                            reader.original_position(),
-                            WasmExtension::ReferenceTypes,
-                        ))?,
+                        wp::ElementItems::Functions(items_reader) => ElementItems::Functions(
+                            items_reader
+                                .into_iter()
+                                .map(|func_idx| {
+                                    func_idx.map(|func_idx| u32_to_usize(func_idx).into())
+                                })
+                                .collect::<Result<Vec<Idx<Function>>, _>>()?,
+                        ),
+                        wp::ElementItems::Expressions(items_reader) => {
+                            metadata
+                                .write()
+                                .unwrap()
+                                .add_used_extension(WasmExtension::ReferenceTypes);
+
+                            let mut items = Vec::with_capacity(u32_to_usize(items_reader.count()));
+                            for item in items_reader.into_iter_with_offsets() {
+                                let (item_offset, item) = item?;
+                                // Most item expressions are just a `ref.func` or `ref.null` and the end instruction.
+                                let mut item_instrs = Vec::with_capacity(2);
+                                for op in item.get_operators_reader() {
+                                    // Same issue regarding `offset` as for global initialization expressions.
+                                    item_instrs.push(parse_instr(
+                                        op?,
+                                        item_offset,
+                                        types,
+                                        metadata,
+                                    )?)
+                                }
+                                items.push(item_instrs);
+                            }
+                            ElementItems::Expressions(items)
+                        }
                     };
 
-                    match element.kind {
+                    let mode = match element.kind {
                         wp::ElementKind::Active {
                             table_index,
                             offset_expr,
                         } => {
-                            let table = module
-                                .tables
-                                .get_mut(u32_to_usize(table_index))
-                                .ok_or_else(|| {
-                                    ParseIssue::index(element_offset, table_index, "table")
-                                })?;
+                            if u32_to_usize(table_index) >= module.tables.len() {
+                                Err(ParseIssue::index(element_offset, table_index, "table"))?
+                            }
 
                             // Most offset expressions are just a constant and the end instruction.
                             let mut offset_instrs = Vec::with_capacity(2);
@@ -288,46 +469,63 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                                 offset_expr.get_operators_reader().into_iter_with_offsets()
                             {
                                 let (op, offset) = op_offset?;
-                                offset_instrs.push(parse_instr(op, offset, &types, &metadata)?)
+                                offset_instrs.push(parse_instr(op, offset, types, metadata)?)
                             }
 
-                            table.elements.push(Element {
+                            ElementMode::Active {
+                                table: table_index.into(),
                                 offset: offset_instrs,
-                                functions: items,
-                            })
+                            }
                         }
-                        wp::ElementKind::Passive => Err(ParseIssue::unsupported(
-                            element_offset,
-                            WasmExtension::BulkMemoryOperations,
-                        ))?,
-                        wp::ElementKind::Declared => Err(ParseIssue::unsupported(
-                            element_offset,
-                            WasmExtension::ReferenceTypes,
-                        ))?,
-                    }
+                        wp::ElementKind::Passive => {
+                            metadata
+                                .write()
+                                .unwrap()
+                                .add_used_extension(WasmExtension::BulkMemoryOperations);
+                            ElementMode::Passive
+                        }
+                        wp::ElementKind::Declared => {
+                            metadata
+                                .write()
+                                .unwrap()
+                                .add_used_extension(WasmExtension::ReferenceTypes);
+                            ElementMode::Declared
+                        }
+                    };
+
+                    module.elements.push(Element {
+                        mode,
+                        ref_type,
+                        items,
+                        name: None,
+                    })
                 }
             }
-            wp::Payload::DataCountSection { count: _, range } => Err(ParseIssue::unsupported(
-                range.start,
-                WasmExtension::BulkMemoryOperations,
-            ))?,
+            wp::Payload::DataCountSection { count, range } => {
+                section_offsets.push((SectionId::DataCount, range.start));
+
+                metadata
+                    .write()
+                    .unwrap()
+                    .add_used_extension(WasmExtension::BulkMemoryOperations);
+                // The count is only needed for single-pass validation, which we don't do.
+                // The encoder re-computes it from the actual data segments.
+                module.data.reserve(u32_to_usize(count));
+            }
             wp::Payload::DataSection(reader) => {
                 section_offsets.push((SectionId::Data, reader.range().start));
 
                 for elem in reader.into_iter_with_offsets() {
                     let (data_offset, data) = elem?;
 
-                    match data.kind {
+                    let mode = match data.kind {
                         wp::DataKind::Active {
                             memory_index,
                             offset_expr,
                         } => {
-                            let memory = module
-                                .memories
-                                .get_mut(u32_to_usize(memory_index))
-                                .ok_or_else(|| {
-                                    ParseIssue::index(data_offset, memory_index, "memory")
-                                })?;
+                            if u32_to_usize(memory_index) >= module.memories.len() {
+                                Err(ParseIssue::index(data_offset, memory_index, "memory"))?
+                            }
 
                             // Most offset expressions are just a constant and the end instruction.
                             let mut offset_instrs = Vec::with_capacity(2);
@@ -335,19 +533,28 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                                 offset_expr.get_operators_reader().into_iter_with_offsets()
                             {
                                 let (op, offset) = op_offset?;
-                                offset_instrs.push(parse_instr(op, offset, &types, &metadata)?)
+                                offset_instrs.push(parse_instr(op, offset, types, metadata)?)
                             }
 
-                            memory.data.push(Data {
+                            DataMode::Active {
+                                memory: memory_index.into(),
                                 offset: offset_instrs,
-                                bytes: data.data.to_vec(),
-                            })
+                            }
                         }
-                        wp::DataKind::Passive => Err(ParseIssue::unsupported(
-                            data_offset,
-                            WasmExtension::BulkMemoryOperations,
-                        ))?,
-                    }
+                        wp::DataKind::Passive => {
+                            metadata
+                                .write()
+                                .unwrap()
+                                .add_used_extension(WasmExtension::BulkMemoryOperations);
+                            DataMode::Passive
+                        }
+                    };
+
+                    module.data.push(Data {
+                        mode,
+                        bytes: data.data.to_vec(),
+                        name: None,
+                    })
                 }
             }
             wp::Payload::CodeSectionStart {
@@ -358,40 +565,18 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                 section_offsets.push((SectionId::Code, range.start));
 
                 function_offsets.reserve_exact(u32_to_usize(count));
-                function_bodies.reserve_exact(u32_to_usize(count));
+                instr_offsets.reserve_exact(u32_to_usize(count));
 
-                code_entries_count = count;
+                *code_entries_count = count;
             }
             wp::Payload::CodeSectionEntry(body) => {
-                let func_index = imported_function_count + current_code_index;
+                let func_index = *imported_function_count + *current_code_index;
 
                 function_offsets.push((func_index.into(), body.range().start));
-                function_bodies.push((func_index, body));
-
-                current_code_index += 1;
-
-                let last_code_entry = current_code_index == code_entries_count;
-                if last_code_entry {
-                    // Parse and convert to high-level instructions in parallel.
-                    let function_bodies = function_bodies
-                        .par_drain(..)
-                        .map(|(func_idx, body)| {
-                            (
-                                func_idx,
-                                body.range().start,
-                                parse_body(body, &types, &metadata),
-                            )
-                        })
-                        .collect::<Vec<_>>();
-                    // Attach the converted function bodies to the function definitions (not parallel).
-                    for (func_idx, offset, code) in function_bodies {
-                        let function = module
-                            .functions
-                            .get_mut(u32_to_usize(func_idx))
-                            .ok_or_else(|| ParseIssue::index(offset, func_idx, "function"))?;
-                        function.code = ImportOrPresent::Present(code?);
-                    }
-                }
+
+                *current_code_index += 1;
+
+                return Ok(Some((func_index, body)));
             }
             wp::Payload::CustomSection(reader) => {
                 let name = reader.name().to_string();
@@ -410,12 +595,14 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                     match parse_name_custom_section(
                         reader.data(),
                         reader.data_offset(),
-                        &mut warnings,
-                        &mut module,
+                        types,
+                        warnings,
+                        module,
+                        metadata,
                     ) {
                         Ok(()) => {
                             // All the names got inserted into the AST, so no need to add a custom section.
-                            continue;
+                            return Ok(None);
                         }
                         Err(name_parsing_aborted) => {
                             warnings.push(ParseIssue::Message {
@@ -486,35 +673,122 @@ pub fn parse_module(bytes: &[u8]) -> Result<(Module, Offsets, ParseWarnings), Pa
                 // there is just no more payload following, isn't there?
             }
         }
+
+        Ok(None)
     }
 
-    let offsets = Offsets {
-        sections: section_offsets,
-        functions_code: function_offsets,
-    };
+    /// Converts function bodies (returned from `payload`) in parallel and attaches them to
+    /// their function definitions.
+    fn parse_bodies(
+        &mut self,
+        function_bodies: Vec<(u32, wp::FunctionBody)>,
+    ) -> Result<(), ParseError> {
+        let options = self.options;
+        let types = &self.types;
+        let metadata = &self.metadata;
+        // Parse and convert to high-level instructions in parallel.
+        let function_bodies = function_bodies
+            .into_par_iter()
+            .map(|(func_idx, body)| {
+                (
+                    func_idx,
+                    body.range().start,
+                    parse_body(body, types, metadata, options),
+                )
+            })
+            .collect::<Vec<_>>();
+        // Attach the converted function bodies to the function definitions (not parallel).
+        for (func_idx, offset, code) in function_bodies {
+            let function = self
+                .module
+                .functions
+                .get_mut(u32_to_usize(func_idx))
+                .ok_or_else(|| ParseIssue::index(offset, func_idx, "function"))?;
+            let (code, instr_offsets) = code?;
+            function.code = ImportOrPresent::Present(code);
+            if let Some(instr_offsets) = instr_offsets {
+                self.instr_offsets.push((func_idx.into(), instr_offsets));
+            }
+        }
+        Ok(())
+    }
 
-    module.metadata = metadata.into_inner().unwrap();
+    fn finish(self) -> (Module, Offsets, ParseWarnings) {
+        let mut module = self.module;
+        let offsets = Offsets {
+            sections: self.section_offsets,
+            functions_code: self.function_offsets,
+            instrs: self.instr_offsets,
+        };
 
-    Ok((module, offsets, warnings))
+        module.metadata = self.metadata.into_inner().unwrap();
+
+        (module, offsets, self.warnings)
+    }
 }
 
 fn parse_body(
     body: wp::FunctionBody,
     types: &Types,
     metadata: &RwLock<ModuleMetadata>,
-) -> Result<Code, ParseError> {
+    options: ParseOptions,
+) -> Result<(Code, Option<Vec<usize>>), ParseError> {
+    let locals = parse_locals(&body, metadata)?;
+    let (body, instr_offsets) = if options.lazy {
+        // Only the instruction boundaries are read, without decoding the instructions.
+        let instr_offsets = if options.instr_offsets {
+            let mut instr_offsets = Vec::new();
+            for op_offset in body.get_operators_reader()?.into_iter_with_offsets() {
+                let (_op, offset) = op_offset?;
+                instr_offsets.push(offset);
+            }
+            Some(instr_offsets)
+        } else {
+            None
+        };
+        let raw = RawBody {
+            bytes: function_body_bytes(&body)?.into(),
+            offset: body.range().start,
+            types: types.clone(),
+        };
+        (LazyExpr::from_raw(raw), instr_offsets)
+    } else {
+        let (instrs, instr_offsets) = parse_instrs(&body, types, metadata, options.instr_offsets)?;
+        (instrs.into(), instr_offsets)
+    };
+    Ok((
+        Code {
+            locals,
+            body,
+            label_names: Vec::new(),
+        },
+        instr_offsets,
+    ))
+}
+
+fn parse_locals(
+    body: &wp::FunctionBody,
+    metadata: &RwLock<ModuleMetadata>,
+) -> Result<Vec<Local>, ParseError> {
     let mut locals_reader = body.get_locals_reader()?;
-    let mut offset = locals_reader.original_position();
     // Pre-allocate: There are at least as many locals as there are _unique_ local types.
     let mut locals = Vec::with_capacity(u32_to_usize(locals_reader.get_count()));
     for _ in 0..locals_reader.get_count() {
         let (count, type_) = locals_reader.read()?;
         let count = u32_to_usize(count);
-        let type_ = parse_val_ty(type_, offset)?;
+        let type_ = parse_val_ty(type_, metadata);
         locals.extend(std::iter::repeat(Local::new(type_)).take(count));
-        offset = locals_reader.original_position();
     }
+    Ok(locals)
+}
 
+/// Returns the offsets of the instructions only if `record_offsets` is true.
+fn parse_instrs(
+    body: &wp::FunctionBody,
+    types: &Types,
+    metadata: &RwLock<ModuleMetadata>,
+    record_offsets: bool,
+) -> Result<(Expr, Option<Vec<usize>>), ParseError> {
     // Pre-allocate: We don't know the exact number of instructions yet,
     // but there are typically one or two bytes per instruction.
     // So conservatively, bytes / 2 should be a good starting point.
@@ -547,16 +821,119 @@ fn parse_body(
     let body_byte_size = body.range().end - body.range().start;
     let approx_instr_count = body_byte_size / 2;
     let mut instrs = Vec::with_capacity(approx_instr_count);
+    let mut instr_offsets = if record_offsets {
+        Vec::with_capacity(approx_instr_count)
+    } else {
+        Vec::new()
+    };
 
     for op_offset in body.get_operators_reader()?.into_iter_with_offsets() {
         let (op, offset) = op_offset?;
         instrs.push(parse_instr(op, offset, types, metadata)?);
+        if record_offsets {
+            instr_offsets.push(offset);
+        }
     }
 
-    Ok(Code {
-        locals,
-        body: instrs,
-    })
+    Ok((instrs, record_offsets.then_some(instr_offsets)))
+}
+
+/// A function body in its original binary encoding, which is only decoded on demand, see
+/// `LazyExpr` and `Module::from_bytes_lazy`.
+#[derive(Debug, Clone)]
+pub(crate) struct RawBody {
+    /// Local declarations and instructions, i.e., without the size prefix.
+    bytes: Box<[u8]>,
+    /// Offset of `bytes` in the original binary, such that errors point to the right location.
+    offset: usize,
+    /// All raw bodies of the same binary share its type section, which is required to decode
+    /// type indices in instructions.
+    types: Types,
+}
+
+impl RawBody {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The type section of the original binary, i.e., type indices in `bytes` refer to this.
+    pub fn types(&self) -> &[FunctionType] {
+        self.types.as_slice()
+    }
+
+    /// Whether both bodies are from the same binary, i.e., their type indices are compatible.
+    pub fn same_types(&self, other: &RawBody) -> bool {
+        match (&self.types.0, &other.types.0) {
+            (Some(types), Some(other_types)) => Arc::ptr_eq(types, other_types),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// The instructions only, i.e., without local declarations.
+    pub fn instr_bytes(&self) -> Result<&[u8], ParseError> {
+        let mut locals_reader = self.function_body().get_locals_reader()?;
+        for _ in 0..locals_reader.get_count() {
+            locals_reader.read()?;
+        }
+        let instrs_start = locals_reader.original_position() - self.offset;
+        Ok(&self.bytes[instrs_start..])
+    }
+
+    /// Whether the local declarations in `bytes` declare exactly the types of `locals`.
+    pub fn locals_match(&self, locals: &[Local]) -> Result<bool, ParseError> {
+        let metadata = RwLock::new(ModuleMetadata::default());
+        Ok(parse_locals(&self.function_body(), &metadata)?
+            .iter()
+            .map(|local| local.type_)
+            .eq(locals.iter().map(|local| local.type_)))
+    }
+
+    /// Whether the instructions contain `memory.init` or `data.drop`, without decoding them.
+    pub fn references_data(&self) -> Result<bool, ParseError> {
+        for op in self.function_body().get_operators_reader()? {
+            if matches!(
+                op?,
+                wp::Operator::MemoryInit { .. } | wp::Operator::DataDrop { .. }
+            ) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Note that extensions used by the instructions are not recorded in the module metadata.
+    pub fn decode(&self) -> Result<Expr, ParseError> {
+        let metadata = RwLock::new(ModuleMetadata::default());
+        let (instrs, _offsets) =
+            parse_instrs(&self.function_body(), &self.types, &metadata, false)?;
+        Ok(instrs)
+    }
+
+    /// Offsets of all instructions relative to the start of `instr_bytes`, without decoding them.
+    pub fn instr_offsets(&self) -> Result<Vec<usize>, ParseError> {
+        let instrs_start = self.offset + self.bytes.len() - self.instr_bytes()?.len();
+        let mut instr_offsets = Vec::new();
+        for op_offset in self
+            .function_body()
+            .get_operators_reader()?
+            .into_iter_with_offsets()
+        {
+            let (_op, offset) = op_offset?;
+            instr_offsets.push(offset - instrs_start);
+        }
+        Ok(instr_offsets)
+    }
+
+    fn function_body(&self) -> wp::FunctionBody<'_> {
+        wp::FunctionBody::new(self.offset, &self.bytes)
+    }
+}
+
+/// The bytes of a function body (local declarations and instructions), without the size prefix.
+fn function_body_bytes<'a>(body: &wp::FunctionBody<'a>) -> Result<&'a [u8], ParseError> {
+    let mut body_reader = body.get_binary_reader();
+    Ok(body_reader.read_bytes(body_reader.bytes_remaining())?)
 }
 
 fn parse_instr(
@@ -567,6 +944,30 @@ fn parse_instr(
 ) -> Result<Instr, ParseError> {
     use crate::Instr::*;
     use wp::Operator as wp;
+
+    // Instructions from (by now standardized) extensions are supported, but still recorded.
+    let with_extension = |instr: Instr, extension: WasmExtension| -> Instr {
+        metadata.write().unwrap().add_used_extension(extension);
+        instr
+    };
+    let sign_extension = |op: UnaryOp| with_extension(Unary(op), WasmExtension::SignExtensionOps);
+    let nontrapping_float_to_int =
+        |op: UnaryOp| with_extension(Unary(op), WasmExtension::NontrappingFloatToInt);
+    let bulk_memory = |instr: Instr| with_extension(instr, WasmExtension::BulkMemoryOperations);
+    let reference_types = |instr: Instr| with_extension(instr, WasmExtension::ReferenceTypes);
+    let simd = |instr: Instr| with_extension(instr, WasmExtension::Simd);
+    let threads = |instr: Instr| with_extension(instr, WasmExtension::ThreadsAtomics);
+    let exception_handling = |instr: Instr| with_extension(instr, WasmExtension::ExceptionHandling);
+    let tail_calls = |instr: Instr| with_extension(instr, WasmExtension::TailCalls);
+    // Memory instructions with a memory index other than 0 are only valid with multiple memories.
+    let multi_memory = |instr: Instr, memory_idxs: &[u32]| {
+        if memory_idxs.iter().any(|&memory_idx| memory_idx != 0) {
+            with_extension(instr, WasmExtension::MultiMemory)
+        } else {
+            instr
+        }
+    };
+
     Ok(match op {
         wp::Unreachable => Unreachable,
         wp::Nop => Nop,
@@ -577,15 +978,14 @@ fn parse_instr(
         wp::Else => Else,
         wp::End => End,
 
-        wp::Try { blockty: _ }
-        | wp::Catch { tag_index: _ }
-        | wp::CatchAll
-        | wp::Throw { tag_index: _ }
-        | wp::Rethrow { relative_depth: _ }
-        | wp::Delegate { relative_depth: _ } => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ExceptionHandling,
-        ))?,
+        wp::Try { blockty } => {
+            exception_handling(Try(parse_block_ty(blockty, offset + 1, types, metadata)?))
+        }
+        wp::Catch { tag_index } => exception_handling(Catch(tag_index.into())),
+        wp::CatchAll => exception_handling(CatchAll),
+        wp::Throw { tag_index } => exception_handling(Throw(tag_index.into())),
+        wp::Rethrow { relative_depth } => exception_handling(Rethrow(relative_depth.into())),
+        wp::Delegate { relative_depth } => exception_handling(Delegate(relative_depth.into())),
 
         wp::Br { relative_depth } => Br(Label::from(relative_depth)),
         wp::BrIf { relative_depth } => BrIf(Label::from(relative_depth)),
@@ -608,29 +1008,37 @@ fn parse_instr(
             table_index,
             table_byte,
         } => {
+            // Similar to `mem_byte` below, `table_byte` is just the non-LEB128 version of
+            // `table_index` (or 0, if the table index was not single-byte encoded).
+            let _ = table_byte;
+            let instr = CallIndirect(types.get(type_index, offset + 1)?, table_index.into());
             if table_index != 0 {
-                Err(ParseIssue::unsupported(
-                    offset,
-                    WasmExtension::ReferenceTypes,
-                ))?
+                reference_types(instr)
+            } else {
+                instr
             }
-            assert!(table_byte == 0, "not sure which extension this is");
-            CallIndirect(types.get(type_index, offset + 1)?, 0usize.into())
         }
 
-        wp::ReturnCall { function_index: _ }
-        | wp::ReturnCallIndirect {
-            type_index: _,
-            table_index: _,
-        } => Err(ParseIssue::unsupported(offset, WasmExtension::TailCalls))?,
+        wp::ReturnCall { function_index } => tail_calls(ReturnCall(function_index.into())),
+        wp::ReturnCallIndirect {
+            type_index,
+            table_index,
+        } => {
+            let instr = tail_calls(ReturnCallIndirect(
+                types.get(type_index, offset + 1)?,
+                table_index.into(),
+            ));
+            if table_index != 0 {
+                reference_types(instr)
+            } else {
+                instr
+            }
+        }
 
         wp::Drop => Drop,
         wp::Select => Select,
 
-        wp::TypedSelect { ty: _ } => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ReferenceTypes,
-        ))?,
+        wp::TypedSelect { ty } => reference_types(TypedSelect(parse_val_ty(ty, metadata))),
 
         wp::LocalGet { local_index } => Local(LocalOp::Get, local_index.into()),
         wp::LocalSet { local_index } => Local(LocalOp::Set, local_index.into()),
@@ -638,57 +1046,47 @@ fn parse_instr(
         wp::GlobalGet { global_index } => Global(GlobalOp::Get, global_index.into()),
         wp::GlobalSet { global_index } => Global(GlobalOp::Set, global_index.into()),
 
-        wp::I32Load { memarg } => Load(LoadOp::I32Load, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load { memarg } => Load(LoadOp::I64Load, parse_memarg(memarg, offset + 1)?),
-        wp::F32Load { memarg } => Load(LoadOp::F32Load, parse_memarg(memarg, offset + 1)?),
-        wp::F64Load { memarg } => Load(LoadOp::F64Load, parse_memarg(memarg, offset + 1)?),
-        wp::I32Load8S { memarg } => Load(LoadOp::I32Load8S, parse_memarg(memarg, offset + 1)?),
-        wp::I32Load8U { memarg } => Load(LoadOp::I32Load8U, parse_memarg(memarg, offset + 1)?),
-        wp::I32Load16S { memarg } => Load(LoadOp::I32Load16S, parse_memarg(memarg, offset + 1)?),
-        wp::I32Load16U { memarg } => Load(LoadOp::I32Load16U, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load8S { memarg } => Load(LoadOp::I64Load8S, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load8U { memarg } => Load(LoadOp::I64Load8U, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load16S { memarg } => Load(LoadOp::I64Load16S, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load16U { memarg } => Load(LoadOp::I64Load16U, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load32S { memarg } => Load(LoadOp::I64Load32S, parse_memarg(memarg, offset + 1)?),
-        wp::I64Load32U { memarg } => Load(LoadOp::I64Load32U, parse_memarg(memarg, offset + 1)?),
-
-        wp::I32Store { memarg } => Store(StoreOp::I32Store, parse_memarg(memarg, offset + 1)?),
-        wp::I64Store { memarg } => Store(StoreOp::I64Store, parse_memarg(memarg, offset + 1)?),
-        wp::F32Store { memarg } => Store(StoreOp::F32Store, parse_memarg(memarg, offset + 1)?),
-        wp::F64Store { memarg } => Store(StoreOp::F64Store, parse_memarg(memarg, offset + 1)?),
-        wp::I32Store8 { memarg } => Store(StoreOp::I32Store8, parse_memarg(memarg, offset + 1)?),
-        wp::I32Store16 { memarg } => Store(StoreOp::I32Store16, parse_memarg(memarg, offset + 1)?),
-        wp::I64Store8 { memarg } => Store(StoreOp::I64Store8, parse_memarg(memarg, offset + 1)?),
-        wp::I64Store16 { memarg } => Store(StoreOp::I64Store16, parse_memarg(memarg, offset + 1)?),
-        wp::I64Store32 { memarg } => Store(StoreOp::I64Store32, parse_memarg(memarg, offset + 1)?),
+        wp::I32Load { memarg } => Load(LoadOp::I32Load, parse_memarg(memarg, metadata)),
+        wp::I64Load { memarg } => Load(LoadOp::I64Load, parse_memarg(memarg, metadata)),
+        wp::F32Load { memarg } => Load(LoadOp::F32Load, parse_memarg(memarg, metadata)),
+        wp::F64Load { memarg } => Load(LoadOp::F64Load, parse_memarg(memarg, metadata)),
+        wp::I32Load8S { memarg } => Load(LoadOp::I32Load8S, parse_memarg(memarg, metadata)),
+        wp::I32Load8U { memarg } => Load(LoadOp::I32Load8U, parse_memarg(memarg, metadata)),
+        wp::I32Load16S { memarg } => Load(LoadOp::I32Load16S, parse_memarg(memarg, metadata)),
+        wp::I32Load16U { memarg } => Load(LoadOp::I32Load16U, parse_memarg(memarg, metadata)),
+        wp::I64Load8S { memarg } => Load(LoadOp::I64Load8S, parse_memarg(memarg, metadata)),
+        wp::I64Load8U { memarg } => Load(LoadOp::I64Load8U, parse_memarg(memarg, metadata)),
+        wp::I64Load16S { memarg } => Load(LoadOp::I64Load16S, parse_memarg(memarg, metadata)),
+        wp::I64Load16U { memarg } => Load(LoadOp::I64Load16U, parse_memarg(memarg, metadata)),
+        wp::I64Load32S { memarg } => Load(LoadOp::I64Load32S, parse_memarg(memarg, metadata)),
+        wp::I64Load32U { memarg } => Load(LoadOp::I64Load32U, parse_memarg(memarg, metadata)),
+
+        wp::I32Store { memarg } => Store(StoreOp::I32Store, parse_memarg(memarg, metadata)),
+        wp::I64Store { memarg } => Store(StoreOp::I64Store, parse_memarg(memarg, metadata)),
+        wp::F32Store { memarg } => Store(StoreOp::F32Store, parse_memarg(memarg, metadata)),
+        wp::F64Store { memarg } => Store(StoreOp::F64Store, parse_memarg(memarg, metadata)),
+        wp::I32Store8 { memarg } => Store(StoreOp::I32Store8, parse_memarg(memarg, metadata)),
+        wp::I32Store16 { memarg } => Store(StoreOp::I32Store16, parse_memarg(memarg, metadata)),
+        wp::I64Store8 { memarg } => Store(StoreOp::I64Store8, parse_memarg(memarg, metadata)),
+        wp::I64Store16 { memarg } => Store(StoreOp::I64Store16, parse_memarg(memarg, metadata)),
+        wp::I64Store32 { memarg } => Store(StoreOp::I64Store32, parse_memarg(memarg, metadata)),
 
         // This is not well documented in wasmparser: `mem_byte` and `mem` essentially contain
         // the same information, it's just that mem_byte is the original (single) byte that was
         // read from the instruction stream, and mem is it if parsed as a LEB128.
         // I think the variable-length parser is more robust, as it can handle memory indices
         // above 255, so ignore `mem_byte` here.
-        wp::MemorySize { mem, mem_byte: _ } => {
-            if mem != 0 {
-                Err(ParseIssue::unsupported(offset, WasmExtension::MultiMemory))?
-            }
-            MemorySize(0u32.into())
-        }
-        wp::MemoryGrow { mem, mem_byte: _ } => {
-            if mem != 0 {
-                Err(ParseIssue::unsupported(offset, WasmExtension::MultiMemory))?
-            }
-            MemoryGrow(0u32.into())
-        }
+        wp::MemorySize { mem, mem_byte: _ } => multi_memory(MemorySize(mem.into()), &[mem]),
+        wp::MemoryGrow { mem, mem_byte: _ } => multi_memory(MemoryGrow(mem.into()), &[mem]),
 
         wp::I32Const { value } => Const(Val::I32(value)),
         wp::I64Const { value } => Const(Val::I64(value)),
         wp::F32Const { value } => Const(Val::F32(OrderedFloat(f32::from_bits(value.bits())))),
         wp::F64Const { value } => Const(Val::F64(OrderedFloat(f64::from_bits(value.bits())))),
 
-        wp::RefNull { ty: _ } | wp::RefIsNull | wp::RefFunc { function_index: _ } => Err(
-            ParseIssue::unsupported(offset, WasmExtension::ReferenceTypes),
-        )?,
+        wp::RefNull { ty } => reference_types(RefNull(parse_ref_ty(ty, offset + 1)?)),
+        wp::RefIsNull => reference_types(RefIsNull),
+        wp::RefFunc { function_index } => reference_types(RefFunc(function_index.into())),
 
         wp::I32Eqz => Unary(UnaryOp::I32Eqz),
         wp::I64Eqz => Unary(UnaryOp::I64Eqz),
@@ -815,370 +1213,628 @@ fn parse_instr(
         wp::F64Max => Binary(BinaryOp::F64Max),
         wp::F64Copysign => Binary(BinaryOp::F64Copysign),
 
-        wp::I32Extend8S
-        | wp::I32Extend16S
-        | wp::I64Extend8S
-        | wp::I64Extend16S
-        | wp::I64Extend32S => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::SignExtensionOps,
-        ))?,
-
-        wp::I32TruncSatF32S
-        | wp::I32TruncSatF32U
-        | wp::I32TruncSatF64S
-        | wp::I32TruncSatF64U
-        | wp::I64TruncSatF32S
-        | wp::I64TruncSatF32U
-        | wp::I64TruncSatF64S
-        | wp::I64TruncSatF64U => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::NontrappingFloatToInt,
-        ))?,
+        wp::I32Extend8S => sign_extension(UnaryOp::I32Extend8S),
+        wp::I32Extend16S => sign_extension(UnaryOp::I32Extend16S),
+        wp::I64Extend8S => sign_extension(UnaryOp::I64Extend8S),
+        wp::I64Extend16S => sign_extension(UnaryOp::I64Extend16S),
+        wp::I64Extend32S => sign_extension(UnaryOp::I64Extend32S),
+
+        wp::I32TruncSatF32S => nontrapping_float_to_int(UnaryOp::I32TruncSatF32S),
+        wp::I32TruncSatF32U => nontrapping_float_to_int(UnaryOp::I32TruncSatF32U),
+        wp::I32TruncSatF64S => nontrapping_float_to_int(UnaryOp::I32TruncSatF64S),
+        wp::I32TruncSatF64U => nontrapping_float_to_int(UnaryOp::I32TruncSatF64U),
+        wp::I64TruncSatF32S => nontrapping_float_to_int(UnaryOp::I64TruncSatF32S),
+        wp::I64TruncSatF32U => nontrapping_float_to_int(UnaryOp::I64TruncSatF32U),
+        wp::I64TruncSatF64S => nontrapping_float_to_int(UnaryOp::I64TruncSatF64S),
+        wp::I64TruncSatF64U => nontrapping_float_to_int(UnaryOp::I64TruncSatF64U),
+
+        wp::MemoryCopy { dst_mem, src_mem } => multi_memory(
+            bulk_memory(MemoryCopy {
+                dst: dst_mem.into(),
+                src: src_mem.into(),
+            }),
+            &[dst_mem, src_mem],
+        ),
+        wp::MemoryFill { mem } => multi_memory(bulk_memory(MemoryFill(mem.into())), &[mem]),
+        wp::MemoryInit { data_index, mem } => multi_memory(
+            bulk_memory(MemoryInit(mem.into(), data_index.into())),
+            &[mem],
+        ),
+        wp::DataDrop { data_index } => bulk_memory(DataDrop(data_index.into())),
+
+        // Table instructions with a table index other than 0 are only valid with multiple tables,
+        // i.e., the reference types extension.
+        wp::TableInit { elem_index, table } => {
+            let instr = bulk_memory(TableInit(table.into(), elem_index.into()));
+            if table != 0 {
+                reference_types(instr)
+            } else {
+                instr
+            }
+        }
+        wp::ElemDrop { elem_index } => bulk_memory(ElemDrop(elem_index.into())),
+        wp::TableCopy {
+            dst_table,
+            src_table,
+        } => {
+            let instr = bulk_memory(TableCopy {
+                dst: dst_table.into(),
+                src: src_table.into(),
+            });
+            if dst_table != 0 || src_table != 0 {
+                reference_types(instr)
+            } else {
+                instr
+            }
+        }
 
-        wp::MemoryInit {
-            data_index: _,
-            mem: _,
+        wp::TableFill { table } => reference_types(TableFill(table.into())),
+        wp::TableGet { table } => reference_types(TableGet(table.into())),
+        wp::TableSet { table } => reference_types(TableSet(table.into())),
+        wp::TableGrow { table } => reference_types(TableGrow(table.into())),
+        wp::TableSize { table } => reference_types(TableSize(table.into())),
+
+        wp::I32AtomicLoad { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I32AtomicLoad,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicLoad { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I64AtomicLoad,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicLoad8U { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I32AtomicLoad8U,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicLoad16U { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I32AtomicLoad16U,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicLoad8U { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I64AtomicLoad8U,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicLoad16U { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I64AtomicLoad16U,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicLoad32U { memarg } => threads(AtomicLoad(
+            AtomicLoadOp::I64AtomicLoad32U,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicStore { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I32AtomicStore,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicStore { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I64AtomicStore,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicStore8 { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I32AtomicStore8,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicStore16 { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I32AtomicStore16,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicStore8 { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I64AtomicStore8,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicStore16 { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I64AtomicStore16,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicStore32 { memarg } => threads(AtomicStore(
+            AtomicStoreOp::I64AtomicStore32,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwAdd { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmwAdd,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwAdd { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmwAdd,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8AddU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw8AddU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16AddU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw16AddU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8AddU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw8AddU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16AddU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw16AddU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32AddU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw32AddU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwSub { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmwSub,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwSub { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmwSub,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8SubU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw8SubU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16SubU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw16SubU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8SubU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw8SubU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16SubU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw16SubU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32SubU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw32SubU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwAnd { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmwAnd,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwAnd { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmwAnd,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8AndU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw8AndU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16AndU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw16AndU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8AndU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw8AndU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16AndU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw16AndU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32AndU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw32AndU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwOr { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmwOr,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwOr { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmwOr,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8OrU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw8OrU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16OrU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw16OrU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8OrU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw8OrU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16OrU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw16OrU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32OrU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw32OrU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwXor { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmwXor,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwXor { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmwXor,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8XorU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw8XorU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16XorU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw16XorU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8XorU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw8XorU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16XorU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw16XorU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32XorU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw32XorU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwXchg { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmwXchg,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwXchg { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmwXchg,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8XchgU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw8XchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16XchgU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I32AtomicRmw16XchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8XchgU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw8XchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16XchgU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw16XchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32XchgU { memarg } => threads(AtomicRmw(
+            AtomicRmwOp::I64AtomicRmw32XchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmwCmpxchg { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I32AtomicRmwCmpxchg,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmwCmpxchg { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I64AtomicRmwCmpxchg,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw8CmpxchgU { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I32AtomicRmw8CmpxchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I32AtomicRmw16CmpxchgU { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I32AtomicRmw16CmpxchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw8CmpxchgU { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I64AtomicRmw8CmpxchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw16CmpxchgU { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I64AtomicRmw16CmpxchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::I64AtomicRmw32CmpxchgU { memarg } => threads(AtomicCmpxchg(
+            AtomicCmpxchgOp::I64AtomicRmw32CmpxchgU,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::MemoryAtomicNotify { memarg } => threads(AtomicWaitNotify(
+            AtomicWaitNotifyOp::MemoryAtomicNotify,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::MemoryAtomicWait32 { memarg } => threads(AtomicWaitNotify(
+            AtomicWaitNotifyOp::MemoryAtomicWait32,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::MemoryAtomicWait64 { memarg } => threads(AtomicWaitNotify(
+            AtomicWaitNotifyOp::MemoryAtomicWait64,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::AtomicFence => threads(AtomicFence),
+
+        wp::V128Load { memarg } => simd(Load(LoadOp::V128Load, parse_memarg(memarg, metadata))),
+        wp::V128Load8x8S { memarg } => {
+            simd(Load(LoadOp::V128Load8x8S, parse_memarg(memarg, metadata)))
         }
-        | wp::DataDrop { data_index: _ }
-        | wp::MemoryCopy {
-            dst_mem: _,
-            src_mem: _,
+        wp::V128Load8x8U { memarg } => {
+            simd(Load(LoadOp::V128Load8x8U, parse_memarg(memarg, metadata)))
         }
-        | wp::MemoryFill { mem: _ }
-        | wp::TableInit {
-            elem_index: _,
-            table: _,
+        wp::V128Load16x4S { memarg } => {
+            simd(Load(LoadOp::V128Load16x4S, parse_memarg(memarg, metadata)))
         }
-        | wp::ElemDrop { elem_index: _ }
-        | wp::TableCopy {
-            dst_table: _,
-            src_table: _,
-        } => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::BulkMemoryOperations,
-        ))?,
-
-        wp::TableFill { table: _ } => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ReferenceTypes,
-        ))?,
-
-        wp::TableGet { table: _ }
-        | wp::TableSet { table: _ }
-        | wp::TableGrow { table: _ }
-        | wp::TableSize { table: _ } => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ReferenceTypes,
-        ))?,
-
-        wp::MemoryAtomicNotify { memarg: _ }
-        | wp::MemoryAtomicWait32 { memarg: _ }
-        | wp::MemoryAtomicWait64 { memarg: _ }
-        | wp::AtomicFence
-        | wp::I32AtomicLoad { memarg: _ }
-        | wp::I64AtomicLoad { memarg: _ }
-        | wp::I32AtomicLoad8U { memarg: _ }
-        | wp::I32AtomicLoad16U { memarg: _ }
-        | wp::I64AtomicLoad8U { memarg: _ }
-        | wp::I64AtomicLoad16U { memarg: _ }
-        | wp::I64AtomicLoad32U { memarg: _ }
-        | wp::I32AtomicStore { memarg: _ }
-        | wp::I64AtomicStore { memarg: _ }
-        | wp::I32AtomicStore8 { memarg: _ }
-        | wp::I32AtomicStore16 { memarg: _ }
-        | wp::I64AtomicStore8 { memarg: _ }
-        | wp::I64AtomicStore16 { memarg: _ }
-        | wp::I64AtomicStore32 { memarg: _ }
-        | wp::I32AtomicRmwAdd { memarg: _ }
-        | wp::I64AtomicRmwAdd { memarg: _ }
-        | wp::I32AtomicRmw8AddU { memarg: _ }
-        | wp::I32AtomicRmw16AddU { memarg: _ }
-        | wp::I64AtomicRmw8AddU { memarg: _ }
-        | wp::I64AtomicRmw16AddU { memarg: _ }
-        | wp::I64AtomicRmw32AddU { memarg: _ }
-        | wp::I32AtomicRmwSub { memarg: _ }
-        | wp::I64AtomicRmwSub { memarg: _ }
-        | wp::I32AtomicRmw8SubU { memarg: _ }
-        | wp::I32AtomicRmw16SubU { memarg: _ }
-        | wp::I64AtomicRmw8SubU { memarg: _ }
-        | wp::I64AtomicRmw16SubU { memarg: _ }
-        | wp::I64AtomicRmw32SubU { memarg: _ }
-        | wp::I32AtomicRmwAnd { memarg: _ }
-        | wp::I64AtomicRmwAnd { memarg: _ }
-        | wp::I32AtomicRmw8AndU { memarg: _ }
-        | wp::I32AtomicRmw16AndU { memarg: _ }
-        | wp::I64AtomicRmw8AndU { memarg: _ }
-        | wp::I64AtomicRmw16AndU { memarg: _ }
-        | wp::I64AtomicRmw32AndU { memarg: _ }
-        | wp::I32AtomicRmwOr { memarg: _ }
-        | wp::I64AtomicRmwOr { memarg: _ }
-        | wp::I32AtomicRmw8OrU { memarg: _ }
-        | wp::I32AtomicRmw16OrU { memarg: _ }
-        | wp::I64AtomicRmw8OrU { memarg: _ }
-        | wp::I64AtomicRmw16OrU { memarg: _ }
-        | wp::I64AtomicRmw32OrU { memarg: _ }
-        | wp::I32AtomicRmwXor { memarg: _ }
-        | wp::I64AtomicRmwXor { memarg: _ }
-        | wp::I32AtomicRmw8XorU { memarg: _ }
-        | wp::I32AtomicRmw16XorU { memarg: _ }
-        | wp::I64AtomicRmw8XorU { memarg: _ }
-        | wp::I64AtomicRmw16XorU { memarg: _ }
-        | wp::I64AtomicRmw32XorU { memarg: _ }
-        | wp::I32AtomicRmwXchg { memarg: _ }
-        | wp::I64AtomicRmwXchg { memarg: _ }
-        | wp::I32AtomicRmw8XchgU { memarg: _ }
-        | wp::I32AtomicRmw16XchgU { memarg: _ }
-        | wp::I64AtomicRmw8XchgU { memarg: _ }
-        | wp::I64AtomicRmw16XchgU { memarg: _ }
-        | wp::I64AtomicRmw32XchgU { memarg: _ }
-        | wp::I32AtomicRmwCmpxchg { memarg: _ }
-        | wp::I64AtomicRmwCmpxchg { memarg: _ }
-        | wp::I32AtomicRmw8CmpxchgU { memarg: _ }
-        | wp::I32AtomicRmw16CmpxchgU { memarg: _ }
-        | wp::I64AtomicRmw8CmpxchgU { memarg: _ }
-        | wp::I64AtomicRmw16CmpxchgU { memarg: _ }
-        | wp::I64AtomicRmw32CmpxchgU { memarg: _ } => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ThreadsAtomics,
-        ))?,
-
-        wp::V128Load { memarg: _ }
-        | wp::V128Load8x8S { memarg: _ }
-        | wp::V128Load8x8U { memarg: _ }
-        | wp::V128Load16x4S { memarg: _ }
-        | wp::V128Load16x4U { memarg: _ }
-        | wp::V128Load32x2S { memarg: _ }
-        | wp::V128Load32x2U { memarg: _ }
-        | wp::V128Load8Splat { memarg: _ }
-        | wp::V128Load16Splat { memarg: _ }
-        | wp::V128Load32Splat { memarg: _ }
-        | wp::V128Load64Splat { memarg: _ }
-        | wp::V128Load32Zero { memarg: _ }
-        | wp::V128Load64Zero { memarg: _ }
-        | wp::V128Store { memarg: _ }
-        | wp::V128Load8Lane { memarg: _, lane: _ }
-        | wp::V128Load16Lane { memarg: _, lane: _ }
-        | wp::V128Load32Lane { memarg: _, lane: _ }
-        | wp::V128Load64Lane { memarg: _, lane: _ }
-        | wp::V128Store8Lane { memarg: _, lane: _ }
-        | wp::V128Store16Lane { memarg: _, lane: _ }
-        | wp::V128Store32Lane { memarg: _, lane: _ }
-        | wp::V128Store64Lane { memarg: _, lane: _ }
-        | wp::V128Const { value: _ }
-        | wp::I8x16Shuffle { lanes: _ }
-        | wp::I8x16ExtractLaneS { lane: _ }
-        | wp::I8x16ExtractLaneU { lane: _ }
-        | wp::I8x16ReplaceLane { lane: _ }
-        | wp::I16x8ExtractLaneS { lane: _ }
-        | wp::I16x8ExtractLaneU { lane: _ }
-        | wp::I16x8ReplaceLane { lane: _ }
-        | wp::I32x4ExtractLane { lane: _ }
-        | wp::I32x4ReplaceLane { lane: _ }
-        | wp::I64x2ExtractLane { lane: _ }
-        | wp::I64x2ReplaceLane { lane: _ }
-        | wp::F32x4ExtractLane { lane: _ }
-        | wp::F32x4ReplaceLane { lane: _ }
-        | wp::F64x2ExtractLane { lane: _ }
-        | wp::F64x2ReplaceLane { lane: _ }
-        | wp::I8x16Swizzle
-        | wp::I8x16Splat
-        | wp::I16x8Splat
-        | wp::I32x4Splat
-        | wp::I64x2Splat
-        | wp::F32x4Splat
-        | wp::F64x2Splat
-        | wp::I8x16Eq
-        | wp::I8x16Ne
-        | wp::I8x16LtS
-        | wp::I8x16LtU
-        | wp::I8x16GtS
-        | wp::I8x16GtU
-        | wp::I8x16LeS
-        | wp::I8x16LeU
-        | wp::I8x16GeS
-        | wp::I8x16GeU
-        | wp::I16x8Eq
-        | wp::I16x8Ne
-        | wp::I16x8LtS
-        | wp::I16x8LtU
-        | wp::I16x8GtS
-        | wp::I16x8GtU
-        | wp::I16x8LeS
-        | wp::I16x8LeU
-        | wp::I16x8GeS
-        | wp::I16x8GeU
-        | wp::I32x4Eq
-        | wp::I32x4Ne
-        | wp::I32x4LtS
-        | wp::I32x4LtU
-        | wp::I32x4GtS
-        | wp::I32x4GtU
-        | wp::I32x4LeS
-        | wp::I32x4LeU
-        | wp::I32x4GeS
-        | wp::I32x4GeU
-        | wp::I64x2Eq
-        | wp::I64x2Ne
-        | wp::I64x2LtS
-        | wp::I64x2GtS
-        | wp::I64x2LeS
-        | wp::I64x2GeS
-        | wp::F32x4Eq
-        | wp::F32x4Ne
-        | wp::F32x4Lt
-        | wp::F32x4Gt
-        | wp::F32x4Le
-        | wp::F32x4Ge
-        | wp::F64x2Eq
-        | wp::F64x2Ne
-        | wp::F64x2Lt
-        | wp::F64x2Gt
-        | wp::F64x2Le
-        | wp::F64x2Ge
-        | wp::V128Not
-        | wp::V128And
-        | wp::V128AndNot
-        | wp::V128Or
-        | wp::V128Xor
-        | wp::V128Bitselect
-        | wp::V128AnyTrue
-        | wp::I8x16Abs
-        | wp::I8x16Neg
-        | wp::I8x16Popcnt
-        | wp::I8x16AllTrue
-        | wp::I8x16Bitmask
-        | wp::I8x16NarrowI16x8S
-        | wp::I8x16NarrowI16x8U
-        | wp::I8x16Shl
-        | wp::I8x16ShrS
-        | wp::I8x16ShrU
-        | wp::I8x16Add
-        | wp::I8x16AddSatS
-        | wp::I8x16AddSatU
-        | wp::I8x16Sub
-        | wp::I8x16SubSatS
-        | wp::I8x16SubSatU
-        | wp::I8x16MinS
-        | wp::I8x16MinU
-        | wp::I8x16MaxS
-        | wp::I8x16MaxU
-        | wp::I8x16AvgrU
-        | wp::I16x8ExtAddPairwiseI8x16S
-        | wp::I16x8ExtAddPairwiseI8x16U
-        | wp::I16x8Abs
-        | wp::I16x8Neg
-        | wp::I16x8Q15MulrSatS
-        | wp::I16x8AllTrue
-        | wp::I16x8Bitmask
-        | wp::I16x8NarrowI32x4S
-        | wp::I16x8NarrowI32x4U
-        | wp::I16x8ExtendLowI8x16S
-        | wp::I16x8ExtendHighI8x16S
-        | wp::I16x8ExtendLowI8x16U
-        | wp::I16x8ExtendHighI8x16U
-        | wp::I16x8Shl
-        | wp::I16x8ShrS
-        | wp::I16x8ShrU
-        | wp::I16x8Add
-        | wp::I16x8AddSatS
-        | wp::I16x8AddSatU
-        | wp::I16x8Sub
-        | wp::I16x8SubSatS
-        | wp::I16x8SubSatU
-        | wp::I16x8Mul
-        | wp::I16x8MinS
-        | wp::I16x8MinU
-        | wp::I16x8MaxS
-        | wp::I16x8MaxU
-        | wp::I16x8AvgrU
-        | wp::I16x8ExtMulLowI8x16S
-        | wp::I16x8ExtMulHighI8x16S
-        | wp::I16x8ExtMulLowI8x16U
-        | wp::I16x8ExtMulHighI8x16U
-        | wp::I32x4ExtAddPairwiseI16x8S
-        | wp::I32x4ExtAddPairwiseI16x8U
-        | wp::I32x4Abs
-        | wp::I32x4Neg
-        | wp::I32x4AllTrue
-        | wp::I32x4Bitmask
-        | wp::I32x4ExtendLowI16x8S
-        | wp::I32x4ExtendHighI16x8S
-        | wp::I32x4ExtendLowI16x8U
-        | wp::I32x4ExtendHighI16x8U
-        | wp::I32x4Shl
-        | wp::I32x4ShrS
-        | wp::I32x4ShrU
-        | wp::I32x4Add
-        | wp::I32x4Sub
-        | wp::I32x4Mul
-        | wp::I32x4MinS
-        | wp::I32x4MinU
-        | wp::I32x4MaxS
-        | wp::I32x4MaxU
-        | wp::I32x4DotI16x8S
-        | wp::I32x4ExtMulLowI16x8S
-        | wp::I32x4ExtMulHighI16x8S
-        | wp::I32x4ExtMulLowI16x8U
-        | wp::I32x4ExtMulHighI16x8U
-        | wp::I64x2Abs
-        | wp::I64x2Neg
-        | wp::I64x2AllTrue
-        | wp::I64x2Bitmask
-        | wp::I64x2ExtendLowI32x4S
-        | wp::I64x2ExtendHighI32x4S
-        | wp::I64x2ExtendLowI32x4U
-        | wp::I64x2ExtendHighI32x4U
-        | wp::I64x2Shl
-        | wp::I64x2ShrS
-        | wp::I64x2ShrU
-        | wp::I64x2Add
-        | wp::I64x2Sub
-        | wp::I64x2Mul
-        | wp::I64x2ExtMulLowI32x4S
-        | wp::I64x2ExtMulHighI32x4S
-        | wp::I64x2ExtMulLowI32x4U
-        | wp::I64x2ExtMulHighI32x4U
-        | wp::F32x4Ceil
-        | wp::F32x4Floor
-        | wp::F32x4Trunc
-        | wp::F32x4Nearest
-        | wp::F32x4Abs
-        | wp::F32x4Neg
-        | wp::F32x4Sqrt
-        | wp::F32x4Add
-        | wp::F32x4Sub
-        | wp::F32x4Mul
-        | wp::F32x4Div
-        | wp::F32x4Min
-        | wp::F32x4Max
-        | wp::F32x4PMin
-        | wp::F32x4PMax
-        | wp::F64x2Ceil
-        | wp::F64x2Floor
-        | wp::F64x2Trunc
-        | wp::F64x2Nearest
-        | wp::F64x2Abs
-        | wp::F64x2Neg
-        | wp::F64x2Sqrt
-        | wp::F64x2Add
-        | wp::F64x2Sub
-        | wp::F64x2Mul
-        | wp::F64x2Div
-        | wp::F64x2Min
-        | wp::F64x2Max
-        | wp::F64x2PMin
-        | wp::F64x2PMax
-        | wp::I32x4TruncSatF32x4S
-        | wp::I32x4TruncSatF32x4U
-        | wp::F32x4ConvertI32x4S
-        | wp::F32x4ConvertI32x4U
-        | wp::I32x4TruncSatF64x2SZero
-        | wp::I32x4TruncSatF64x2UZero
-        | wp::F64x2ConvertLowI32x4S
-        | wp::F64x2ConvertLowI32x4U
-        | wp::F32x4DemoteF64x2Zero
-        | wp::F64x2PromoteLowF32x4 => Err(ParseIssue::unsupported(offset, WasmExtension::Simd))?,
+        wp::V128Load16x4U { memarg } => {
+            simd(Load(LoadOp::V128Load16x4U, parse_memarg(memarg, metadata)))
+        }
+        wp::V128Load32x2S { memarg } => {
+            simd(Load(LoadOp::V128Load32x2S, parse_memarg(memarg, metadata)))
+        }
+        wp::V128Load32x2U { memarg } => {
+            simd(Load(LoadOp::V128Load32x2U, parse_memarg(memarg, metadata)))
+        }
+        wp::V128Load8Splat { memarg } => {
+            simd(Load(LoadOp::V128Load8Splat, parse_memarg(memarg, metadata)))
+        }
+        wp::V128Load16Splat { memarg } => simd(Load(
+            LoadOp::V128Load16Splat,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::V128Load32Splat { memarg } => simd(Load(
+            LoadOp::V128Load32Splat,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::V128Load64Splat { memarg } => simd(Load(
+            LoadOp::V128Load64Splat,
+            parse_memarg(memarg, metadata),
+        )),
+        wp::V128Load32Zero { memarg } => {
+            simd(Load(LoadOp::V128Load32Zero, parse_memarg(memarg, metadata)))
+        }
+        wp::V128Load64Zero { memarg } => {
+            simd(Load(LoadOp::V128Load64Zero, parse_memarg(memarg, metadata)))
+        }
+        wp::V128Store { memarg } => simd(Store(StoreOp::V128Store, parse_memarg(memarg, metadata))),
+        wp::V128Load8Lane { memarg, lane } => simd(LoadLane(
+            LoadLaneOp::V128Load8Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Load16Lane { memarg, lane } => simd(LoadLane(
+            LoadLaneOp::V128Load16Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Load32Lane { memarg, lane } => simd(LoadLane(
+            LoadLaneOp::V128Load32Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Load64Lane { memarg, lane } => simd(LoadLane(
+            LoadLaneOp::V128Load64Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Store8Lane { memarg, lane } => simd(StoreLane(
+            StoreLaneOp::V128Store8Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Store16Lane { memarg, lane } => simd(StoreLane(
+            StoreLaneOp::V128Store16Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Store32Lane { memarg, lane } => simd(StoreLane(
+            StoreLaneOp::V128Store32Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Store64Lane { memarg, lane } => simd(StoreLane(
+            StoreLaneOp::V128Store64Lane,
+            parse_memarg(memarg, metadata),
+            lane,
+        )),
+        wp::V128Const { value } => simd(Const(Val::V128(V128(*value.bytes())))),
+        wp::I8x16Shuffle { lanes } => simd(I8x16Shuffle(lanes)),
+        wp::I8x16ExtractLaneS { lane } => simd(ExtractLane(ExtractLaneOp::I8x16ExtractLaneS, lane)),
+        wp::I8x16ExtractLaneU { lane } => simd(ExtractLane(ExtractLaneOp::I8x16ExtractLaneU, lane)),
+        wp::I16x8ExtractLaneS { lane } => simd(ExtractLane(ExtractLaneOp::I16x8ExtractLaneS, lane)),
+        wp::I16x8ExtractLaneU { lane } => simd(ExtractLane(ExtractLaneOp::I16x8ExtractLaneU, lane)),
+        wp::I32x4ExtractLane { lane } => simd(ExtractLane(ExtractLaneOp::I32x4ExtractLane, lane)),
+        wp::I64x2ExtractLane { lane } => simd(ExtractLane(ExtractLaneOp::I64x2ExtractLane, lane)),
+        wp::F32x4ExtractLane { lane } => simd(ExtractLane(ExtractLaneOp::F32x4ExtractLane, lane)),
+        wp::F64x2ExtractLane { lane } => simd(ExtractLane(ExtractLaneOp::F64x2ExtractLane, lane)),
+        wp::I8x16ReplaceLane { lane } => simd(ReplaceLane(ReplaceLaneOp::I8x16ReplaceLane, lane)),
+        wp::I16x8ReplaceLane { lane } => simd(ReplaceLane(ReplaceLaneOp::I16x8ReplaceLane, lane)),
+        wp::I32x4ReplaceLane { lane } => simd(ReplaceLane(ReplaceLaneOp::I32x4ReplaceLane, lane)),
+        wp::I64x2ReplaceLane { lane } => simd(ReplaceLane(ReplaceLaneOp::I64x2ReplaceLane, lane)),
+        wp::F32x4ReplaceLane { lane } => simd(ReplaceLane(ReplaceLaneOp::F32x4ReplaceLane, lane)),
+        wp::F64x2ReplaceLane { lane } => simd(ReplaceLane(ReplaceLaneOp::F64x2ReplaceLane, lane)),
+        wp::V128Bitselect => simd(V128Bitselect),
+        wp::I8x16Splat => simd(Unary(UnaryOp::I8x16Splat)),
+        wp::I16x8Splat => simd(Unary(UnaryOp::I16x8Splat)),
+        wp::I32x4Splat => simd(Unary(UnaryOp::I32x4Splat)),
+        wp::I64x2Splat => simd(Unary(UnaryOp::I64x2Splat)),
+        wp::F32x4Splat => simd(Unary(UnaryOp::F32x4Splat)),
+        wp::F64x2Splat => simd(Unary(UnaryOp::F64x2Splat)),
+        wp::V128Not => simd(Unary(UnaryOp::V128Not)),
+        wp::V128AnyTrue => simd(Unary(UnaryOp::V128AnyTrue)),
+        wp::I8x16Abs => simd(Unary(UnaryOp::I8x16Abs)),
+        wp::I8x16Neg => simd(Unary(UnaryOp::I8x16Neg)),
+        wp::I8x16Popcnt => simd(Unary(UnaryOp::I8x16Popcnt)),
+        wp::I8x16AllTrue => simd(Unary(UnaryOp::I8x16AllTrue)),
+        wp::I8x16Bitmask => simd(Unary(UnaryOp::I8x16Bitmask)),
+        wp::I16x8ExtAddPairwiseI8x16S => simd(Unary(UnaryOp::I16x8ExtAddPairwiseI8x16S)),
+        wp::I16x8ExtAddPairwiseI8x16U => simd(Unary(UnaryOp::I16x8ExtAddPairwiseI8x16U)),
+        wp::I16x8Abs => simd(Unary(UnaryOp::I16x8Abs)),
+        wp::I16x8Neg => simd(Unary(UnaryOp::I16x8Neg)),
+        wp::I16x8AllTrue => simd(Unary(UnaryOp::I16x8AllTrue)),
+        wp::I16x8Bitmask => simd(Unary(UnaryOp::I16x8Bitmask)),
+        wp::I16x8ExtendLowI8x16S => simd(Unary(UnaryOp::I16x8ExtendLowI8x16S)),
+        wp::I16x8ExtendHighI8x16S => simd(Unary(UnaryOp::I16x8ExtendHighI8x16S)),
+        wp::I16x8ExtendLowI8x16U => simd(Unary(UnaryOp::I16x8ExtendLowI8x16U)),
+        wp::I16x8ExtendHighI8x16U => simd(Unary(UnaryOp::I16x8ExtendHighI8x16U)),
+        wp::I32x4ExtAddPairwiseI16x8S => simd(Unary(UnaryOp::I32x4ExtAddPairwiseI16x8S)),
+        wp::I32x4ExtAddPairwiseI16x8U => simd(Unary(UnaryOp::I32x4ExtAddPairwiseI16x8U)),
+        wp::I32x4Abs => simd(Unary(UnaryOp::I32x4Abs)),
+        wp::I32x4Neg => simd(Unary(UnaryOp::I32x4Neg)),
+        wp::I32x4AllTrue => simd(Unary(UnaryOp::I32x4AllTrue)),
+        wp::I32x4Bitmask => simd(Unary(UnaryOp::I32x4Bitmask)),
+        wp::I32x4ExtendLowI16x8S => simd(Unary(UnaryOp::I32x4ExtendLowI16x8S)),
+        wp::I32x4ExtendHighI16x8S => simd(Unary(UnaryOp::I32x4ExtendHighI16x8S)),
+        wp::I32x4ExtendLowI16x8U => simd(Unary(UnaryOp::I32x4ExtendLowI16x8U)),
+        wp::I32x4ExtendHighI16x8U => simd(Unary(UnaryOp::I32x4ExtendHighI16x8U)),
+        wp::I64x2Abs => simd(Unary(UnaryOp::I64x2Abs)),
+        wp::I64x2Neg => simd(Unary(UnaryOp::I64x2Neg)),
+        wp::I64x2AllTrue => simd(Unary(UnaryOp::I64x2AllTrue)),
+        wp::I64x2Bitmask => simd(Unary(UnaryOp::I64x2Bitmask)),
+        wp::I64x2ExtendLowI32x4S => simd(Unary(UnaryOp::I64x2ExtendLowI32x4S)),
+        wp::I64x2ExtendHighI32x4S => simd(Unary(UnaryOp::I64x2ExtendHighI32x4S)),
+        wp::I64x2ExtendLowI32x4U => simd(Unary(UnaryOp::I64x2ExtendLowI32x4U)),
+        wp::I64x2ExtendHighI32x4U => simd(Unary(UnaryOp::I64x2ExtendHighI32x4U)),
+        wp::F32x4Ceil => simd(Unary(UnaryOp::F32x4Ceil)),
+        wp::F32x4Floor => simd(Unary(UnaryOp::F32x4Floor)),
+        wp::F32x4Trunc => simd(Unary(UnaryOp::F32x4Trunc)),
+        wp::F32x4Nearest => simd(Unary(UnaryOp::F32x4Nearest)),
+        wp::F32x4Abs => simd(Unary(UnaryOp::F32x4Abs)),
+        wp::F32x4Neg => simd(Unary(UnaryOp::F32x4Neg)),
+        wp::F32x4Sqrt => simd(Unary(UnaryOp::F32x4Sqrt)),
+        wp::F64x2Ceil => simd(Unary(UnaryOp::F64x2Ceil)),
+        wp::F64x2Floor => simd(Unary(UnaryOp::F64x2Floor)),
+        wp::F64x2Trunc => simd(Unary(UnaryOp::F64x2Trunc)),
+        wp::F64x2Nearest => simd(Unary(UnaryOp::F64x2Nearest)),
+        wp::F64x2Abs => simd(Unary(UnaryOp::F64x2Abs)),
+        wp::F64x2Neg => simd(Unary(UnaryOp::F64x2Neg)),
+        wp::F64x2Sqrt => simd(Unary(UnaryOp::F64x2Sqrt)),
+        wp::I32x4TruncSatF32x4S => simd(Unary(UnaryOp::I32x4TruncSatF32x4S)),
+        wp::I32x4TruncSatF32x4U => simd(Unary(UnaryOp::I32x4TruncSatF32x4U)),
+        wp::F32x4ConvertI32x4S => simd(Unary(UnaryOp::F32x4ConvertI32x4S)),
+        wp::F32x4ConvertI32x4U => simd(Unary(UnaryOp::F32x4ConvertI32x4U)),
+        wp::I32x4TruncSatF64x2SZero => simd(Unary(UnaryOp::I32x4TruncSatF64x2SZero)),
+        wp::I32x4TruncSatF64x2UZero => simd(Unary(UnaryOp::I32x4TruncSatF64x2UZero)),
+        wp::F64x2ConvertLowI32x4S => simd(Unary(UnaryOp::F64x2ConvertLowI32x4S)),
+        wp::F64x2ConvertLowI32x4U => simd(Unary(UnaryOp::F64x2ConvertLowI32x4U)),
+        wp::F32x4DemoteF64x2Zero => simd(Unary(UnaryOp::F32x4DemoteF64x2Zero)),
+        wp::F64x2PromoteLowF32x4 => simd(Unary(UnaryOp::F64x2PromoteLowF32x4)),
+        wp::I8x16Eq => simd(Binary(BinaryOp::I8x16Eq)),
+        wp::I8x16Ne => simd(Binary(BinaryOp::I8x16Ne)),
+        wp::I8x16LtS => simd(Binary(BinaryOp::I8x16LtS)),
+        wp::I8x16LtU => simd(Binary(BinaryOp::I8x16LtU)),
+        wp::I8x16GtS => simd(Binary(BinaryOp::I8x16GtS)),
+        wp::I8x16GtU => simd(Binary(BinaryOp::I8x16GtU)),
+        wp::I8x16LeS => simd(Binary(BinaryOp::I8x16LeS)),
+        wp::I8x16LeU => simd(Binary(BinaryOp::I8x16LeU)),
+        wp::I8x16GeS => simd(Binary(BinaryOp::I8x16GeS)),
+        wp::I8x16GeU => simd(Binary(BinaryOp::I8x16GeU)),
+        wp::I16x8Eq => simd(Binary(BinaryOp::I16x8Eq)),
+        wp::I16x8Ne => simd(Binary(BinaryOp::I16x8Ne)),
+        wp::I16x8LtS => simd(Binary(BinaryOp::I16x8LtS)),
+        wp::I16x8LtU => simd(Binary(BinaryOp::I16x8LtU)),
+        wp::I16x8GtS => simd(Binary(BinaryOp::I16x8GtS)),
+        wp::I16x8GtU => simd(Binary(BinaryOp::I16x8GtU)),
+        wp::I16x8LeS => simd(Binary(BinaryOp::I16x8LeS)),
+        wp::I16x8LeU => simd(Binary(BinaryOp::I16x8LeU)),
+        wp::I16x8GeS => simd(Binary(BinaryOp::I16x8GeS)),
+        wp::I16x8GeU => simd(Binary(BinaryOp::I16x8GeU)),
+        wp::I32x4Eq => simd(Binary(BinaryOp::I32x4Eq)),
+        wp::I32x4Ne => simd(Binary(BinaryOp::I32x4Ne)),
+        wp::I32x4LtS => simd(Binary(BinaryOp::I32x4LtS)),
+        wp::I32x4LtU => simd(Binary(BinaryOp::I32x4LtU)),
+        wp::I32x4GtS => simd(Binary(BinaryOp::I32x4GtS)),
+        wp::I32x4GtU => simd(Binary(BinaryOp::I32x4GtU)),
+        wp::I32x4LeS => simd(Binary(BinaryOp::I32x4LeS)),
+        wp::I32x4LeU => simd(Binary(BinaryOp::I32x4LeU)),
+        wp::I32x4GeS => simd(Binary(BinaryOp::I32x4GeS)),
+        wp::I32x4GeU => simd(Binary(BinaryOp::I32x4GeU)),
+        wp::I64x2Eq => simd(Binary(BinaryOp::I64x2Eq)),
+        wp::I64x2Ne => simd(Binary(BinaryOp::I64x2Ne)),
+        wp::I64x2LtS => simd(Binary(BinaryOp::I64x2LtS)),
+        wp::I64x2GtS => simd(Binary(BinaryOp::I64x2GtS)),
+        wp::I64x2LeS => simd(Binary(BinaryOp::I64x2LeS)),
+        wp::I64x2GeS => simd(Binary(BinaryOp::I64x2GeS)),
+        wp::F32x4Eq => simd(Binary(BinaryOp::F32x4Eq)),
+        wp::F32x4Ne => simd(Binary(BinaryOp::F32x4Ne)),
+        wp::F32x4Lt => simd(Binary(BinaryOp::F32x4Lt)),
+        wp::F32x4Gt => simd(Binary(BinaryOp::F32x4Gt)),
+        wp::F32x4Le => simd(Binary(BinaryOp::F32x4Le)),
+        wp::F32x4Ge => simd(Binary(BinaryOp::F32x4Ge)),
+        wp::F64x2Eq => simd(Binary(BinaryOp::F64x2Eq)),
+        wp::F64x2Ne => simd(Binary(BinaryOp::F64x2Ne)),
+        wp::F64x2Lt => simd(Binary(BinaryOp::F64x2Lt)),
+        wp::F64x2Gt => simd(Binary(BinaryOp::F64x2Gt)),
+        wp::F64x2Le => simd(Binary(BinaryOp::F64x2Le)),
+        wp::F64x2Ge => simd(Binary(BinaryOp::F64x2Ge)),
+        wp::V128And => simd(Binary(BinaryOp::V128And)),
+        wp::V128AndNot => simd(Binary(BinaryOp::V128AndNot)),
+        wp::V128Or => simd(Binary(BinaryOp::V128Or)),
+        wp::V128Xor => simd(Binary(BinaryOp::V128Xor)),
+        wp::I8x16NarrowI16x8S => simd(Binary(BinaryOp::I8x16NarrowI16x8S)),
+        wp::I8x16NarrowI16x8U => simd(Binary(BinaryOp::I8x16NarrowI16x8U)),
+        wp::I8x16Shl => simd(Binary(BinaryOp::I8x16Shl)),
+        wp::I8x16ShrS => simd(Binary(BinaryOp::I8x16ShrS)),
+        wp::I8x16ShrU => simd(Binary(BinaryOp::I8x16ShrU)),
+        wp::I8x16Add => simd(Binary(BinaryOp::I8x16Add)),
+        wp::I8x16AddSatS => simd(Binary(BinaryOp::I8x16AddSatS)),
+        wp::I8x16AddSatU => simd(Binary(BinaryOp::I8x16AddSatU)),
+        wp::I8x16Sub => simd(Binary(BinaryOp::I8x16Sub)),
+        wp::I8x16SubSatS => simd(Binary(BinaryOp::I8x16SubSatS)),
+        wp::I8x16SubSatU => simd(Binary(BinaryOp::I8x16SubSatU)),
+        wp::I8x16MinS => simd(Binary(BinaryOp::I8x16MinS)),
+        wp::I8x16MinU => simd(Binary(BinaryOp::I8x16MinU)),
+        wp::I8x16MaxS => simd(Binary(BinaryOp::I8x16MaxS)),
+        wp::I8x16MaxU => simd(Binary(BinaryOp::I8x16MaxU)),
+        wp::I8x16AvgrU => simd(Binary(BinaryOp::I8x16AvgrU)),
+        wp::I8x16Swizzle => simd(Binary(BinaryOp::I8x16Swizzle)),
+        wp::I16x8Q15MulrSatS => simd(Binary(BinaryOp::I16x8Q15MulrSatS)),
+        wp::I16x8NarrowI32x4S => simd(Binary(BinaryOp::I16x8NarrowI32x4S)),
+        wp::I16x8NarrowI32x4U => simd(Binary(BinaryOp::I16x8NarrowI32x4U)),
+        wp::I16x8Shl => simd(Binary(BinaryOp::I16x8Shl)),
+        wp::I16x8ShrS => simd(Binary(BinaryOp::I16x8ShrS)),
+        wp::I16x8ShrU => simd(Binary(BinaryOp::I16x8ShrU)),
+        wp::I16x8Add => simd(Binary(BinaryOp::I16x8Add)),
+        wp::I16x8AddSatS => simd(Binary(BinaryOp::I16x8AddSatS)),
+        wp::I16x8AddSatU => simd(Binary(BinaryOp::I16x8AddSatU)),
+        wp::I16x8Sub => simd(Binary(BinaryOp::I16x8Sub)),
+        wp::I16x8SubSatS => simd(Binary(BinaryOp::I16x8SubSatS)),
+        wp::I16x8SubSatU => simd(Binary(BinaryOp::I16x8SubSatU)),
+        wp::I16x8Mul => simd(Binary(BinaryOp::I16x8Mul)),
+        wp::I16x8MinS => simd(Binary(BinaryOp::I16x8MinS)),
+        wp::I16x8MinU => simd(Binary(BinaryOp::I16x8MinU)),
+        wp::I16x8MaxS => simd(Binary(BinaryOp::I16x8MaxS)),
+        wp::I16x8MaxU => simd(Binary(BinaryOp::I16x8MaxU)),
+        wp::I16x8AvgrU => simd(Binary(BinaryOp::I16x8AvgrU)),
+        wp::I16x8ExtMulLowI8x16S => simd(Binary(BinaryOp::I16x8ExtMulLowI8x16S)),
+        wp::I16x8ExtMulHighI8x16S => simd(Binary(BinaryOp::I16x8ExtMulHighI8x16S)),
+        wp::I16x8ExtMulLowI8x16U => simd(Binary(BinaryOp::I16x8ExtMulLowI8x16U)),
+        wp::I16x8ExtMulHighI8x16U => simd(Binary(BinaryOp::I16x8ExtMulHighI8x16U)),
+        wp::I32x4Shl => simd(Binary(BinaryOp::I32x4Shl)),
+        wp::I32x4ShrS => simd(Binary(BinaryOp::I32x4ShrS)),
+        wp::I32x4ShrU => simd(Binary(BinaryOp::I32x4ShrU)),
+        wp::I32x4Add => simd(Binary(BinaryOp::I32x4Add)),
+        wp::I32x4Sub => simd(Binary(BinaryOp::I32x4Sub)),
+        wp::I32x4Mul => simd(Binary(BinaryOp::I32x4Mul)),
+        wp::I32x4MinS => simd(Binary(BinaryOp::I32x4MinS)),
+        wp::I32x4MinU => simd(Binary(BinaryOp::I32x4MinU)),
+        wp::I32x4MaxS => simd(Binary(BinaryOp::I32x4MaxS)),
+        wp::I32x4MaxU => simd(Binary(BinaryOp::I32x4MaxU)),
+        wp::I32x4DotI16x8S => simd(Binary(BinaryOp::I32x4DotI16x8S)),
+        wp::I32x4ExtMulLowI16x8S => simd(Binary(BinaryOp::I32x4ExtMulLowI16x8S)),
+        wp::I32x4ExtMulHighI16x8S => simd(Binary(BinaryOp::I32x4ExtMulHighI16x8S)),
+        wp::I32x4ExtMulLowI16x8U => simd(Binary(BinaryOp::I32x4ExtMulLowI16x8U)),
+        wp::I32x4ExtMulHighI16x8U => simd(Binary(BinaryOp::I32x4ExtMulHighI16x8U)),
+        wp::I64x2Shl => simd(Binary(BinaryOp::I64x2Shl)),
+        wp::I64x2ShrS => simd(Binary(BinaryOp::I64x2ShrS)),
+        wp::I64x2ShrU => simd(Binary(BinaryOp::I64x2ShrU)),
+        wp::I64x2Add => simd(Binary(BinaryOp::I64x2Add)),
+        wp::I64x2Sub => simd(Binary(BinaryOp::I64x2Sub)),
+        wp::I64x2Mul => simd(Binary(BinaryOp::I64x2Mul)),
+        wp::I64x2ExtMulLowI32x4S => simd(Binary(BinaryOp::I64x2ExtMulLowI32x4S)),
+        wp::I64x2ExtMulHighI32x4S => simd(Binary(BinaryOp::I64x2ExtMulHighI32x4S)),
+        wp::I64x2ExtMulLowI32x4U => simd(Binary(BinaryOp::I64x2ExtMulLowI32x4U)),
+        wp::I64x2ExtMulHighI32x4U => simd(Binary(BinaryOp::I64x2ExtMulHighI32x4U)),
+        wp::F32x4Add => simd(Binary(BinaryOp::F32x4Add)),
+        wp::F32x4Sub => simd(Binary(BinaryOp::F32x4Sub)),
+        wp::F32x4Mul => simd(Binary(BinaryOp::F32x4Mul)),
+        wp::F32x4Div => simd(Binary(BinaryOp::F32x4Div)),
+        wp::F32x4Min => simd(Binary(BinaryOp::F32x4Min)),
+        wp::F32x4Max => simd(Binary(BinaryOp::F32x4Max)),
+        wp::F32x4PMin => simd(Binary(BinaryOp::F32x4PMin)),
+        wp::F32x4PMax => simd(Binary(BinaryOp::F32x4PMax)),
+        wp::F64x2Add => simd(Binary(BinaryOp::F64x2Add)),
+        wp::F64x2Sub => simd(Binary(BinaryOp::F64x2Sub)),
+        wp::F64x2Mul => simd(Binary(BinaryOp::F64x2Mul)),
+        wp::F64x2Div => simd(Binary(BinaryOp::F64x2Div)),
+        wp::F64x2Min => simd(Binary(BinaryOp::F64x2Min)),
+        wp::F64x2Max => simd(Binary(BinaryOp::F64x2Max)),
+        wp::F64x2PMin => simd(Binary(BinaryOp::F64x2PMin)),
+        wp::F64x2PMax => simd(Binary(BinaryOp::F64x2PMax)),
 
         wp::I8x16RelaxedSwizzle
         | wp::I32x4RelaxedTruncSatF32x4S
@@ -1206,71 +1862,110 @@ fn parse_instr(
     })
 }
 
-fn parse_memarg(memarg: wp::MemArg, parser_offset: usize) -> Result<Memarg, ParseError> {
+fn parse_memarg(memarg: wp::MemArg, metadata: &RwLock<ModuleMetadata>) -> Memarg {
     if memarg.memory != 0 {
-        Err(ParseIssue::unsupported(
-            parser_offset,
-            WasmExtension::MultiMemory,
-        ))?
+        metadata
+            .write()
+            .unwrap()
+            .add_used_extension(WasmExtension::MultiMemory);
     }
-    let offset: u32 = memarg
-        .offset
-        .try_into()
-        .map_err(|_| ParseIssue::unsupported(parser_offset, WasmExtension::Memory64))?;
-    Ok(Memarg {
+    Memarg {
         alignment_exp: memarg.align,
-        offset,
-    })
+        offset: memarg.offset,
+        memory: memarg.memory.into(),
+    }
 }
 
-fn parse_memory_ty(ty: wp::MemoryType, offset: usize) -> Result<Limits, ParseError> {
-    if ty.memory64 {
-        Err(ParseIssue::unsupported(offset, WasmExtension::Memory64))?
-    }
-    if ty.shared {
-        Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ThreadsAtomics,
-        ))?
+/// Marks the multi-memory extension as used, if the module has more than one memory.
+fn mark_multi_memory(memories: &[Memory], metadata: &RwLock<ModuleMetadata>) {
+    if memories.len() > 1 {
+        metadata
+            .write()
+            .unwrap()
+            .add_used_extension(WasmExtension::MultiMemory);
     }
-    Ok(Limits {
-        initial_size: ty
-            .initial
-            .try_into()
-            .expect("guaranteed u32 by wasmparser if !memory64"),
-        max_size: ty.maximum.map(|u| {
-            u.try_into()
-                .expect("guaranteed u32 by wasmparser if !memory64")
-        }),
-    })
 }
 
-fn parse_table_ty(ty: wp::TableType, offset: usize) -> Result<Limits, ParseError> {
-    parse_elem_ty(ty.element_type, offset)?;
-    Ok(Limits {
+fn parse_memory_ty(ty: wp::MemoryType, metadata: &RwLock<ModuleMetadata>) -> Limits {
+    if ty.shared {
+        metadata
+            .write()
+            .unwrap()
+            .add_used_extension(WasmExtension::ThreadsAtomics);
+    }
+    if ty.memory64 {
+        metadata
+            .write()
+            .unwrap()
+            .add_used_extension(WasmExtension::Memory64);
+    }
+    Limits {
         initial_size: ty.initial,
         max_size: ty.maximum,
-    })
+        shared: ty.shared,
+        memory64: ty.memory64,
+    }
 }
 
-fn parse_elem_ty(ty: wp::ValType, offset: usize) -> Result<(), ParseError> {
-    use wp::ValType::*;
-    match ty {
-        I32 | I64 | F32 | F64 => Err(ParseIssue::message(
+fn parse_table_ty(ty: wp::TableType, offset: usize) -> Result<(Limits, RefType), ParseError> {
+    let elem_type = parse_ref_ty(ty.element_type, offset)?;
+    let limits = Limits {
+        initial_size: ty.initial.into(),
+        max_size: ty.maximum.map(u64::from),
+        shared: false,
+        memory64: false,
+    };
+    Ok((limits, elem_type))
+}
+
+/// Marks the reference types extension as used, if the tables of the module go beyond the
+/// WebAssembly MVP, i.e., there are multiple tables or a table does not contain `funcref`s.
+fn mark_reference_types_tables(tables: &[Table], metadata: &RwLock<ModuleMetadata>) {
+    if tables.len() > 1
+        || tables
+            .iter()
+            .any(|table| table.elem_type != RefType::FuncRef)
+    {
+        metadata
+            .write()
+            .unwrap()
+            .add_used_extension(WasmExtension::ReferenceTypes);
+    }
+}
+
+/// Tags are only valid with the exception handling extension, so this marks it as used.
+fn parse_tag_ty(
+    ty: wp::TagType,
+    offset: usize,
+    types: &Types,
+    metadata: &RwLock<ModuleMetadata>,
+) -> Result<FunctionType, ParseError> {
+    metadata
+        .write()
+        .unwrap()
+        .add_used_extension(WasmExtension::ExceptionHandling);
+    let wp::TagKind::Exception = ty.kind;
+    let type_ = types.get(ty.func_type_idx, offset)?;
+    if !type_.results().is_empty() {
+        Err(ParseIssue::message(
             offset,
-            "only reftypes, not value types are allowed as table elements",
+            "exception tag types must not have results",
             None,
-        ))?,
-        V128 => Err(ParseIssue::message(
+        ))?
+    }
+    Ok(type_)
+}
+
+fn parse_ref_ty(ty: wp::ValType, offset: usize) -> Result<RefType, ParseError> {
+    use wp::ValType::*;
+    match ty {
+        I32 | I64 | F32 | F64 | V128 => Err(ParseIssue::message(
             offset,
-            "only reftypes, not value types are allowed as table elements",
+            "only reftypes, not value types are allowed here",
             None,
         ))?,
-        FuncRef => Ok(()),
-        ExternRef => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ReferenceTypes,
-        ))?,
+        FuncRef => Ok(RefType::FuncRef),
+        ExternRef => Ok(RefType::ExternRef),
     }
 }
 
@@ -1283,7 +1978,7 @@ fn parse_block_ty(
     use wp::BlockType::*;
     match ty {
         Empty => Ok(FunctionType::empty()),
-        Type(ty) => Ok(FunctionType::new(&[], &[parse_val_ty(ty, offset)?])),
+        Type(ty) => Ok(FunctionType::new(&[], &[parse_val_ty(ty, metadata)])),
         FuncType(type_idx) => {
             metadata
                 .write()
@@ -1294,49 +1989,45 @@ fn parse_block_ty(
     }
 }
 
-fn parse_func_ty(ty: wp::FuncType, offset: usize) -> Result<FunctionType, ParseError> {
-    let convert_tys = |tys: &[wp::ValType]| -> Result<_, ParseError> {
-        let mut smallvec: SmallVec<[ValType; 8]> = SmallVec::new();
-        for ty in tys {
-            // The `offset` for error reporting is not exactly correct, because it marks the start
-            // of the function type, not the individual wrong parameter/result type.
-            smallvec.push(parse_val_ty(*ty, offset)?);
-        }
-        Ok(smallvec)
+fn parse_func_ty(ty: wp::FuncType, metadata: &RwLock<ModuleMetadata>) -> FunctionType {
+    let convert_tys = |tys: &[wp::ValType]| -> SmallVec<[ValType; 8]> {
+        tys.iter().map(|ty| parse_val_ty(*ty, metadata)).collect()
     };
 
-    Ok(FunctionType::new(
-        &convert_tys(ty.params())?,
-        &convert_tys(ty.results())?,
-    ))
+    if ty.results().len() > 1 {
+        metadata
+            .write()
+            .unwrap()
+            .add_used_extension(WasmExtension::MultiValue);
+    }
+
+    FunctionType::new(&convert_tys(ty.params()), &convert_tys(ty.results()))
 }
 
-fn parse_global_ty(ty: wp::GlobalType, offset: usize) -> Result<GlobalType, ParseError> {
-    Ok(GlobalType(
-        parse_val_ty(ty.content_type, offset)?,
+fn parse_global_ty(ty: wp::GlobalType, metadata: &RwLock<ModuleMetadata>) -> GlobalType {
+    GlobalType(
+        parse_val_ty(ty.content_type, metadata),
         if ty.mutable {
             Mutability::Mut
         } else {
             Mutability::Const
         },
-    ))
+    )
 }
 
-fn parse_val_ty(ty: wp::ValType, offset: usize) -> Result<ValType, ParseError> {
+fn parse_val_ty(ty: wp::ValType, metadata: &RwLock<ModuleMetadata>) -> ValType {
+    let with_extension = |ty: ValType, extension: WasmExtension| -> ValType {
+        metadata.write().unwrap().add_used_extension(extension);
+        ty
+    };
     match ty {
-        wp::ValType::I32 => Ok(ValType::I32),
-        wp::ValType::I64 => Ok(ValType::I64),
-        wp::ValType::F32 => Ok(ValType::F32),
-        wp::ValType::F64 => Ok(ValType::F64),
-        wp::ValType::V128 => Err(ParseIssue::unsupported(offset, WasmExtension::Simd))?,
-        wp::ValType::FuncRef => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ReferenceTypes,
-        ))?,
-        wp::ValType::ExternRef => Err(ParseIssue::unsupported(
-            offset,
-            WasmExtension::ReferenceTypes,
-        ))?,
+        wp::ValType::I32 => ValType::I32,
+        wp::ValType::I64 => ValType::I64,
+        wp::ValType::F32 => ValType::F32,
+        wp::ValType::F64 => ValType::F64,
+        wp::ValType::V128 => with_extension(ValType::V128, WasmExtension::Simd),
+        wp::ValType::FuncRef => with_extension(ValType::FuncRef, WasmExtension::ReferenceTypes),
+        wp::ValType::ExternRef => with_extension(ValType::ExternRef, WasmExtension::ReferenceTypes),
     }
 }
 
@@ -1345,8 +2036,10 @@ fn parse_val_ty(ty: wp::ValType, offset: usize) -> Result<ValType, ParseError> {
 fn parse_name_custom_section(
     data: &[u8],
     data_offset: usize,
+    types: &Types,
     warnings: &mut Vec<ParseIssue>,
     module: &mut Module,
+    metadata: &RwLock<ModuleMetadata>,
 ) -> Result<(), ParseIssue> {
     for name_subsection in wp::NameSectionReader::new(data, data_offset) {
         use wp::Name;
@@ -1412,19 +2105,98 @@ fn parse_name_custom_section(
                     }
                 }
             }
-            Name::Label(name_map) => warnings.push(ParseIssue::unsupported(
-                name_map.range().start,
-                WasmExtension::ExtendedNameSection,
-            )),
-            Name::Type(name_map)
-            | Name::Table(name_map)
-            | Name::Memory(name_map)
-            | Name::Global(name_map)
-            | Name::Element(name_map)
-            | Name::Data(name_map) => warnings.push(ParseIssue::unsupported(
-                name_map.range().start,
-                WasmExtension::ExtendedNameSection,
-            )),
+            Name::Label(indirect_name_map) => {
+                metadata
+                    .write()
+                    .unwrap()
+                    .add_used_extension(WasmExtension::ExtendedNameSection);
+                for elem in indirect_name_map.into_iter_with_offsets() {
+                    let (
+                        offset,
+                        wp::IndirectNaming {
+                            index: function_index,
+                            names,
+                        },
+                    ) = elem?;
+                    let function = module
+                        .functions
+                        .get_mut(u32_to_usize(function_index))
+                        .ok_or_else(|| ParseIssue::index(offset, function_index, "function"))?;
+                    let Some(code) = function.code_mut() else {
+                        warnings.push(ParseIssue::message(
+                            offset,
+                            "name section: label names for imported function",
+                            None,
+                        ));
+                        continue;
+                    };
+
+                    for elem in names {
+                        // Label indices are not checked against the number of blocks in the body,
+                        // since that would require decoding lazily parsed bodies.
+                        let wp::Naming {
+                            index: label_index,
+                            name,
+                        } = elem?;
+                        let label_index = u32_to_usize(label_index);
+                        if code.label_names.len() <= label_index {
+                            code.label_names.resize(label_index + 1, None);
+                        }
+                        code.label_names[label_index] = Some(name.to_string());
+                    }
+                }
+            }
+            Name::Type(name_map) => {
+                metadata
+                    .write()
+                    .unwrap()
+                    .add_used_extension(WasmExtension::ExtendedNameSection);
+                for elem in name_map.into_iter_with_offsets() {
+                    let (offset, wp::Naming { index, name }) = elem?;
+                    let type_ = *types
+                        .as_slice()
+                        .get(u32_to_usize(index))
+                        .ok_or_else(|| ParseIssue::index(offset, index, "type"))?;
+                    // Structurally equal types are not distinguished in the AST, so keep the first name.
+                    module
+                        .type_names
+                        .entry(type_)
+                        .or_insert_with(|| name.to_string());
+                }
+            }
+            Name::Table(name_map) => {
+                parse_name_map(name_map, &mut module.tables, "table", metadata, |table| {
+                    &mut table.name
+                })?
+            }
+            Name::Memory(name_map) => parse_name_map(
+                name_map,
+                &mut module.memories,
+                "memory",
+                metadata,
+                |memory| &mut memory.name,
+            )?,
+            Name::Global(name_map) => parse_name_map(
+                name_map,
+                &mut module.globals,
+                "global",
+                metadata,
+                |global| &mut global.name,
+            )?,
+            Name::Element(name_map) => parse_name_map(
+                name_map,
+                &mut module.elements,
+                "element segment",
+                metadata,
+                |element| &mut element.name,
+            )?,
+            Name::Data(name_map) => parse_name_map(
+                name_map,
+                &mut module.data,
+                "data segment",
+                metadata,
+                |data| &mut data.name,
+            )?,
             Name::Unknown {
                 ty: _,
                 data: _,
@@ -1440,10 +2212,40 @@ fn parse_name_custom_section(
     Ok(())
 }
 
+/// Sets the names of `items` from a subsection of the extended name section.
+fn parse_name_map<T>(
+    name_map: wp::NameMap,
+    items: &mut [T],
+    index_space: &'static str,
+    metadata: &RwLock<ModuleMetadata>,
+    name: impl Fn(&mut T) -> &mut Option<String>,
+) -> Result<(), ParseIssue> {
+    metadata
+        .write()
+        .unwrap()
+        .add_used_extension(WasmExtension::ExtendedNameSection);
+    for elem in name_map.into_iter_with_offsets() {
+        let (
+            offset,
+            wp::Naming {
+                index,
+                name: item_name,
+            },
+        ) = elem?;
+        let item = items
+            .get_mut(u32_to_usize(index))
+            .ok_or_else(|| ParseIssue::index(offset, index, index_space))?;
+        *name(item) = Some(item_name.to_string());
+    }
+    Ok(())
+}
+
 // Wrapper for type map, to offer some convenience like:
 // - u32 indices (which we get from wasmparser) instead of usize (which Vec expects)
 // - checking that type section exists only a single time and type index is valid
-struct Types(Option<Vec<FunctionType>>);
+/// Shared (via `Arc`) with lazily decoded function bodies, see `RawBody`.
+#[derive(Debug, Clone)]
+struct Types(Option<Arc<Vec<FunctionType>>>);
 
 impl Types {
     /// Initial state, where the type section has not been parsed yet.
@@ -1457,7 +2259,9 @@ impl Types {
         count: u32,
         type_section_offset: usize,
     ) -> Result<(), ParseError> {
-        let prev_state = self.0.replace(Vec::with_capacity(u32_to_usize(count)));
+        let prev_state = self
+            .0
+            .replace(Arc::new(Vec::with_capacity(u32_to_usize(count))));
         match prev_state {
             Some(_) => Err(ParseIssue::message(
                 type_section_offset,
@@ -1469,22 +2273,27 @@ impl Types {
     }
 
     pub fn add(&mut self, ty: FunctionType) {
-        self.0
-            .as_mut()
-            .expect("type section should be present, we are in the process of parsing it")
-            .push(ty);
+        Arc::get_mut(
+            self.0
+                .as_mut()
+                .expect("type section should be present, we are in the process of parsing it"),
+        )
+        .expect("types should not be shared before the type section is complete")
+        .push(ty);
     }
 
     pub fn get(&self, index: u32, index_offset: usize) -> Result<FunctionType, ParseError> {
         Ok(self
-            .0
-            .as_deref()
-            // No type section == empty type vector.
-            .unwrap_or(&[])
+            .as_slice()
             .get(u32_to_usize(index))
             .cloned()
             .ok_or_else(|| ParseIssue::index(index_offset, index, "type"))?)
     }
+
+    pub fn as_slice(&self) -> &[FunctionType] {
+        // No type section == empty type vector.
+        self.0.as_deref().map(Vec::as_slice).unwrap_or(&[])
+    }
 }
 
 fn u32_to_usize(u: u32) -> usize {
@@ -0,0 +1,1734 @@
+//! Parser for the WebAssembly text format (`.wat`), producing our AST.
+//!
+//! Supports all module fields (including the inline abbreviations for imports, exports, and
+//! element and data segments of tables and memories), flat and folded instructions, and symbolic
+//! identifiers for all index spaces and for block labels.
+//! Identifiers of the module, functions, parameters, and locals are kept as debug names in the
+//! AST (like `wat2wasm --debug-names` does), such that `print_wat.rs` prints them again.
+//!
+//! Most instructions are parsed by first resolving their symbolic identifiers to numeric indices
+//! and then handing them to `Instr::from_str`.
+//! Instructions, whose immediates differ in syntax from our `Display`/`FromStr` format (e.g.,
+//! block types, which are type uses in the text format), are parsed here directly.
+//!
+//! Limitations:
+//! - Only single modules, not the `.wast` script format of the specification tests.
+//! - Function types that only appear in type uses (and not in a `(type ...)` definition) are not
+//!   added to the type index space, so they cannot be referred to by a numeric type index.
+//! - `Module::metadata` (i.e., the used extensions) is not filled.
+
+use std::str::FromStr;
+
+use rustc_hash::FxHashMap;
+
+use crate::*;
+
+pub fn parse_module(text: &str) -> Result<Module> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        text,
+        tokens,
+        pos: 0,
+        names: ModuleNames::default(),
+        types: Vec::new(),
+        memory64: Vec::new(),
+        locals: Names::default(),
+        labels: Vec::new(),
+        label_names: Vec::new(),
+    };
+    parser.module()
+}
+
+/* Lexer. */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    LParen,
+    RParen,
+    /// Keywords, numbers, identifiers (starting with `$`), and all other reserved tokens.
+    Atom(&'a str),
+    /// Already with all escapes replaced, but not necessarily valid UTF-8.
+    String(Vec<u8>),
+}
+
+/// Returns all tokens of the input together with their byte offset, without whitespace and
+/// comments.
+fn tokenize(text: &str) -> Result<Vec<(Token<'_>, usize)>> {
+    let error = |offset, message: &str| WatParseError::new(text, offset, message);
+
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let start = pos;
+        match bytes[pos] {
+            b' ' | b'\t' | b'\n' | b'\r' => pos += 1,
+            b';' if bytes.get(pos + 1) == Some(&b';') => {
+                pos = text[pos..]
+                    .find('\n')
+                    .map(|newline| pos + newline)
+                    .unwrap_or(bytes.len());
+            }
+            b'(' if bytes.get(pos + 1) == Some(&b';') => {
+                // Block comments can be nested.
+                let mut depth = 0;
+                loop {
+                    match bytes.get(pos..pos + 2) {
+                        Some(b"(;") => {
+                            depth += 1;
+                            pos += 2;
+                        }
+                        Some(b";)") => {
+                            depth -= 1;
+                            pos += 2;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        Some(_) => pos += 1,
+                        None => return Err(error(start, "unterminated block comment")),
+                    }
+                }
+            }
+            b'(' => {
+                tokens.push((Token::LParen, start));
+                pos += 1;
+            }
+            b')' => {
+                tokens.push((Token::RParen, start));
+                pos += 1;
+            }
+            b'"' => {
+                pos += 1;
+                let mut string = Vec::new();
+                loop {
+                    match bytes.get(pos) {
+                        None => return Err(error(start, "unterminated string")),
+                        Some(b'"') => {
+                            pos += 1;
+                            break;
+                        }
+                        Some(b'\\') => {
+                            let escape_start = pos;
+                            pos += 2;
+                            match bytes.get(escape_start + 1) {
+                                Some(b't') => string.push(b'\t'),
+                                Some(b'n') => string.push(b'\n'),
+                                Some(b'r') => string.push(b'\r'),
+                                Some(&c @ (b'"' | b'\'' | b'\\')) => string.push(c),
+                                Some(b'u') => {
+                                    // Unicode escape, e.g., `\u{1F600}`.
+                                    let hex = text[pos..]
+                                        .strip_prefix('{')
+                                        .and_then(|rest| rest.split_once('}'))
+                                        .map(|(hex, _)| hex)
+                                        .ok_or_else(|| error(escape_start, "invalid escape"))?;
+                                    pos += hex.len() + 2;
+                                    let code_point = u32::from_str_radix(&hex.replace('_', ""), 16)
+                                        .ok()
+                                        .and_then(char::from_u32)
+                                        .ok_or_else(|| error(escape_start, "invalid escape"))?;
+                                    let mut utf8 = [0; 4];
+                                    string.extend(code_point.encode_utf8(&mut utf8).as_bytes());
+                                }
+                                Some(_) => {
+                                    let byte = text
+                                        .get(escape_start + 1..escape_start + 3)
+                                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                                        .ok_or_else(|| error(escape_start, "invalid escape"))?;
+                                    string.push(byte);
+                                    pos += 1;
+                                }
+                                None => return Err(error(start, "unterminated string")),
+                            }
+                        }
+                        Some(&byte) => {
+                            string.push(byte);
+                            pos += 1;
+                        }
+                    }
+                }
+                tokens.push((Token::String(string), start));
+            }
+            _ => {
+                let len = text[pos..]
+                    .find(|c: char| c.is_ascii_whitespace() || "()\";".contains(c))
+                    .unwrap_or(bytes.len() - pos);
+                if len == 0 {
+                    return Err(error(start, "unexpected character"));
+                }
+                pos += len;
+                tokens.push((Token::Atom(&text[start..pos]), start));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_keyword(atom: &str) -> bool {
+    atom.starts_with(|c: char| c.is_ascii_lowercase())
+}
+
+/* Identifiers and index spaces. */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexSpace {
+    Type,
+    Function,
+    Table,
+    Memory,
+    Global,
+    Tag,
+    Element,
+    Data,
+    Local,
+    Label,
+}
+
+impl IndexSpace {
+    fn name(self) -> &'static str {
+        use IndexSpace::*;
+        match self {
+            Type => "type",
+            Function => "function",
+            Table => "table",
+            Memory => "memory",
+            Global => "global",
+            Tag => "tag",
+            Element => "element segment",
+            Data => "data segment",
+            Local => "local",
+            Label => "label",
+        }
+    }
+}
+
+/// Maps identifiers (without the `$`) in one index space to their index.
+#[derive(Debug, Default)]
+struct Names<'a> {
+    ids: FxHashMap<&'a str, u32>,
+    count: u32,
+}
+
+impl<'a> Names<'a> {
+    /// Returns whether the identifier (if any) was not defined before.
+    fn define(&mut self, id: Option<&'a str>) -> bool {
+        let unique = match id {
+            Some(id) => self.ids.insert(id, self.count).is_none(),
+            None => true,
+        };
+        self.count += 1;
+        unique
+    }
+}
+
+/// Identifiers of all module-level index spaces.
+#[derive(Debug, Default)]
+struct ModuleNames<'a> {
+    types: Names<'a>,
+    functions: Names<'a>,
+    tables: Names<'a>,
+    memories: Names<'a>,
+    globals: Names<'a>,
+    tags: Names<'a>,
+    elements: Names<'a>,
+    data: Names<'a>,
+}
+
+impl<'a> ModuleNames<'a> {
+    fn get(&self, space: IndexSpace) -> Option<&Names<'a>> {
+        use IndexSpace::*;
+        Some(match space {
+            Type => &self.types,
+            Function => &self.functions,
+            Table => &self.tables,
+            Memory => &self.memories,
+            Global => &self.globals,
+            Tag => &self.tags,
+            Element => &self.elements,
+            Data => &self.data,
+            Local | Label => return None,
+        })
+    }
+
+    fn get_mut(&mut self, space: IndexSpace) -> Option<&mut Names<'a>> {
+        use IndexSpace::*;
+        Some(match space {
+            Type => &mut self.types,
+            Function => &mut self.functions,
+            Table => &mut self.tables,
+            Memory => &mut self.memories,
+            Global => &mut self.globals,
+            Tag => &mut self.tags,
+            Element => &mut self.elements,
+            Data => &mut self.data,
+            Local | Label => return None,
+        })
+    }
+}
+
+/// Exports given as separate module field, applied after all items are parsed.
+enum Export {
+    Function(Idx<Function>),
+    Table(Idx<Table>),
+    Memory(Idx<Memory>),
+    Global(Idx<Global>),
+    Tag(Idx<Tag>),
+}
+
+/// Module and name of an imported item.
+type Import = (String, String);
+
+/* Parser. */
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<(Token<'a>, usize)>,
+    pos: usize,
+
+    names: ModuleNames<'a>,
+    /// Function types from `(type ...)` definitions.
+    types: Vec<FunctionType>,
+    /// For every memory, whether it has 64-bit addresses (memory64 extension), since this
+    /// determines the range of memory argument offsets also in functions before the memory.
+    memory64: Vec<bool>,
+
+    // Only valid while parsing a function body.
+    locals: Names<'a>,
+    /// Innermost block label last, `None` for blocks without identifier.
+    labels: Vec<Option<&'a str>>,
+    /// All block labels of the current function body, in the order of the blocks.
+    label_names: Vec<Option<String>>,
+}
+
+type Result<T> = std::result::Result<T, WatParseError>;
+
+// Basic token handling.
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<String>) -> WatParseError {
+        let offset = self
+            .tokens
+            .get(self.pos)
+            .map(|(_, offset)| *offset)
+            .unwrap_or(self.text.len());
+        WatParseError::new(self.text, offset, message)
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn peek_atom(&self) -> Option<&'a str> {
+        match self.peek() {
+            Some(Token::Atom(atom)) => Some(atom),
+            _ => None,
+        }
+    }
+
+    fn peek_keyword(&self) -> Option<&'a str> {
+        self.peek_atom().filter(|atom| is_keyword(atom))
+    }
+
+    fn peek_lparen(&self) -> bool {
+        self.peek() == Some(&Token::LParen)
+    }
+
+    fn peek_rparen(&self) -> bool {
+        self.peek() == Some(&Token::RParen)
+    }
+
+    /// Returns whether the next tokens are an opening parenthesis and the given keyword.
+    fn peek_field(&self, keyword: &str) -> bool {
+        self.peek_lparen()
+            && matches!(self.tokens.get(self.pos + 1), Some((Token::Atom(atom), _)) if *atom == keyword)
+    }
+
+    fn lparen(&mut self) -> Result<()> {
+        if !self.peek_lparen() {
+            return Err(self.error("expected '('"));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn rparen(&mut self) -> Result<()> {
+        if !self.peek_rparen() {
+            return Err(self.error("expected ')'"));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn atom(&mut self) -> Result<&'a str> {
+        let atom = self
+            .peek_atom()
+            .ok_or_else(|| self.error("expected keyword, number, or identifier"))?;
+        self.pos += 1;
+        Ok(atom)
+    }
+
+    fn keyword(&mut self) -> Result<&'a str> {
+        let keyword = self
+            .peek_keyword()
+            .ok_or_else(|| self.error("expected keyword"))?;
+        self.pos += 1;
+        Ok(keyword)
+    }
+
+    /// Consumes the given keyword, if it is the next token.
+    fn try_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_atom() == Some(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Consumes the opening parenthesis and keyword, if a field with this keyword comes next.
+    fn try_field(&mut self, keyword: &str) -> bool {
+        let found = self.peek_field(keyword);
+        if found {
+            self.pos += 2;
+        }
+        found
+    }
+
+    /// Consumes an identifier, if it is the next token, and returns it without the `$`.
+    fn id(&mut self) -> Option<&'a str> {
+        let id = self.peek_atom()?.strip_prefix('$')?;
+        self.pos += 1;
+        Some(id)
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>> {
+        match self.peek() {
+            Some(Token::String(string)) => {
+                let string = string.clone();
+                self.pos += 1;
+                Ok(string)
+            }
+            _ => Err(self.error("expected string")),
+        }
+    }
+
+    /// A string that must be valid UTF-8, e.g., for import and export names.
+    fn name(&mut self) -> Result<String> {
+        let string = self.string()?;
+        String::from_utf8(string).map_err(|_| {
+            self.pos -= 1;
+            self.error("malformed UTF-8 encoding")
+        })
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let atom = self.atom()?;
+        parse_uint(atom).ok_or_else(|| {
+            self.pos -= 1;
+            self.error(format!("expected 64-bit unsigned integer, got '{atom}'"))
+        })
+    }
+
+    /// Skips a single token or a whole parenthesized S-expression.
+    fn skip(&mut self) -> Result<()> {
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unexpected end of input")),
+                Some(Token::LParen) => depth += 1,
+                Some(Token::RParen) if depth == 0 => return Err(self.error("unexpected ')'")),
+                Some(Token::RParen) => depth -= 1,
+                Some(_) => {}
+            }
+            self.pos += 1;
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Index resolution.
+impl<'a> Parser<'a> {
+    /// Resolves a symbolic identifier or numeric index in the given index space.
+    /// Numeric indices must refer to an existing item (or enclosing block), as in `wat2wasm`.
+    fn resolve(&self, space: IndexSpace, atom: &'a str) -> Result<u32> {
+        let Some(id) = atom.strip_prefix('$') else {
+            let index: u32 = parse_uint(atom)
+                .and_then(|int| int.try_into().ok())
+                .ok_or_else(|| {
+                    self.error(format!("expected {} index, got '{atom}'", space.name()))
+                })?;
+            let count = match space {
+                IndexSpace::Local => self.locals.count,
+                // Branches may also target the function body itself.
+                IndexSpace::Label => self.labels.len() as u32 + 1,
+                space => self.names.get(space).map_or(0, |names| names.count),
+            };
+            if index >= count {
+                return Err(self.error(format!("unknown {} {index}", space.name())));
+            }
+            return Ok(index);
+        };
+        let index = match space {
+            IndexSpace::Local => self.locals.ids.get(id).copied(),
+            IndexSpace::Label => self
+                .labels
+                .iter()
+                .rev()
+                .position(|label| *label == Some(id))
+                .map(|depth| depth as u32),
+            space => self
+                .names
+                .get(space)
+                .and_then(|names| names.ids.get(id).copied()),
+        };
+        index.ok_or_else(|| self.error(format!("unknown {} identifier ${id}", space.name())))
+    }
+
+    fn index(&mut self, space: IndexSpace) -> Result<u32> {
+        let atom = self
+            .peek_atom()
+            .ok_or_else(|| self.error(format!("expected {} index", space.name())))?;
+        let index = self.resolve(space, atom)?;
+        self.pos += 1;
+        Ok(index)
+    }
+
+    fn idx<T>(&mut self, space: IndexSpace) -> Result<Idx<T>> {
+        self.index(space).map(Idx::from)
+    }
+
+    fn label(&mut self) -> Result<Label> {
+        self.index(IndexSpace::Label).map(Label::from)
+    }
+
+    /// Parses an index, if the next token is a number or identifier.
+    fn try_idx<T>(&mut self, space: IndexSpace) -> Result<Option<Idx<T>>> {
+        match self.peek_atom() {
+            Some(atom) if !is_keyword(atom) => self.idx(space).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+// Module structure.
+impl<'a> Parser<'a> {
+    fn module(&mut self) -> Result<Module> {
+        let mut module = Module::new();
+
+        // The module fields can either be wrapped in `(module ...)` or be given directly.
+        let wrapped = self.try_field("module");
+        if wrapped {
+            module.name = self.id().map(String::from);
+        }
+
+        self.collect_names()?;
+        let mut type_ids: Vec<_> = self.names.types.ids.iter().collect();
+        type_ids.sort_by_key(|(_, idx)| **idx);
+        for (id, idx) in type_ids {
+            let type_ = self.types[*idx as usize];
+            module
+                .type_names
+                .entry(type_)
+                .or_insert_with(|| id.to_string());
+        }
+
+        let mut exports = Vec::new();
+        while self.peek_lparen() {
+            self.module_field(&mut module, &mut exports)?;
+        }
+        if wrapped {
+            self.rparen()?;
+        }
+        if self.peek().is_some() {
+            return Err(self.error("unexpected token after module"));
+        }
+
+        for (name, export) in exports {
+            match export {
+                Export::Function(idx) => module.functions[idx.to_usize()].export.push(name),
+                Export::Table(idx) => module.tables[idx.to_usize()].export.push(name),
+                Export::Memory(idx) => module.memories[idx.to_usize()].export.push(name),
+                Export::Global(idx) => module.globals[idx.to_usize()].export.push(name),
+                Export::Tag(idx) => module.tags[idx.to_usize()].export.push(name),
+            }
+        }
+
+        Ok(module)
+    }
+
+    /// First pass over all module fields, which assigns indices to all items (and parses all
+    /// type definitions), such that identifiers can be referred to before they are defined.
+    fn collect_names(&mut self) -> Result<()> {
+        let start = self.pos;
+        while self.peek_lparen() {
+            let field_start = self.pos;
+            self.lparen()?;
+            let space = match self.keyword()? {
+                "type" => Some(IndexSpace::Type),
+                "import" => {
+                    self.name()?;
+                    self.name()?;
+                    self.lparen()?;
+                    Some(self.item_kind()?)
+                }
+                "func" => Some(IndexSpace::Function),
+                "table" => Some(IndexSpace::Table),
+                "memory" => Some(IndexSpace::Memory),
+                "global" => Some(IndexSpace::Global),
+                "tag" => Some(IndexSpace::Tag),
+                "elem" => Some(IndexSpace::Element),
+                "data" => Some(IndexSpace::Data),
+                _ => None,
+            };
+            if let Some(space) = space {
+                let id_pos = self.pos;
+                let id = self.id();
+                let names = self.names.get_mut(space).expect("module-level index space");
+                if !names.define(id) {
+                    self.pos = id_pos;
+                    return Err(self.error(format!(
+                        "duplicate {} identifier ${}",
+                        space.name(),
+                        id.unwrap_or_default()
+                    )));
+                }
+
+                match space {
+                    IndexSpace::Type => {
+                        self.lparen()?;
+                        if self.keyword()? != "func" {
+                            self.pos -= 1;
+                            return Err(self.error("expected function type"));
+                        }
+                        let (type_, _) = self.params_results()?;
+                        self.types.push(type_);
+                    }
+                    // Tables and memories with inline segments also define an (unnamed) segment.
+                    IndexSpace::Table | IndexSpace::Memory => {
+                        if space == IndexSpace::Memory {
+                            while self.peek_field("export") || self.peek_field("import") {
+                                self.skip()?;
+                            }
+                            self.memory64.push(self.peek_keyword() == Some("i64"));
+                        }
+                        let (segment_space, keyword) = match space {
+                            IndexSpace::Table => (IndexSpace::Element, "elem"),
+                            _ => (IndexSpace::Data, "data"),
+                        };
+                        while !self.peek_rparen() {
+                            if self.peek_field(keyword) {
+                                let names = self.names.get_mut(segment_space).unwrap();
+                                names.define(None);
+                            }
+                            self.skip()?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            self.pos = field_start;
+            self.skip()?;
+        }
+
+        self.pos = start;
+        Ok(())
+    }
+
+    fn item_kind(&mut self) -> Result<IndexSpace> {
+        Ok(match self.keyword()? {
+            "func" => IndexSpace::Function,
+            "table" => IndexSpace::Table,
+            "memory" => IndexSpace::Memory,
+            "global" => IndexSpace::Global,
+            "tag" => IndexSpace::Tag,
+            kind => {
+                self.pos -= 1;
+                return Err(self.error(format!("unknown import or export kind '{kind}'")));
+            }
+        })
+    }
+
+    fn module_field(
+        &mut self,
+        module: &mut Module,
+        exports: &mut Vec<(String, Export)>,
+    ) -> Result<()> {
+        self.lparen()?;
+        match self.keyword()? {
+            // Already parsed in `collect_names`.
+            "type" => {
+                self.pos -= 2;
+                self.skip()?;
+                return Ok(());
+            }
+            "import" => {
+                let import = (self.name()?, self.name()?);
+                self.lparen()?;
+                match self.item_kind()? {
+                    IndexSpace::Function => self.function(module, Some(import))?,
+                    IndexSpace::Table => self.table(module, Some(import))?,
+                    IndexSpace::Memory => self.memory(module, Some(import))?,
+                    IndexSpace::Global => self.global(module, Some(import))?,
+                    IndexSpace::Tag => self.tag(module, Some(import))?,
+                    _ => unreachable!("only item kinds are returned"),
+                }
+                // Closes the import description, the import field itself is closed below.
+                self.rparen()?;
+            }
+            "func" => self.function(module, None)?,
+            "table" => self.table(module, None)?,
+            "memory" => self.memory(module, None)?,
+            "global" => self.global(module, None)?,
+            "tag" => self.tag(module, None)?,
+            "export" => {
+                let name = self.name()?;
+                self.lparen()?;
+                let space = self.item_kind()?;
+                let index = self.index(space)?;
+                let export = match space {
+                    IndexSpace::Function => Export::Function(index.into()),
+                    IndexSpace::Table => Export::Table(index.into()),
+                    IndexSpace::Memory => Export::Memory(index.into()),
+                    IndexSpace::Global => Export::Global(index.into()),
+                    IndexSpace::Tag => Export::Tag(index.into()),
+                    _ => unreachable!("only item kinds are returned"),
+                };
+                self.rparen()?;
+                exports.push((name, export));
+            }
+            "start" => {
+                if module.start.is_some() {
+                    return Err(self.error("multiple start functions"));
+                }
+                module.start = Some(self.idx(IndexSpace::Function)?);
+            }
+            "elem" => {
+                let id = self.id();
+                let element = self.element()?;
+                module.elements.push(Element {
+                    name: id.map(String::from),
+                    ..element
+                });
+            }
+            "data" => {
+                let id = self.id();
+                let data = self.data()?;
+                module.data.push(Data {
+                    name: id.map(String::from),
+                    ..data
+                });
+            }
+            field => {
+                self.pos -= 1;
+                return Err(self.error(format!("unknown module field '{field}'")));
+            }
+        }
+        self.rparen()
+    }
+
+    /// Parses the inline exports and import of an item, unless it is already imported via a
+    /// separate import field.
+    fn inline_exports_import(
+        &mut self,
+        import: Option<Import>,
+    ) -> Result<(Vec<String>, Option<Import>)> {
+        let mut exports = Vec::new();
+        while self.try_field("export") {
+            exports.push(self.name()?);
+            self.rparen()?;
+        }
+        let import = match import {
+            Some(import) => Some(import),
+            None if self.try_field("import") => {
+                let import = (self.name()?, self.name()?);
+                self.rparen()?;
+                Some(import)
+            }
+            None => None,
+        };
+        Ok((exports, import))
+    }
+
+    fn function(&mut self, module: &mut Module, import: Option<Import>) -> Result<()> {
+        let id = self.id();
+        let (exports, import) = self.inline_exports_import(import)?;
+        let (type_, param_ids) = self.type_use()?;
+
+        self.locals = Names::default();
+        for param_id in &param_ids {
+            if !self.locals.define(*param_id) {
+                return Err(self.error(format!("duplicate parameter ${}", param_id.unwrap())));
+            }
+        }
+
+        let mut function = match import {
+            Some((module, name)) => Function::new_imported(type_, module, name, exports),
+            None => {
+                let mut locals = Vec::new();
+                while self.try_field("local") {
+                    if let Some(id) = self.id() {
+                        if !self.locals.define(Some(id)) {
+                            return Err(self.error(format!("duplicate local ${id}")));
+                        }
+                        let mut local = Local::new(self.val_type()?);
+                        local.name = Some(id.to_string());
+                        locals.push(local);
+                    } else {
+                        while !self.peek_rparen() {
+                            self.locals.define(None);
+                            locals.push(Local::new(self.val_type()?));
+                        }
+                    }
+                    self.rparen()?;
+                }
+
+                self.labels.clear();
+                self.label_names.clear();
+                let mut body = Vec::new();
+                self.instrs(&mut body)?;
+                body.push(Instr::End);
+
+                // Local indices are only valid inside the function body.
+                self.locals = Names::default();
+                Function::new(
+                    type_,
+                    Code {
+                        locals,
+                        body: body.into(),
+                        label_names: std::mem::take(&mut self.label_names),
+                    },
+                    exports,
+                )
+            }
+        };
+
+        function.name = id.map(String::from);
+        for (param_idx, param_id) in param_ids.into_iter().enumerate() {
+            if let Some(param_id) = param_id {
+                *function.param_or_local_name_mut(param_idx.into()) = Some(param_id.to_string());
+            }
+        }
+        module.functions.push(function);
+        Ok(())
+    }
+
+    fn table(&mut self, module: &mut Module, import: Option<Import>) -> Result<()> {
+        let id = self.id();
+        let (export, import) = self.inline_exports_import(import)?;
+
+        let table = match import {
+            Some((import_module, import_name)) => {
+                let limits = self.limits()?;
+                Table::new_imported(limits, self.ref_type()?, import_module, import_name)
+            }
+            None if self.peek_keyword().is_some() => {
+                // Abbreviation with inline element segment, which determines the table size.
+                let elem_type = self.ref_type()?;
+                if !self.try_field("elem") {
+                    return Err(self.error("expected inline element segment"));
+                }
+                let items = self.element_items(elem_type)?;
+                self.rparen()?;
+
+                let size = match &items {
+                    ElementItems::Functions(functions) => functions.len(),
+                    ElementItems::Expressions(exprs) => exprs.len(),
+                } as u64;
+                module.elements.push(Element {
+                    mode: ElementMode::Active {
+                        table: module.tables.len().into(),
+                        offset: vec![Instr::Const(Val::I32(0)), Instr::End],
+                    },
+                    ref_type: elem_type,
+                    items,
+                    name: None,
+                });
+                Table::new(
+                    Limits {
+                        initial_size: size,
+                        max_size: Some(size),
+                        shared: false,
+                        memory64: false,
+                    },
+                    elem_type,
+                )
+            }
+            None => {
+                let limits = self.limits()?;
+                Table::new(limits, self.ref_type()?)
+            }
+        };
+
+        module.tables.push(Table {
+            export,
+            name: id.map(String::from),
+            ..table
+        });
+        Ok(())
+    }
+
+    fn memory(&mut self, module: &mut Module, import: Option<Import>) -> Result<()> {
+        let id = self.id();
+        let (export, import) = self.inline_exports_import(import)?;
+
+        let memory64 = self.try_keyword("i64");
+        if !memory64 {
+            self.try_keyword("i32");
+        }
+
+        let memory = if import.is_none() && self.try_field("data") {
+            // Abbreviation with inline data segment, which determines the memory size.
+            let mut bytes = Vec::new();
+            while !self.peek_rparen() {
+                bytes.extend(self.string()?);
+            }
+            self.rparen()?;
+
+            const PAGE_SIZE: u64 = 64 * 1024;
+            let pages = (bytes.len() as u64).div_ceil(PAGE_SIZE);
+            let address_zero = match memory64 {
+                true => Val::I64(0),
+                false => Val::I32(0),
+            };
+            module.data.push(Data {
+                mode: DataMode::Active {
+                    memory: module.memories.len().into(),
+                    offset: vec![Instr::Const(address_zero), Instr::End],
+                },
+                bytes,
+                name: None,
+            });
+            Memory::new(Limits {
+                initial_size: pages,
+                max_size: Some(pages),
+                shared: false,
+                memory64,
+            })
+        } else {
+            let mut limits = self.limits()?;
+            limits.memory64 = memory64;
+            limits.shared = self.try_keyword("shared");
+            match import {
+                Some((import_module, import_name)) => {
+                    Memory::new_imported(limits, import_module, import_name)
+                }
+                None => Memory::new(limits),
+            }
+        };
+
+        module.memories.push(Memory {
+            export,
+            name: id.map(String::from),
+            ..memory
+        });
+        Ok(())
+    }
+
+    fn global(&mut self, module: &mut Module, import: Option<Import>) -> Result<()> {
+        let id = self.id();
+        let (export, import) = self.inline_exports_import(import)?;
+
+        let type_ = if self.try_field("mut") {
+            let val_type = self.val_type()?;
+            self.rparen()?;
+            GlobalType(val_type, Mutability::Mut)
+        } else {
+            GlobalType(self.val_type()?, Mutability::Const)
+        };
+
+        let global = match import {
+            Some((import_module, import_name)) => {
+                Global::new_imported(type_, import_module, import_name)
+            }
+            None => Global::new(type_, self.const_expr()?),
+        };
+
+        module.globals.push(Global {
+            export,
+            name: id.map(String::from),
+            ..global
+        });
+        Ok(())
+    }
+
+    fn tag(&mut self, module: &mut Module, import: Option<Import>) -> Result<()> {
+        self.id();
+        let (export, import) = self.inline_exports_import(import)?;
+        let (type_, _) = self.type_use()?;
+
+        let tag = match import {
+            Some((import_module, import_name)) => {
+                Tag::new_imported(type_, import_module, import_name)
+            }
+            None => Tag::new(type_),
+        };
+
+        module.tags.push(Tag { export, ..tag });
+        Ok(())
+    }
+
+    fn element(&mut self) -> Result<Element> {
+        let mode = if self.try_keyword("declare") {
+            ElementMode::Declared
+        } else {
+            let table = if self.try_field("table") {
+                let table = self.idx(IndexSpace::Table)?;
+                self.rparen()?;
+                Some(table)
+            } else {
+                None
+            };
+            if table.is_some() || self.peek_lparen() {
+                ElementMode::Active {
+                    table: table.unwrap_or_else(|| 0u32.into()),
+                    offset: self.offset_expr()?,
+                }
+            } else {
+                ElementMode::Passive
+            }
+        };
+
+        // The `func` keyword may be omitted in active segments of function indices (as in the
+        // WebAssembly MVP).
+        let ref_type = if self.try_keyword("func") {
+            RefType::FuncRef
+        } else if self.peek_keyword().is_some() {
+            self.ref_type()?
+        } else {
+            RefType::FuncRef
+        };
+        let items = self.element_items(ref_type)?;
+
+        Ok(Element {
+            mode,
+            ref_type,
+            items,
+            name: None,
+        })
+    }
+
+    /// Either a list of function indices or of `(item ...)` expressions (or the abbreviation of a
+    /// single folded instruction).
+    fn element_items(&mut self, ref_type: RefType) -> Result<ElementItems> {
+        if ref_type == RefType::FuncRef && !self.peek_lparen() {
+            let mut functions = Vec::new();
+            while !self.peek_rparen() {
+                functions.push(self.idx(IndexSpace::Function)?);
+            }
+            Ok(ElementItems::Functions(functions))
+        } else {
+            let mut exprs = Vec::new();
+            while !self.peek_rparen() {
+                let mut expr = Vec::new();
+                if self.try_field("item") {
+                    self.instrs(&mut expr)?;
+                    self.rparen()?;
+                } else {
+                    self.folded_instr(&mut expr)?;
+                }
+                expr.push(Instr::End);
+                exprs.push(expr);
+            }
+            Ok(ElementItems::Expressions(exprs))
+        }
+    }
+
+    fn data(&mut self) -> Result<Data> {
+        let memory = if self.try_field("memory") {
+            let memory = self.idx(IndexSpace::Memory)?;
+            self.rparen()?;
+            Some(memory)
+        } else {
+            None
+        };
+        let mode = if memory.is_some() || self.peek_lparen() {
+            DataMode::Active {
+                memory: memory.unwrap_or_else(|| 0u32.into()),
+                offset: self.offset_expr()?,
+            }
+        } else {
+            DataMode::Passive
+        };
+
+        let mut bytes = Vec::new();
+        while !self.peek_rparen() {
+            bytes.extend(self.string()?);
+        }
+
+        Ok(Data {
+            mode,
+            bytes,
+            name: None,
+        })
+    }
+
+    /// Either `(offset instr*)` or the abbreviation of a single folded instruction.
+    fn offset_expr(&mut self) -> Result<Expr> {
+        let mut expr = Vec::new();
+        if self.try_field("offset") {
+            self.instrs(&mut expr)?;
+            self.rparen()?;
+        } else {
+            self.folded_instr(&mut expr)?;
+        }
+        expr.push(Instr::End);
+        Ok(expr)
+    }
+
+    /// Constant expression until the closing parenthesis of the surrounding field.
+    fn const_expr(&mut self) -> Result<Expr> {
+        self.labels.clear();
+        let mut expr = Vec::new();
+        self.instrs(&mut expr)?;
+        expr.push(Instr::End);
+        Ok(expr)
+    }
+}
+
+// Types.
+impl<'a> Parser<'a> {
+    fn val_type(&mut self) -> Result<ValType> {
+        let atom = self.atom()?;
+        ValType::from_str(atom).map_err(|()| {
+            self.pos -= 1;
+            self.error(format!("unknown value type '{atom}'"))
+        })
+    }
+
+    fn ref_type(&mut self) -> Result<RefType> {
+        let atom = self.atom()?;
+        RefType::from_str(atom).map_err(|()| {
+            self.pos -= 1;
+            self.error(format!("unknown reference type '{atom}'"))
+        })
+    }
+
+    fn limits(&mut self) -> Result<Limits> {
+        let initial_size = self.u64()?;
+        let max_size = match self.peek_atom() {
+            Some(atom) if !is_keyword(atom) => Some(self.u64()?),
+            _ => None,
+        };
+        Ok(Limits {
+            initial_size,
+            max_size,
+            shared: false,
+            memory64: false,
+        })
+    }
+
+    /// Parses `(param ...)*` and `(result ...)*` and returns the function type and the
+    /// (optional) identifiers of all parameters.
+    fn params_results(&mut self) -> Result<(FunctionType, Vec<Option<&'a str>>)> {
+        let mut params = Vec::new();
+        let mut param_ids = Vec::new();
+        while self.try_field("param") {
+            if let Some(id) = self.id() {
+                params.push(self.val_type()?);
+                param_ids.push(Some(id));
+            } else {
+                while !self.peek_rparen() {
+                    params.push(self.val_type()?);
+                    param_ids.push(None);
+                }
+            }
+            self.rparen()?;
+        }
+
+        let mut results = Vec::new();
+        while self.try_field("result") {
+            while !self.peek_rparen() {
+                results.push(self.val_type()?);
+            }
+            self.rparen()?;
+        }
+
+        Ok((FunctionType::new(&params, &results), param_ids))
+    }
+
+    /// Parses a reference to a type definition `(type x)`, or an inline function type, or both
+    /// (in which case they must match).
+    fn type_use(&mut self) -> Result<(FunctionType, Vec<Option<&'a str>>)> {
+        let type_def = if self.try_field("type") {
+            let idx: Idx<FunctionType> = self.idx(IndexSpace::Type)?;
+            self.rparen()?;
+            let type_ = self
+                .types
+                .get(idx.to_usize())
+                .copied()
+                .ok_or_else(|| self.error(format!("unknown type {}", idx.to_u32())))?;
+            Some(type_)
+        } else {
+            None
+        };
+
+        let inline_start = self.pos;
+        let (inline_type, param_ids) = self.params_results()?;
+        match type_def {
+            Some(type_) if self.pos == inline_start => {
+                Ok((type_, vec![None; type_.inputs().len()]))
+            }
+            Some(type_) if type_ != inline_type => {
+                Err(self.error("inline function type does not match type definition"))
+            }
+            _ => Ok((inline_type, param_ids)),
+        }
+    }
+}
+
+// Instructions.
+impl<'a> Parser<'a> {
+    /// Parses instructions until the closing parenthesis of the surrounding S-expression.
+    fn instrs(&mut self, instrs: &mut Vec<Instr>) -> Result<()> {
+        loop {
+            match self.peek() {
+                Some(Token::RParen) => return Ok(()),
+                Some(Token::LParen) => self.folded_instr(instrs)?,
+                Some(_) => self.plain_instr(instrs)?,
+                None => return Err(self.error("unexpected end of input")),
+            }
+        }
+    }
+
+    fn block_start(&mut self) -> Result<(Option<&'a str>, FunctionType)> {
+        let label = self.id();
+        let (type_, _) = self.type_use()?;
+        Ok((label, type_))
+    }
+
+    /// Parses a folded instruction, where the operands come first in the flat output.
+    fn folded_instr(&mut self, instrs: &mut Vec<Instr>) -> Result<()> {
+        self.lparen()?;
+        match self.keyword()? {
+            "block" | "loop" => {
+                self.pos -= 1;
+                self.plain_instr(instrs)?;
+                self.instrs(instrs)?;
+                self.labels.pop();
+                instrs.push(Instr::End);
+            }
+            "if" => {
+                let (label, type_) = self.block_start()?;
+                self.label_names.push(label.map(String::from));
+                // The condition is evaluated outside of the if block, i.e., without its label.
+                while !self.peek_field("then") {
+                    if !self.peek_lparen() {
+                        return Err(self.error("expected folded condition or '(then'"));
+                    }
+                    self.folded_instr(instrs)?;
+                }
+                self.labels.push(label);
+                instrs.push(Instr::If(type_));
+
+                self.try_field("then");
+                self.instrs(instrs)?;
+                self.rparen()?;
+                if self.try_field("else") {
+                    instrs.push(Instr::Else);
+                    self.instrs(instrs)?;
+                    self.rparen()?;
+                }
+
+                self.labels.pop();
+                instrs.push(Instr::End);
+            }
+            "try" => {
+                let (label, type_) = self.block_start()?;
+                self.labels.push(label);
+                self.label_names.push(label.map(String::from));
+                instrs.push(Instr::Try(type_));
+
+                if !self.try_field("do") {
+                    return Err(self.error("expected '(do'"));
+                }
+                self.instrs(instrs)?;
+                self.rparen()?;
+
+                if self.try_field("delegate") {
+                    self.labels.pop();
+                    instrs.push(Instr::Delegate(self.label()?));
+                    self.rparen()?;
+                } else {
+                    loop {
+                        if self.try_field("catch") {
+                            instrs.push(Instr::Catch(self.idx(IndexSpace::Tag)?));
+                        } else if self.try_field("catch_all") {
+                            instrs.push(Instr::CatchAll);
+                        } else {
+                            break;
+                        }
+                        self.instrs(instrs)?;
+                        self.rparen()?;
+                    }
+                    self.labels.pop();
+                    instrs.push(Instr::End);
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                let mut instr = Vec::with_capacity(1);
+                self.plain_instr(&mut instr)?;
+                while self.peek_lparen() {
+                    self.folded_instr(instrs)?;
+                }
+                instrs.extend(instr);
+            }
+        }
+        self.rparen()
+    }
+
+    /// Parses a single flat instruction with its immediates.
+    fn plain_instr(&mut self, instrs: &mut Vec<Instr>) -> Result<()> {
+        use Instr::*;
+
+        let op_pos = self.pos;
+        let op = self.keyword()?;
+        let instr = match op {
+            "block" | "loop" | "if" | "try" => {
+                let (label, type_) = self.block_start()?;
+                self.labels.push(label);
+                self.label_names.push(label.map(String::from));
+                match op {
+                    "block" => Block(type_),
+                    "loop" => Loop(type_),
+                    "if" => If(type_),
+                    _ => Try(type_),
+                }
+            }
+            // The block label may be repeated after else, catch, and end.
+            "else" | "catch_all" => {
+                self.id();
+                if op == "else" {
+                    Else
+                } else {
+                    CatchAll
+                }
+            }
+            "catch" => {
+                // If two identifiers/indices follow, the first is the (repeated) block label.
+                let second_is_idx = matches!(
+                    self.tokens.get(self.pos + 1),
+                    Some((Token::Atom(atom), _)) if !is_keyword(atom)
+                );
+                if second_is_idx {
+                    self.id();
+                }
+                Catch(self.idx(IndexSpace::Tag)?)
+            }
+            "end" => {
+                self.id();
+                self.labels.pop();
+                End
+            }
+            "delegate" => {
+                // The label of delegate is relative to the blocks surrounding the try block.
+                self.labels.pop();
+                Delegate(self.label()?)
+            }
+
+            "call_indirect" | "return_call_indirect" => {
+                let table = self.try_idx(IndexSpace::Table)?.unwrap_or(0u32.into());
+                let (type_, _) = self.type_use()?;
+                match op {
+                    "call_indirect" => CallIndirect(type_, table),
+                    _ => ReturnCallIndirect(type_, table),
+                }
+            }
+            "select" => {
+                if self.try_field("result") {
+                    let type_ = self.val_type()?;
+                    self.rparen()?;
+                    TypedSelect(type_)
+                } else {
+                    Select
+                }
+            }
+            "ref.null" => RefNull(match self.keyword()? {
+                "func" | "funcref" => RefType::FuncRef,
+                "extern" | "externref" => RefType::ExternRef,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("expected reference type"));
+                }
+            }),
+
+            "i32.const" | "i64.const" | "f32.const" | "f64.const" => {
+                let atom = self.atom()?;
+                let val = match op {
+                    "i32.const" => parse_int(atom, 32).map(|bits| Val::I32(bits as u32 as i32)),
+                    "i64.const" => parse_int(atom, 64).map(|bits| Val::I64(bits as i64)),
+                    "f32.const" => parse_float(atom, F32_FORMAT)
+                        .map(|bits| Val::F32(f32::from_bits(bits as u32).into())),
+                    _ => parse_float(atom, F64_FORMAT)
+                        .map(|bits| Val::F64(f64::from_bits(bits).into())),
+                };
+                Const(val.ok_or_else(|| {
+                    self.pos -= 1;
+                    self.error(format!("invalid {op} literal '{atom}'"))
+                })?)
+            }
+            "v128.const" => Const(Val::V128(self.v128()?)),
+
+            _ => {
+                // All other instructions have only indices, memargs, and lane indices as
+                // immediates, which we resolve here and pass to `Instr::from_str`.
+                let immediates_pos = self.pos;
+                let mut immediates = Vec::new();
+                while let Some(atom) = self.peek_atom() {
+                    if is_keyword(atom)
+                        && !atom.starts_with("offset=")
+                        && !atom.starts_with("align=")
+                    {
+                        break;
+                    }
+                    immediates.push(atom);
+                    self.pos += 1;
+                }
+                let end_pos = self.pos;
+
+                let spaces = immediate_index_spaces(op, immediates.len());
+                // Lane indices are the last immediate (or all, for shuffles), not indices.
+                let lane_count = lane_count(op);
+                let lanes_start = match op {
+                    "i8x16.shuffle" => 0,
+                    _ if lane_count.is_some() => immediates.len().saturating_sub(1),
+                    _ => immediates.len(),
+                };
+                let mut memory = 0;
+                let mut resolved = Vec::with_capacity(immediates.len() + 1);
+                for (i, atom) in immediates.iter().enumerate() {
+                    // Point errors to the immediate that could not be resolved.
+                    self.pos = immediates_pos + i;
+                    let space = spaces[i.min(spaces.len() - 1)];
+                    resolved.push(match (atom.split_once('='), lane_count) {
+                        (Some((field, value)), _) => {
+                            let value = parse_uint(value).ok_or_else(|| {
+                                self.error(format!("invalid memory argument '{atom}'"))
+                            })?;
+                            let memory64 = self.memory64.get(memory).copied().unwrap_or(false);
+                            if field == "offset" && value > u32::MAX.into() && !memory64 {
+                                return Err(self.error(format!(
+                                    "offset {value} out of range for memory with 32-bit addresses"
+                                )));
+                            }
+                            format!("{field}={value}")
+                        }
+                        (None, Some(lane_count)) if i >= lanes_start => match parse_uint(atom) {
+                            Some(lane) if lane < lane_count => lane.to_string(),
+                            _ => return Err(self.error(format!("invalid lane index '{atom}'"))),
+                        },
+                        (None, _) => {
+                            let index = self.resolve(space, atom)?;
+                            if space == IndexSpace::Memory {
+                                memory = index as usize;
+                            }
+                            index.to_string()
+                        }
+                    });
+                }
+                self.pos = end_pos;
+
+                // In contrast to `Instr::from_str`, the table index is optional in the text format.
+                match (op, resolved.len()) {
+                    ("table.get" | "table.set" | "table.size" | "table.grow" | "table.fill", 0)
+                    | ("table.init", 1) => resolved.insert(0, "0".to_string()),
+                    ("table.copy", 0) => resolved.extend(["0".to_string(), "0".to_string()]),
+                    _ => {}
+                }
+
+                let instr_str = format!("{op} {}", resolved.join(" "));
+                Instr::from_str(&instr_str).map_err(|()| {
+                    self.pos = op_pos;
+                    self.error(format!(
+                        "unknown instruction or invalid immediates '{instr_str}'"
+                    ))
+                })?
+            }
+        };
+        instrs.push(instr);
+        Ok(())
+    }
+
+    /// Parses the shape and lanes of a `v128.const` instruction.
+    fn v128(&mut self) -> Result<V128> {
+        let shape = self.keyword()?;
+        let (lane_count, lane_bits, is_float) = match shape {
+            "i8x16" => (16, 8, false),
+            "i16x8" => (8, 16, false),
+            "i32x4" => (4, 32, false),
+            "i64x2" => (2, 64, false),
+            "f32x4" => (4, 32, true),
+            "f64x2" => (2, 64, true),
+            _ => {
+                self.pos -= 1;
+                return Err(self.error(format!("unknown vector shape '{shape}'")));
+            }
+        };
+
+        let mut bytes = Vec::with_capacity(16);
+        for _ in 0..lane_count {
+            let atom = self.atom()?;
+            let lane = match (is_float, lane_bits) {
+                (false, bits) => parse_int(atom, bits),
+                (true, 32) => parse_float(atom, F32_FORMAT),
+                (true, _) => parse_float(atom, F64_FORMAT),
+            }
+            .ok_or_else(|| {
+                self.pos -= 1;
+                self.error(format!("invalid {shape} lane '{atom}'"))
+            })?;
+            bytes.extend_from_slice(&lane.to_le_bytes()[..lane_bits as usize / 8]);
+        }
+        Ok(V128(bytes.try_into().expect("16 bytes in total")))
+    }
+}
+
+/// Number of lanes that the lane index immediate(s) of an instruction can refer to, `None` if the
+/// instruction has no lane indices.
+fn lane_count(op: &str) -> Option<u64> {
+    if op == "i8x16.shuffle" {
+        // Lanes of both input vectors.
+        return Some(32);
+    }
+    if !op.contains("_lane") {
+        return None;
+    }
+    match op.split_once('.')? {
+        // E.g., `v128.load16_lane` has 8 lanes of 16 bits each.
+        ("v128", access) => {
+            let bits: u64 = access
+                .trim_matches(|char: char| !char.is_ascii_digit())
+                .parse()
+                .ok()?;
+            Some(128 / bits)
+        }
+        // E.g., `i16x8.extract_lane_s`.
+        (shape, _) => shape.split_once('x')?.1.parse().ok(),
+    }
+}
+
+/// Index spaces of the symbolic identifiers in the immediates of an instruction.
+/// If there are more immediates than index spaces (e.g., for `br_table`), the last one repeats.
+fn immediate_index_spaces(op: &str, immediate_count: usize) -> &'static [IndexSpace] {
+    use IndexSpace::*;
+    match (op, immediate_count) {
+        ("local.get" | "local.set" | "local.tee", _) => &[Local],
+        ("global.get" | "global.set", _) => &[Global],
+        ("call" | "return_call" | "ref.func", _) => &[Function],
+        ("br" | "br_if" | "br_table" | "rethrow", _) => &[Label],
+        ("throw", _) => &[Tag],
+        ("memory.init", 1) | ("data.drop", _) => &[Data],
+        ("memory.init", _) => &[Memory, Data],
+        ("table.init", 1) | ("elem.drop", _) => &[Element],
+        ("table.init", _) => &[Table, Element],
+        (op, _) if op.starts_with("table.") => &[Table],
+        // All memory instructions (incl. loads and stores).
+        _ => &[Memory],
+    }
+}
+
+/* Numbers. */
+
+/// Parses an unsigned integer (decimal or hexadecimal, with optional `_` separators).
+fn parse_uint(str: &str) -> Option<u64> {
+    let digits = str.replace('_', "");
+    match digits.strip_prefix("0x") {
+        Some(hex_digits) if !hex_digits.is_empty() => u64::from_str_radix(hex_digits, 16).ok(),
+        Some(_) => None,
+        None if digits.starts_with(|c: char| c.is_ascii_digit()) => digits.parse().ok(),
+        None => None,
+    }
+}
+
+/// Parses an integer literal of the given bit width, which can be written either as signed or
+/// unsigned integer, and returns its bit pattern (in the lower `bits` bits).
+fn parse_int(str: &str, bits: u32) -> Option<u64> {
+    let (negative, digits) = match str.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, str.strip_prefix('+').unwrap_or(str)),
+    };
+    let magnitude = parse_uint(digits)? as u128;
+    let modulus = 1u128 << bits;
+    let bit_pattern = match negative {
+        false if magnitude < modulus => magnitude,
+        true if magnitude <= modulus / 2 => (modulus - magnitude) % modulus,
+        _ => return None,
+    };
+    Some(bit_pattern as u64)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FloatFormat {
+    /// Without the implicit leading bit.
+    mantissa_bits: u32,
+    exponent_bits: u32,
+}
+
+const F32_FORMAT: FloatFormat = FloatFormat {
+    mantissa_bits: 23,
+    exponent_bits: 8,
+};
+const F64_FORMAT: FloatFormat = FloatFormat {
+    mantissa_bits: 52,
+    exponent_bits: 11,
+};
+
+/// Parses a floating-point literal (decimal, hexadecimal, `inf`, `nan`, or `nan:0x...` with
+/// explicit payload) and returns its bit pattern.
+fn parse_float(str: &str, format: FloatFormat) -> Option<u64> {
+    let FloatFormat {
+        mantissa_bits,
+        exponent_bits,
+    } = format;
+    let sign_bit = 1u64 << (mantissa_bits + exponent_bits);
+    let infinity = ((1u64 << exponent_bits) - 1) << mantissa_bits;
+
+    let (negative, str) = match str.strip_prefix('-') {
+        Some(str) => (true, str),
+        None => (false, str.strip_prefix('+').unwrap_or(str)),
+    };
+    let bits = if str == "inf" {
+        infinity
+    } else if str == "nan" {
+        // Canonical NaN, i.e., only the most significant bit of the payload is set.
+        infinity | 1 << (mantissa_bits - 1)
+    } else if let Some(payload) = str.strip_prefix("nan:0x") {
+        match u64::from_str_radix(&payload.replace('_', ""), 16) {
+            Ok(payload) if payload != 0 && payload < 1 << mantissa_bits => infinity | payload,
+            _ => return None,
+        }
+    } else if let Some(hex) = str.strip_prefix("0x") {
+        parse_hex_float(&hex.replace('_', ""), format)?
+    } else if str.starts_with(|c: char| c.is_ascii_digit()) {
+        let str = str.replace('_', "");
+        let bits = match mantissa_bits {
+            23 => {
+                let float: f32 = str.parse().ok()?;
+                float.is_finite().then_some(float.to_bits() as u64)?
+            }
+            _ => {
+                let float: f64 = str.parse().ok()?;
+                float.is_finite().then_some(float.to_bits())?
+            }
+        };
+        // Remove sign, to handle it uniformly below.
+        bits & !sign_bit
+    } else {
+        return None;
+    };
+    Some(if negative { bits | sign_bit } else { bits })
+}
+
+/// Parses a hexadecimal float without sign and `0x` prefix, e.g., `1.8p3`, with correct
+/// (round-to-nearest-even) rounding, and returns its bit pattern.
+fn parse_hex_float(str: &str, format: FloatFormat) -> Option<u64> {
+    let (mantissa, exponent) = match str.split_once(['p', 'P']) {
+        Some((mantissa, exponent)) => {
+            let (negative, digits) = match exponent.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, exponent.strip_prefix('+').unwrap_or(exponent)),
+            };
+            if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            // Saturate, such that huge exponents still result in overflow or zero below.
+            let exponent = digits
+                .parse::<i64>()
+                .unwrap_or(i64::MAX / 4)
+                .min(i64::MAX / 4);
+            (mantissa, if negative { -exponent } else { exponent })
+        }
+        None => (str, 0),
+    };
+    let (integer_digits, fraction_digits) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if integer_digits.is_empty() {
+        return None;
+    }
+
+    // Accumulate the significand in 64 bits (the value is `significand * 2^exponent`).
+    // Digits that do not fit anymore are only remembered as "sticky" bit for rounding.
+    let mut significand = 0u64;
+    let mut exponent = exponent;
+    let mut sticky = false;
+    for (i, c) in integer_digits
+        .chars()
+        .chain(fraction_digits.chars())
+        .enumerate()
+    {
+        let digit = c.to_digit(16)? as u64;
+        let is_fraction = i >= integer_digits.len();
+        if significand >> 60 == 0 {
+            significand = significand << 4 | digit;
+            if is_fraction {
+                exponent -= 4;
+            }
+        } else {
+            sticky |= digit != 0;
+            if !is_fraction {
+                exponent += 4;
+            }
+        }
+    }
+    if significand == 0 {
+        return Some(0);
+    }
+    if sticky {
+        significand |= 1;
+    }
+
+    let FloatFormat {
+        mantissa_bits,
+        exponent_bits,
+    } = format;
+    let precision = mantissa_bits as i64 + 1;
+    let bias = (1i64 << (exponent_bits - 1)) - 1;
+    let min_exponent = 1 - bias;
+
+    // Exponent of the most significant bit, and how many bits fit into the format there
+    // (fewer for subnormal numbers).
+    let msb = 63 - significand.leading_zeros() as i64;
+    let msb_exponent = msb + exponent;
+    let available_bits = if msb_exponent >= min_exponent {
+        precision
+    } else {
+        precision - (min_exponent - msb_exponent)
+    };
+
+    let shift = msb + 1 - available_bits;
+    let mut mantissa = if shift <= 0 {
+        (significand as u128) << -shift
+    } else if shift >= 128 {
+        0
+    } else {
+        let significand = significand as u128;
+        let truncated = significand >> shift;
+        let remainder = significand & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        if remainder > half || (remainder == half && truncated & 1 == 1) {
+            truncated + 1
+        } else {
+            truncated
+        }
+    };
+    let mut lsb_exponent = exponent + shift;
+    if mantissa == 1 << precision {
+        // Rounding carried over into the next power of two.
+        mantissa >>= 1;
+        lsb_exponent += 1;
+    }
+
+    if mantissa < 1 << (precision - 1) {
+        // Subnormal (or zero), the exponent field is zero.
+        Some(mantissa as u64)
+    } else {
+        let msb_exponent = lsb_exponent + precision - 1;
+        if msb_exponent > bias {
+            // Overflow, which is not allowed for literals.
+            return None;
+        }
+        let biased_exponent = (msb_exponent + bias) as u64;
+        Some(biased_exponent << mantissa_bits | (mantissa as u64 & ((1 << mantissa_bits) - 1)))
+    }
+}
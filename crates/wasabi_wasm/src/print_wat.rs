@@ -0,0 +1,577 @@
+//! Code for printing our AST in the WebAssembly text format (`.wat`).
+//!
+//! The output is in the flat (i.e., not folded) format, where the instructions of nested blocks
+//! are only indented.
+//! Debug names (from the name section) are used as identifiers for functions and locals, all
+//! other items (and block labels) are referred to by their numeric index.
+//!
+//! Since in the text format (just like in the binary format) all imports must come before the
+//! locally-defined functions, globals, etc., items are re-indexed the same way as in `encode.rs`.
+//! The original index is thus not necessarily the one in the output.
+//! Custom sections cannot be represented in the text format, so they are only given as comments.
+
+use std::fmt;
+use std::fmt::Write;
+
+use rustc_hash::FxHashSet;
+
+use crate::*;
+
+/// Printing state, necessary for referring to items in the module.
+#[derive(Default)]
+struct Printer {
+    // Mapping of high-level indices to indices in the text format (see `EncodeState`).
+    // Empty when printing a single function without module, in which case indices are unchanged.
+    function_idx: Vec<u32>,
+    global_idx: Vec<u32>,
+    table_idx: Vec<u32>,
+    memory_idx: Vec<u32>,
+    tag_idx: Vec<u32>,
+
+    /// Identifiers from the debug names of functions, indexed by high-level function index.
+    function_ids: Vec<Option<String>>,
+}
+
+pub fn print_module(module: &Module) -> String {
+    let mut out = String::new();
+    write_module(&mut out, module).expect("writing to a String cannot fail");
+    out
+}
+
+pub fn print_function(function: &Function) -> String {
+    let mut out = String::new();
+    let id = function.name.as_deref().map(to_id);
+    Printer::default()
+        .write_function(&mut out, function, id.as_deref(), None, 0)
+        .expect("writing to a String cannot fail");
+    out
+}
+
+fn write_module(out: &mut String, module: &Module) -> fmt::Result {
+    let printer = Printer {
+        function_idx: reindex(module.functions.iter().map(|f| f.import().is_some())),
+        global_idx: reindex(module.globals.iter().map(|g| g.import().is_some())),
+        table_idx: reindex(module.tables.iter().map(|t| t.import().is_some())),
+        memory_idx: reindex(module.memories.iter().map(|m| m.import().is_some())),
+        tag_idx: reindex(module.tags.iter().map(|t| t.import().is_some())),
+        function_ids: unique_ids(module.functions.iter().map(|f| f.name.as_deref())),
+    };
+
+    out.write_str("(module")?;
+    if let Some(name) = &module.name {
+        write!(out, " ${}", to_id(name))?;
+    }
+    out.write_char('\n')?;
+
+    // Imports first (in the same order as in the binary), then all other definitions.
+    for imports in [true, false] {
+        for (idx, function) in module.functions() {
+            if function.import().is_some() == imports {
+                let id = printer.function_ids[idx.to_usize()].as_deref();
+                let text_idx = printer.function(idx);
+                printer.write_function(out, function, id, Some(text_idx), 1)?;
+            }
+        }
+        for (idx, table) in module.tables() {
+            if table.import().is_some() == imports {
+                write!(out, "  (table (;{};)", printer.table(idx).to_u32())?;
+                write_exports_import(out, &table.export, table.import())?;
+                write_limits(out, table.limits)?;
+                writeln!(out, " {})", table.elem_type)?;
+            }
+        }
+        for (idx, memory) in module.memories() {
+            if memory.import().is_some() == imports {
+                write!(out, "  (memory (;{};)", printer.memory(idx).to_u32())?;
+                write_exports_import(out, &memory.export, memory.import())?;
+                if memory.limits.memory64 {
+                    out.write_str(" i64")?;
+                }
+                write_limits(out, memory.limits)?;
+                if memory.limits.shared {
+                    out.write_str(" shared")?;
+                }
+                out.write_str(")\n")?;
+            }
+        }
+        for (idx, global) in module.globals() {
+            if global.import().is_some() == imports {
+                write!(out, "  (global (;{};)", printer.global(idx).to_u32())?;
+                write_exports_import(out, &global.export, global.import())?;
+                match global.type_ {
+                    GlobalType(ty, Mutability::Const) => write!(out, " {ty}")?,
+                    GlobalType(ty, Mutability::Mut) => write!(out, " (mut {ty})")?,
+                }
+                if let Some(init) = global.init() {
+                    printer.write_const_expr(out, init)?;
+                }
+                out.write_str(")\n")?;
+            }
+        }
+        for (idx, tag) in module.tags() {
+            if tag.import().is_some() == imports {
+                write!(out, "  (tag (;{};)", printer.tag(idx).to_u32())?;
+                write_exports_import(out, &tag.export, tag.import())?;
+                write_type_use(out, tag.type_)?;
+                out.write_str(")\n")?;
+            }
+        }
+    }
+
+    if let Some(start) = module.start {
+        writeln!(out, "  (start {})", printer.function_ref(start))?;
+    }
+
+    for (idx, element) in module.elements() {
+        write!(out, "  (elem (;{};)", idx.to_u32())?;
+        match &element.mode {
+            ElementMode::Active { table, offset } => {
+                write!(out, " (table {}) (offset", printer.table(*table).to_u32())?;
+                printer.write_const_expr(out, offset)?;
+                out.write_char(')')?;
+            }
+            ElementMode::Passive => {}
+            ElementMode::Declared => out.write_str(" declare")?,
+        }
+        match &element.items {
+            ElementItems::Functions(functions) => {
+                out.write_str(" func")?;
+                for &function in functions {
+                    write!(out, " {}", printer.function_ref(function))?;
+                }
+            }
+            ElementItems::Expressions(exprs) => {
+                write!(out, " {}", element.ref_type)?;
+                for expr in exprs {
+                    out.write_str(" (item")?;
+                    printer.write_const_expr(out, expr)?;
+                    out.write_char(')')?;
+                }
+            }
+        }
+        out.write_str(")\n")?;
+    }
+
+    for (idx, data) in module.data() {
+        write!(out, "  (data (;{};)", idx.to_u32())?;
+        if let DataMode::Active { memory, offset } = &data.mode {
+            write!(
+                out,
+                " (memory {}) (offset",
+                printer.memory(*memory).to_u32()
+            )?;
+            printer.write_const_expr(out, offset)?;
+            out.write_char(')')?;
+        }
+        out.write_char(' ')?;
+        write_string(out, &data.bytes)?;
+        out.write_str(")\n")?;
+    }
+
+    for custom_section in &module.custom_sections {
+        out.write_str("  ;; custom section ")?;
+        write_string(out, custom_section.name.as_bytes())?;
+        writeln!(out, " with {} bytes", custom_section.content.len())?;
+    }
+
+    out.write_str(")\n")
+}
+
+impl Printer {
+    fn function(&self, idx: Idx<Function>) -> Idx<Function> {
+        map_idx(&self.function_idx, idx)
+    }
+
+    fn global(&self, idx: Idx<Global>) -> Idx<Global> {
+        map_idx(&self.global_idx, idx)
+    }
+
+    fn table(&self, idx: Idx<Table>) -> Idx<Table> {
+        map_idx(&self.table_idx, idx)
+    }
+
+    fn memory(&self, idx: Idx<Memory>) -> Idx<Memory> {
+        map_idx(&self.memory_idx, idx)
+    }
+
+    fn tag(&self, idx: Idx<Tag>) -> Idx<Tag> {
+        map_idx(&self.tag_idx, idx)
+    }
+
+    /// Returns the identifier of the function, if it has a debug name, or its index otherwise.
+    fn function_ref(&self, idx: Idx<Function>) -> String {
+        match self.function_ids.get(idx.to_usize()) {
+            Some(Some(id)) => format!("${id}"),
+            _ => self.function(idx).to_u32().to_string(),
+        }
+    }
+
+    /// `indent` is the nesting level of the function itself (1 inside a module).
+    fn write_function(
+        &self,
+        out: &mut String,
+        function: &Function,
+        id: Option<&str>,
+        text_idx: Option<Idx<Function>>,
+        indent: usize,
+    ) -> fmt::Result {
+        write_indent(out, indent)?;
+        out.write_str("(func")?;
+        if let Some(id) = id {
+            write!(out, " ${id}")?;
+        }
+        if let Some(text_idx) = text_idx {
+            write!(out, " (;{};)", text_idx.to_u32())?;
+        }
+        write_exports_import(out, &function.export, function.import())?;
+
+        let local_ids = unique_ids(function.param_or_locals().map(|(_, local)| local.name()));
+        write_params_or_locals(
+            out,
+            "param",
+            function
+                .params()
+                .map(|(idx, param)| (local_ids[idx.to_usize()].as_deref(), param.type_)),
+        )?;
+        if !function.type_.results().is_empty() {
+            out.write_str(" (result")?;
+            for ty in function.type_.results() {
+                write!(out, " {ty}")?;
+            }
+            out.write_char(')')?;
+        }
+
+        if let Some(code) = function.code() {
+            if !code.locals.is_empty() {
+                let mut locals = String::new();
+                write_params_or_locals(
+                    &mut locals,
+                    "local",
+                    function
+                        .locals()
+                        .map(|(idx, local)| (local_ids[idx.to_usize()].as_deref(), local.type_)),
+                )?;
+                out.write_char('\n')?;
+                write_indent(out, indent + 1)?;
+                out.write_str(locals.trim_start())?;
+            }
+
+            // The final `end` of the function body is implicit in the text format.
+            let instrs = match code.body.split_last() {
+                Some((Instr::End, instrs)) => instrs,
+                _ => &code.body,
+            };
+            let mut block_depth: usize = 0;
+            for instr in instrs {
+                use Instr::*;
+                let line_depth = match instr {
+                    End | Delegate(_) => {
+                        block_depth = block_depth.saturating_sub(1);
+                        block_depth
+                    }
+                    // Printed at the same level as the beginning of the block.
+                    Else | Catch(_) | CatchAll => block_depth.saturating_sub(1),
+                    _ => block_depth,
+                };
+                out.write_char('\n')?;
+                write_indent(out, indent + 1 + line_depth)?;
+                self.write_instr(out, instr, &local_ids)?;
+                if let Block(_) | Loop(_) | If(_) | Try(_) = instr {
+                    block_depth += 1;
+                }
+            }
+
+            out.write_char('\n')?;
+            write_indent(out, indent)?;
+        }
+        out.write_str(")\n")
+    }
+
+    /// Writes a constant expression (e.g., in global initializers or segment offsets) as a
+    /// sequence of folded instructions, each in their own parentheses.
+    fn write_const_expr(&self, out: &mut String, expr: &Expr) -> fmt::Result {
+        for instr in expr {
+            if *instr != Instr::End {
+                out.write_str(" (")?;
+                self.write_instr(out, instr, &[])?;
+                out.write_char(')')?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_instr(
+        &self,
+        out: &mut String,
+        instr: &Instr,
+        local_ids: &[Option<String>],
+    ) -> fmt::Result {
+        use Instr::*;
+        match instr {
+            // The text format has no syntax for our `FunctionType`s, so use type uses instead.
+            Block(ty) | Loop(ty) | If(ty) | Try(ty) => {
+                out.write_str(instr.to_name())?;
+                write_type_use(out, *ty)
+            }
+            CallIndirect(ty, table) | ReturnCallIndirect(ty, table) => {
+                write!(out, "{} {}", instr.to_name(), self.table(*table).to_u32())?;
+                write_type_use(out, *ty)
+            }
+            TypedSelect(ty) => write!(out, "select (result {ty})"),
+
+            Call(function) | ReturnCall(function) | RefFunc(function) => {
+                write!(out, "{} {}", instr.to_name(), self.function_ref(*function))
+            }
+            Local(_, local) => match local_ids.get(local.to_usize()) {
+                Some(Some(id)) => write!(out, "{} ${id}", instr.to_name()),
+                _ => write!(out, "{instr}"),
+            },
+
+            Const(Val::F32(value)) => {
+                out.write_str("f32.const ")?;
+                write_f32(out, value.into_inner())
+            }
+            Const(Val::F64(value)) => {
+                out.write_str("f64.const ")?;
+                write_f64(out, value.into_inner())
+            }
+
+            // All other instructions are printed as by `Display`, but with re-indexed immediates.
+            instr => write!(out, "{}", self.map_idxs(instr)),
+        }
+    }
+
+    /// Returns the instruction with its global, table, memory, and tag indices re-indexed.
+    fn map_idxs(&self, instr: &Instr) -> Instr {
+        use Instr::*;
+        let memarg = |memarg: Memarg| Memarg {
+            memory: self.memory(memarg.memory),
+            ..memarg
+        };
+        match instr.clone() {
+            Global(op, global) => Global(op, self.global(global)),
+
+            Load(op, m) => Load(op, memarg(m)),
+            Store(op, m) => Store(op, memarg(m)),
+            LoadLane(op, m, lane) => LoadLane(op, memarg(m), lane),
+            StoreLane(op, m, lane) => StoreLane(op, memarg(m), lane),
+            AtomicLoad(op, m) => AtomicLoad(op, memarg(m)),
+            AtomicStore(op, m) => AtomicStore(op, memarg(m)),
+            AtomicRmw(op, m) => AtomicRmw(op, memarg(m)),
+            AtomicCmpxchg(op, m) => AtomicCmpxchg(op, memarg(m)),
+            AtomicWaitNotify(op, m) => AtomicWaitNotify(op, memarg(m)),
+
+            MemorySize(memory) => MemorySize(self.memory(memory)),
+            MemoryGrow(memory) => MemoryGrow(self.memory(memory)),
+            MemoryFill(memory) => MemoryFill(self.memory(memory)),
+            MemoryCopy { dst, src } => MemoryCopy {
+                dst: self.memory(dst),
+                src: self.memory(src),
+            },
+            MemoryInit(memory, data) => MemoryInit(self.memory(memory), data),
+
+            TableGet(table) => TableGet(self.table(table)),
+            TableSet(table) => TableSet(self.table(table)),
+            TableSize(table) => TableSize(self.table(table)),
+            TableGrow(table) => TableGrow(self.table(table)),
+            TableFill(table) => TableFill(self.table(table)),
+            TableCopy { dst, src } => TableCopy {
+                dst: self.table(dst),
+                src: self.table(src),
+            },
+            TableInit(table, element) => TableInit(self.table(table), element),
+
+            Throw(tag) => Throw(self.tag(tag)),
+            Catch(tag) => Catch(self.tag(tag)),
+
+            instr => instr,
+        }
+    }
+}
+
+/// Computes the indices in the text format, where imported items come first.
+fn reindex(is_import: impl Iterator<Item = bool>) -> Vec<u32> {
+    let is_import: Vec<bool> = is_import.collect();
+    let mut text_idxs = vec![0; is_import.len()];
+    let mut next_idx = 0;
+    for imports in [true, false] {
+        for (text_idx, _) in text_idxs
+            .iter_mut()
+            .zip(&is_import)
+            .filter(|(_, &is_import)| is_import == imports)
+        {
+            *text_idx = next_idx;
+            next_idx += 1;
+        }
+    }
+    text_idxs
+}
+
+/// Indices that are out of bounds (i.e., in invalid modules) are returned unchanged.
+fn map_idx<T>(text_idxs: &[u32], idx: Idx<T>) -> Idx<T> {
+    text_idxs
+        .get(idx.to_usize())
+        .map(|&text_idx| text_idx.into())
+        .unwrap_or(idx)
+}
+
+/// Converts a debug name to a valid identifier, by replacing all characters that are not allowed
+/// in identifiers with underscores.
+fn to_id(name: &str) -> String {
+    // See https://webassembly.github.io/spec/core/text/values.html#text-id
+    let is_idchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c);
+    let id: String = name
+        .chars()
+        .map(|c| if is_idchar(c) { c } else { '_' })
+        .collect();
+    if id.is_empty() {
+        "_".to_string()
+    } else {
+        id
+    }
+}
+
+/// Converts debug names of items in the same index space to identifiers, which must be unique.
+/// Duplicate names get a numeric suffix, e.g., `$f`, `$f.1`, `$f.2`.
+fn unique_ids<'a>(names: impl Iterator<Item = Option<&'a str>>) -> Vec<Option<String>> {
+    let mut used_ids = FxHashSet::default();
+    names
+        .map(|name| {
+            name.map(|name| {
+                let id = to_id(name);
+                let mut unique_id = id.clone();
+                let mut suffix = 1;
+                while !used_ids.insert(unique_id.clone()) {
+                    unique_id = format!("{id}.{suffix}");
+                    suffix += 1;
+                }
+                unique_id
+            })
+        })
+        .collect()
+}
+
+fn write_indent(out: &mut String, indent: usize) -> fmt::Result {
+    for _ in 0..indent {
+        out.write_str("  ")?;
+    }
+    Ok(())
+}
+
+/// Writes a string literal, where all bytes that are not printable ASCII characters are escaped.
+fn write_string(out: &mut String, bytes: &[u8]) -> fmt::Result {
+    out.write_char('"')?;
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{}", byte as char)?,
+            0x20..=0x7e => out.write_char(byte as char)?,
+            _ => write!(out, "\\{byte:02x}")?,
+        }
+    }
+    out.write_char('"')
+}
+
+fn write_exports_import(
+    out: &mut String,
+    exports: &[String],
+    import: Option<(&str, &str)>,
+) -> fmt::Result {
+    for export in exports {
+        out.write_str(" (export ")?;
+        write_string(out, export.as_bytes())?;
+        out.write_char(')')?;
+    }
+    if let Some((module, name)) = import {
+        out.write_str(" (import ")?;
+        write_string(out, module.as_bytes())?;
+        out.write_char(' ')?;
+        write_string(out, name.as_bytes())?;
+        out.write_char(')')?;
+    }
+    Ok(())
+}
+
+fn write_limits(out: &mut String, limits: Limits) -> fmt::Result {
+    write!(out, " {}", limits.initial_size)?;
+    if let Some(max_size) = limits.max_size {
+        write!(out, " {max_size}")?;
+    }
+    Ok(())
+}
+
+/// Writes the function type inline, e.g., ` (param i32) (result i32)` (or nothing, if empty).
+fn write_type_use(out: &mut String, ty: FunctionType) -> fmt::Result {
+    if !ty.inputs().is_empty() {
+        out.write_str(" (param")?;
+        for ty in ty.inputs() {
+            write!(out, " {ty}")?;
+        }
+        out.write_char(')')?;
+    }
+    if !ty.results().is_empty() {
+        out.write_str(" (result")?;
+        for ty in ty.results() {
+            write!(out, " {ty}")?;
+        }
+        out.write_char(')')?;
+    }
+    Ok(())
+}
+
+/// Writes named parameters or locals individually, and groups consecutive unnamed ones, e.g.,
+/// ` (param $x i32) (param i32 i64)`.
+fn write_params_or_locals<'a>(
+    out: &mut String,
+    keyword: &str,
+    params_or_locals: impl Iterator<Item = (Option<&'a str>, ValType)>,
+) -> fmt::Result {
+    let mut in_unnamed_group = false;
+    for (id, ty) in params_or_locals {
+        match id {
+            Some(id) => {
+                if in_unnamed_group {
+                    out.write_char(')')?;
+                    in_unnamed_group = false;
+                }
+                write!(out, " ({keyword} ${id} {ty})")?;
+            }
+            None if in_unnamed_group => write!(out, " {ty}")?,
+            None => {
+                write!(out, " ({keyword} {ty}")?;
+                in_unnamed_group = true;
+            }
+        }
+    }
+    if in_unnamed_group {
+        out.write_char(')')?;
+    }
+    Ok(())
+}
+
+// Float literals must preserve NaN payloads, which are lost with Rust's `Display`.
+
+fn write_f32(out: &mut String, value: f32) -> fmt::Result {
+    if value.is_nan() {
+        let bits = value.to_bits();
+        let sign = if bits >> 31 != 0 { "-" } else { "" };
+        match bits & 0x7f_ffff {
+            0x40_0000 => write!(out, "{sign}nan"),
+            payload => write!(out, "{sign}nan:{payload:#x}"),
+        }
+    } else {
+        write!(out, "{value}")
+    }
+}
+
+fn write_f64(out: &mut String, value: f64) -> fmt::Result {
+    if value.is_nan() {
+        let bits = value.to_bits();
+        let sign = if bits >> 63 != 0 { "-" } else { "" };
+        match bits & 0xf_ffff_ffff_ffff {
+            0x8_0000_0000_0000 => write!(out, "{sign}nan"),
+            payload => write!(out, "{sign}nan:{payload:#x}"),
+        }
+    } else {
+        write!(out, "{value}")
+    }
+}
@@ -8,6 +8,7 @@ use rayon::prelude::*;
 
 use test_utilities::*;
 
+use crate::nested::Body;
 use crate::types::TypeChecker;
 use crate::*;
 
@@ -15,10 +16,15 @@ const NAME_SECTION_TEST_BINARY: &str =
     "../../test-inputs/wasm-feature-tests/name-section/wabt-tests/names.wasm";
 const BANANABREAD_REAL_WORLD_TEST_BINARY: &str =
     "../../test-inputs/real-world-binaries/bananabread/bb.wasm";
+const DWARF_TEST_BINARY: &str =
+    "../../test-inputs/wasm-feature-tests/name-section/extended-name-section/vuln.wasm";
+const EXTENDED_NAME_SECTION_TEST_BINARY: &str = DWARF_TEST_BINARY;
+const WASM_FEATURE_TESTS_DIR: &str = "../../test-inputs/wasm-feature-tests";
 
-// Removed this test, because when changing to wasmparser,
-// we did not port over the low-level parsing of the extended name section.
-// const WASM_TEST_INPUT_EXTENDED_NAMES_SECTION: &str = "../../test-inputs/wasm-feature-tests/name-section/extended-name-section/vuln.wasm";
+const WITH_INSTR_OFFSETS: ParseOptions = ParseOptions {
+    lazy: false,
+    instr_offsets: true,
+};
 
 #[test]
 fn collect_all_function_types_in_test_set() {
@@ -106,6 +112,162 @@ fn roundtrip_produces_same_module_ast() {
     });
 }
 
+#[test]
+fn parse_from_reader_same_as_from_bytes() {
+    /// Returns only a few bytes per `read` call, such that sections and function bodies are
+    /// split across reads.
+    struct SmallReads<R>(R);
+    impl<R: std::io::Read> std::io::Read for SmallReads<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1000);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let bytes = fs::read(path).unwrap();
+        let (module, offsets, warnings) = Module::from_bytes(&bytes).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        let (module_streamed, offsets_streamed, warnings_streamed) =
+            Module::from_reader(SmallReads(bytes.as_slice())).unwrap_or_else(|err| {
+                panic!(
+                    "Could not parse valid binary '{}' from reader: {err}",
+                    path.display()
+                )
+            });
+        assert_eq!(
+            module,
+            module_streamed,
+            "Parsing from reader produced different AST for binary '{}'",
+            path.display()
+        );
+        assert_eq!(offsets, offsets_streamed);
+        assert_eq!(warnings.len(), warnings_streamed.len());
+    });
+}
+
+#[test]
+fn parse_from_reader_truncated_binary_is_error() {
+    let bytes = fs::read(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
+    let truncated = &bytes[..bytes.len() / 2];
+    assert!(Module::from_bytes(truncated).is_err());
+    assert!(Module::from_reader(truncated).is_err());
+}
+
+#[test]
+fn new_code_body_is_empty_and_mutable() {
+    let mut code = Code::new();
+    assert!(code.body.is_empty());
+    code.body.push(Instr::Nop);
+    code.body.push(Instr::End);
+    assert_eq!(*code.body, vec![Instr::Nop, Instr::End]);
+    assert!(code.body.is_decoded());
+}
+
+#[test]
+fn lazy_parse_reemits_untouched_bodies_unchanged() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let bytes = fs::read(path).unwrap();
+        let (module, _, _) = Module::from_bytes(&bytes).unwrap();
+        let (module_lazy, _, _) = Module::from_bytes_lazy(&bytes).unwrap_or_else(|err| {
+            panic!(
+                "Could not lazily parse valid binary '{}': {err}",
+                path.display()
+            )
+        });
+
+        let bytes_lazy = module_lazy.to_bytes().unwrap();
+        assert!(
+            module_lazy
+                .functions
+                .iter()
+                .filter_map(Function::code)
+                .all(|code| !code.body.is_decoded()),
+            "Encoding should not decode lazily parsed bodies of '{}'",
+            path.display()
+        );
+
+        // Bodies are copied verbatim, unless the original type section has duplicate types.
+        let (module_lazy_roundtrip, _, _) = Module::from_bytes_lazy(&bytes_lazy).unwrap();
+        let original_types = module_lazy
+            .functions
+            .iter()
+            .filter_map(Function::code)
+            .find_map(|code| code.body.raw())
+            .map(|raw| raw.types().to_vec())
+            .unwrap_or_default();
+        let mut unique_types = original_types.clone();
+        unique_types.sort();
+        unique_types.dedup();
+        if unique_types.len() == original_types.len() {
+            for (code, code_roundtrip) in
+                module_lazy.functions.iter().filter_map(Function::code).zip(
+                    module_lazy_roundtrip
+                        .functions
+                        .iter()
+                        .filter_map(Function::code),
+                )
+            {
+                assert!(
+                    code.body.raw().unwrap().bytes() == code_roundtrip.body.raw().unwrap().bytes(),
+                    "Function body was not re-emitted unchanged for binary '{}'",
+                    path.display()
+                );
+            }
+        }
+
+        // Decoding on access gives the same AST as eager parsing, except that extensions used
+        // only inside function bodies are not recorded when parsing lazily.
+        let (module_roundtrip, _, _) = Module::from_bytes(&bytes_lazy).unwrap();
+        let module_lazy = Module {
+            metadata: module.metadata.clone(),
+            ..module_lazy
+        };
+        assert_eq!(module_lazy, module);
+        assert_eq!(module_roundtrip, module);
+    });
+}
+
+#[test]
+fn lazy_parse_with_shifted_indices_is_reencoded() {
+    let bytes = fs::read(BANANABREAD_REAL_WORLD_TEST_BINARY).unwrap();
+    let (mut module, _, _) = Module::from_bytes(&bytes).unwrap();
+    let (mut module_lazy, _, _) = Module::from_bytes_lazy(&bytes).unwrap();
+
+    // Imports are encoded before all other functions, so this shifts all function indices.
+    for module in [&mut module, &mut module_lazy] {
+        module.add_function_import(FunctionType::new(&[], &[]), "env".into(), "new".into());
+        let function = module
+            .functions
+            .iter_mut()
+            .find(|function| function.code().is_some())
+            .unwrap();
+        function.instrs_mut().unwrap().insert(0, Instr::Nop);
+    }
+
+    let (module_roundtrip, _, _) = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    let (module_lazy_roundtrip, _, _) =
+        Module::from_bytes(&module_lazy.to_bytes().unwrap()).unwrap();
+    assert_eq!(module_roundtrip, module_lazy_roundtrip);
+}
+
+#[test]
+fn lazy_parse_reports_malformed_body_on_access() {
+    let module = Module::from_wat("(module (func nop))").unwrap();
+    let mut bytes = module.to_bytes().unwrap();
+    // The body is encoded last, so replace its `end` with an invalid opcode.
+    assert_eq!(bytes.last(), Some(&0x0b));
+    *bytes.last_mut().unwrap() = 0xff;
+
+    assert!(Module::from_bytes(&bytes).is_err());
+    let (module_lazy, _, _) = Module::from_bytes_lazy(&bytes).unwrap();
+    assert!(module_lazy.functions[0].try_instrs().is_err());
+    assert!(module_lazy.decode_lazy_bodies().is_err());
+    assert!(module_lazy.to_bytes().is_err());
+}
+
 #[test]
 fn type_checking_valid_files() {
     for_each_valid_wasm_binary_in_test_set(|path| {
@@ -122,6 +284,340 @@ fn type_checking_valid_files() {
     });
 }
 
+#[test]
+fn instrs_with_types_of_valid_files() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        for (func_idx, function) in module.functions() {
+            let streaming: Vec<_> = function
+                .instrs_with_types(&module)
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|err| {
+                    panic!("Function {func_idx:?} in '{}': {err}", path.display())
+                });
+            let concrete = function
+                .instrs_with_concrete_types(&module)
+                .unwrap_or_else(|err| {
+                    panic!("Function {func_idx:?} in '{}': {err}", path.display())
+                });
+            assert_eq!(streaming.len(), function.instr_count());
+            assert_eq!(concrete.len(), function.instr_count());
+
+            // Both agree on reachable instructions, the non-streaming one has types also for dead code.
+            for (streaming, concrete) in streaming.into_iter().zip(concrete) {
+                assert_eq!(streaming.instr, concrete.instr);
+                match (streaming.type_, streaming.stack_before) {
+                    (
+                        types::InferredInstructionType::Reachable(type_),
+                        types::StackType::Reachable(stack),
+                    ) => {
+                        assert!(concrete.reachable);
+                        assert_eq!(type_, concrete.type_);
+                        assert_eq!(stack, concrete.stack_before);
+                    }
+                    (
+                        types::InferredInstructionType::Unreachable,
+                        types::StackType::Unreachable(_),
+                    ) => {
+                        assert!(!concrete.reachable)
+                    }
+                    (type_, stack) => {
+                        panic!("type {type_} does not match reachability of stack {stack}")
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn control_flow_graphs_of_valid_files() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        for (func_idx, function) in module.functions() {
+            let Some(code) = function.code() else {
+                continue;
+            };
+            let cfg = cfg::ControlFlowGraph::new(&code.body).unwrap_or_else(|err| {
+                panic!("Function {func_idx:?} in '{}': {err}", path.display())
+            });
+
+            // Blocks partition the body and edges are consistent in both directions.
+            let mut next_instr = 0;
+            for (block_idx, block) in cfg.blocks() {
+                assert_eq!(block.instrs.start, next_instr);
+                next_instr = block.instrs.end;
+                for instr_idx in block.instr_idxs() {
+                    assert_eq!(cfg.block_of(instr_idx), block_idx);
+                }
+                for successor in &block.successors {
+                    assert!(cfg.block(*successor).predecessors.contains(&block_idx));
+                }
+            }
+            assert_eq!(next_instr, code.body.len());
+
+            let dominators = cfg.dominators();
+            for (block_idx, block) in cfg.blocks() {
+                if dominators.is_reachable(block_idx) {
+                    assert!(dominators.dominates(cfg.entry(), block_idx));
+                } else {
+                    assert!(block
+                        .predecessors
+                        .iter()
+                        .all(|pred| !dominators.is_reachable(*pred)));
+                }
+            }
+
+            for (_, loop_) in cfg.loops(&dominators).iter() {
+                let header = cfg.block(loop_.header);
+                assert!(matches!(code.body[header.instrs.start], Instr::Loop(_)));
+            }
+        }
+    });
+}
+
+#[test]
+fn validate_valid_files() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        if let Err(errors) = module.validate() {
+            panic!(
+                "Valid binary '{}' should validate, but got errors: {errors:#?}",
+                path.display()
+            );
+        }
+    });
+}
+
+#[test]
+fn validate_reports_all_errors_with_locations() {
+    let mut module = Module::from_wat(
+        r#"(module
+          (memory 2 1)
+          (global $g i32 (i32.const 0))
+          (func $f (param i32)
+            i32.const 1
+            global.set $g
+            i32.const 0
+            i32.load align=8
+            drop)
+          (func $h (result i32)
+            f32.const 0
+            i32.const 1
+            i32.add)
+          (export "f" (func $f))
+          (export "f" (global $g))
+          (start $f))"#,
+    )
+    .unwrap();
+    // Not representable in the text format, because it would not even parse.
+    module.functions[1]
+        .instrs_mut()
+        .unwrap()
+        .insert(0, Instr::Call(42_u32.into()));
+
+    let errors = module.validate().unwrap_err();
+    let locations: Vec<_> = errors.iter().map(|error| error.location).collect();
+    assert_eq!(
+        locations,
+        vec![
+            ValidationLocation::Memory(0_u32.into()),
+            ValidationLocation::Start,
+            ValidationLocation::Global(0_u32.into()),
+            ValidationLocation::Instr(0_u32.into(), 1_u32.into()),
+            ValidationLocation::Instr(0_u32.into(), 3_u32.into()),
+            ValidationLocation::Instr(1_u32.into(), 0_u32.into()),
+        ],
+        "{errors:#?}"
+    );
+    assert!(
+        errors[0].message.contains("initial size 2"),
+        "{}",
+        errors[0]
+    );
+    assert!(
+        errors[2].message.contains("duplicate export name 'f'"),
+        "{}",
+        errors[2]
+    );
+    assert!(errors[3].message.contains("immutable"), "{}", errors[3]);
+    assert!(
+        errors[5].message.contains("invalid function index 42"),
+        "{}",
+        errors[5]
+    );
+}
+
+#[test]
+fn validate_reports_type_errors_and_const_exprs() {
+    let module = Module::from_wat(
+        r#"(module
+          (global $mut (mut i32) (i32.const 0))
+          (global i32 (global.get $mut))
+          (global i64 (i32.const 0))
+          (func (result i32)
+            f32.const 0
+            i32.const 1
+            i32.add))"#,
+    )
+    .unwrap();
+
+    let errors = module.validate().unwrap_err();
+    let locations: Vec<_> = errors.iter().map(|error| error.location).collect();
+    assert_eq!(
+        locations,
+        vec![
+            ValidationLocation::GlobalInit(1_u32.into(), 0_u32.into()),
+            ValidationLocation::GlobalInit(2_u32.into(), 1_u32.into()),
+            ValidationLocation::Instr(0_u32.into(), 2_u32.into()),
+        ],
+        "{errors:#?}"
+    );
+}
+
+#[test]
+fn call_graph_resolves_indirect_calls_by_table_and_type() {
+    let module = Module::from_wat(
+        r#"(module
+          (import "env" "log" (func $log (param i32)))
+          (type $unary (func (param i32) (result i32)))
+          (table 3 funcref)
+          (elem (i32.const 0) $double $negate $nullary)
+          (func $main (export "main") (param i32) (result i32)
+            (call $log (local.get 0))
+            (call_indirect (type $unary) (local.get 0) (local.get 0)))
+          (func $double (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 2)))
+          (func $negate (param i32) (result i32)
+            (i32.sub (i32.const 0) (call $double (local.get 0))))
+          (func $nullary (result i32)
+            (i32.const 0))
+          (func $init
+            (call $helper))
+          (func $helper)
+          (func $dead
+            (call $helper))
+          (start $init))"#,
+    )
+    .unwrap();
+
+    let call_graph = module.call_graph();
+    let idx = |idx: u32| Idx::<Function>::from(idx);
+    assert_eq!(call_graph.roots(), &[idx(1), idx(5)]);
+    assert_eq!(
+        call_graph.callees(idx(1)),
+        &[
+            CallEdge {
+                callee: idx(0),
+                kind: CallKind::Direct
+            },
+            CallEdge {
+                callee: idx(2),
+                kind: CallKind::Indirect
+            },
+            CallEdge {
+                callee: idx(3),
+                kind: CallKind::Indirect
+            },
+        ]
+    );
+    // $nullary is in the table, but has the wrong type for the call.
+    assert!(!call_graph.may_call(idx(1), idx(4)));
+    assert_eq!(call_graph.callers(idx(2)), &[idx(1), idx(3)]);
+    assert_eq!(call_graph.callers(idx(6)), &[idx(5), idx(7)]);
+    assert_eq!(call_graph.unreachable_functions(), vec![idx(4), idx(7)]);
+
+    // Tables that are modified or exported may contain any referenced function.
+    let module = Module::from_wat(
+        r#"(module
+          (type $nullary (func))
+          (table $modified 1 funcref)
+          (table $exported (export "table") 1 funcref)
+          (elem declare func $referenced)
+          (func $main (export "main")
+            (table.set $modified (i32.const 0) (ref.func $referenced))
+            (call_indirect $modified (type $nullary) (i32.const 0)))
+          (func $referenced)
+          (func $via_host_only
+            (call_indirect $exported (type $nullary) (i32.const 0))))"#,
+    )
+    .unwrap();
+
+    let call_graph = module.call_graph();
+    assert_eq!(call_graph.roots(), &[idx(0), idx(1)]);
+    assert!(call_graph.may_call(idx(0), idx(1)));
+    assert!(call_graph.may_call(idx(2), idx(0)));
+    assert!(call_graph.may_call(idx(2), idx(1)));
+    assert_eq!(call_graph.unreachable_functions(), vec![idx(2)]);
+}
+
+#[test]
+fn call_graph_of_valid_files() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        let call_graph = module.call_graph();
+        for (caller, function) in module.functions() {
+            for instr in function.instrs() {
+                if let Instr::Call(callee) | Instr::ReturnCall(callee) = instr {
+                    assert!(call_graph.may_call(caller, *callee));
+                }
+            }
+        }
+        for (caller, edge) in call_graph.edges() {
+            assert!(call_graph.callers(edge.callee).contains(&caller));
+        }
+
+        let reachable = call_graph.reachable_functions();
+        let unreachable = call_graph.unreachable_functions();
+        assert_eq!(reachable.len() + unreachable.len(), module.functions.len());
+        assert!(call_graph
+            .roots()
+            .iter()
+            .all(|root| reachable.contains(root)));
+    });
+}
+
+#[test]
+fn nested_body_roundtrip() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        for (func_idx, function) in module.functions() {
+            let instrs = function.instrs();
+            if instrs.is_empty() {
+                continue;
+            }
+            let body = Body::from_flat(instrs.iter().cloned()).unwrap_or_else(|err| {
+                panic!(
+                    "Could not nest body of function #{} in '{}': {err}",
+                    func_idx.to_usize(),
+                    path.display()
+                )
+            });
+            assert!(
+                body.to_flat() == instrs,
+                "Nested roundtrip failed for function #{} in '{}'",
+                func_idx.to_usize(),
+                path.display()
+            );
+        }
+    });
+}
+
 #[test]
 fn decode_encode_is_valid_wasm() {
     for_each_valid_wasm_binary_in_test_set(|path| {
@@ -146,6 +642,418 @@ fn decode_encode_is_valid_wasm() {
     });
 }
 
+#[test]
+fn write_to_file_same_as_to_bytes() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+
+        let bytes = module.to_bytes().unwrap();
+        let output_path = &output_file(path, "write_to").unwrap();
+        let bytes_written = module.to_file(output_path).unwrap();
+        assert_eq!(bytes_written, bytes.len());
+        assert!(
+            fs::read(output_path).unwrap() == bytes,
+            "Streaming encoder wrote different binary for '{}'",
+            path.display()
+        );
+    });
+}
+
+#[test]
+fn print_wat_and_assemble_roundtrip() {
+    use std::process::Command;
+
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (mut module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+        // wat2wasm does not produce a name section by default.
+        strip_debug_info(&mut module);
+
+        let wat = module.to_wat();
+        let wat_path = output_file(path, "print_wat")
+            .unwrap()
+            .with_extension("wat");
+        let wasm_path = wat_path.with_extension("wat.wasm");
+        fs::write(&wat_path, &wat).unwrap();
+
+        let wat2wasm_output = Command::new("wat2wasm")
+            .arg(&wat_path)
+            .arg("-o")
+            .arg(&wasm_path)
+            .output()
+            .expect("could not run wat2wasm, is WABT on $PATH?");
+        assert!(
+            wat2wasm_output.status.success(),
+            "Printed text format of '{}' could not be assembled:\n{}",
+            path.display(),
+            String::from_utf8_lossy(&wat2wasm_output.stderr)
+        );
+
+        let (reassembled, _, _) = Module::from_file(&wasm_path).unwrap();
+        assert!(
+            reassembled.to_wat() == wat,
+            "Printed text format of '{}' changed after assembling it, see '{}'",
+            path.display(),
+            wat_path.display()
+        );
+    });
+}
+
+#[test]
+fn print_wat_uses_debug_names() {
+    let (module, _, _) = Module::from_file(NAME_SECTION_TEST_BINARY).unwrap();
+    assert_eq!(
+        module.to_wat(),
+        "(module $M0
+  (func $F0 (;0;) (result i32)
+    (local $L0 i32)
+    local.get $L0
+  )
+)
+"
+    );
+    assert_eq!(
+        module.functions[0].to_wat(),
+        "(func $F0 (result i32)
+  (local $L0 i32)
+  local.get $L0
+)
+"
+    );
+}
+
+#[test]
+fn parse_wat_of_printed_module_roundtrip() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (mut module, _, _) = Module::from_file(path).unwrap_or_else(|err| {
+            panic!("Could not parse valid binary '{}': {err}", path.display())
+        });
+        // Custom sections are only printed as comments, so they cannot be parsed back.
+        module.custom_sections.clear();
+
+        let wat = module.to_wat();
+        let parsed = Module::from_wat(&wat).unwrap_or_else(|err| {
+            panic!(
+                "Could not parse printed text format of '{}': {err}",
+                path.display()
+            )
+        });
+        assert!(
+            parsed.to_wat() == wat,
+            "Parsing the printed text format of '{}' changed the module",
+            path.display()
+        );
+    });
+}
+
+#[test]
+fn parse_wat_like_wat2wasm() {
+    // Compare against the binaries in the test set that were produced by wat2wasm.
+    let mut compared_count = 0;
+    for feature_dir in fs::read_dir(WASM_FEATURE_TESTS_DIR).unwrap() {
+        let src_dir = feature_dir.unwrap().path().join("src");
+        let Ok(src_files) = fs::read_dir(&src_dir) else {
+            continue;
+        };
+        for wat_path in src_files {
+            let wat_path = wat_path.unwrap().path();
+            let wasm_path = src_dir
+                .with_file_name("build")
+                .join(wat_path.file_name().unwrap())
+                .with_extension("wat.wasm");
+            if wat_path.extension() != Some("wat".as_ref()) || !wasm_path.exists() {
+                continue;
+            }
+
+            let mut parsed = Module::from_wat(&fs::read_to_string(&wat_path).unwrap())
+                .unwrap_or_else(|err| panic!("Could not parse '{}': {err}", wat_path.display()));
+            let (mut expected, _, _) = Module::from_file(&wasm_path).unwrap();
+            strip_debug_info(&mut parsed);
+            strip_debug_info(&mut expected);
+            assert!(
+                parsed == expected,
+                "Parsing '{}' differs from '{}'",
+                wat_path.display(),
+                wasm_path.display()
+            );
+            compared_count += 1;
+        }
+    }
+    assert!(compared_count > 10);
+}
+
+/// Remove debug names, custom sections, and metadata, e.g., for comparing modules that were
+/// produced by different tools.
+fn strip_debug_info(module: &mut Module) {
+    module.name = None;
+    module.type_names.clear();
+    module.custom_sections.clear();
+    module.metadata = ModuleMetadata::default();
+    for function in &mut module.functions {
+        function.name = None;
+        let locals: Vec<_> = function.param_or_locals().map(|(idx, _)| idx).collect();
+        for local in locals {
+            *function.param_or_local_name_mut(local) = None;
+        }
+        if let Some(code) = function.code_mut() {
+            code.label_names.clear();
+        }
+    }
+    module
+        .globals
+        .iter_mut()
+        .for_each(|global| global.name = None);
+    module.tables.iter_mut().for_each(|table| table.name = None);
+    module
+        .memories
+        .iter_mut()
+        .for_each(|memory| memory.name = None);
+    module
+        .elements
+        .iter_mut()
+        .for_each(|element| element.name = None);
+    module.data.iter_mut().for_each(|data| data.name = None);
+}
+
+#[test]
+fn parse_wat_symbolic_names_and_folded_instrs() {
+    use BinaryOp::*;
+    use Instr::*;
+    use ValType::*;
+
+    let module = Module::from_wat(
+        r#"
+        (module $m
+          (import "env" "log" (func $log (param i32)))
+          (memory (export "mem") 1)
+          (global $counter (mut i32) (i32.const 0))
+          (func $countdown (export "countdown") (param $n i32) (result i32)
+            (local $steps i32)
+            block $exit
+              loop $continue
+                (br_if $exit (i32.eqz (local.get $n)))
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (call $log (local.get $n))
+                (global.set $counter (i32.add (global.get $counter) (i32.const 0x1)))
+                local.get $steps
+                i32.const 1
+                i32.add
+                local.set $steps
+                br $continue
+              end
+            end
+            (if (result i32) (local.get $steps)
+              (then (i32.load offset=4 (i32.const 0)))
+              (else (f32.const -0x1.8p1) drop (i32.const -1)))))
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(module.name.as_deref(), Some("m"));
+    assert_eq!(module.functions.len(), 2);
+    assert_eq!(module.functions[0].import(), Some(("env", "log")));
+    assert_eq!(module.memories[0].export, vec!["mem".to_string()]);
+    assert_eq!(module.globals[0].name.as_deref(), Some("counter"));
+
+    let function = &module.functions[1];
+    assert_eq!(function.name.as_deref(), Some("countdown"));
+    assert_eq!(function.export, vec!["countdown".to_string()]);
+    assert_eq!(function.type_, FunctionType::new(&[I32], &[I32]));
+    assert_eq!(function.param_or_local_name(0u32.into()), Some("n"));
+    assert_eq!(function.param_or_local_name(1u32.into()), Some("steps"));
+    assert_eq!(
+        function.code().unwrap().label_names,
+        vec![Some("exit".to_string()), Some("continue".to_string()), None]
+    );
+
+    let n = Idx::from(0u32);
+    let steps = Idx::from(1u32);
+    let counter = Idx::from(0u32);
+    let empty = FunctionType::new(&[], &[]);
+    assert_eq!(
+        function.instrs(),
+        &[
+            Block(empty),
+            Loop(empty),
+            Local(LocalOp::Get, n),
+            Unary(UnaryOp::I32Eqz),
+            BrIf(Label::from(1u32)),
+            Local(LocalOp::Get, n),
+            Const(Val::I32(1)),
+            Binary(I32Sub),
+            Local(LocalOp::Set, n),
+            Local(LocalOp::Get, n),
+            Call(Idx::from(0u32)),
+            Global(GlobalOp::Get, counter),
+            Const(Val::I32(1)),
+            Binary(I32Add),
+            Global(GlobalOp::Set, counter),
+            Local(LocalOp::Get, steps),
+            Const(Val::I32(1)),
+            Binary(I32Add),
+            Local(LocalOp::Set, steps),
+            Br(Label::from(0u32)),
+            End,
+            End,
+            Local(LocalOp::Get, steps),
+            If(FunctionType::new(&[], &[I32])),
+            Const(Val::I32(0)),
+            Load(
+                LoadOp::I32Load,
+                Memarg {
+                    offset: 4,
+                    ..Memarg::default(LoadOp::I32Load)
+                }
+            ),
+            Else,
+            Const(Val::F32((-3.0).into())),
+            Drop,
+            Const(Val::I32(-1)),
+            End,
+            End,
+        ]
+    );
+}
+
+#[test]
+fn parse_wat_error_position() {
+    let err = Module::from_wat("(module\n  (func\n    call $missing))").unwrap_err();
+    assert_eq!((err.line, err.column), (3, 10));
+    assert!(err.message.contains("$missing"), "{err}");
+
+    let err = Module::from_wat(r#"(module (export "a" (func 5)))"#).unwrap_err();
+    assert_eq!((err.line, err.column), (1, 27));
+    assert!(err.message.contains("unknown function 5"), "{err}");
+    for export in ["(table 0)", "(memory 1)", "(global 0)", "(tag 0)"] {
+        let wat = format!(r#"(module (func) (export "a" {export}))"#);
+        assert!(Module::from_wat(&wat).is_err(), "{wat}");
+    }
+
+    // Like `wat2wasm`, all numeric indices must refer to existing items.
+    for (wat, message) in [
+        ("(module (func (call 7)))", "unknown function 7"),
+        ("(module (func (local.get 5)))", "unknown local 5"),
+        ("(module (func (param i32) (local.get 1) drop))", "unknown local 1"),
+        ("(module (func (block (br 2))))", "unknown label 2"),
+        ("(module (table 1 funcref) (elem (i32.const 0) 5))", "unknown function 5"),
+        ("(module (global i32 (global.get 3)))", "unknown global 3"),
+        ("(module (func) (start 3))", "unknown function 3"),
+        ("(module (func (memory.size 1) drop))", "unknown memory 1"),
+        ("(module (func (elem.drop 0)))", "unknown element segment 0"),
+        ("(module (func (throw 0)))", "unknown tag 0"),
+        ("(module (func (type 2)))", "unknown type 2"),
+        // Lane indices and offsets are not indices, but must be in range as well.
+        (
+            "(module (func (i32x4.extract_lane 9 (v128.const i32x4 0 0 0 0)) drop))",
+            "invalid lane index '9'",
+        ),
+        (
+            "(module (memory 1) (func (v128.load8_lane 16 (i32.const 0) (v128.const i64x2 0 0)) drop))",
+            "invalid lane index '16'",
+        ),
+        (
+            "(module (func (i8x16.shuffle 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 40 (v128.const i64x2 0 0) (v128.const i64x2 0 0)) drop))",
+            "invalid lane index '40'",
+        ),
+        (
+            "(module (func (i32.load offset=4294967296 (i32.const 0)) drop) (memory 1))",
+            "out of range",
+        ),
+    ] {
+        let err = Module::from_wat(wat).unwrap_err();
+        assert!(err.message.contains(message), "{wat}: {err}");
+    }
+    // Upper bounds are still accepted, also for 64-bit memories declared after their use.
+    Module::from_wat(
+        r#"(module
+          (func (param i32)
+            (i32x4.extract_lane 3 (v128.const i32x4 0 0 0 0)) drop
+            (v128.load8_lane 1 offset=1 15 (i64.const 0) (v128.const i64x2 0 0)) drop
+            (i32.load 1 offset=4294967296 (i64.const 0)) drop
+            (local.get 0) drop
+            (block (br 1)))
+          (memory 1)
+          (memory (export "m") i64 1))"#,
+    )
+    .unwrap();
+}
+
+#[test]
+fn extended_name_section_names_are_parsed() {
+    let (module, _, warnings) = Module::from_file(EXTENDED_NAME_SECTION_TEST_BINARY).unwrap();
+    assert!(
+        !warnings
+            .iter()
+            .any(|warning| matches!(warning, ParseIssue::Unsupported { .. })),
+        "{warnings:?}"
+    );
+    assert!(module
+        .metadata
+        .used_extensions()
+        .any(|extension| extension == WasmExtension::ExtendedNameSection));
+
+    // Expected values are taken from the name section of the binary.
+    assert_eq!(module.globals[0].name.as_deref(), Some("__stack_pointer"));
+    assert_eq!(module.data.len(), 25);
+    assert_eq!(module.data[0].name.as_deref(), Some(".rodata"));
+    assert_eq!(module.data[24].name.as_deref(), Some(".data.10"));
+
+    let (reparsed, _, _) = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(reparsed.globals, module.globals);
+    assert_eq!(reparsed.data, module.data);
+}
+
+#[test]
+fn extended_names_encoded_with_binary_indices() {
+    use ValType::*;
+
+    let mut module = Module::from_wat(
+        r#"
+        (module
+          (type $unary (func (param i32) (result i32)))
+          (import "env" "table" (table $imported_table 1 funcref))
+          (memory $mem 1)
+          (global $defined (mut i32) (i32.const 0))
+          (func $f (type $unary)
+            block $outer
+              local.get 0
+              if $inner
+                nop
+              end
+            end
+            local.get 0)
+          (elem $elems (i32.const 0) $f)
+          (data $bytes (i32.const 0) "hi"))
+        "#,
+    )
+    .unwrap();
+    // Imported globals come first in the binary, even if added last to the AST.
+    let mut imported_global =
+        Global::new_imported(GlobalType(I32, Mutability::Const), "env".into(), "g".into());
+    imported_global.name = Some("imported".to_string());
+    module.globals.push(imported_global);
+
+    let (parsed, _, warnings) = Module::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert_eq!(
+        parsed.type_names.get(&FunctionType::new(&[I32], &[I32])),
+        Some(&"unary".to_string())
+    );
+    assert_eq!(parsed.tables[0].name.as_deref(), Some("imported_table"));
+    assert_eq!(parsed.memories[0].name.as_deref(), Some("mem"));
+    assert_eq!(parsed.globals[0].name.as_deref(), Some("imported"));
+    assert_eq!(parsed.globals[1].name.as_deref(), Some("defined"));
+    assert_eq!(parsed.elements[0].name.as_deref(), Some("elems"));
+    assert_eq!(parsed.data[0].name.as_deref(), Some("bytes"));
+    assert_eq!(
+        parsed.functions[0].code().unwrap().label_names,
+        vec![Some("outer".to_string()), Some("inner".to_string())]
+    );
+}
+
 // TODO: Also ensure that used_wasm_extensions(encode(decode(wasm))) <= used_wasm_extensions(wasm), i.e., that our
 // encoding does not introduce new extensions.
 
@@ -209,6 +1117,186 @@ fn code_offsets_like_objdump() {
     );
 }
 
+#[test]
+fn instr_offsets_consistent_with_instrs() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let (module, offsets, _) =
+            Module::from_file_with_options(path, WITH_INSTR_OFFSETS).unwrap();
+        assert_eq!(offsets.instrs.len(), offsets.functions_code.len());
+
+        // Only recorded when requested, also without decoding the instructions.
+        let (_, offsets_default, _) = Module::from_file(path).unwrap();
+        assert!(offsets_default.instrs.is_empty());
+        assert_eq!(offsets_default.functions_code, offsets.functions_code);
+        let lazy_options = ParseOptions {
+            lazy: true,
+            ..WITH_INSTR_OFFSETS
+        };
+        let (_, offsets_lazy, _) = Module::from_file_with_options(path, lazy_options).unwrap();
+        assert_eq!(offsets_lazy, offsets);
+
+        for (function, instr_offsets) in &offsets.instrs {
+            let code = module.function(*function).code().unwrap();
+            assert_eq!(instr_offsets.len(), code.body.len(), "{}", path.display());
+            assert!(
+                instr_offsets.windows(2).all(|pair| pair[0] < pair[1]),
+                "instruction offsets not increasing in '{}'",
+                path.display()
+            );
+            for (instr, &offset) in instr_offsets.iter().enumerate() {
+                let instr = Idx::from(instr);
+                assert_eq!(offsets.instr_idx_to_offset(*function, instr), Some(offset));
+                assert_eq!(
+                    offsets.instr_offset_to_idx(offset),
+                    Some((*function, instr))
+                );
+            }
+        }
+    });
+}
+
+#[test]
+fn instr_offsets_of_encoder_same_as_reparsing() {
+    for_each_valid_wasm_binary_in_test_set(|path| {
+        let bytes = fs::read(path).unwrap();
+        for lazy in [false, true] {
+            let (module, _, _) = if lazy {
+                Module::from_bytes_lazy(&bytes).unwrap()
+            } else {
+                Module::from_bytes(&bytes).unwrap()
+            };
+
+            let (encoded, encoded_offsets) = module.to_bytes_with_offsets().unwrap();
+            let (_, reparsed_offsets, _) =
+                Module::from_bytes_with_options(&encoded, WITH_INSTR_OFFSETS).unwrap();
+            assert_eq!(
+                encoded_offsets,
+                reparsed_offsets,
+                "offsets from encoding (lazy: {lazy}) and reparsing '{}' differ",
+                path.display()
+            );
+        }
+    });
+}
+
+#[test]
+fn dwarf_source_locations_like_llvm_dwarfdump() {
+    let (module, offsets, _warnings) =
+        Module::from_file_with_options(DWARF_TEST_BINARY, WITH_INSTR_OFFSETS).unwrap();
+    let debug_info = module.debug_info(&offsets).unwrap();
+
+    // DWARF addresses are relative to the code section.
+    let code_section_offset = offsets.section_offsets(SectionId::Code)[0];
+    let instr_at = |address: usize| {
+        offsets
+            .instr_offset_to_idx(code_section_offset + address)
+            .unwrap()
+    };
+    let previous_instr = |instr: Idx<Instr>| Idx::from(instr.to_usize() - 1);
+
+    // Expected values are taken from llvm-dwarfdump output.
+    const VULN_C: &str = "/home/daniel/SOLA/WebAssembly/fuzzing/vuln-in-wasm-not-native/vuln.c";
+    let vuln = offsets
+        .function_offset_to_idx(code_section_offset + 0x6)
+        .unwrap();
+    assert_eq!(debug_info.function_name(vuln), Some("vuln"));
+    assert_eq!(debug_info.function_location(vuln), Some((VULN_C, 6, 0)));
+    assert_eq!(
+        debug_info.source_location(vuln, 0u32.into()),
+        Some((VULN_C, 6, 0))
+    );
+    let (function, instr) = instr_at(0x14);
+    assert_eq!(function, vuln);
+    assert_eq!(
+        debug_info.source_location(vuln, instr),
+        Some((VULN_C, 7, 3))
+    );
+    assert_eq!(
+        debug_info.source_location(vuln, previous_instr(instr)),
+        Some((VULN_C, 6, 0))
+    );
+    let (_, instr) = instr_at(0x21);
+    assert_eq!(
+        debug_info.source_location(vuln, instr),
+        Some((VULN_C, 10, 37))
+    );
+    assert_eq!(
+        debug_info.source_location(vuln, previous_instr(instr)),
+        Some((VULN_C, 7, 3))
+    );
+
+    let main = offsets
+        .function_offset_to_idx(code_section_offset + 0x6c)
+        .unwrap();
+    assert_eq!(debug_info.function_name(main), Some("main"));
+    let (function, instr) = instr_at(0xe8);
+    assert_eq!(function, main);
+    assert_eq!(
+        debug_info.source_location(main, instr),
+        Some((VULN_C, 26, 3))
+    );
+
+    let (_, last_instr) = instr_at(0xea);
+    assert_eq!(
+        debug_info.source_location(main, last_instr),
+        Some((VULN_C, 33, 1))
+    );
+}
+
+#[test]
+fn dwarf_source_locations_updated_after_inserting_instrs() {
+    let (mut module, original_offsets, _warnings) =
+        Module::from_file_with_options(DWARF_TEST_BINARY, WITH_INSTR_OFFSETS).unwrap();
+    let original_debug_info = module.debug_info(&original_offsets).unwrap();
+
+    // Shift all instructions by one, which changes every code offset.
+    for (_, function) in module.functions_mut() {
+        if let Some(instrs) = function.instrs_mut() {
+            instrs.insert(0, Instr::Nop);
+        }
+    }
+    let next_instr = |_, instr: Idx<Instr>| Some(Idx::from(instr.to_usize() + 1));
+    let (_, new_offsets) = module.to_bytes_with_offsets().unwrap();
+    module
+        .update_debug_info(&original_offsets, &new_offsets, next_instr)
+        .unwrap();
+
+    let (module, offsets, _warnings) =
+        Module::from_bytes_with_options(&module.to_bytes().unwrap(), WITH_INSTR_OFFSETS).unwrap();
+    assert_eq!(offsets.instrs, new_offsets.instrs);
+    let debug_info = module.debug_info(&offsets).unwrap();
+    let mut source_locations = 0;
+    for (function_idx, function) in module.functions() {
+        assert_eq!(
+            debug_info.function_name(function_idx),
+            original_debug_info.function_name(function_idx)
+        );
+        assert_eq!(
+            debug_info.function_location(function_idx),
+            original_debug_info.function_location(function_idx)
+        );
+        for instr_idx in 1..function.instrs().len() {
+            let original = original_debug_info
+                .source_location(function_idx, Idx::from(instr_idx - 1))
+                .map(|(file, line, column)| (file.to_string(), line, column));
+            let new = debug_info.source_location(function_idx, Idx::from(instr_idx));
+            assert_eq!(
+                new.map(|(file, line, column)| (file.to_string(), line, column)),
+                original,
+                "function {function_idx:?}, instruction {instr_idx}"
+            );
+            source_locations += original.is_some() as usize;
+        }
+    }
+    assert!(source_locations > 0);
+}
+
+#[test]
+fn no_dwarf_source_locations_without_debug_sections() {
+    let (module, offsets, _warnings) = Module::from_file(NAME_SECTION_TEST_BINARY).unwrap();
+    assert!(module.debug_info(&offsets).unwrap().is_empty());
+}
+
 #[test]
 // Unfortunately, when switching from my own low-level parser to wasmparser, this fails
 // because it is not quite as strict as my error reporting was.
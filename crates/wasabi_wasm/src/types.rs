@@ -142,14 +142,16 @@
 //! all instructions that are reachable.
 //! - For all unreachable instructions, we do _not_ assign an instruction type.
 //!
+//! If concrete types are required also for unreachable instructions, e.g., when instrumenting dead
+//! code, `Function::instrs_with_concrete_types()` implements a non-streaming algorithm on top:
+//! It models the stack in dead code with type variables and buffers the types of all instructions
+//! until the end of the function, when the type variables can be resolved.
+//!
 // TODO Inspect V8, SpiderMonkey, JSC, Wasmtime, Wasmer, WABT, Binaryen, spec interpreter for how
 // they do type checking.
 // TODO Measure how many real-world binaries use unreachable code.
 // TODO Prove that a streaming, fully concrete, all instruction algorithm cannot exist; by showing
 // that you can insert always more instructions after an unreachable before its type is fixed.
-// TODO Implement a non-streaming algorithm that assigns concrete types to all instructions;
-// by either doing O(n^2) runtime and scanning for the next end, or using O(n) memory and buffering
-// types until an end is seen.
 // TODO Measure in pratice how many instructions there are after an unreachable instruction, i.e.,
 // how high the "latency"/memory overhead of such an algorithm would be.
 // TODO Find bugs in WebAssembly type checking implementations by fuzzing, reading source code,
@@ -181,6 +183,7 @@ use crate::Idx;
 use crate::ImportOrPresent;
 use crate::Instr;
 use crate::Label;
+use crate::Memory;
 use crate::Module;
 use crate::ValType;
 
@@ -505,6 +508,17 @@ struct BlockFrame {
     /// block receive?
     // TODO: Switch to a "small vector" since there will usually be very few elements.
     if_inputs: Option<Vec<ValType>>,
+
+    /// Needed for checking that `catch`, `catch_all`, and `delegate` only end a `try` (or previous
+    /// `catch`) block and that `rethrow` only targets `catch` blocks (exception handling extension).
+    try_kind: Option<TryBlockKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TryBlockKind {
+    Try,
+    Catch,
+    CatchAll,
 }
 
 impl<'module> TypeChecker<'module> {
@@ -555,6 +569,7 @@ impl<'module> TypeChecker<'module> {
                         e
                     })?;
             }
+            type_checker.check_end()?;
         }
         Ok(())
     }
@@ -563,10 +578,11 @@ impl<'module> TypeChecker<'module> {
     pub fn check_global_init(global: &Global, module: &Module) -> Result<(), TypeError> {
         if let ImportOrPresent::Present(init) = &global.init {
             let pseudo_function_for_init = Function::new(
-                FunctionType::new(&[], &[]),
+                FunctionType::new(&[], &[global.type_.0]),
                 Code {
                     locals: Vec::new(),
-                    body: init.clone(),
+                    body: init.clone().into(),
+                    label_names: Vec::new(),
                 },
                 Vec::new(),
             );
@@ -581,6 +597,7 @@ impl<'module> TypeChecker<'module> {
                         e
                     })?;
             }
+            type_checker.check_end()?;
         }
         Ok(())
     }
@@ -606,9 +623,34 @@ impl<'module> TypeChecker<'module> {
         &mut self,
         instr: &'_ Instr,
     ) -> Result<InferredInstructionType, TypeError> {
+        // If we are already in dead code, do not return a concrete instruction type, because it
+        // could contain unconstrained types (which we don't want), and we also would not be able to
+        // produce a type in O(1) runtime/memory (which we also don't want).
+        let was_unreachable = self.top_block()?.unreachable;
         // Pulled out of the impl only for formatting reasons: put very long function after the
         // interface definitions here.
-        check_instr(self, instr, self.function, self.module)
+        let ty = check_instr(self, instr, self.function, self.module)?;
+        Ok(match (ty, was_unreachable) {
+            (_, true) => InferredInstructionType::Unreachable,
+            (Some(ty), false) => InferredInstructionType::Reachable(ty),
+            (None, false) => {
+                unreachable!("unconstrained value type should never appear in reachable code")
+            }
+        })
+    }
+
+    /// Checks that all blocks, including the implicit function block, were ended, i.e., that no
+    /// instructions are missing at the end of the function body.
+    pub fn check_end(&self) -> Result<(), TypeError> {
+        match self.block_stack.len() {
+            0 => Ok(()),
+            1 => Err("missing end instruction at the end of the function".into()),
+            open_blocks => Err(format!(
+                "{} block(s) not ended at the end of the function",
+                open_blocks - 1
+            )
+            .into()),
+        }
     }
 
     /// Returns the type stack in the current block (without the surrounding parent stacks, since
@@ -690,13 +732,19 @@ impl<'module> TypeChecker<'module> {
             expected_results: results.clone(),
             label_inputs: results,
             if_inputs: None,
+            try_kind: None,
         })
     }
 
     fn push_block(&mut self, instr: &Instr, inputs: &[ValType], results: &[ValType]) {
         let label_inputs = match instr {
             Instr::Loop(_) => inputs,
-            Instr::Block(_) | Instr::If(_) | Instr::Else => results,
+            Instr::Block(_)
+            | Instr::If(_)
+            | Instr::Else
+            | Instr::Try(_)
+            | Instr::Catch(_)
+            | Instr::CatchAll => results,
             _ => unreachable!(
                 "push_block() should never be called with non-block instruction {:?}",
                 instr
@@ -706,12 +754,19 @@ impl<'module> TypeChecker<'module> {
             Instr::If(_) => Some(inputs.to_vec()),
             _ => None,
         };
+        let try_kind = match instr {
+            Instr::Try(_) => Some(TryBlockKind::Try),
+            Instr::Catch(_) => Some(TryBlockKind::Catch),
+            Instr::CatchAll => Some(TryBlockKind::CatchAll),
+            _ => None,
+        };
         self.block_stack.push(BlockFrame {
             value_stack: inputs.iter().cloned().map(InferredValType::from).collect(),
             unreachable: false,
             expected_results: results.to_vec(),
             label_inputs: label_inputs.to_vec(),
             if_inputs,
+            try_kind,
         });
     }
 
@@ -751,6 +806,15 @@ impl<'module> TypeChecker<'module> {
         let frame = self.top_block()?;
         let results = frame.expected_results.clone();
         self.pop_vals_expected(&results)?;
+        // Also after unreachable code, values that were explicitly pushed must be consumed.
+        let remaining = &self.top_block()?.value_stack;
+        if !remaining.is_empty() {
+            return Err(format!(
+                "{} value(s) remaining on the stack at the end of the block",
+                remaining.len()
+            )
+            .into());
+        }
 
         Ok(self
             .block_stack
@@ -779,30 +843,31 @@ impl<'module> TypeChecker<'module> {
     }
 }
 
+/// Checks `instr` and returns its type, or `None` if the type contains unconstrained types, which
+/// can only happen for value-polymorphic instructions in unreachable code.
+///
+/// For all other instructions, this returns a concrete type also in unreachable code (e.g., the
+/// type of `i32.add` is always `[i32, i32] -> [i32]`). It is up to the caller to decide whether to
+/// expose those types, see `TypeChecker::check_next_instr()` and `instrs_with_concrete_types()`.
 #[inline(always)]
 fn check_instr(
     state: &mut TypeChecker,
     instr: &Instr,
     function: &Function,
     module: &Module,
-) -> Result<InferredInstructionType, TypeError> {
-    // If we are already in dead code, do not return a concrete instruction type, because it could
-    // contain unconstrained types (which we don't want), and we also would not be able to produce a
-    // type in O(1) runtime/memory (which we also don't want).
-    let was_unreachable = state.top_block()?.unreachable;
-    let to_inferred_type = |func_ty| {
-        if was_unreachable {
-            InferredInstructionType::Unreachable
-        } else {
-            InferredInstructionType::Reachable(func_ty)
-        }
+) -> Result<Option<FunctionType>, TypeError> {
+    // In the simple cases, we know the type from the instruction alone (and for memory
+    // instructions, the address type of the accessed memory, which is i64 for memory64).
+    let address_ty = |memory: Idx<Memory>| {
+        module
+            .memories
+            .get(memory.to_usize())
+            .map_or(ValType::I32, Memory::address_type)
     };
-
-    // In the simple cases, we know the type from the instruction alone.
-    if let Some(ty) = instr.simple_type() {
+    if let Some(ty) = instr.simple_type_with_memories(address_ty) {
         state.pop_vals_expected(ty.inputs())?;
         state.push_vals(ty.results())?;
-        return Ok(to_inferred_type(ty));
+        return Ok(Some(ty));
     }
 
     // The other cases are a bit more complex:
@@ -815,30 +880,49 @@ fn check_instr(
             let op_ty = op.to_type(local_ty);
             state.pop_vals_expected(op_ty.inputs())?;
             state.push_vals(op_ty.results())?;
-            to_inferred_type(op_ty)
+            Some(op_ty)
         }
         Global(op, idx) => {
             let global_ty = module.global(*idx);
             let op_ty = op.to_type(global_ty.type_.0);
             state.pop_vals_expected(op_ty.inputs())?;
             state.push_vals(op_ty.results())?;
-            to_inferred_type(op_ty)
+            Some(op_ty)
         }
         Call(idx) => {
             let function_ty = module.function(*idx).type_;
             state.pop_vals_expected(function_ty.inputs())?;
             state.push_vals(function_ty.results())?;
-            to_inferred_type(function_ty)
+            Some(function_ty)
+        }
+        TableGet(idx) | TableSet(idx) | TableGrow(idx) | TableFill(idx) => {
+            let elem_ty = module.table(*idx).elem_type.into();
+            let op_ty = match instr {
+                TableGet(_) => FunctionType::new(&[ValType::I32], &[elem_ty]),
+                TableSet(_) => FunctionType::new(&[ValType::I32, elem_ty], &[]),
+                TableGrow(_) => FunctionType::new(&[elem_ty, ValType::I32], &[ValType::I32]),
+                TableFill(_) => FunctionType::new(&[ValType::I32, elem_ty, ValType::I32], &[]),
+                _ => unreachable!(),
+            };
+            state.pop_vals_expected(op_ty.inputs())?;
+            state.push_vals(op_ty.results())?;
+            Some(op_ty)
         }
 
         // Value-polymorphic instructions:
         Drop => {
             let ty = state.pop_val()?;
-            match (ValType::try_from(ty), was_unreachable) {
-                (_, true) => InferredInstructionType::Unreachable,
-                (Ok(ty), false) => InferredInstructionType::Reachable(FunctionType::new(&[ty], &[])),
-                (Err(UnconstrainedTypeError), false) => unreachable!("unconstrained value type should never appear in reachable code"),
+            ValType::try_from(ty).ok().map(|ty| FunctionType::new(&[ty], &[]))
+        }
+        RefIsNull => {
+            let ty = state.pop_val()?;
+            if let Ok(ty) = ValType::try_from(ty) {
+                if ty.to_ref_type().is_none() {
+                    return Err(TypeError::from(format!("expected a reference type for ref.is_null argument, but got {ty}")));
+                }
             }
+            state.push_val(ValType::I32)?;
+            ValType::try_from(ty).ok().map(|ty| FunctionType::new(&[ty], &[ValType::I32]))
         }
         Select => {
             state.pop_val_expected(ValType::I32)?;
@@ -847,11 +931,7 @@ fn check_instr(
             let ty = ty1.join(ty2)
                 .ok_or_else(|| TypeError::from(format!("incompatible types {ty1} and {ty2} for select arguments")))?;
             state.push_val(ty)?;
-            match (ValType::try_from(ty), was_unreachable) {
-                (_, true) => InferredInstructionType::Unreachable,
-                (Ok(ty), false) => InferredInstructionType::Reachable(FunctionType::new(&[ValType::I32, ty, ty], &[ty])),
-                (Err(UnconstrainedTypeError), false) => unreachable!("unconstrained value type should never appear in reachable code"),
-            }
+            ValType::try_from(ty).ok().map(|ty| FunctionType::new(&[ty, ty, ValType::I32], &[ty]))
         }
 
         // Blocks, i.e., block/loop/if/else.
@@ -863,13 +943,15 @@ fn check_instr(
         // See https://github.com/WebAssembly/spec/blob/master/interpreter/valid/valid.ml
         // and https://github.com/WasmCert/WasmCert-Isabelle/blob/master/WebAssembly/Wasm_Checker_Types.thy
         Block(block_ty) | Loop(block_ty) => {
+            state.pop_vals_expected(block_ty.inputs())?;
             state.push_block(instr, block_ty.inputs(), block_ty.results());
-            to_inferred_type(FunctionType::new(block_ty.inputs(), &[]))
+            Some(FunctionType::new(block_ty.inputs(), &[]))
         }
         If(block_ty) => {
             state.pop_val_expected(ValType::I32)?;
+            state.pop_vals_expected(block_ty.inputs())?;
             state.push_block(instr, block_ty.inputs(), block_ty.results());
-            to_inferred_type(FunctionType::from_iter(
+            Some(FunctionType::from_iter(
                 std::iter::once(ValType::I32).chain(block_ty.inputs().iter().copied()),
                 std::iter::empty()
             ))
@@ -882,7 +964,7 @@ fn check_instr(
             if !is_function_end {
                 state.push_vals(&frame.expected_results)?;
             }
-            to_inferred_type(FunctionType::new(&[], &frame.expected_results))
+            Some(FunctionType::new(&[], &frame.expected_results))
         }
         Else => {
             let if_frame = state.pop_block()?;
@@ -892,7 +974,57 @@ fn check_instr(
             // The if instruction has already popped the inputs from the parent stack, so that would be an argument for
             // using empty input types here. On the other hand, the else also starts a new block itself with the given
             // inputs on its child stack, so we add them here.
-            to_inferred_type(FunctionType::new(&if_inputs, &if_frame.expected_results))
+            Some(FunctionType::new(&if_inputs, &if_frame.expected_results))
+        }
+
+        // Exception handling: try/catch/catch_all/delegate are typed analogous to block/else/end.
+        Try(block_ty) => {
+            state.pop_vals_expected(block_ty.inputs())?;
+            state.push_block(instr, block_ty.inputs(), block_ty.results());
+            Some(FunctionType::new(block_ty.inputs(), &[]))
+        }
+        Catch(_) | CatchAll => {
+            let try_frame = state.pop_block()?;
+            match try_frame.try_kind {
+                Some(TryBlockKind::Try) | Some(TryBlockKind::Catch) => {}
+                _ => return Err(format!("{} instruction not matching try or catch", instr.to_name()).into()),
+            }
+            let tag_inputs = match instr {
+                Catch(tag_idx) => module.tags.get(tag_idx.to_usize())
+                    .ok_or_else(|| TypeError::from(format!("invalid tag index {}", tag_idx.to_u32())))?
+                    .type_.inputs(),
+                _ => &[],
+            };
+            state.push_block(instr, tag_inputs, &try_frame.expected_results);
+            // Same weirdness as for else: The values of the caught exception are the inputs of the
+            // new handler block.
+            Some(FunctionType::new(tag_inputs, &try_frame.expected_results))
+        }
+        Delegate(label) => {
+            let try_frame = state.pop_block()?;
+            if try_frame.try_kind != Some(TryBlockKind::Try) {
+                return Err("delegate instruction not matching try".into());
+            }
+            // The label is relative to the blocks surrounding the try block.
+            state.get_block(*label)?;
+            state.push_vals(&try_frame.expected_results)?;
+            Some(FunctionType::new(&[], &try_frame.expected_results))
+        }
+        Throw(tag_idx) => {
+            let tag_ty = module.tags.get(tag_idx.to_usize())
+                .ok_or_else(|| TypeError::from(format!("invalid tag index {}", tag_idx.to_u32())))?
+                .type_;
+            state.pop_vals_expected(tag_ty.inputs())?;
+            state.unreachable()?;
+            Some(FunctionType::new(tag_ty.inputs(), &[]))
+        }
+        Rethrow(label) => {
+            match state.get_block(*label)?.try_kind {
+                Some(TryBlockKind::Catch) | Some(TryBlockKind::CatchAll) => {}
+                _ => return Err(format!("rethrow target label {} is not a catch block", label.to_u32()).into()),
+            }
+            state.unreachable()?;
+            Some(FunctionType::new(&[], &[]))
         }
 
         // Branches: br_if is the only branch that is not followed by dead code.
@@ -908,7 +1040,7 @@ fn check_instr(
             // target label.
             let mut input_tys = vec![ValType::I32];
             input_tys.extend_from_slice(&label_input_tys);
-            to_inferred_type(FunctionType::new(&input_tys, &label_input_tys))
+            Some(FunctionType::new(&input_tys, &label_input_tys))
         }
 
         // All of these branches are followed by dead code, which makes their
@@ -916,7 +1048,7 @@ fn check_instr(
         Br(label) => {
             let label_inputs = state.get_block(*label)?.label_inputs.clone();
             state.unreachable()?;
-            to_inferred_type(FunctionType::new(&label_inputs, &[]))
+            Some(FunctionType::new(&label_inputs, &[]))
         }
         BrTable { table, default } => {
             // Branch index.
@@ -940,13 +1072,39 @@ fn check_instr(
             // targets (we use the last one, but they are all the same anyways).
             let mut input_tys = vec![ValType::I32];
             input_tys.extend_from_slice(&label_inputs);
-            to_inferred_type(FunctionType::new(&input_tys, &[]))
+            Some(FunctionType::new(&input_tys, &[]))
         }
         Return => {
             let tys = function.type_.results();
             state.pop_vals_expected(tys)?;
             state.unreachable()?;
-            to_inferred_type(FunctionType::new(tys, &[]))
+            Some(FunctionType::new(tys, &[]))
+        }
+        // Tail calls: like a call followed by a return, so the callee must return exactly the
+        // results of the current function.
+        ReturnCall(_) | ReturnCallIndirect(_, _) => {
+            let (callee_ty, input_tys) = match instr {
+                ReturnCall(idx) => {
+                    let callee_ty = module.function(*idx).type_;
+                    (callee_ty, callee_ty.inputs().to_vec())
+                }
+                ReturnCallIndirect(callee_ty, _) => {
+                    // Table index is the last input.
+                    let mut input_tys = callee_ty.inputs().to_vec();
+                    input_tys.push(ValType::I32);
+                    (*callee_ty, input_tys)
+                }
+                _ => unreachable!(),
+            };
+            if callee_ty.results() != function.type_.results() {
+                return Err(TypeError::from(format!(
+                    "tail call to function of type {callee_ty} does not match results of current function type {}",
+                    function.type_
+                )));
+            }
+            state.pop_vals_expected(&input_tys)?;
+            state.unreachable()?;
+            Some(FunctionType::new(&input_tys, &[]))
         }
 
         // The prototypical stack-polymorphic instruction.
@@ -961,7 +1119,7 @@ fn check_instr(
         // the "ellipsis" is "expanded" in concrete types).
         Unreachable => {
             state.unreachable()?;
-            to_inferred_type(FunctionType::new(&[], &[]))
+            Some(FunctionType::new(&[], &[]))
         }
 
         instr => unreachable!(
@@ -971,40 +1129,350 @@ fn check_instr(
     })
 }
 
-// /// Extension trait for adding types to instruction sequences.
-// pub trait WithTypes: Sized {
-//     /// Checks and infers types for instructions from `function`.
-//     fn with_types<'a>(self, function: &'a Function, module: &'a Module) -> WithTypesIter<'a, Self>;
-// }
-
-// impl<Iter, I> WithTypes for Iter
-// where
-//     Iter: Iterator<Item = I>,
-//     I: Borrow<Instr>
-// {
-//     fn with_types<'a>(self, function: &'a Function, module: &'a Module) -> WithTypesIter<'a, Self> {
-//         let mut type_checker = TypeChecker::new();
-//         type_checker.push_ctrl_func(function.type_.results.to_vec());
-//         WithTypesIter(self, TypeChecker::new(), function, module)
-//     }
-// }
-
-// pub struct WithTypesIter<'a, I>(I, TypeChecker, &'a Function, &'a Module);
-
-// impl<'a, Iter, I> Iterator for WithTypesIter<'a, Iter>
-// where
-//     Iter: Iterator<Item = I>,
-//     I: Borrow<Instr>
-// {
-//     type Item = (I, Result<InstructionType, TypeError>);
-
-//     fn next(&mut self) -> Option<Self::Item> {
-//         // TODO add location information to error
-//         let instr = self.0.next()?;
-//         let ty = self.1.instr(instr.borrow(), self.2, self.3);
-//         Some((instr, ty))
-//     }
-// }
+/// An instruction together with its inferred type and the type stack before it.
+/// See `Function::instrs_with_types()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstrWithType<'a> {
+    pub instr: &'a Instr,
+    /// `InferredInstructionType::Unreachable` for instructions in dead code.
+    pub type_: InferredInstructionType,
+    /// The type stack of the current block before the instruction, without the parent blocks'
+    /// stacks (since they are not accessible from inside the current block anyway).
+    pub stack_before: StackType,
+}
+
+/// Streaming iterator over the instructions of a function together with their types.
+/// See `Function::instrs_with_types()`.
+///
+/// If the function is not well-typed, the last item is the type error.
+#[derive(Debug)]
+pub struct InstrsWithTypes<'a> {
+    instrs: std::iter::Enumerate<std::slice::Iter<'a, Instr>>,
+    type_checker: TypeChecker<'a>,
+    done: bool,
+}
+
+impl<'a> InstrsWithTypes<'a> {
+    pub(crate) fn new(function: &'a Function, module: &'a Module) -> Self {
+        InstrsWithTypes {
+            instrs: function.instrs().iter().enumerate(),
+            type_checker: TypeChecker::begin_function(function, module),
+            // Imported functions have no instructions, so there is nothing to check.
+            done: function.code().is_none(),
+        }
+    }
+}
+
+impl<'a> Iterator for InstrsWithTypes<'a> {
+    type Item = Result<InstrWithType<'a>, TypeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let Some((instr_idx, instr)) = self.instrs.next() else {
+            self.done = true;
+            return self.type_checker.check_end().err().map(Err);
+        };
+
+        let type_checker = &mut self.type_checker;
+        let result = type_checker
+            .current_block_type_stack()
+            .and_then(|stack_before| {
+                let type_ = type_checker.check_next_instr(instr)?;
+                Ok(InstrWithType {
+                    instr,
+                    type_,
+                    stack_before,
+                })
+            })
+            .map_err(|err| {
+                self.done = true;
+                add_instr_location(err, instr_idx, instr, type_checker.function)
+            });
+        Some(result)
+    }
+}
+
+fn add_instr_location(
+    mut err: TypeError,
+    instr_idx: usize,
+    instr: &Instr,
+    function: &Function,
+) -> TypeError {
+    err.0.instruction_idx = Some(instr_idx.into());
+    err.0.instruction = Some(instr.clone());
+    err.0.function_name.clone_from(&function.name);
+    err
+}
+
+/// An instruction together with its concrete type and the type stack before it, also for
+/// instructions in dead code. See `Function::instrs_with_concrete_types()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstrWithConcreteType<'a> {
+    pub instr: &'a Instr,
+    pub type_: FunctionType,
+    /// The type stack of the current block before the instruction. In dead code, this contains
+    /// only the values that were explicitly pushed after the stack-polymorphic instruction (e.g.,
+    /// `unreachable` or `br`), not the values it produced "out of thin air".
+    pub stack_before: Vec<ValType>,
+    /// Whether the instruction is in reachable code. If not, its type was resolved from the
+    /// subsequent instructions or, if those do not constrain it, chosen arbitrarily.
+    pub reachable: bool,
+}
+
+/// Non-streaming type inference, which assigns concrete types also to instructions in dead code.
+/// See the module comment for why this cannot be done in a streaming fashion.
+///
+/// Runs the streaming type checker and additionally models the stack of every block in dead code
+/// with type variables in place of unconstrained types. Those variables are bound by the
+/// instructions that consume them (possibly much later, e.g., after several `select`s), and only
+/// resolved once the whole function was seen. Variables that are never constrained, e.g., the
+/// input of a `drop` directly after `unreachable`, are resolved to `i32` (or `funcref`, if they
+/// must be a reference type).
+pub(crate) fn instrs_with_concrete_types<'a>(
+    function: &'a Function,
+    module: &'a Module,
+) -> Result<Vec<InstrWithConcreteType<'a>>, TypeError> {
+    let Some(code) = function.code() else {
+        return Ok(Vec::new());
+    };
+
+    let mut type_checker = TypeChecker::begin_function(function, module);
+    let mut vars = TypeVars::default();
+    // Mirrors the type checker's block stack. `None` for blocks in reachable code, where the type
+    // checker's stack is already concrete.
+    let mut dead_stacks: Vec<Option<Vec<TypeSlot>>> = vec![None];
+    let mut types = Vec::with_capacity(code.body.len());
+
+    for (instr_idx, instr) in code.body.iter().enumerate() {
+        let add_location = |err| add_instr_location(err, instr_idx, instr, function);
+
+        let frame = type_checker.top_block().map_err(add_location)?;
+        let block_results = frame.expected_results.clone();
+        let dead_stack_before = dead_stacks.last().cloned().flatten();
+        let stack_before = match &dead_stack_before {
+            Some(dead_stack) => DeadOrReachable::Dead(dead_stack.clone()),
+            None => DeadOrReachable::Reachable(
+                Vec::try_from(
+                    type_checker
+                        .current_block_type_stack()
+                        .map_err(add_location)?,
+                )
+                .expect("reachable code has only concrete types"),
+            ),
+        };
+
+        let type_ =
+            check_instr(&mut type_checker, instr, function, module).map_err(add_location)?;
+
+        // Ending a dead block consumes its results, which can bind type variables.
+        use Instr::*;
+        if let (End | Else | Catch(_) | CatchAll | Delegate(_), Some(dead_stack)) =
+            (instr, dead_stacks.last_mut().and_then(Option::as_mut))
+        {
+            vars.pop_expected(dead_stack, &block_results);
+        }
+        let type_ = match (instr, type_) {
+            (Block(_) | Loop(_) | If(_) | Try(_), Some(type_)) => {
+                if let Some(Some(dead_stack)) = dead_stacks.last_mut() {
+                    vars.pop_expected(dead_stack, type_.inputs());
+                }
+                dead_stacks.push(None);
+                DeadOrReachable::Reachable(type_)
+            }
+            (Else | Catch(_) | CatchAll, Some(type_)) => {
+                dead_stacks.pop();
+                dead_stacks.push(None);
+                DeadOrReachable::Reachable(type_)
+            }
+            (End | Delegate(_), Some(type_)) => {
+                dead_stacks.pop();
+                if let Some(Some(dead_stack)) = dead_stacks.last_mut() {
+                    dead_stack.extend(type_.results().iter().copied().map(TypeSlot::Known));
+                }
+                DeadOrReachable::Reachable(type_)
+            }
+            (_, Some(type_)) => {
+                if let Some(Some(dead_stack)) = dead_stacks.last_mut() {
+                    vars.pop_expected(dead_stack, type_.inputs());
+                    dead_stack.extend(type_.results().iter().copied().map(TypeSlot::Known));
+                }
+                DeadOrReachable::Reachable(type_)
+            }
+            // Value-polymorphic instructions with unconstrained types, only in dead code.
+            (instr, None) => {
+                let dead_stack = dead_stacks
+                    .last_mut()
+                    .and_then(Option::as_mut)
+                    .expect("unconstrained value type should never appear in reachable code");
+                let (inputs, results) = match instr {
+                    Drop => (vec![vars.pop(dead_stack)], vec![]),
+                    RefIsNull => {
+                        let ty = vars.pop(dead_stack);
+                        vars.require_ref(ty);
+                        (vec![ty], vec![TypeSlot::Known(ValType::I32)])
+                    }
+                    Select => {
+                        vars.pop_expected(dead_stack, &[ValType::I32]);
+                        let ty1 = vars.pop(dead_stack);
+                        let ty2 = vars.pop(dead_stack);
+                        let ty = vars.unify(ty1, ty2);
+                        (vec![ty, ty, TypeSlot::Known(ValType::I32)], vec![ty])
+                    }
+                    instr => unreachable!("instruction {instr} should not be value-polymorphic"),
+                };
+                dead_stack.extend_from_slice(&results);
+                DeadOrReachable::Dead((inputs, results))
+            }
+        };
+
+        // Stack-polymorphic instructions make the rest of the block dead.
+        if matches!(
+            instr,
+            Unreachable
+                | Br(_)
+                | BrTable { .. }
+                | Return
+                | Throw(_)
+                | Rethrow(_)
+                | ReturnCall(_)
+                | ReturnCallIndirect(_, _)
+        ) {
+            *dead_stacks
+                .last_mut()
+                .expect("stack-polymorphic instruction must be inside a block") = Some(Vec::new());
+        }
+
+        types.push((instr, type_, stack_before, dead_stack_before.is_none()));
+    }
+    type_checker.check_end()?;
+
+    // Only now that all instructions were seen, the type variables can be resolved.
+    Ok(types
+        .into_iter()
+        .map(
+            |(instr, type_, stack_before, reachable)| InstrWithConcreteType {
+                instr,
+                type_: match type_ {
+                    DeadOrReachable::Reachable(type_) => type_,
+                    DeadOrReachable::Dead((inputs, results)) => FunctionType::from_iter(
+                        inputs.into_iter().map(|slot| vars.resolve(slot)),
+                        results.into_iter().map(|slot| vars.resolve(slot)),
+                    ),
+                },
+                stack_before: match stack_before {
+                    DeadOrReachable::Reachable(stack) => stack,
+                    DeadOrReachable::Dead(stack) => {
+                        stack.into_iter().map(|slot| vars.resolve(slot)).collect()
+                    }
+                },
+                reachable,
+            },
+        )
+        .collect())
+}
+
+/// Something that is only known after the whole function was seen for dead code (`D`), but
+/// immediately in reachable code (`R`).
+enum DeadOrReachable<D, R> {
+    Dead(D),
+    Reachable(R),
+}
+
+/// A value type on the stack in dead code, which is either known or a type variable.
+#[derive(Debug, Clone, Copy)]
+enum TypeSlot {
+    Known(ValType),
+    Var(usize),
+}
+
+/// Type variables, unified with a simple union-find data structure.
+#[derive(Debug, Default)]
+struct TypeVars {
+    /// The representative variable of each variable's equivalence class.
+    parent: Vec<usize>,
+    /// Only meaningful for representatives.
+    bound: Vec<Option<ValType>>,
+    ref_only: Vec<bool>,
+}
+
+impl TypeVars {
+    fn fresh(&mut self) -> TypeSlot {
+        let var = self.parent.len();
+        self.parent.push(var);
+        self.bound.push(None);
+        self.ref_only.push(false);
+        TypeSlot::Var(var)
+    }
+
+    fn find(&self, mut var: usize) -> usize {
+        while self.parent[var] != var {
+            var = self.parent[var];
+        }
+        var
+    }
+
+    /// Pops a value from the stack in dead code, which is "out of thin air" (i.e., a fresh type
+    /// variable) if the stack is empty.
+    fn pop(&mut self, stack: &mut Vec<TypeSlot>) -> TypeSlot {
+        match stack.pop() {
+            Some(slot) => slot,
+            None => self.fresh(),
+        }
+    }
+
+    fn pop_expected(&mut self, stack: &mut Vec<TypeSlot>, expected: &[ValType]) {
+        for &expected in expected.iter().rev() {
+            if let TypeSlot::Var(var) = self.pop(stack) {
+                let var = self.find(var);
+                // The type checker already made sure that the types are compatible.
+                self.bound[var] = Some(expected);
+            }
+        }
+    }
+
+    fn require_ref(&mut self, slot: TypeSlot) {
+        if let TypeSlot::Var(var) = slot {
+            let var = self.find(var);
+            self.ref_only[var] = true;
+        }
+    }
+
+    fn unify(&mut self, slot1: TypeSlot, slot2: TypeSlot) -> TypeSlot {
+        match (slot1, slot2) {
+            (TypeSlot::Var(var1), TypeSlot::Var(var2)) => {
+                let var1 = self.find(var1);
+                let var2 = self.find(var2);
+                self.parent[var2] = var1;
+                self.bound[var1] = self.bound[var1].or(self.bound[var2]);
+                self.ref_only[var1] |= self.ref_only[var2];
+                TypeSlot::Var(var1)
+            }
+            (TypeSlot::Known(ty), TypeSlot::Var(var))
+            | (TypeSlot::Var(var), TypeSlot::Known(ty)) => {
+                let var = self.find(var);
+                self.bound[var] = Some(ty);
+                TypeSlot::Var(var)
+            }
+            (TypeSlot::Known(ty), TypeSlot::Known(_)) => TypeSlot::Known(ty),
+        }
+    }
+
+    fn resolve(&self, slot: TypeSlot) -> ValType {
+        match slot {
+            TypeSlot::Known(ty) => ty,
+            TypeSlot::Var(var) => {
+                let var = self.find(var);
+                match (self.bound[var], self.ref_only[var]) {
+                    (Some(ty), _) => ty,
+                    (None, false) => ValType::I32,
+                    (None, true) => ValType::FuncRef,
+                }
+            }
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -1017,11 +1485,14 @@ mod tests {
     use crate::Instr::*;
     use crate::Label;
     use crate::LocalOp;
+    use crate::Module;
     use crate::UnaryOp::*;
     use crate::Val;
     use crate::ValType;
     use crate::ValType::*;
 
+    use super::InferredInstructionType;
+    use super::StackType;
     use super::TypeChecker;
 
     // Utility test functions.
@@ -1034,7 +1505,8 @@ mod tests {
             FunctionType::new(&[I64], &[F64]),
             Code {
                 locals: vec![crate::Local::new(F32)],
-                body: Vec::new(),
+                body: Vec::new().into(),
+                label_names: Vec::new(),
             },
             Vec::new(),
         )));
@@ -1080,6 +1552,323 @@ mod tests {
         assert_reachable_type(&mut type_checker, Binary(I32Add), &[I32, I32], &[I32]);
     }
 
+    #[test]
+    pub fn sign_extension_and_saturating_truncation_extensions() {
+        let mut type_checker = init_function_module_type_checker();
+        assert_reachable_type(&mut type_checker, Const(Val::I64(-1)), &[], &[I64]);
+        assert_reachable_type(&mut type_checker, Unary(I64Extend32S), &[I64], &[I64]);
+        assert_reachable_type(&mut type_checker, Drop, &[I64], &[]);
+        assert_reachable_type(&mut type_checker, Const(Val::F32(0.5.into())), &[], &[F32]);
+        assert_reachable_type(&mut type_checker, Unary(I32TruncSatF32U), &[F32], &[I32]);
+        assert_reachable_type(&mut type_checker, Unary(I32Extend8S), &[I32], &[I32]);
+    }
+
+    #[test]
+    pub fn reference_types_extension() {
+        let function = Box::leak(Box::new(Function::new(
+            FunctionType::new(&[ExternRef], &[]),
+            Code::new(),
+            Vec::new(),
+        )));
+        let module = Box::leak(Box::new(crate::Module {
+            tables: vec![crate::Table::new(
+                crate::Limits {
+                    initial_size: 1,
+                    max_size: None,
+                    shared: false,
+                    memory64: false,
+                },
+                crate::RefType::ExternRef,
+            )],
+            ..Default::default()
+        }));
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        let table = Idx::from(0u32);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert_reachable_type(
+            &mut type_checker,
+            Local(LocalOp::Get, 0u32.into()),
+            &[],
+            &[ExternRef],
+        );
+        assert_reachable_type(&mut type_checker, TableSet(table), &[I32, ExternRef], &[]);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, TableGet(table), &[I32], &[ExternRef]);
+        assert_reachable_type(
+            &mut type_checker,
+            RefNull(crate::RefType::ExternRef),
+            &[],
+            &[ExternRef],
+        );
+        assert_reachable_type(&mut type_checker, Const(Val::I32(1)), &[], &[I32]);
+        assert_reachable_type(
+            &mut type_checker,
+            TypedSelect(ExternRef),
+            &[ExternRef, ExternRef, I32],
+            &[ExternRef],
+        );
+        assert_reachable_type(&mut type_checker, RefIsNull, &[ExternRef], &[I32]);
+        assert_reachable_type(&mut type_checker, Unary(I32Eqz), &[I32], &[I32]);
+        assert!(
+            type_checker.check_next_instr(&RefIsNull).is_err(),
+            "ref.is_null on an i32 should fail"
+        );
+    }
+
+    #[test]
+    pub fn simd_extension() {
+        let mut type_checker = init_function_module_type_checker();
+        let memarg = crate::Memarg::default(crate::LoadOp::V128Load);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert_reachable_type(
+            &mut type_checker,
+            Load(crate::LoadOp::V128Load, memarg),
+            &[I32],
+            &[V128],
+        );
+        assert_reachable_type(
+            &mut type_checker,
+            Const(Val::V128(crate::V128::from_i128(1))),
+            &[],
+            &[V128],
+        );
+        assert_reachable_type(&mut type_checker, Binary(I32x4Add), &[V128, V128], &[V128]);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(1)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Binary(I32x4Shl), &[V128, I32], &[V128]);
+        assert_reachable_type(&mut type_checker, Const(Val::F32(0.5.into())), &[], &[F32]);
+        assert_reachable_type(
+            &mut type_checker,
+            ReplaceLane(crate::ReplaceLaneOp::F32x4ReplaceLane, 3),
+            &[V128, F32],
+            &[V128],
+        );
+        assert_reachable_type(
+            &mut type_checker,
+            ExtractLane(crate::ExtractLaneOp::I8x16ExtractLaneS, 15),
+            &[V128],
+            &[I32],
+        );
+        assert_reachable_type(&mut type_checker, Unary(I32x4Splat), &[I32], &[V128]);
+        assert_reachable_type(&mut type_checker, Unary(V128AnyTrue), &[V128], &[I32]);
+        assert!(
+            type_checker.check_next_instr(&Unary(V128Not)).is_err(),
+            "v128.not on an i32 should fail"
+        );
+    }
+
+    #[test]
+    pub fn threads_extension() {
+        use crate::AtomicCmpxchgOp::I64AtomicRmw32CmpxchgU;
+        use crate::AtomicRmwOp::I32AtomicRmw8AddU;
+        use crate::AtomicWaitNotifyOp::MemoryAtomicWait64;
+        use crate::Memarg;
+        let mut type_checker = init_function_module_type_checker();
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(1)), &[], &[I32]);
+        assert_reachable_type(
+            &mut type_checker,
+            AtomicRmw(I32AtomicRmw8AddU, Memarg::default(I32AtomicRmw8AddU)),
+            &[I32, I32],
+            &[I32],
+        );
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert_reachable_type(
+            &mut type_checker,
+            Local(LocalOp::Get, 0u32.into()),
+            &[],
+            &[I64],
+        );
+        assert_reachable_type(
+            &mut type_checker,
+            Local(LocalOp::Get, 0u32.into()),
+            &[],
+            &[I64],
+        );
+        assert_reachable_type(
+            &mut type_checker,
+            AtomicCmpxchg(
+                I64AtomicRmw32CmpxchgU,
+                Memarg::default(I64AtomicRmw32CmpxchgU),
+            ),
+            &[I32, I64, I64],
+            &[I64],
+        );
+        assert_reachable_type(&mut type_checker, Const(Val::I64(-1)), &[], &[I64]);
+        assert_reachable_type(
+            &mut type_checker,
+            AtomicWaitNotify(MemoryAtomicWait64, Memarg::default(MemoryAtomicWait64)),
+            &[I32, I64, I64],
+            &[I32],
+        );
+        assert_reachable_type(&mut type_checker, AtomicFence, &[], &[]);
+    }
+
+    #[test]
+    pub fn memory64_extension() {
+        let function = Box::leak(Box::new(Function::new(
+            FunctionType::new(&[], &[]),
+            Code::new(),
+            Vec::new(),
+        )));
+        let module = Box::leak(Box::new(crate::Module {
+            memories: vec![crate::Memory::new(crate::Limits {
+                initial_size: 1,
+                max_size: Some(1 << 32),
+                shared: false,
+                memory64: true,
+            })],
+            ..Default::default()
+        }));
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        let memory = Idx::from(0u32);
+        let memarg = crate::Memarg::default(crate::LoadOp::I32Load);
+        assert_reachable_type(&mut type_checker, MemorySize(memory), &[], &[I64]);
+        assert_reachable_type(&mut type_checker, MemoryGrow(memory), &[I64], &[I64]);
+        assert_reachable_type(
+            &mut type_checker,
+            Load(crate::LoadOp::I32Load, memarg),
+            &[I64],
+            &[I32],
+        );
+        assert!(
+            type_checker
+                .check_next_instr(&Load(crate::LoadOp::I32Load, memarg))
+                .is_err(),
+            "load with an i32 address from a 64-bit memory should fail"
+        );
+    }
+
+    #[test]
+    pub fn multi_memory_extension() {
+        let function = Box::leak(Box::new(Function::new(
+            FunctionType::new(&[], &[]),
+            Code::new(),
+            Vec::new(),
+        )));
+        let limits = crate::Limits {
+            initial_size: 1,
+            max_size: None,
+            shared: false,
+            memory64: false,
+        };
+        let module = Box::leak(Box::new(crate::Module {
+            memories: vec![
+                crate::Memory::new(limits),
+                crate::Memory::new(crate::Limits {
+                    memory64: true,
+                    ..limits
+                }),
+            ],
+            ..Default::default()
+        }));
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        let memarg = crate::Memarg {
+            memory: 1u32.into(),
+            ..crate::Memarg::default(crate::LoadOp::I32Load)
+        };
+        assert_reachable_type(&mut type_checker, Const(Val::I64(0)), &[], &[I64]);
+        assert_reachable_type(
+            &mut type_checker,
+            Load(crate::LoadOp::I32Load, memarg),
+            &[I64],
+            &[I32],
+        );
+        assert_reachable_type(&mut type_checker, MemorySize(1u32.into()), &[], &[I64]);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(8)), &[], &[I32]);
+        assert_reachable_type(
+            &mut type_checker,
+            MemoryCopy {
+                dst: 1u32.into(),
+                src: 0u32.into(),
+            },
+            &[I64, I32, I32],
+            &[],
+        );
+    }
+
+    #[test]
+    pub fn exception_handling_extension() {
+        let function = Box::leak(Box::new(Function::new(
+            FunctionType::new(&[], &[I32]),
+            Code::new(),
+            Vec::new(),
+        )));
+        let module = Box::leak(Box::new(crate::Module {
+            tags: vec![crate::Tag::new(FunctionType::new(&[I32, F64], &[]))],
+            ..Default::default()
+        }));
+        let tag = Idx::from(0u32);
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        assert_reachable_type(
+            &mut type_checker,
+            Try(FunctionType::new(&[], &[I32])),
+            &[],
+            &[],
+        );
+        assert_reachable_type(&mut type_checker, Const(Val::I32(1)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Catch(tag), &[I32, F64], &[I32]);
+        assert_reachable_type(&mut type_checker, Drop, &[F64], &[]);
+        assert_reachable_type(&mut type_checker, CatchAll, &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(2)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Const(Val::F64(3.0.into())), &[], &[F64]);
+        assert_reachable_type(&mut type_checker, Throw(tag), &[I32, F64], &[]);
+        assert_unreachable_type(&mut type_checker, Rethrow(Label::from(0u32)));
+        assert!(
+            type_checker
+                .check_next_instr(&Delegate(Label::from(0u32)))
+                .is_err(),
+            "delegate after catch_all should fail"
+        );
+
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        assert_reachable_type(
+            &mut type_checker,
+            Try(FunctionType::new(&[], &[I32])),
+            &[],
+            &[],
+        );
+        assert_reachable_type(&mut type_checker, Const(Val::I32(1)), &[], &[I32]);
+        assert_reachable_type(&mut type_checker, Delegate(Label::from(0u32)), &[], &[I32]);
+        assert!(
+            type_checker
+                .check_next_instr(&Rethrow(Label::from(0u32)))
+                .is_err(),
+            "rethrow outside of catch should fail"
+        );
+    }
+
+    #[test]
+    pub fn tail_calls_extension() {
+        let callee = Function::new(FunctionType::new(&[I64], &[I32]), Code::new(), Vec::new());
+        let wrong_callee = Function::new(FunctionType::new(&[], &[]), Code::new(), Vec::new());
+        let function = Box::leak(Box::new(Function::new(
+            FunctionType::new(&[], &[I32]),
+            Code::new(),
+            Vec::new(),
+        )));
+        let module = Box::leak(Box::new(crate::Module {
+            functions: vec![callee, wrong_callee],
+            ..Default::default()
+        }));
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        assert_reachable_type(&mut type_checker, Const(Val::I64(1)), &[], &[I64]);
+        assert_reachable_type(&mut type_checker, ReturnCall(Idx::from(0u32)), &[I64], &[]);
+        assert_unreachable_type(
+            &mut type_checker,
+            ReturnCallIndirect(FunctionType::new(&[], &[I32]), Idx::from(0u32)),
+        );
+
+        let mut type_checker = TypeChecker::begin_function(function, module);
+        assert_reachable_type(&mut type_checker, Const(Val::I32(0)), &[], &[I32]);
+        assert!(
+            type_checker
+                .check_next_instr(&ReturnCall(Idx::from(1u32)))
+                .is_err(),
+            "tail call with different results should fail"
+        );
+    }
+
     #[test]
     pub fn function_parameter_type() {
         let mut type_checker = init_function_module_type_checker();
@@ -1157,6 +1946,8 @@ mod tests {
     #[test]
     pub fn block_with_inputs_multi_value_extension() {
         let mut type_checker = init_function_module_type_checker();
+        // Block input:
+        assert_reachable_type(&mut type_checker, Const(Val::F32(1.0.into())), &[], &[F32]);
         assert_reachable_type(
             &mut type_checker,
             Block(FunctionType::new(&[F32], &[I64])),
@@ -1302,7 +2093,118 @@ mod tests {
         );
         assert_unreachable_type(&mut type_checker, Binary(I64Add));
         assert_unreachable_type(&mut type_checker, End);
-        assert_reachable_type(&mut type_checker, Const(Val::I64(0)), &[], &[I64]);
+        // The result of the outer block is produced by the inner block.
         assert_reachable_type(&mut type_checker, End, &[], &[I64]);
     }
+
+    fn function_with_body(type_: FunctionType, body: Vec<Instr>) -> Function {
+        Function::new(
+            type_,
+            Code {
+                locals: Vec::new(),
+                body: body.into(),
+                label_names: Vec::new(),
+            },
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn instrs_with_types_streaming() {
+        let function = function_with_body(
+            FunctionType::new(&[I64], &[F64]),
+            vec![
+                Local(LocalOp::Get, Idx::from(0u32)),
+                Unary(F64ConvertI64S),
+                Unreachable,
+                Drop,
+                End,
+            ],
+        );
+        let module = Module::default();
+
+        let types: Vec<_> = function
+            .instrs_with_types(&module)
+            .map(|instr| {
+                let instr = instr.unwrap();
+                (instr.type_, instr.stack_before)
+            })
+            .collect();
+        let reachable = |inputs, results| {
+            InferredInstructionType::Reachable(FunctionType::new(inputs, results))
+        };
+        assert_eq!(
+            types,
+            vec![
+                (reachable(&[], &[I64]), StackType::Reachable(vec![])),
+                (reachable(&[I64], &[F64]), StackType::Reachable(vec![I64])),
+                (reachable(&[], &[]), StackType::Reachable(vec![F64])),
+                (
+                    InferredInstructionType::Unreachable,
+                    StackType::Unreachable(vec![])
+                ),
+                (
+                    InferredInstructionType::Unreachable,
+                    StackType::Unreachable(vec![])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn instrs_with_types_stops_after_error() {
+        let function =
+            function_with_body(FunctionType::new(&[], &[]), vec![Binary(I64Add), Drop, End]);
+        let module = Module::default();
+
+        let mut instrs = function.instrs_with_types(&module);
+        let error = instrs.next().unwrap().unwrap_err();
+        assert_eq!(error.0.instruction_idx, Some(Idx::from(0u32)));
+        assert_eq!(instrs.next(), None);
+
+        let missing_end = function_with_body(FunctionType::new(&[], &[]), vec![Nop]);
+        let mut instrs = missing_end.instrs_with_types(&module);
+        assert!(instrs.next().unwrap().is_ok());
+        assert!(instrs.next().unwrap().is_err());
+        assert_eq!(instrs.next(), None);
+    }
+
+    #[test]
+    fn instrs_with_concrete_types_resolves_dead_code() {
+        let function = function_with_body(
+            FunctionType::new(&[], &[I64]),
+            vec![
+                Unreachable,
+                // Unconstrained, so can be any type.
+                Drop,
+                // Only constrained by the subsequent instruction.
+                Select,
+                Binary(I64Add),
+                Block(FunctionType::new(&[I64], &[I64])),
+                End,
+                End,
+            ],
+        );
+        let module = Module::default();
+
+        let types: Vec<_> = function
+            .instrs_with_concrete_types(&module)
+            .unwrap()
+            .into_iter()
+            .map(|instr| (instr.type_.to_string(), instr.stack_before, instr.reachable))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("[] -> []".to_string(), vec![], true),
+                ("[i32] -> []".to_string(), vec![], false),
+                ("[i64, i64, i32] -> [i64]".to_string(), vec![], false),
+                ("[i64, i64] -> [i64]".to_string(), vec![I64], false),
+                ("[i64] -> []".to_string(), vec![I64], false),
+                // Inside the block, code is reachable again.
+                ("[] -> [i64]".to_string(), vec![I64], true),
+                ("[] -> [i64]".to_string(), vec![I64], false),
+            ]
+        );
+    }
 }
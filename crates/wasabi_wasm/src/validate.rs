@@ -0,0 +1,544 @@
+//! Validation of modules according to the WebAssembly specification, see `Module::validate`.
+//!
+//! Follows the validation rules in https://webassembly.github.io/spec/core/valid/index.html,
+//! including those of the extensions that can be represented in the AST.
+//! The typing of function bodies is checked by the `TypeChecker`, everything else (index bounds,
+//! limits, constant expressions, export names, etc.) is checked here.
+
+use nohash_hasher::IntSet;
+use rayon::prelude::*;
+use rustc_hash::FxHashSet;
+
+use crate::types::TypeChecker;
+use crate::*;
+
+/// Largest number of pages of a memory with 32-bit addresses, i.e., 4 GiB.
+const MAX_PAGES_MEMORY32: u64 = 1 << 16;
+/// Largest number of pages of a memory with 64-bit addresses (memory64 extension).
+const MAX_PAGES_MEMORY64: u64 = 1 << 48;
+
+pub fn validate_module(module: &Module) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    let mut error = |location, message| errors.push(ValidationError { location, message });
+
+    for (table_idx, table) in module.tables() {
+        let location = ValidationLocation::Table(table_idx);
+        if table.limits.shared || table.limits.memory64 {
+            error(location, "tables cannot be shared or 64-bit".to_string());
+        }
+        if let Err(message) = check_limits(table.limits, u32::MAX.into()) {
+            error(location, message);
+        }
+    }
+
+    for (memory_idx, memory) in module.memories() {
+        let location = ValidationLocation::Memory(memory_idx);
+        let max_pages = match memory.limits.memory64 {
+            true => MAX_PAGES_MEMORY64,
+            false => MAX_PAGES_MEMORY32,
+        };
+        if let Err(message) = check_limits(memory.limits, max_pages) {
+            error(location, message);
+        }
+        if memory.limits.shared && memory.limits.max_size.is_none() {
+            error(
+                location,
+                "shared memory must have a maximum size".to_string(),
+            );
+        }
+    }
+
+    for (tag_idx, tag) in module.tags() {
+        if !tag.type_.results().is_empty() {
+            error(
+                ValidationLocation::Tag(tag_idx),
+                format!("tag type {} must not have results", tag.type_),
+            );
+        }
+    }
+
+    for (global_idx, global) in module.globals() {
+        if let Some(init) = global.init() {
+            check_const_expr(init, global.type_.0, module, &mut error, |instr| {
+                ValidationLocation::GlobalInit(global_idx, instr)
+            });
+        }
+    }
+
+    for (element_idx, element) in module.elements.iter().enumerate() {
+        let element_idx = Idx::<Element>::from(element_idx);
+        let location = ValidationLocation::Element(element_idx);
+        if let ElementMode::Active { table, offset } = &element.mode {
+            match module.tables.get(table.to_usize()) {
+                None => error(location, format!("invalid table index {}", table.to_u32())),
+                Some(table) if table.elem_type != element.ref_type => error(
+                    location,
+                    format!(
+                        "element type {} does not match table element type {}",
+                        element.ref_type, table.elem_type
+                    ),
+                ),
+                Some(_) => {}
+            }
+            check_const_expr(offset, ValType::I32, module, &mut error, |instr| {
+                ValidationLocation::ElementOffset(element_idx, instr)
+            });
+        }
+        match &element.items {
+            ElementItems::Functions(functions) => {
+                if element.ref_type != RefType::FuncRef {
+                    error(
+                        location,
+                        format!(
+                            "function items require element type funcref, not {}",
+                            element.ref_type
+                        ),
+                    );
+                }
+                for (item, function) in functions.iter().enumerate() {
+                    if let Err(message) = check_idx(*function, module.functions.len(), "function") {
+                        error(
+                            ValidationLocation::ElementItem(element_idx, item, None),
+                            message,
+                        );
+                    }
+                }
+            }
+            ElementItems::Expressions(exprs) => {
+                for (item, expr) in exprs.iter().enumerate() {
+                    check_const_expr(expr, element.ref_type.into(), module, &mut error, |instr| {
+                        ValidationLocation::ElementItem(element_idx, item, Some(instr))
+                    });
+                }
+            }
+        }
+    }
+
+    for (data_idx, data) in module.data.iter().enumerate() {
+        let data_idx = Idx::<Data>::from(data_idx);
+        if let DataMode::Active { memory, offset } = &data.mode {
+            let address_type = match module.memories.get(memory.to_usize()) {
+                Some(memory) => memory.address_type(),
+                None => {
+                    error(
+                        ValidationLocation::Data(data_idx),
+                        format!("invalid memory index {}", memory.to_u32()),
+                    );
+                    ValType::I32
+                }
+            };
+            check_const_expr(offset, address_type, module, &mut error, |instr| {
+                ValidationLocation::DataOffset(data_idx, instr)
+            });
+        }
+    }
+
+    if let Some(start) = module.start {
+        match module.functions.get(start.to_usize()) {
+            None => error(
+                ValidationLocation::Start,
+                format!("invalid function index {}", start.to_u32()),
+            ),
+            Some(function) if function.type_ != FunctionType::empty() => error(
+                ValidationLocation::Start,
+                format!(
+                    "start function must have type [] -> [], not {}",
+                    function.type_
+                ),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    // Export names must be unique across all kinds of exported items.
+    let mut export_names = FxHashSet::default();
+    let exports = (module
+        .functions()
+        .map(|(idx, f)| (ValidationLocation::Function(idx), &f.export)))
+    .chain(
+        module
+            .tables()
+            .map(|(idx, t)| (ValidationLocation::Table(idx), &t.export)),
+    )
+    .chain(
+        module
+            .memories()
+            .map(|(idx, m)| (ValidationLocation::Memory(idx), &m.export)),
+    )
+    .chain(
+        module
+            .globals()
+            .map(|(idx, g)| (ValidationLocation::Global(idx), &g.export)),
+    )
+    .chain(
+        module
+            .tags()
+            .map(|(idx, t)| (ValidationLocation::Tag(idx), &t.export)),
+    );
+    for (location, names) in exports {
+        for name in names {
+            if !export_names.insert(name.as_str()) {
+                error(location, format!("duplicate export name '{name}'"));
+            }
+        }
+    }
+
+    // Function bodies are by far the most work, so validate them in parallel.
+    let declared_functions = declared_functions(module);
+    let function_errors: Vec<Vec<ValidationError>> = module
+        .functions
+        .par_iter()
+        .enumerate()
+        .map(|(function_idx, function)| {
+            check_function(function_idx.into(), function, module, &declared_functions)
+        })
+        .collect();
+    errors.extend(function_errors.into_iter().flatten());
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn check_limits(limits: Limits, max_allowed: u64) -> Result<(), String> {
+    if limits.initial_size > max_allowed {
+        return Err(format!(
+            "initial size {} is larger than the allowed maximum {max_allowed}",
+            limits.initial_size
+        ));
+    }
+    if let Some(max_size) = limits.max_size {
+        if max_size > max_allowed {
+            return Err(format!(
+                "maximum size {max_size} is larger than the allowed maximum {max_allowed}"
+            ));
+        }
+        if limits.initial_size > max_size {
+            return Err(format!(
+                "initial size {} is larger than maximum size {max_size}",
+                limits.initial_size
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_idx<T>(idx: Idx<T>, len: usize, index_space: &str) -> Result<(), String> {
+    if idx.to_usize() < len {
+        Ok(())
+    } else {
+        Err(format!("invalid {index_space} index {}", idx.to_u32()))
+    }
+}
+
+/// Checks that `expr` is a constant expression that produces a single value of type `expected`.
+/// `location` gives the location of an instruction in `expr`.
+fn check_const_expr(
+    expr: &[Instr],
+    expected: ValType,
+    module: &Module,
+    error: &mut impl FnMut(ValidationLocation, String),
+    location: impl Fn(Idx<Instr>) -> ValidationLocation,
+) {
+    let Some((Instr::End, instrs)) = expr.split_last() else {
+        error(
+            location(expr.len().into()),
+            "missing end instruction at the end of the constant expression".to_string(),
+        );
+        return;
+    };
+
+    let mut stack = Vec::new();
+    for (instr_idx, instr) in instrs.iter().enumerate() {
+        let type_ = match instr {
+            Instr::Const(val) => Ok(val.to_type()),
+            Instr::RefNull(ref_type) => Ok((*ref_type).into()),
+            Instr::RefFunc(function) => {
+                check_idx(*function, module.functions.len(), "function").map(|()| ValType::FuncRef)
+            }
+            // Since the Wasm 2.0 specification, constant expressions can only read imported
+            // globals, not previously defined ones.
+            Instr::Global(GlobalOp::Get, global) => match module.globals.get(global.to_usize()) {
+                None => Err(format!("invalid global index {}", global.to_u32())),
+                Some(global) if global.import().is_none() || global.type_.1 == Mutability::Mut => {
+                    Err(
+                        "constant expressions can only read imported, immutable globals"
+                            .to_string(),
+                    )
+                }
+                Some(global) => Ok(global.type_.0),
+            },
+            instr => Err(format!(
+                "instruction {instr} is not allowed in constant expressions"
+            )),
+        };
+        match type_ {
+            Ok(type_) => stack.push(type_),
+            Err(message) => return error(location(instr_idx.into()), message),
+        }
+    }
+
+    if stack != [expected] {
+        error(
+            location(instrs.len().into()),
+            format!(
+                "constant expression must produce a single {expected} value, but produces {}",
+                FunctionType::new(&[], &stack)
+            ),
+        );
+    }
+}
+
+/// Functions that are referenced outside of function bodies, which are the only ones that
+/// `ref.func` instructions inside function bodies can refer to.
+fn declared_functions(module: &Module) -> IntSet<Idx<Function>> {
+    fn add_refs(declared: &mut IntSet<Idx<Function>>, expr: &[Instr]) {
+        declared.extend(expr.iter().filter_map(|instr| match instr {
+            Instr::RefFunc(function) => Some(*function),
+            _ => None,
+        }));
+    }
+
+    let mut declared = IntSet::default();
+    for element in &module.elements {
+        if let ElementMode::Active { offset, .. } = &element.mode {
+            add_refs(&mut declared, offset);
+        }
+        match &element.items {
+            ElementItems::Functions(functions) => declared.extend(functions.iter().copied()),
+            ElementItems::Expressions(exprs) => {
+                for expr in exprs {
+                    add_refs(&mut declared, expr);
+                }
+            }
+        }
+    }
+    for data in &module.data {
+        if let DataMode::Active { offset, .. } = &data.mode {
+            add_refs(&mut declared, offset);
+        }
+    }
+    for global in &module.globals {
+        if let Some(init) = global.init() {
+            add_refs(&mut declared, init);
+        }
+    }
+    for (function_idx, function) in module.functions() {
+        if !function.export.is_empty() {
+            declared.insert(function_idx);
+        }
+    }
+    declared
+}
+
+fn check_function(
+    function_idx: Idx<Function>,
+    function: &Function,
+    module: &Module,
+    declared_functions: &IntSet<Idx<Function>>,
+) -> Vec<ValidationError> {
+    let Some(code) = function.code() else {
+        return Vec::new();
+    };
+    let body = match code.body.try_get() {
+        Ok(body) => body,
+        Err(err) => {
+            return vec![ValidationError {
+                location: ValidationLocation::Function(function_idx),
+                message: format!("could not decode function body: {err}"),
+            }]
+        }
+    };
+
+    let mut errors = Vec::new();
+    let mut invalid_indices = false;
+    for (instr_idx, instr) in body.iter().enumerate() {
+        let result = check_instr_indices(instr, function, module)
+            .inspect_err(|_| invalid_indices = true)
+            .and_then(|()| check_instr_immediates(instr, module, declared_functions));
+        if let Err(message) = result {
+            errors.push(ValidationError {
+                location: ValidationLocation::Instr(function_idx, instr_idx.into()),
+                message,
+            });
+        }
+    }
+
+    // The type checker assumes that all indices are valid, so only run it afterwards.
+    if !invalid_indices {
+        if let Err(type_error) = TypeChecker::check_function(function, module) {
+            let location = match type_error.0.instruction_idx {
+                Some(instr_idx) => ValidationLocation::Instr(function_idx, instr_idx),
+                None => ValidationLocation::Function(function_idx),
+            };
+            errors.push(ValidationError {
+                location,
+                message: type_error.0.message,
+            });
+        }
+    }
+
+    errors
+}
+
+/// Checks that all indices in `instr` refer to existing items, except for labels, which are
+/// checked during type checking.
+fn check_instr_indices(instr: &Instr, function: &Function, module: &Module) -> Result<(), String> {
+    use Instr::*;
+
+    let function_ = |idx| check_idx(idx, module.functions.len(), "function");
+    let table = |idx| check_idx(idx, module.tables.len(), "table");
+    let memory = |idx| check_idx(idx, module.memories.len(), "memory");
+    let element = |idx| check_idx(idx, module.elements.len(), "element segment");
+    let data = |idx| check_idx(idx, module.data.len(), "data segment");
+    let tag = |idx| check_idx(idx, module.tags.len(), "tag");
+
+    match *instr {
+        Call(idx) | ReturnCall(idx) | RefFunc(idx) => function_(idx),
+        CallIndirect(_, idx)
+        | ReturnCallIndirect(_, idx)
+        | TableGet(idx)
+        | TableSet(idx)
+        | TableSize(idx)
+        | TableGrow(idx)
+        | TableFill(idx) => table(idx),
+        TableCopy { dst, src } => table(dst).and_then(|()| table(src)),
+        TableInit(table_idx, element_idx) => table(table_idx).and_then(|()| element(element_idx)),
+        ElemDrop(idx) => element(idx),
+        Local(_, idx) => check_idx(
+            idx,
+            function.param_count() + function.local_count(),
+            "local",
+        ),
+        Global(_, idx) => check_idx(idx, module.globals.len(), "global"),
+        Catch(idx) | Throw(idx) => tag(idx),
+        MemoryCopy { dst, src } => memory(dst).and_then(|()| memory(src)),
+        MemoryInit(memory_idx, data_idx) => memory(memory_idx).and_then(|()| data(data_idx)),
+        DataDrop(idx) => data(idx),
+        _ => match instr.memory() {
+            Some(idx) => memory(idx),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Checks the immediates of `instr` (other than indices) and the items they refer to.
+/// Assumes that all indices are valid, see `check_instr_indices`.
+fn check_instr_immediates(
+    instr: &Instr,
+    module: &Module,
+    declared_functions: &IntSet<Idx<Function>>,
+) -> Result<(), String> {
+    use Instr::*;
+    match *instr {
+        Global(GlobalOp::Set, idx) if module.global(idx).type_.1 == Mutability::Const => {
+            Err(format!("cannot set immutable global {}", idx.to_u32()))
+        }
+        RefFunc(idx) if !declared_functions.contains(&idx) => Err(format!(
+            "function {} is not referenced outside of function bodies (e.g., in an element segment or export), so it cannot be referenced with ref.func",
+            idx.to_u32()
+        )),
+        CallIndirect(_, idx) | ReturnCallIndirect(_, idx) => {
+            let elem_type = module.table(idx).elem_type;
+            if elem_type == RefType::FuncRef {
+                Ok(())
+            } else {
+                Err(format!(
+                    "indirect calls require a funcref table, but table {} has element type {elem_type}",
+                    idx.to_u32()
+                ))
+            }
+        }
+        TableInit(table, element) => {
+            let table_type = module.table(table).elem_type;
+            let element_type = module.elements[element.to_usize()].ref_type;
+            if table_type == element_type {
+                Ok(())
+            } else {
+                Err(format!(
+                    "element type {element_type} does not match table element type {table_type}"
+                ))
+            }
+        }
+        TableCopy { dst, src } => {
+            let dst_type = module.table(dst).elem_type;
+            let src_type = module.table(src).elem_type;
+            if dst_type == src_type {
+                Ok(())
+            } else {
+                Err(format!(
+                    "source table element type {src_type} does not match destination table element type {dst_type}"
+                ))
+            }
+        }
+
+        Load(op, memarg) => check_memarg(op, memarg, false, module),
+        Store(op, memarg) => check_memarg(op, memarg, false, module),
+        LoadLane(op, memarg, lane) => {
+            check_memarg(op, memarg, false, module)?;
+            check_lane(lane, 16 >> op.natural_alignment_exp())
+        }
+        StoreLane(op, memarg, lane) => {
+            check_memarg(op, memarg, false, module)?;
+            check_lane(lane, 16 >> op.natural_alignment_exp())
+        }
+        AtomicLoad(op, memarg) => check_memarg(op, memarg, true, module),
+        AtomicStore(op, memarg) => check_memarg(op, memarg, true, module),
+        AtomicRmw(op, memarg) => check_memarg(op, memarg, true, module),
+        AtomicCmpxchg(op, memarg) => check_memarg(op, memarg, true, module),
+        AtomicWaitNotify(op, memarg) => check_memarg(op, memarg, true, module),
+
+        ExtractLane(op, lane) => check_lane(lane, op.lane_count()),
+        ReplaceLane(op, lane) => check_lane(lane, op.lane_count()),
+        // Selects lanes from the concatenation of both input vectors.
+        I8x16Shuffle(lanes) => lanes.iter().try_for_each(|lane| check_lane(*lane, 32)),
+
+        _ => Ok(()),
+    }
+}
+
+/// Atomic memory accesses must be naturally aligned, other accesses at most naturally aligned.
+fn check_memarg(
+    op: impl MemoryOp,
+    memarg: Memarg,
+    atomic: bool,
+    module: &Module,
+) -> Result<(), String> {
+    let natural_alignment_exp = op.natural_alignment_exp();
+    if atomic && memarg.alignment_exp != natural_alignment_exp {
+        return Err(format!(
+            "alignment {} of {} must be equal to its natural alignment {}",
+            memarg.alignment(),
+            op.to_name(),
+            op.natural_alignment()
+        ));
+    }
+    if memarg.alignment_exp > natural_alignment_exp {
+        return Err(format!(
+            "alignment {} of {} must not be larger than its natural alignment {}",
+            memarg.alignment(),
+            op.to_name(),
+            op.natural_alignment()
+        ));
+    }
+    if !module.memory(memarg.memory).limits.memory64 && memarg.offset > u32::MAX.into() {
+        return Err(format!(
+            "offset {} does not fit into the 32-bit address space of memory {}",
+            memarg.offset,
+            memarg.memory.to_u32()
+        ));
+    }
+    Ok(())
+}
+
+fn check_lane(lane: u8, lane_count: u8) -> Result<(), String> {
+    if lane < lane_count {
+        Ok(())
+    } else {
+        Err(format!(
+            "lane index {lane} out of bounds, must be smaller than {lane_count}"
+        ))
+    }
+}
@@ -31,13 +31,16 @@ Wasabi.analysis = {
         console.log(location, "br_table, table =", table, ", default target =", defaultTarget, ", table index =", tableIdx);
     },
 
-    begin(location, type) {
-        console.log(location, "begin", type);
+    // ifLocation === location of the matching if block for else (or try block for catch)
+    // values === block inputs (multi-value), empty for functions
+    begin(location, type, ifLocation, values) {
+        console.log(location, "begin", type, "(if begin @", ifLocation, "), values =", values);
     },
 
-    // ifLocation === location of the matching if block for else
-    end(location, type, beginLocation, ifLocation) {
-        console.log(location, "end", type, "(begin @", beginLocation, ", if begin @", ifLocation, ")");
+    // ifLocation === location of the matching if block for else (or try block for catch)
+    // values === block results, empty if the block was left via a branch or an exception
+    end(location, type, beginLocation, ifLocation, values) {
+        console.log(location, "end", type, "(begin @", beginLocation, ", if begin @", ifLocation, "), values =", values);
     },
 
     drop(location, value) {
@@ -61,6 +64,20 @@ Wasabi.analysis = {
         console.log(location, (location.instr === -1) ? "implicit" : "explicit", "return, values = ", values);
     },
 
+    throw_(location, tagIdx, values) {
+        console.log(location, "throw, tag #", tagIdx, "values =", values);
+    },
+
+    // target === the catch block whose exception is rethrown
+    rethrow(location, target) {
+        console.log(location, "rethrow, exception of label", target.label, "(==", target.location, ")");
+    },
+
+    // tagIdx === undefined iff the handler is catch_all
+    catch_begin(location, tryLocation, tagIdx, values) {
+        console.log(location, "catch, try @", tryLocation, "tag #", tagIdx, "values =", values);
+    },
+
     const_(location, op, value) {
         console.log(location, op, "value =", value);
     },
@@ -81,12 +98,55 @@ Wasabi.analysis = {
         console.log(location, op, "value =", value, "to =", memarg);
     },
 
-    memory_size(location, currentSizePages) {
-        console.log(location, "memory_size, size (in pages) =", currentSizePages);
+    memory_size(location, currentSizePages, memoryIdx) {
+        console.log(location, "memory_size of memory #", memoryIdx, ", size (in pages) =", currentSizePages);
+    },
+
+    memory_grow(location, byPages, previousSizePages, memoryIdx) {
+        console.log(location, "memory_grow of memory #", memoryIdx, ", delta (in pages) =", byPages, "previous size (in pages) =", previousSizePages);
+    },
+
+    memory_copy(location, dest, src, len, destMemoryIdx, srcMemoryIdx) {
+        console.log(location, "memory_copy, from =", src, "in memory #", srcMemoryIdx, "to =", dest, "in memory #", destMemoryIdx, "length (in bytes) =", len);
+    },
+
+    memory_fill(location, dest, value, len, memoryIdx) {
+        console.log(location, "memory_fill, value =", value, "to =", dest, "in memory #", memoryIdx, "length (in bytes) =", len);
+    },
+
+    // src is the offset inside the data segment, not a memory address
+    memory_init(location, dataIdx, dest, src, len, memoryIdx) {
+        console.log(location, "memory_init, from data segment #", dataIdx, "at offset", src, "to =", dest, "in memory #", memoryIdx, "length (in bytes) =", len);
+    },
+
+    data_drop(location, dataIdx) {
+        console.log(location, "data_drop, data segment #", dataIdx);
+    },
+
+    atomic_load(location, op, memarg, value) {
+        console.log(location, op, "value =", value, "from =", memarg);
+    },
+
+    atomic_store(location, op, memarg, value) {
+        console.log(location, op, "value =", value, "to =", memarg);
+    },
+
+    // expected is only given for compare-exchange operations
+    atomic_rmw(location, op, memarg, value, oldValue, expected) {
+        console.log(location, op, "value =", value, "old value =", oldValue, "expected =", expected, "at =", memarg);
+    },
+
+    // result: 0 = woken up by notify, 1 = value was not as expected, 2 = timed out
+    atomic_wait(location, op, memarg, expected, timeout, result) {
+        console.log(location, op, "expected =", expected, "timeout (in ns) =", timeout, "result =", result, "at =", memarg);
+    },
+
+    atomic_notify(location, memarg, count, wokenCount) {
+        console.log(location, "atomic_notify, count =", count, "woken up =", wokenCount, "at =", memarg);
     },
 
-    memory_grow(location, byPages, previousSizePages) {
-        console.log(location, "memory_grow, delta (in pages) =", byPages, "previous size (in pages) =", previousSizePages);
+    atomic_fence(location) {
+        console.log(location, "atomic_fence");
     },
 
     local(location, op, localIndex, value) {
@@ -1,15 +1,32 @@
 {
 	// TODO possibly compress accesses to avoid OOM
-	const accesses /* : [{func, instr, addr, write: bool}] */ = [];
+	const accesses /* : [{func, instr, addr, write: bool, len?: number, memory: number}] */ = [];
 
-	function access({func, instr}, {addr, offset}, write) {
-		accesses.push({func, instr, addr: addr+offset, write});
+	function access({func, instr}, {addr, offset, memory}, write) {
+		accesses.push({func, instr, addr: addr+offset, write, memory});
+	};
+
+	// bulk memory instructions access a whole range of bytes at once
+	function accessRange({func, instr}, addr, len, write, memory) {
+		accesses.push({func, instr, addr, write, len, memory});
 	};
 
 	Wasabi.analysis = {
 		load(loc, op, memarg) { access(loc, memarg, false) },
 		store(loc, op, memarg) { access(loc, memarg, true) },
+		memory_copy(loc, dest, src, len, destMemoryIdx, srcMemoryIdx) {
+			accessRange(loc, src, len, false, srcMemoryIdx);
+			accessRange(loc, dest, len, true, destMemoryIdx);
+		},
+		memory_fill(loc, dest, value, len, memoryIdx) { accessRange(loc, dest, len, true, memoryIdx) },
+		memory_init(loc, dataIdx, dest, src, len, memoryIdx) { accessRange(loc, dest, len, true, memoryIdx) },
+		atomic_load(loc, op, memarg) { access(loc, memarg, false) },
+		atomic_store(loc, op, memarg) { access(loc, memarg, true) },
+		atomic_rmw(loc, op, memarg) {
+			access(loc, memarg, false);
+			access(loc, memarg, true);
+		},
 	};
 
 	Wasabi.analysisResult = accesses;
-}
\ No newline at end of file
+}
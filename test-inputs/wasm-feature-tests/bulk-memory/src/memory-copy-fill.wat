@@ -0,0 +1,18 @@
+(module
+  (memory (export "memory") 1)
+  (func $copy (export "copy") (param $dest i32) (param $src i32) (param $len i32)
+    local.get $dest
+    local.get $src
+    local.get $len
+    memory.copy)
+  (func $fill (export "fill") (param $dest i32) (param $value i32) (param $len i32)
+    local.get $dest
+    local.get $value
+    local.get $len
+    memory.fill)
+  (func $zero-first-page (export "zeroFirstPage")
+    i32.const 0
+    i32.const 0
+    i32.const 65536
+    memory.fill)
+)
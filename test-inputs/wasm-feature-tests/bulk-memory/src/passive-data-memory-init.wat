@@ -0,0 +1,13 @@
+(module
+  (memory 1)
+  (data $passive "hello, world")
+  (data (i32.const 64) "active")
+  (func $init (export "init") (param $dest i32) (result i32)
+    local.get $dest
+    i32.const 7
+    i32.const 5
+    memory.init $passive
+    data.drop $passive
+    local.get $dest
+    i32.load8_u)
+)
@@ -0,0 +1,10 @@
+(module
+  (table 2 funcref)
+  (func $a (result i32) i32.const 1)
+  (func $b (result i32) i32.const 2)
+  (elem (i32.const 0) $a)
+  (elem $passive func $a $b)
+  (func (export "callSecond") (result i32)
+    i32.const 1
+    call_indirect (result i32))
+)
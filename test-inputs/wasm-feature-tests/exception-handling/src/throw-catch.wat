@@ -0,0 +1,56 @@
+(module
+  (tag $imported (import "env" "tag") (param i32))
+  (tag $e (export "e") (param i32 i64))
+  (tag $empty)
+
+  ;; caught by the matching handler, after unwinding the inner block
+  (func $catch (export "catch") (result i32)
+    try (result i32)
+      block
+        i32.const 1
+        i64.const 2
+        throw $e
+      end
+      i32.const 0
+    catch $empty
+      i32.const -1
+    catch $e
+      drop
+      i32.const 1
+      i32.add
+    end)
+
+  ;; rethrown from catch_all, caught in the caller
+  (func $rethrow (export "rethrow")
+    try
+      throw $empty
+    catch_all
+      rethrow 0
+    end)
+
+  (func $call-rethrow (export "call_rethrow") (result i32)
+    try (result i32)
+      call $rethrow
+      i32.const 0
+    catch_all
+      i32.const 1
+    end)
+
+  ;; forwarded to the outer try by delegate
+  (func $delegate (export "delegate") (param i32) (result i32)
+    try (result i32)
+      block
+        try
+          local.get 0
+          throw $imported
+        delegate 1
+      end
+      i32.const 0
+    catch $imported
+    end)
+
+  ;; not caught in this function at all
+  (func $uncaught (export "uncaught")
+    block
+      throw $empty
+    end))
@@ -0,0 +1,18 @@
+(module
+  (memory $mem i64 1 4)
+  (data (i64.const 16) "\01\02\03\04")
+  (func $main (export "main") (result i32)
+    ;; store and load with i64 addresses and a static offset
+    (i64.store offset=8 (i64.const 100) (i64.const 0x1122334455667788))
+    (drop (i64.load offset=8 (i64.const 100)))
+    (i32.store8 (i64.const 4) (i32.const 255))
+    (drop (i32.load8_u (i64.const 4)))
+    ;; memory.size and memory.grow use i64 page counts
+    (drop (memory.grow (i64.const 1)))
+    (drop (memory.size))
+    ;; bulk memory operations with i64 addresses and lengths
+    (memory.fill (i64.const 32) (i32.const 7) (i64.const 8))
+    (memory.copy (i64.const 64) (i64.const 16) (i64.const 4))
+    (i32.load (i64.const 64))
+  )
+)
@@ -0,0 +1,19 @@
+(module
+  (memory $a 1)
+  (memory $b 2 3)
+  (data (memory $b) (i32.const 16) "\01\02\03\04")
+  (func $main (export "main") (result i32)
+    ;; loads and stores with an explicit memory index
+    (i32.store $a (i32.const 0) (i32.const 42))
+    (i32.store $b offset=4 (i32.const 0) (i32.load $a (i32.const 0)))
+    (drop (i32.load $b offset=4 (i32.const 0)))
+    ;; memory.size and memory.grow of the second memory
+    (drop (memory.grow $b (i32.const 1)))
+    (drop (memory.size $b))
+    (drop (memory.size $a))
+    ;; bulk memory operations between and on different memories
+    (memory.copy $a $b (i32.const 100) (i32.const 16) (i32.const 4))
+    (memory.fill $b (i32.const 200) (i32.const 7) (i32.const 8))
+    (i32.add (i32.load $a (i32.const 100)) (i32.load8_u $b (i32.const 200)))
+  )
+)
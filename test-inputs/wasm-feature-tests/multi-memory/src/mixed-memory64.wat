@@ -0,0 +1,10 @@
+(module
+  (memory $small 1)
+  (memory $large i64 1)
+  (func $main (export "main") (result i64)
+    (i64.store $large offset=8 (i64.const 0) (i64.const -1))
+    ;; copying between a 32-bit and a 64-bit memory takes an i32 length
+    (memory.copy $small $large (i32.const 0) (i64.const 8) (i32.const 8))
+    (i64.load $small (i32.const 0))
+  )
+)
@@ -0,0 +1,106 @@
+;; Blocks, loops, and ifs with inputs (block parameters) and multiple results,
+;; including i64 values, which are split into two i32 when passed to hooks.
+(module
+  (type $i32_i32_to_i32_i32 (func (param i32 i32) (result i32 i32)))
+
+  (func $block_swap (param i32 i32) (result i32 i32)
+    local.get 0
+    local.get 1
+    block (param i32 i32) (result i32 i32)
+      local.set 0
+      local.set 1
+      local.get 0
+      local.get 1
+    end)
+
+  (func $typed_block (param i32 i32) (result i32 i32)
+    local.get 0
+    local.get 1
+    block (type $i32_i32_to_i32_i32)
+      i32.add
+      i32.const 1
+    end)
+
+  ;; sum of 1..n, with the accumulator passed as loop input
+  (func $loop_sum (param $n i32) (result i32)
+    i32.const 0
+    loop (param i32) (result i32)
+      local.get $n
+      i32.add
+      local.get $n
+      i32.const 1
+      i32.sub
+      local.tee $n
+      br_if 0
+    end)
+
+  (func $if_else_params (param i64 f64 i32) (result f64 i64)
+    local.get 0
+    local.get 1
+    local.get 2
+    if (param i64 f64) (result f64 i64)
+      f64.neg
+      local.set 1
+      drop
+      local.get 1
+      i64.const 42
+    else
+      local.set 1
+      drop
+      local.get 1
+      local.get 0
+    end)
+
+  ;; branches out of multi-value blocks
+  (func $br_out (param i32) (result i32 i64)
+    i32.const 1
+    i64.const 2
+    block (param i32 i64) (result i32 i64)
+      local.get 0
+      br_if 0
+      drop
+      drop
+      i32.const 3
+      i64.const 4
+      br 0
+    end)
+
+  (func $br_table_out (param i32) (result f32 f32)
+    block (result f32 f32)
+      block (result f32 f32)
+        f32.const 1
+        f32.const 2
+        local.get 0
+        br_table 0 1
+      end
+      f32.add
+      f32.const 4
+    end)
+
+  (func (export "main") (result i32)
+    (call $block_swap (i32.const 1) (i32.const 2))
+    i32.sub
+    (call $typed_block (i32.const 1) (i32.const 2))
+    i32.add
+    i32.add
+    (call $loop_sum (i32.const 10))
+    i32.add
+    (call $if_else_params (i64.const 0x100000000) (f64.const 1.5) (i32.const 1))
+    drop
+    drop
+    (call $if_else_params (i64.const -1) (f64.const 2.5) (i32.const 0))
+    drop
+    drop
+    (call $br_out (i32.const 0))
+    drop
+    i32.add
+    (call $br_out (i32.const 1))
+    drop
+    i32.add
+    (call $br_table_out (i32.const 0))
+    drop
+    drop
+    (call $br_table_out (i32.const 1))
+    drop
+    drop)
+)
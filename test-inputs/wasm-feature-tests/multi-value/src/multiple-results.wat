@@ -0,0 +1,46 @@
+;; Functions with multiple results: explicit and implicit returns, direct and indirect calls.
+(module
+  (type $divmod (func (param i32 i32) (result i32 i32)))
+
+  (table 1 funcref)
+  (elem (i32.const 0) $divmod)
+
+  (func $divmod (type $divmod)
+    local.get 0
+    local.get 1
+    i32.div_u
+    local.get 0
+    local.get 1
+    i32.rem_u)
+
+  (func $early_return (param i32) (result i64 f32 i32)
+    local.get 0
+    if
+      i64.const -1
+      f32.const 0.5
+      i32.const 1
+      return
+    end
+    i64.const 7
+    f32.const 1.5
+    i32.const 0)
+
+  (func $pass_through (result i64 f32 i32)
+    i32.const 1
+    call $early_return)
+
+  (func (export "main") (result i32)
+    (call $divmod (i32.const 17) (i32.const 5))
+    i32.add
+    (call_indirect (type $divmod) (i32.const 9) (i32.const 4) (i32.const 0))
+    i32.add
+    i32.add
+    (call $early_return (i32.const 0))
+    drop
+    drop
+    drop
+    call $pass_through
+    drop
+    drop
+    drop)
+)
@@ -0,0 +1,39 @@
+;; Reference-typed values: externref parameters and results, funcref locals and globals,
+;; ref.null, ref.is_null, ref.func, and typed select.
+(module
+  (import "env" "log" (func $log (param externref)))
+
+  (global $last_func (mut funcref) (ref.null func))
+
+  (func $identity (param externref) (result externref)
+    local.get 0)
+
+  (func $is_null (param externref) (result i32)
+    local.get 0
+    ref.is_null)
+
+  (func $select_ref (param externref externref i32) (result externref)
+    local.get 0
+    local.get 1
+    local.get 2
+    select (result externref))
+
+  (func $remember (param funcref)
+    local.get 0
+    global.set $last_func)
+
+  (func (export "main") (param $value externref) (result i32)
+    (local $f funcref)
+    ref.func $identity
+    local.tee $f
+    call $remember
+    (call $log (call $identity (local.get $value)))
+    (call $select_ref (ref.null extern) (local.get $value) (i32.const 1))
+    call $is_null
+    drop
+    local.get $f
+    ref.is_null)
+
+  ;; Forward declaration of the function referenced with ref.func above.
+  (elem declare func $identity)
+)
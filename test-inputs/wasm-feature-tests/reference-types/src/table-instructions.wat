@@ -0,0 +1,39 @@
+;; Multiple tables (funcref and externref), table.* instructions, call_indirect on a table
+;; other than 0, and element segments made of expressions.
+(module
+  (type $binop (func (param i32 i32) (result i32)))
+
+  (table $funcs 4 funcref)
+  (table $externs 2 10 externref)
+  (table $more_funcs 2 funcref)
+
+  (func $add (type $binop) (i32.add (local.get 0) (local.get 1)))
+  (func $sub (type $binop) (i32.sub (local.get 0) (local.get 1)))
+
+  (elem (table $funcs) (i32.const 0) func $add $sub)
+  (elem $exprs funcref (ref.func $sub) (ref.null func) (ref.func $add))
+  (elem (table $more_funcs) (i32.const 0) funcref (ref.func $add) (ref.null func))
+
+  (func (export "store") (param $idx i32) (param $value externref)
+    (table.set $externs (local.get $idx) (local.get $value)))
+
+  (func (export "load") (param $idx i32) (result externref)
+    (table.get $externs (local.get $idx)))
+
+  (func (export "grow") (param $value externref) (result i32)
+    (table.grow $externs (local.get $value) (i32.const 2)))
+
+  (func (export "size") (result i32)
+    (i32.add (table.size $funcs) (table.size $externs)))
+
+  (func (export "fill") (param $value externref)
+    (table.fill $externs (i32.const 0) (local.get $value) (table.size $externs)))
+
+  (func (export "init_and_copy")
+    (table.init $funcs $exprs (i32.const 1) (i32.const 0) (i32.const 3))
+    (elem.drop $exprs)
+    (table.copy $more_funcs $funcs (i32.const 0) (i32.const 1) (i32.const 2)))
+
+  (func (export "call_more_funcs") (param $idx i32) (result i32)
+    (call_indirect $more_funcs (type $binop) (i32.const 3) (i32.const 2) (local.get $idx)))
+)
@@ -0,0 +1,39 @@
+(module
+  (memory 1)
+  (global $g (mut v128) (v128.const i32x4 0 0 0 0))
+  (func $add (param $a v128) (param $b v128) (result v128)
+    local.get $a
+    local.get $b
+    i32x4.add)
+  (func $main (export "main") (result i32)
+    (local $v v128)
+    ;; Constants in different shapes.
+    v128.const i8x16 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
+    v128.const i32x4 -1 0x7fffffff 0 42
+    call $add
+    local.set $v
+    ;; Splat, unary, binary, shifts, and comparisons.
+    f32.const 1.5
+    f32x4.splat
+    f32x4.neg
+    f32x4.sqrt
+    local.get $v
+    f32x4.mul
+    v128.not
+    i32.const 3
+    i16x8.shl
+    local.get $v
+    i64x2.eq
+    i8x16.popcnt
+    global.set $g
+    ;; Memory.
+    i32.const 0
+    global.get $g
+    v128.store
+    i32.const 0
+    v128.load
+    i32.const 16
+    v128.load32_splat
+    i16x8.extmul_high_i8x16_s
+    v128.any_true)
+)
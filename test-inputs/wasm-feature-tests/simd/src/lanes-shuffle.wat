@@ -0,0 +1,32 @@
+(module
+  (memory 1)
+  (func $main (export "main") (result i32)
+    (local $v v128)
+    v128.const i64x2 0x0102030405060708 -1
+    local.set $v
+    ;; Lane accesses.
+    local.get $v
+    i32.const 0
+    i8x16.replace_lane 15
+    f64.const 2.5
+    f64x2.replace_lane 0
+    local.tee $v
+    i16x8.extract_lane_u 7
+    drop
+    ;; Lane loads and stores.
+    i32.const 8
+    local.get $v
+    v128.load16_lane 3
+    local.set $v
+    i32.const 32
+    local.get $v
+    v128.store64_lane offset=8 1
+    ;; Shuffle and bitselect.
+    local.get $v
+    v128.const i32x4 1 2 3 4
+    i8x16.shuffle 0 1 2 3 16 17 18 19 4 5 6 7 20 21 22 23
+    local.get $v
+    v128.const f32x4 0 0 -1 nan
+    v128.bitselect
+    i32x4.extract_lane 2)
+)
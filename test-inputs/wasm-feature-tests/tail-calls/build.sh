@@ -0,0 +1,9 @@
+#!/bin/sh
+
+rm -rf build/
+mkdir build
+
+for wat in src/*.wat
+do
+    wat2wasm --enable-tail-call "$wat" -o "build/$(basename $wat).wasm"
+done
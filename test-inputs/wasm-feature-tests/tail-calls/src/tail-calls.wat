@@ -0,0 +1,38 @@
+(module
+  (type $binop (func (param i64 i32) (result i64)))
+  (table 1 funcref)
+  (elem (i32.const 0) $fac_acc)
+
+  ;; factorial with accumulator, tail-recursive via direct tail calls
+  (func $fac_acc (param $n i64) (param $acc i32) (result i64)
+    local.get $n
+    i64.eqz
+    if (result i64)
+      local.get $acc
+      i64.extend_i32_u
+    else
+      local.get $n
+      i64.const 1
+      i64.sub
+      local.get $acc
+      local.get $n
+      i32.wrap_i64
+      i32.mul
+      return_call $fac_acc
+    end)
+
+  (func (export "fac") (param i64) (result i64)
+    block
+      local.get 0
+      i32.const 1
+      return_call $fac_acc
+    end
+    unreachable)
+
+  ;; indirect tail call through the table
+  (func (export "fac_indirect") (param i64) (result i64)
+    local.get 0
+    i32.const 1
+    i32.const 0
+    return_call_indirect (type $binop))
+)
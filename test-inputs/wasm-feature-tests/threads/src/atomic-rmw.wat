@@ -0,0 +1,41 @@
+(module
+  (memory 1 1 shared)
+  (func $main (export "main") (result i64)
+    ;; Plain atomic loads and stores.
+    i32.const 0
+    i32.const 42
+    i32.atomic.store
+    i32.const 8
+    i64.const 0x100000000
+    i64.atomic.store
+    i32.const 0
+    i32.atomic.load8_u
+    drop
+    ;; Read-modify-write, returns the old value.
+    i32.const 0
+    i32.const 1
+    i32.atomic.rmw.add
+    drop
+    i32.const 2
+    i32.const 0xff
+    i32.atomic.rmw16.xor_u
+    drop
+    i32.const 8
+    i64.const 7
+    i64.atomic.rmw.xchg
+    drop
+    atomic.fence
+    ;; Compare-exchange, only the first one succeeds.
+    i32.const 0
+    i32.const 43
+    i32.const 100
+    i32.atomic.rmw.cmpxchg
+    drop
+    i32.const 8
+    i64.const 0
+    i64.const 100
+    i64.atomic.rmw32.cmpxchg_u
+    drop
+    i32.const 8
+    i64.atomic.load)
+)
@@ -0,0 +1,19 @@
+(module
+  (memory 1 1 shared)
+  (func $main (export "main") (result i32)
+    ;; Nobody is waiting, so nobody is woken up.
+    i32.const 0
+    i32.const 1
+    memory.atomic.notify
+    ;; Value in memory is not as expected, so returns immediately with 1 ("not-equal").
+    i32.const 0
+    i32.const 1
+    i64.const -1
+    memory.atomic.wait32
+    i32.add
+    i32.const 8
+    i64.const 1
+    i64.const 0
+    memory.atomic.wait64
+    i32.add)
+)